@@ -0,0 +1,88 @@
+use std::path::Path;
+
+#[cfg(not(windows))]
+use anyhow::bail;
+#[cfg(windows)]
+use anyhow::{Context, bail};
+
+/// Fails if access control lists can't be preserved on this platform.
+pub fn check_supported() -> anyhow::Result<()> {
+    #[cfg(not(windows))]
+    bail!("--preserve-acl is only supported on Windows");
+
+    #[cfg(windows)]
+    Ok(())
+}
+
+/// Reads a file's owner and access control list in SDDL form.
+#[cfg(windows)]
+pub fn read_acl(path: &Path) -> anyhow::Result<String> {
+    let script = format!(
+        "(Get-Acl -LiteralPath {}).Sddl",
+        quote(&path.to_string_lossy())
+    );
+    run_powershell(&script).with_context(|| format!("Failed to read ACL of {}", path.display()))
+}
+
+/// Reads a file's owner and access control list in SDDL form.
+#[cfg(not(windows))]
+pub fn read_acl(_path: &Path) -> anyhow::Result<String> {
+    check_supported().map(|()| String::new())
+}
+
+/// Replaces a file's owner and access control list with ones in SDDL form.
+///
+/// Changing the owner to someone else needs the privilege to restore files,
+/// which usually means running as an administrator.
+#[cfg(windows)]
+pub fn write_acl(path: &Path, sddl: &str) -> anyhow::Result<()> {
+    let path_arg = quote(&path.to_string_lossy());
+    let script = format!(
+        "$acl = Get-Acl -LiteralPath {path_arg}; \
+         $acl.SetSecurityDescriptorSddlForm({}); \
+         Set-Acl -LiteralPath {path_arg} -AclObject $acl",
+        quote(sddl),
+    );
+    run_powershell(&script)
+        .map(drop)
+        .with_context(|| format!("Failed to restore ACL of {}", path.display()))
+}
+
+/// Replaces a file's owner and access control list with ones in SDDL form.
+#[cfg(not(windows))]
+pub fn write_acl(_path: &Path, _sddl: &str) -> anyhow::Result<()> {
+    check_supported()
+}
+
+/// Runs a PowerShell script, returning what it printed.
+#[cfg(windows)]
+fn run_powershell(script: &str) -> anyhow::Result<String> {
+    let output = std::process::Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", script])
+        .output()
+        .context("Failed to run PowerShell")?;
+    if !output.status.success() {
+        bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Quotes a string for PowerShell, so it's used literally.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(r"C:\data\app.json" => r"'C:\data\app.json'"; "plain")]
+    #[test_case(r"C:\Bob's files\$HOME.txt" => r"'C:\Bob''s files\$HOME.txt'"; "quotes and variables")]
+    fn quote_literal(value: &str) -> String {
+        quote(value)
+    }
+}
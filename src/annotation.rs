@@ -0,0 +1,91 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{ErrorKind, Read},
+    path::Path,
+};
+
+use anyhow::Context;
+
+use crate::dotenv::interpolate_defined;
+
+/// Annotation that declares the name of the blob a file is synchronized with.
+pub const BLOB_ANNOTATION: &str = "azsync-blob:";
+
+/// How much of the start of a file is searched for annotations.
+const FRONT_MATTER_SIZE: u64 = 4096;
+
+/// How many lines at the start of a file are searched for annotations.
+const FRONT_MATTER_LINES: usize = 10;
+
+/// Reads the blob name a file declares for itself in its front matter, like
+/// `# azsync-blob: configs/${ENVIRONMENT}/app.yaml`.
+///
+/// References to variables are expanded the same way as in the config file,
+/// but referencing a variable that isn't defined is an error, rather than
+/// naming a different blob. Files that don't exist have no annotation.
+pub fn read_blob_annotation(
+    path: &Path,
+    variables: &HashMap<String, String>,
+) -> anyhow::Result<Option<String>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(error) => {
+            return Err(error).with_context(|| format!("Can't read {}", path.display()));
+        }
+    };
+
+    let mut front_matter = Vec::new();
+    file.take(FRONT_MATTER_SIZE)
+        .read_to_end(&mut front_matter)
+        .with_context(|| format!("Can't read {}", path.display()))?;
+    let front_matter = String::from_utf8_lossy(&front_matter);
+    parse_blob_annotation(&front_matter)
+        .map(|name| interpolate_defined(name, variables))
+        .transpose()
+        .with_context(|| format!("Invalid blob annotation in {}", path.display()))
+}
+
+/// Finds the blob name annotation in the first lines of a file.
+///
+/// The annotation can be in any kind of comment, so anything before it on its
+/// line must be punctuation, like `#`, `//`, or `<!--`.
+fn parse_blob_annotation(front_matter: &str) -> Option<&str> {
+    front_matter
+        .lines()
+        .take(FRONT_MATTER_LINES)
+        .find_map(|line| {
+            let (before, after) = line.split_once(BLOB_ANNOTATION)?;
+            if before.chars().any(char::is_alphanumeric) {
+                return None;
+            }
+
+            let name = after.trim();
+            let name = name
+                .strip_suffix("-->")
+                .or_else(|| name.strip_suffix("*/"))
+                .unwrap_or(name)
+                .trim();
+            Some(name).filter(|name| !name.is_empty())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("# azsync-blob: configs/app.yaml\nkey: value" => Some("configs/app.yaml"); "hash comment")]
+    #[test_case("{\n// azsync-blob: app.json\n}" => Some("app.json"); "slash comment")]
+    #[test_case("<!-- azsync-blob: docs/page.html -->" => Some("docs/page.html"); "html comment")]
+    #[test_case("/* azsync-blob: style.css */" => Some("style.css"); "block comment")]
+    #[test_case("---\nazsync-blob: posts/${ENVIRONMENT}.md\n---" => Some("posts/${ENVIRONMENT}.md"); "yaml front matter")]
+    #[test_case("message: see azsync-blob: other.yaml" => None; "not a comment")]
+    #[test_case("# azsync-blob:" => None; "empty")]
+    #[test_case("a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n# azsync-blob: late.yaml" => None; "too late")]
+    fn parse(front_matter: &str) -> Option<&str> {
+        parse_blob_annotation(front_matter)
+    }
+}
@@ -1,33 +1,87 @@
-use std::io::stderr;
+use std::process::ExitCode;
 
-use clap::Parser;
+use clap::{CommandFactory, Parser};
+use clap_complete::CompleteEnv;
 use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{Layer, filter::filter_fn, layer::SubscriberExt, util::SubscriberInitExt};
 
 use crate::{
-    cli::{Cli, CliCommand},
-    commands::Command,
+    cli::{Cli, CliCommand, LogFormat},
+    commands::{Command, Status},
+    firewall,
+    lock::RunLock,
+    log_format::{ACTION_TARGET, SpanTimings, StructuredFormat, StructuredLogs},
+    progress::LogWriter,
+    setup,
+    sync::EXIT_FAILED,
 };
 
-pub async fn run() -> anyhow::Result<()> {
+/// Runs azsync, returning the status to exit with.
+///
+/// Errors are logged and exit with [`EXIT_FAILED`]. Commands that find things
+/// out of sync exit with the statuses in [`crate::sync`] instead of success.
+pub async fn run() -> ExitCode {
+    // Respond to dynamic completion requests from the shell
+    CompleteEnv::with_factory(Cli::command).complete();
+
     // Parse CLI options
     let options = Cli::parse();
     init_tracing(&options);
 
     // Run command
-    let result = match options.subcommand {
-        CliCommand::Completions(command) => command.execute(&options.global).await,
-        CliCommand::Dotenv(command) => command.execute(&options.global).await,
-        CliCommand::File(command) => command.execute(&options.global).await,
-    };
+    let result = run_command(options).await;
 
     // Report errors
-    if let Err(error) = result {
-        for cause in error.chain() {
-            tracing::error!("{cause}");
-        }
+    let error = match result {
+        Ok(status) => return status.into(),
+        Err(error) => error,
+    };
+    for cause in error.chain() {
+        tracing::error!("{cause}");
+    }
+    if let Some(explanation) = firewall::explain(&error) {
+        tracing::error!("{explanation}");
+    }
+
+    ExitCode::from(EXIT_FAILED)
+}
+
+/// Runs the selected subcommand, holding the lock on local files if it
+/// modifies them.
+async fn run_command(options: Cli) -> anyhow::Result<Status> {
+    // Offer to set up azsync the first time it's used
+    if options.subcommand.uses_configured_resources() && setup::is_first_run(&options.global) {
+        setup::run(&options.global).await?;
     }
 
-    Ok(())
+    let _lock = match &options.subcommand {
+        CliCommand::Batch(_)
+        | CliCommand::Dir(_)
+        | CliCommand::Dotenv(_)
+        | CliCommand::File(_)
+        | CliCommand::Keyvault(_)
+        | CliCommand::Resolve(_)
+        | CliCommand::Trash(_) => Some(RunLock::acquire(options.global.wait_for_lock).await?),
+        _ => None,
+    };
+
+    match options.subcommand {
+        CliCommand::Batch(command) => command.execute(&options.global).await,
+        CliCommand::Completions(command) => command.execute(&options.global).await,
+        CliCommand::Dir(command) => command.execute(&options.global).await,
+        CliCommand::Dotenv(command) => command.execute(&options.global).await,
+        CliCommand::ExecTemplate(command) => command.execute(&options.global).await,
+        CliCommand::File(command) => command.execute(&options.global).await,
+        CliCommand::Gc(command) => command.execute(&options.global).await,
+        CliCommand::Inventory(command) => command.execute(&options.global).await,
+        CliCommand::Keyvault(command) => command.execute(&options.global).await,
+        CliCommand::Mv(command) => command.execute(&options.global).await,
+        CliCommand::Resolve(command) => command.execute(&options.global).await,
+        CliCommand::Share(command) => command.execute(&options.global).await,
+        CliCommand::Size(command) => command.execute(&options.global).await,
+        CliCommand::Trash(command) => command.execute(&options.global).await,
+        CliCommand::Whoami(command) => command.execute(&options.global).await,
+    }
 }
 
 /// Setup the tracing subscriber based on the provided CLI options.
@@ -39,16 +93,38 @@ fn init_tracing(options: &Cli) {
         3.. => LevelFilter::TRACE,
     };
 
-    let subscriber = tracing_subscriber::fmt()
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(SpanTimings);
+    let layer = tracing_subscriber::fmt::layer()
         .compact()
-        .with_max_level(filter)
-        .with_writer(stderr)
+        .with_writer(LogWriter::new)
         .with_target(options.global.verbose > 1);
 
+    let structured = match options.global.log_format {
+        LogFormat::Text => None,
+        LogFormat::Json => Some(StructuredFormat::Json),
+        LogFormat::Logfmt => Some(StructuredFormat::Logfmt),
+    };
+    if let Some(format) = structured {
+        let format = StructuredLogs {
+            format,
+            command: options.subcommand.name(),
+        };
+        registry.with(layer.event_format(format)).init();
+        return;
+    }
+
+    // Leave out events for each finished action unless verbose, since the
+    // list of actions was already printed
+    let verbose = options.global.verbose > 1;
+    let actions = filter_fn(move |metadata| verbose || metadata.target() != ACTION_TARGET);
     if options.global.verbose == 0 {
         // Exclude timestamps for non-verbose output
-        subscriber.without_time().init();
+        registry
+            .with(layer.without_time().with_filter(actions))
+            .init();
     } else {
-        subscriber.init();
+        registry.with(layer.with_filter(actions)).init();
     }
 }
@@ -0,0 +1,211 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{Context, bail};
+use azure_core::credentials::TokenCredential;
+use serde::{Deserialize, Serialize};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use typespec_client_core::http::{HttpClient, Method, Request, StatusCode, new_http_client};
+use url::Url;
+
+/// Version of the App Configuration data plane API.
+const API_VERSION: &str = "1.0";
+
+/// Content type of key-values sent to App Configuration.
+const KV_CONTENT_TYPE: &str = "application/vnd.microsoft.appconfig.kv+json";
+
+/// Largest key-value App Configuration accepts, including its key.
+pub const MAX_KEY_VALUE_SIZE: usize = 10 * 1024;
+
+/// A minimal Azure App Configuration client.
+///
+/// Variables are stored as key-values named with a prefix and optionally
+/// grouped under a label.
+pub struct AppConfigClient {
+    endpoint: Url,
+    credential: Arc<dyn TokenCredential>,
+    client: Arc<dyn HttpClient>,
+    label: Option<String>,
+    prefix: String,
+}
+
+/// A key-value in App Configuration.
+#[derive(Clone, Debug)]
+pub struct KeyValue {
+    /// The value, if it has one.
+    pub value: Option<String>,
+
+    /// When the key-value was last modified.
+    pub last_modified: Option<OffsetDateTime>,
+
+    /// The key-value's content type, if it has one.
+    pub content_type: Option<String>,
+
+    /// The key-value's tags.
+    pub tags: HashMap<String, String>,
+}
+
+impl AppConfigClient {
+    /// Creates a client for the store at `endpoint`.
+    pub fn new(
+        endpoint: Url,
+        credential: Arc<dyn TokenCredential>,
+        label: Option<String>,
+        prefix: String,
+    ) -> Self {
+        Self {
+            endpoint,
+            credential,
+            client: new_http_client(),
+            label,
+            prefix,
+        }
+    }
+
+    /// Gets the URL of the store.
+    pub fn endpoint(&self) -> &Url {
+        &self.endpoint
+    }
+
+    /// Gets the key a variable is stored under.
+    pub fn key(&self, name: &str) -> String {
+        format!("{}{name}", self.prefix)
+    }
+
+    /// Gets the URL of the key-value a variable is stored in.
+    pub fn key_url(&self, name: &str) -> anyhow::Result<Url> {
+        let mut url = self.endpoint.clone();
+        url.set_query(None);
+        url.path_segments_mut()
+            .map_err(|()| anyhow::anyhow!("Invalid App Configuration URL"))?
+            .pop_if_empty()
+            .extend(["kv", &self.key(name)]);
+        if let Some(label) = &self.label {
+            url.query_pairs_mut().append_pair("label", label);
+        }
+
+        Ok(url)
+    }
+
+    /// Gets the key-value a variable is stored in, if it exists.
+    pub async fn get(&self, name: &str) -> anyhow::Result<Option<KeyValue>> {
+        let request = self.request(name, Method::Get).await?;
+        let response = self.client.execute_request(&request).await?;
+        let status = response.status();
+        let body = response.into_body().collect().await?;
+        if status == StatusCode::NotFound {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            bail!(
+                "Failed to get {} from App Configuration ({status}): {}",
+                self.key(name),
+                String::from_utf8_lossy(&body)
+            );
+        }
+
+        let key_value: RawKeyValue =
+            serde_json::from_slice(&body).context("Invalid response from App Configuration")?;
+        let last_modified = key_value
+            .last_modified
+            .as_deref()
+            .map(|time| OffsetDateTime::parse(time, &Rfc3339))
+            .transpose()
+            .context("Invalid modified time from App Configuration")?;
+        Ok(Some(KeyValue {
+            value: key_value.value,
+            last_modified,
+            content_type: key_value
+                .content_type
+                .filter(|content_type| !content_type.is_empty()),
+            tags: key_value.tags.unwrap_or_default(),
+        }))
+    }
+
+    /// Sets the value of the key-value a variable is stored in.
+    pub async fn set(
+        &self,
+        name: &str,
+        value: &str,
+        content_type: Option<&str>,
+        tags: HashMap<String, String>,
+    ) -> anyhow::Result<()> {
+        let mut request = self.request(name, Method::Put).await?;
+        request.insert_header("content-type", KV_CONTENT_TYPE);
+        request.set_body(serde_json::to_vec(&NewKeyValue {
+            value,
+            content_type,
+            tags,
+        })?);
+
+        let response = self.client.execute_request(&request).await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.into_body().collect().await?;
+            bail!(
+                "Failed to set {} in App Configuration ({status}): {}",
+                self.key(name),
+                String::from_utf8_lossy(&body)
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Creates an authenticated request for the key-value a variable is
+    /// stored in.
+    async fn request(&self, name: &str, method: Method) -> anyhow::Result<Request> {
+        let scope = format!("{}/.default", self.endpoint.origin().ascii_serialization());
+        let token = self
+            .credential
+            .get_token(&[&scope], None)
+            .await
+            .context("Failed to get a token for App Configuration")?;
+
+        let mut url = self.key_url(name)?;
+        url.query_pairs_mut()
+            .append_pair("api-version", API_VERSION);
+        let mut request = Request::new(url, method);
+        request.insert_header("authorization", format!("Bearer {}", token.token.secret()));
+        Ok(request)
+    }
+}
+
+/// A key-value as returned by App Configuration.
+#[derive(Deserialize)]
+struct RawKeyValue {
+    value: Option<String>,
+    last_modified: Option<String>,
+    content_type: Option<String>,
+    tags: Option<HashMap<String, String>>,
+}
+
+/// A key-value as sent to App Configuration.
+#[derive(Serialize)]
+struct NewKeyValue<'a> {
+    value: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_type: Option<&'a str>,
+    tags: HashMap<String, String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::storage::PlaceholderCredential;
+
+    #[test_case("https://store.azconfig.io", None, "", "API_KEY" => "https://store.azconfig.io/kv/API_KEY"; "plain")]
+    #[test_case("https://store.azconfig.io/", None, "myapp:", "API_KEY" => "https://store.azconfig.io/kv/myapp:API_KEY"; "prefix")]
+    #[test_case("https://store.azconfig.io", Some("dev"), "", "API_KEY" => "https://store.azconfig.io/kv/API_KEY?label=dev"; "label")]
+    #[test_case("https://store.azconfig.io", None, "myapp/", "API_KEY" => "https://store.azconfig.io/kv/myapp%2FAPI_KEY"; "encoded prefix")]
+    fn key_url(endpoint: &str, label: Option<&str>, prefix: &str, name: &str) -> String {
+        let client = AppConfigClient::new(
+            endpoint.parse().unwrap(),
+            Arc::new(PlaceholderCredential),
+            label.map(ToString::to_string),
+            prefix.to_string(),
+        );
+        client.key_url(name).unwrap().to_string()
+    }
+}
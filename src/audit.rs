@@ -0,0 +1,341 @@
+use std::{
+    env::var,
+    fs::{OpenOptions, create_dir_all, read},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+};
+
+use anyhow::Context;
+use azure_core::credentials::TokenCredential;
+use azure_storage_blob::{AppendBlobClient, models::AppendBlobClientCreateOptions};
+use hmac::{Hmac, Mac};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use tokio::sync::OnceCell;
+use tracing::info;
+use typespec_client_core::http::StatusCode;
+use url::Url;
+
+use crate::{log_format::ACTION_TARGET, metrics, storage::parse_blob_url};
+
+/// Where to record executed actions.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuditConfig {
+    /// A local file to append records to.
+    pub path: Option<PathBuf>,
+
+    /// The URL of an append blob to append records to.
+    ///
+    /// The blob is created if it doesn't exist.
+    pub blob: Option<Url>,
+}
+
+/// A change made by an executed action.
+#[derive(Clone, Debug, Serialize)]
+pub struct AuditEntry {
+    /// The kind of action, like `push` or `delete`.
+    pub action: &'static str,
+
+    /// The remote resource that was synchronized.
+    pub resource: String,
+
+    /// A hash of the value that was replaced, if known.
+    ///
+    /// Secret values are hashed with [`hash_secret`].
+    pub before: Option<String>,
+
+    /// A hash of the new value, if known.
+    ///
+    /// Secret values are hashed with [`hash_secret`].
+    pub after: Option<String>,
+
+    /// How many bytes were transferred, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<u64>,
+}
+
+/// An append-only log of executed actions.
+///
+/// If no destinations are configured, nothing is recorded.
+#[derive(Default)]
+pub struct AuditLog {
+    /// The user running azsync.
+    user: String,
+
+    /// The local file to append records to.
+    path: Option<PathBuf>,
+
+    /// The append blob to append records to.
+    blob: Option<AuditBlob>,
+}
+
+impl AuditLog {
+    /// Creates an audit log for the configured destinations.
+    pub fn new(config: &AuditConfig, credential: Arc<dyn TokenCredential>) -> anyhow::Result<Self> {
+        let blob = config
+            .blob
+            .as_ref()
+            .map(|url| AuditBlob::new(url, credential))
+            .transpose()?;
+        let user = var("USER")
+            .or_else(|_| var("USERNAME"))
+            .unwrap_or_else(|_| "unknown".to_string());
+
+        Ok(Self {
+            user,
+            path: config.path.clone(),
+            blob,
+        })
+    }
+
+    /// Records an executed action.
+    ///
+    /// The action is also logged for structured logs, which include how long
+    /// it took.
+    pub async fn record(&self, entry: AuditEntry) -> anyhow::Result<()> {
+        metrics::record_action(entry.action, entry.bytes);
+        info!(
+            target: ACTION_TARGET,
+            action = entry.action,
+            resource = entry.resource,
+            bytes = entry.bytes,
+            "Finished {} of {}",
+            entry.action,
+            entry.resource
+        );
+        if self.path.is_none() && self.blob.is_none() {
+            return Ok(());
+        }
+
+        let record = AuditRecord {
+            time: OffsetDateTime::now_utc().format(&Rfc3339)?,
+            user: &self.user,
+            entry,
+        };
+        let mut line = serde_json::to_string(&record)?;
+        line.push('\n');
+
+        if let Some(path) = &self.path {
+            append_to_file(path, &line)
+                .with_context(|| format!("Failed to write audit log {}", path.display()))?;
+        }
+        if let Some(blob) = &self.blob {
+            blob.append(line)
+                .await
+                .context("Failed to write audit log blob")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Computes a hash of a value that isn't secret, like a file's contents.
+pub fn hash_value(value: impl AsRef<[u8]>) -> String {
+    format!("{:x}", Sha256::digest(value.as_ref()))
+}
+
+/// Where the key secret values are hashed with is kept.
+pub const HASH_KEY_FILE: &str = ".azsync/hash.key";
+
+/// The key secret values are hashed with, once loaded.
+static HASH_KEY: OnceLock<[u8; HASH_KEY_LEN]> = OnceLock::new();
+
+/// The length of the key secret values are hashed with in bytes.
+const HASH_KEY_LEN: usize = 32;
+
+/// Loads the key secret values are hashed with in this directory, creating it
+/// if it doesn't exist yet.
+///
+/// This must be called before [`hash_secret`] so hashes can be compared with
+/// ones recorded before.
+pub fn load_hash_key() -> anyhow::Result<()> {
+    if HASH_KEY.get().is_some() {
+        return Ok(());
+    }
+
+    let path = Path::new(HASH_KEY_FILE);
+    let key = match read(path) {
+        Ok(key) => key,
+        Err(error) if error.kind() == ErrorKind::NotFound => {
+            create_hash_key(path).with_context(|| format!("Failed to create {HASH_KEY_FILE}"))?
+        }
+        Err(error) => {
+            return Err(error).with_context(|| format!("Failed to read {HASH_KEY_FILE}"));
+        }
+    };
+    let key = key
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("{HASH_KEY_FILE} must be {HASH_KEY_LEN} bytes"))?;
+    let _ = HASH_KEY.set(key);
+
+    Ok(())
+}
+
+/// Creates a random key to hash secret values with, readable only by the
+/// current user, returning it.
+///
+/// If another process created the key first, its key is returned instead.
+fn create_hash_key(path: &Path) -> anyhow::Result<Vec<u8>> {
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+    let mut key = vec![0; HASH_KEY_LEN];
+    SystemRandom::new()
+        .fill(&mut key)
+        .map_err(|_| anyhow::anyhow!("Failed to generate a key"))?;
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    match options.open(path) {
+        Ok(mut file) => file.write_all(&key)?,
+        Err(error) if error.kind() == ErrorKind::AlreadyExists => return Ok(read(path)?),
+        Err(error) => return Err(error.into()),
+    }
+
+    Ok(key)
+}
+
+/// Computes a keyed hash of a secret value, like a variable's value.
+///
+/// Unlike [`hash_value`], the hash can't be used to guess the value without
+/// the key in [`HASH_KEY_FILE`], so it's safe to write to logs and local
+/// files. Hashes are only comparable with ones computed in the same
+/// directory.
+///
+/// # Panics
+///
+/// Panics if [`load_hash_key`] wasn't called first.
+pub fn hash_secret(value: impl AsRef<[u8]>) -> String {
+    let key = hash_key();
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(value.as_ref());
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+/// Gets the key secret values are hashed with.
+#[cfg(not(test))]
+fn hash_key() -> &'static [u8; HASH_KEY_LEN] {
+    HASH_KEY.get().expect("load_hash_key must be called first")
+}
+
+/// Gets the key secret values are hashed with, which is random in tests since
+/// they don't load one.
+#[cfg(test)]
+fn hash_key() -> &'static [u8; HASH_KEY_LEN] {
+    HASH_KEY.get_or_init(|| {
+        let mut key = [0; HASH_KEY_LEN];
+        SystemRandom::new()
+            .fill(&mut key)
+            .expect("random keys can be generated");
+        key
+    })
+}
+
+/// A record in the audit log.
+#[derive(Serialize)]
+struct AuditRecord<'a> {
+    time: String,
+    user: &'a str,
+    #[serde(flatten)]
+    entry: AuditEntry,
+}
+
+/// An append blob that audit records are written to.
+struct AuditBlob {
+    client: AppendBlobClient,
+
+    /// Whether the blob is known to exist.
+    created: OnceCell<()>,
+}
+
+impl AuditBlob {
+    fn new(url: &Url, credential: Arc<dyn TokenCredential>) -> anyhow::Result<Self> {
+        let (endpoint, container_name, blob_name) =
+            parse_blob_url(url).context("Invalid audit log blob URL")?;
+        let client = AppendBlobClient::new(&endpoint, container_name, blob_name, credential, None)?;
+
+        Ok(Self {
+            client,
+            created: OnceCell::new(),
+        })
+    }
+
+    /// Appends data to the blob, creating it first if needed.
+    async fn append(&self, data: String) -> anyhow::Result<()> {
+        self.created
+            .get_or_try_init(|| async {
+                let result = self
+                    .client
+                    .create(Some(AppendBlobClientCreateOptions {
+                        if_none_match: Some("*".to_string()),
+                        ..Default::default()
+                    }))
+                    .await;
+                match result {
+                    Ok(_) => Ok(()),
+                    Err(error) if error.http_status() == Some(StatusCode::Conflict) => Ok(()),
+                    Err(error) => Err(error),
+                }
+            })
+            .await?;
+
+        let data = data.into_bytes();
+        let len = data.len() as u64;
+        self.client
+            .append_block(data.try_into()?, len, None)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Appends a line to a local file, creating it if needed.
+fn append_to_file(path: &Path, line: &str) -> std::io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(line.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn record_format() {
+        let record = AuditRecord {
+            time: "2024-01-01T00:00:00Z".to_string(),
+            user: "someone",
+            entry: AuditEntry {
+                action: "push",
+                resource: "https://account.blob.core.windows.net/container/file.txt".to_string(),
+                before: None,
+                after: Some(hash_value("abc")),
+                bytes: None,
+            },
+        };
+
+        assert_eq!(
+            serde_json::to_string(&record).unwrap(),
+            concat!(
+                r#"{"time":"2024-01-01T00:00:00Z","user":"someone","action":"push","#,
+                r#""resource":"https://account.blob.core.windows.net/container/file.txt","#,
+                r#""before":null,"#,
+                r#""after":"ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"}"#,
+            )
+        );
+    }
+
+    #[test]
+    fn secret_hashes_are_keyed() {
+        assert_eq!(hash_secret("abc"), hash_secret("abc"));
+        assert_ne!(hash_secret("abc"), hash_value("abc"));
+        assert_ne!(hash_secret("abc"), hash_secret("abd"));
+    }
+}
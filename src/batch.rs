@@ -0,0 +1,160 @@
+use std::{collections::BTreeMap, fs::read_to_string, path::Path};
+
+use anyhow::{Context, bail};
+use serde::Deserialize;
+use serde_yaml_ng::Value;
+
+/// A file listing synchronization jobs to run together with `azsync batch`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JobFile {
+    /// The jobs to run, in order.
+    pub jobs: Vec<Job>,
+}
+
+impl JobFile {
+    /// Loads a job file.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let source =
+            read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let file: Self = serde_yaml_ng::from_str(&source)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        if file.jobs.is_empty() {
+            bail!("{} doesn't list any jobs", path.display());
+        }
+
+        Ok(file)
+    }
+}
+
+/// A command to run as part of a batch.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Job {
+    /// A name to show for the job instead of its command.
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// The command to run, like `dotenv`.
+    pub command: String,
+
+    /// The profile in the config file to use.
+    #[serde(default)]
+    pub profile: Option<String>,
+
+    /// Options to pass to the command, by their long names without the
+    /// leading `--`.
+    ///
+    /// `true` passes a flag, and a list passes the option once for each item.
+    #[serde(default)]
+    pub options: BTreeMap<String, Value>,
+
+    /// Positional arguments to pass to the command, like file names.
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+impl Job {
+    /// Gets the name to show for this job.
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.command)
+    }
+
+    /// Builds the command line that runs this job, starting with `azsync`.
+    pub fn command_line(&self) -> anyhow::Result<Vec<String>> {
+        let mut args = vec!["azsync".to_string()];
+        if let Some(profile) = &self.profile {
+            args.extend(["--profile".to_string(), profile.clone()]);
+        }
+        args.push(self.command.clone());
+
+        for (name, value) in &self.options {
+            let flag = format!("--{name}");
+            match value {
+                Value::Bool(true) => args.push(flag),
+                Value::Bool(false) | Value::Null => {}
+                Value::Sequence(values) => {
+                    for value in values {
+                        args.extend([flag.clone(), scalar(name, value)?]);
+                    }
+                }
+                value => args.extend([flag, scalar(name, value)?]),
+            }
+        }
+        args.extend(self.args.iter().cloned());
+
+        Ok(args)
+    }
+}
+
+/// Formats the value of an option as an argument.
+fn scalar(name: &str, value: &Value) -> anyhow::Result<String> {
+    match value {
+        Value::String(value) => Ok(value.clone()),
+        Value::Number(value) => Ok(value.to_string()),
+        Value::Bool(value) => Ok(value.to_string()),
+        _ => bail!("Option {name} must be a string, number, or boolean, or a list of them"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const JOBS: &str = "\
+jobs:
+  - command: dotenv
+    profile: dev
+    options:
+      sync-mode: pull
+      only: [DB_*, REDIS_URL]
+      explain: true
+      check-only: false
+      max-concurrency: 4
+  - name: configs
+    command: file
+    args: [config.json, appsettings.json]
+";
+
+    #[test]
+    fn command_lines() {
+        let file: JobFile = serde_yaml_ng::from_str(JOBS).unwrap();
+
+        let lines: Vec<_> = file
+            .jobs
+            .iter()
+            .map(|job| (job.name(), job.command_line().unwrap().join(" ")))
+            .collect();
+
+        assert_eq!(
+            lines,
+            [
+                (
+                    "dotenv",
+                    "azsync --profile dev dotenv --explain --max-concurrency 4 --only DB_* --only REDIS_URL --sync-mode pull".to_string()
+                ),
+                (
+                    "configs",
+                    "azsync file config.json appsettings.json".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_option() {
+        let file: JobFile =
+            serde_yaml_ng::from_str("jobs:\n  - command: file\n    options:\n      tag: {a: b}\n")
+                .unwrap();
+        assert!(file.jobs[0].command_line().is_err());
+    }
+
+    #[test]
+    fn unknown_field() {
+        assert!(
+            serde_yaml_ng::from_str::<JobFile>("jobs:\n  - command: file\n    dir: x\n").is_err()
+        );
+    }
+}
@@ -1,19 +1,51 @@
 mod app;
+mod app_config;
+mod batch;
 mod completions;
+mod dir;
 mod dotenv;
+mod duration;
 mod file;
+mod gc;
 mod global;
+mod inventory;
 mod key_vault;
+mod keyvault;
 mod maybe_env;
+mod mv;
+mod resolve;
+mod share;
+mod size;
 mod storage;
 mod sync;
+mod template;
+mod trash;
+mod usage;
+mod watch;
+mod whoami;
 
 pub use app::*;
+pub use app_config::*;
+pub use batch::*;
 pub use completions::*;
+pub use dir::*;
 pub use dotenv::*;
+pub use duration::*;
 pub use file::*;
+pub use gc::*;
 pub use global::*;
+pub use inventory::*;
 pub use key_vault::*;
+pub use keyvault::*;
 pub use maybe_env::*;
+pub use mv::*;
+pub use resolve::*;
+pub use share::*;
+pub use size::*;
 pub use storage::*;
 pub use sync::*;
+pub use template::*;
+pub use trash::*;
+pub use usage::*;
+pub use watch::*;
+pub use whoami::*;
@@ -1,7 +1,11 @@
 use clap::{Parser, Subcommand};
 use clap_cargo::style::CLAP_STYLING;
 
-use crate::cli::{CompletionsOptions, GlobalOptions, SyncDotenvOptions, SyncFileOptions};
+use crate::cli::{
+    BatchOptions, CompletionsOptions, DotenvBackend, ExecTemplateOptions, GcOptions, GlobalOptions,
+    InventoryOptions, MoveOptions, ResolveOptions, ShareOptions, SyncDirOptions, SyncDotenvOptions,
+    SyncFileOptions, SyncSecretsOptions, TrashOptions, UsageOptions, WhoamiOptions,
+};
 
 /// Quickly synchronize local files with Azure.
 ///
@@ -26,13 +30,37 @@ pub struct Cli {
 
 /// A subcommand to execute.
 #[derive(Clone, Debug, Subcommand)]
+#[expect(
+    clippy::large_enum_variant,
+    reason = "Only one command is parsed per run"
+)]
 pub enum CliCommand {
+    /// Run several synchronization jobs listed in a YAML file.
+    ///
+    /// Every job is planned first, checking that each change would succeed,
+    /// and then they're all confirmed at once. If any job can't be planned,
+    /// nothing is changed. Jobs that fail while running don't stop the rest,
+    /// but make the batch fail.
+    Batch(BatchOptions),
+
     /// Generate shell completions.
     ///
     /// Completions are written to stdout. Save them to the appropriate place
     /// for your shell.
     Completions(CompletionsOptions),
 
+    /// Synchronize a directory with Azure, including its subdirectories.
+    ///
+    /// Each file is synchronized with a blob named after its path relative to
+    /// the directory, so the directory's structure is kept. Files that only
+    /// exist remotely are pulled, and files that only exist locally are
+    /// pushed.
+    ///
+    /// Without --prefix, the whole container is synchronized with the
+    /// directory, which is also what `azsync container` does.
+    #[command(visible_alias = "container")]
+    Dir(SyncDirOptions),
+
     /// Synchronize variables defined in your local dotenv file with Azure.
     ///
     /// This only synchronizes variables defined in your dotenv file (or dotenv
@@ -44,8 +72,128 @@ pub enum CliCommand {
     /// you when either pushing or pulling variables.
     Dotenv(SyncDotenvOptions),
 
+    /// Render a template file using the values of variables.
+    ///
+    /// Placeholders like `${VAR}` or `$VAR` in the template are replaced with
+    /// the values of variables from your dotenv file, falling back to
+    /// environment variables. Everything else is copied as-is, and
+    /// placeholders preceded by a backslash are left alone.
+    ExecTemplate(ExecTemplateOptions),
+
     /// Synchronize files with Azure.
     File(SyncFileOptions),
+
+    /// Clean up blocks left behind by failed uploads.
+    ///
+    /// Blobs that were never committed because their upload failed are
+    /// deleted. Blobs that have been committed, but still have blocks left
+    /// over from a failed upload, are reported instead. Azure discards those
+    /// blocks automatically after one week.
+    Gc(GcOptions),
+
+    /// List every file and variable azsync tracks.
+    ///
+    /// Files declared in the config file and variables in your dotenv file
+    /// are listed with the blob or secret they're synchronized with, when it
+    /// was last synchronized, and a hash and size of the local copy. Values
+    /// themselves are never written. Use `--output json` to hand the list to
+    /// other tools, like after each deployment.
+    Inventory(InventoryOptions),
+
+    /// Synchronize Key Vault secrets with individual files.
+    ///
+    /// Each secret is stored in its own file, which is useful for
+    /// certificates, keys, and configuration that's kept in Key Vault as a
+    /// whole. Secrets are synchronized the same way as with `azsync dotenv`,
+    /// using the modified time of each file.
+    Keyvault(SyncSecretsOptions),
+
+    /// Rename a remote blob.
+    ///
+    /// The blob's contents are copied to the new name along with its metadata,
+    /// including when the synchronized file was last modified, and then the
+    /// original blob is deleted. If either blob changes while renaming, the
+    /// rename fails.
+    Mv(MoveOptions),
+
+    /// Resolve conflicts written to a conflict report.
+    ///
+    /// The command that reported the conflicts is run again with the same
+    /// options, but only conflicts that were decided on are synchronized. Run
+    /// this from the same directory. Conflicts whose local or remote copy
+    /// changed since they were reported are skipped.
+    Resolve(ResolveOptions),
+
+    /// Create a temporary download link for a remote blob.
+    ///
+    /// The link is signed with a user delegation key obtained using your Azure
+    /// credential, so you need permission to read the blob yourself. The URL is
+    /// written to stdout.
+    Share(ShareOptions),
+
+    /// Report how much space remote blobs take up.
+    ///
+    /// Blobs are counted and their sizes added up by top-level folder or by
+    /// extension, so you can keep an eye on what synchronizing is
+    /// accumulating. The report is written to stdout as a table, or as JSON.
+    Size(UsageOptions),
+
+    /// Manage local files moved to the trash.
+    ///
+    /// When deletions are mirrored with `--delete`, local files are moved to
+    /// `.azsync/trash` instead of being deleted, unless `--hard-delete` is
+    /// passed.
+    Trash(TrashOptions),
+
+    /// Show which Azure identity azsync is using.
+    ///
+    /// Reports which credential was used to sign in, who it belongs to, and
+    /// which scopes its tokens are granted. Where possible, the roles assigned
+    /// to it on the configured storage account and Key Vault are listed too.
+    Whoami(WhoamiOptions),
+}
+
+impl CliCommand {
+    /// The name the command is run with, like `dotenv`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            CliCommand::Batch(_) => "batch",
+            CliCommand::Completions(_) => "completions",
+            CliCommand::Dir(_) => "dir",
+            CliCommand::Dotenv(_) => "dotenv",
+            CliCommand::ExecTemplate(_) => "exec-template",
+            CliCommand::File(_) => "file",
+            CliCommand::Gc(_) => "gc",
+            CliCommand::Inventory(_) => "inventory",
+            CliCommand::Keyvault(_) => "keyvault",
+            CliCommand::Mv(_) => "mv",
+            CliCommand::Resolve(_) => "resolve",
+            CliCommand::Share(_) => "share",
+            CliCommand::Size(_) => "size",
+            CliCommand::Trash(_) => "trash",
+            CliCommand::Whoami(_) => "whoami",
+        }
+    }
+
+    /// Whether this command synchronizes with Azure resources that weren't
+    /// passed on the command line, so they come from the config file or the
+    /// environment.
+    pub fn uses_configured_resources(&self) -> bool {
+        match self {
+            CliCommand::Dir(options) => options.azure_storage.is_unset(),
+            CliCommand::Dotenv(options) => {
+                options.command.is_none()
+                    && options.backend == DotenvBackend::KeyVault
+                    && options.key_vault.is_unset()
+            }
+            CliCommand::File(options) => options.azure_storage.is_unset(),
+            CliCommand::Inventory(options) => {
+                options.azure_storage.is_unset() && options.key_vault.is_unset()
+            }
+            CliCommand::Keyvault(options) => options.key_vault.is_unset(),
+            _ => false,
+        }
+    }
 }
 
 const AFTER_HELP: &str = concat!(
@@ -0,0 +1,61 @@
+use clap::{Args, ValueEnum};
+use clap_complete::ArgValueCompleter;
+use url::Url;
+
+use crate::{
+    cli::{MaybeEnv, complete_env_var},
+    dotenv::DotenvFile,
+};
+
+/// Where dotenv variables are stored in Azure.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default, ValueEnum)]
+pub enum DotenvBackend {
+    /// Secrets in Key Vault.
+    #[default]
+    #[value(name = "key-vault")]
+    KeyVault,
+
+    /// Key-values in App Configuration.
+    #[value(name = "app-config")]
+    AppConfig,
+}
+
+/// Options for configuring the App Configuration store.
+#[derive(Clone, Debug, Args)]
+#[command(next_help_heading = "App Configuration")]
+pub struct AppConfigOptions {
+    /// The URL to the App Configuration store, like
+    /// `https://my-store.azconfig.io`.
+    ///
+    /// Supports the `env:` scheme, like --key-vault-url. If not provided,
+    /// `env:APP_CONFIG_URL` is used.
+    #[arg(long, add = ArgValueCompleter::new(complete_env_var))]
+    pub app_config_url: Option<MaybeEnv<Url>>,
+
+    /// The label of the key-values to synchronize with.
+    ///
+    /// If not provided, key-values without a label are used.
+    #[arg(long)]
+    pub label: Option<String>,
+
+    /// A prefix to add to each variable's name to get its key.
+    ///
+    /// For example, with `--key-prefix myapp:`, `API_KEY` is stored as
+    /// `myapp:API_KEY`.
+    #[arg(long, default_value = "")]
+    pub key_prefix: String,
+}
+
+impl AppConfigOptions {
+    /// Resolves the App Configuration store's URL.
+    pub fn resolve(&self, dotenv: Option<&DotenvFile>) -> anyhow::Result<Url> {
+        let url = self
+            .app_config_url
+            .clone()
+            .unwrap_or_else(|| MaybeEnv::EnvVar("APP_CONFIG_URL".to_string()))
+            .resolve(dotenv)?
+            .into_owned();
+
+        Ok(url)
+    }
+}
@@ -0,0 +1,35 @@
+use std::{num::NonZeroUsize, path::PathBuf};
+
+use clap::Args;
+
+/// Options for running several synchronization jobs from a job file.
+#[derive(Clone, Debug, Args)]
+pub struct BatchOptions {
+    /// The YAML file listing the jobs to run.
+    ///
+    /// Each job names a command (`dir`, `dotenv`, `file`, or `keyvault`), and
+    /// can set the profile to use, options by their long names, and
+    /// positional arguments.
+    #[arg(value_name = "PATH")]
+    pub jobs: PathBuf,
+
+    /// How many jobs to run at once.
+    ///
+    /// Jobs that run at the same time shouldn't synchronize the same files.
+    #[arg(long, value_name = "N", default_value = "1")]
+    pub parallel_jobs: NonZeroUsize,
+
+    /// Only plan every job and check that each change would succeed, without
+    /// making any changes.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Don't ask for confirmation before running the jobs.
+    ///
+    /// Otherwise, the batch is confirmed once, and each job still asks before
+    /// deleting anything or if its plan changed since it was confirmed.
+    ///
+    /// This is a potentially destructive action. Use with caution.
+    #[arg(long, short = 'y')]
+    pub no_confirm: bool,
+}
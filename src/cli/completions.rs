@@ -13,6 +13,22 @@ pub struct CompletionsOptions {
         doc = " If not provided, a default shell will be selected for your platform."
     )]
     pub shell: Shell,
+
+    /// Generate completions that call back into azsync while typing.
+    ///
+    /// This allows values like `env:` variable names to be completed from
+    /// local dotenv files. azsync must be on your `PATH` for these completions
+    /// to work. Nushell is not supported.
+    #[arg(long)]
+    pub dynamic: bool,
+
+    /// The name azsync is run as, if it's installed or wrapped under a
+    /// different name (like `azs`).
+    ///
+    /// Completions are registered for this command, and dynamic completions
+    /// call back into it.
+    #[arg(long, value_name = "NAME")]
+    pub bin_name: Option<String>,
 }
 
 /// A shell that completions can be generated for.
@@ -0,0 +1,173 @@
+use std::{num::NonZeroUsize, path::PathBuf};
+
+use clap::Args;
+use clap_complete::ArgValueCompleter;
+
+use crate::{
+    cli::{
+        AzureStorageOptions, Compression, ConflictStrategy, MaybeEnv, SyncOptions, WatchOptions,
+        complete_env_var, parse_size,
+    },
+    conflicts::{CONFLICTS_FILE, Decisions},
+    glob::Pattern,
+};
+
+/// Options for synchronizing a directory.
+#[derive(Clone, Debug, Args)]
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "Each flag is an independent switch"
+)]
+pub struct SyncDirOptions {
+    /// The local directory to sync.
+    ///
+    /// Files in subdirectories are synchronized too, and blobs found under
+    /// the prefix are pulled into the matching subdirectories, which are
+    /// created if needed.
+    pub path: PathBuf,
+
+    /// The prefix of the blobs the directory is synchronized with.
+    ///
+    /// Each file is stored in a blob named with this prefix followed by its
+    /// path relative to the directory, using `/` as the separator. For
+    /// example, with `--prefix configs`, the file `app/settings.json` is
+    /// stored in the blob `configs/app/settings.json`.
+    #[arg(long, default_value = "")]
+    pub prefix: String,
+
+    /// Skip files and blobs matching this pattern (repeatable).
+    ///
+    /// Patterns are matched against paths relative to the directory. `*`
+    /// matches anything except `/`, `**` matches anything, and `?` matches a
+    /// single character. Patterns without a `/`, like `*.log`, match any file
+    /// or directory name in the path, and patterns ending with `/` match
+    /// everything in a directory.
+    #[arg(long, value_name = "PATTERN")]
+    pub exclude: Vec<Pattern>,
+
+    /// Compare and record checksums of synchronized files.
+    ///
+    /// See `azsync file --help` for details.
+    #[arg(long)]
+    pub checksum: bool,
+
+    /// Ignore differences in line endings and trailing newlines when
+    /// comparing checksums of text files.
+    ///
+    /// See `azsync file --help` for details.
+    #[arg(long, requires = "checksum")]
+    pub normalize_text: bool,
+
+    /// Encrypt pushed files with this key, and decrypt pulled ones.
+    ///
+    /// The key is read from a file, or from a variable with `env:VAR`. See
+    /// `azsync file --help` for details.
+    #[arg(long, value_name = "KEY_FILE", add = ArgValueCompleter::new(complete_env_var))]
+    pub encrypt_key: Option<MaybeEnv<PathBuf>>,
+
+    /// Compress pushed files.
+    ///
+    /// See `azsync file --help` for details.
+    #[arg(long, value_enum, value_name = "ALGORITHM")]
+    pub compress: Option<Compression>,
+
+    /// Mirror deletions, so one side ends up matching the other.
+    ///
+    /// See `azsync file --help` for details.
+    #[arg(long)]
+    pub delete: bool,
+
+    /// Delete local files outright when mirroring deletions.
+    ///
+    /// See `azsync file --help` for details.
+    #[arg(long, requires = "delete")]
+    pub hard_delete: bool,
+
+    /// How to handle files that changed both locally and remotely.
+    ///
+    /// See `azsync file --help` for details.
+    #[arg(long, value_enum, default_value_t)]
+    pub on_conflict: ConflictStrategy,
+
+    /// Where to write conflicts with `--on-conflict report`.
+    #[arg(long, value_name = "PATH", default_value = CONFLICTS_FILE)]
+    pub conflict_report: PathBuf,
+
+    /// How to resolve reported conflicts, when run by `azsync resolve`.
+    #[arg(skip)]
+    pub decisions: Decisions,
+
+    /// Push files even if they appear to contain secrets.
+    #[arg(long)]
+    pub allow_secrets: bool,
+
+    /// Write pulled files into this directory instead of in place.
+    ///
+    /// Pulled files are written to the same paths relative to this directory
+    /// as they have relative to the synchronized directory. See
+    /// `azsync file --help` for details.
+    #[arg(long)]
+    pub stage_dir: Option<PathBuf>,
+
+    /// Where to write pulled files while they're downloading.
+    ///
+    /// By default, each file is downloaded next to its destination. See
+    /// `azsync file --help` for details.
+    #[arg(long, env = "AZSYNC_TEMP_DIR")]
+    pub temp_dir: Option<PathBuf>,
+
+    /// Keep interrupted downloads and continue them on the next run.
+    ///
+    /// See `azsync file --help` for details.
+    #[arg(long)]
+    pub resume: bool,
+
+    /// Replace parts of paths that aren't valid Unicode in blob names.
+    ///
+    /// See `azsync file --help` for details.
+    #[arg(long)]
+    pub lossy_names: bool,
+
+    /// Write the properties of each pulled blob to a sidecar file.
+    ///
+    /// Sidecar files are never pushed. See `azsync file --help` for details.
+    #[arg(long)]
+    pub sidecar: bool,
+
+    /// Preserve the owner and access control list of each file (Windows
+    /// only).
+    ///
+    /// See `azsync file --help` for details.
+    #[arg(long)]
+    pub preserve_acl: bool,
+
+    /// Report progress while pushing and pulling files.
+    #[arg(long)]
+    pub progress: bool,
+
+    /// The size of each block when uploading files in blocks, like `8MiB`.
+    ///
+    /// See `azsync file --help` for details.
+    #[arg(long, value_name = "SIZE", default_value = "4MiB", value_parser = parse_size)]
+    pub block_size: u64,
+
+    /// How many blocks of each file to upload at once.
+    #[arg(long, value_name = "N", default_value = "4")]
+    pub block_concurrency: NonZeroUsize,
+
+    /// Skip planning if nothing changed since the last identical run.
+    #[arg(long)]
+    pub idempotent: bool,
+
+    /// Options for configuring how to synchronize with Azure.
+    #[command(flatten)]
+    pub sync: SyncOptions,
+
+    /// Options for watching the directory for changes.
+    #[command(flatten)]
+    pub watch: WatchOptions,
+
+    /// Options for configuring the Storage Account.
+    #[command(flatten)]
+    pub azure_storage: AzureStorageOptions,
+}
@@ -1,12 +1,36 @@
-use std::path::PathBuf;
+use std::{
+    fmt::{Display, Formatter},
+    path::PathBuf,
+    str::FromStr,
+};
 
-use clap::Args;
+use anyhow::{Context, ensure};
+use clap::{Args, Subcommand, ValueEnum};
+use url::Url;
 
-use crate::cli::{KeyVaultOptions, SyncOptions};
+use crate::{
+    cli::{
+        AppConfigOptions, ConflictStrategy, DotenvBackend, KeyVaultOptions, MaybeEnv, SyncOptions,
+        WatchOptions,
+    },
+    conflicts::{CONFLICTS_FILE, Decisions},
+    dotenv::MAX_SECRET_VALUE_SIZE,
+    glob::Pattern,
+};
 
 /// Options for configuring syncing a dotenv file.
 #[derive(Clone, Debug, Args)]
+#[command(args_conflicts_with_subcommands = true)]
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "Each flag is an independent switch"
+)]
 pub struct SyncDotenvOptions {
+    /// Another operation to perform on the dotenv file instead of
+    /// synchronizing it.
+    #[command(subcommand)]
+    pub command: Option<DotenvCommand>,
+
     /// The dotenv template file.
     ///
     /// If present, variable names defined in it will be the ONLY variables
@@ -17,9 +41,14 @@ pub struct SyncDotenvOptions {
     /// Note that values defined in the template file will not be used, nor
     /// will that file be modified in any manner.
     ///
+    /// To share a template through Blob Storage, pass the blob's URL, or use
+    /// the `blob:` scheme for a blob in the configured container. For example,
+    /// `blob:.env.example` uses the blob named `.env.example`. Remote templates
+    /// must exist.
+    ///
     /// If the file does not exist, this option is ignored.
     #[arg(long, short = 't', default_value = ".env.example")]
-    pub template_file: PathBuf,
+    pub template_file: TemplateSource,
 
     /// Disable --template-file.
     ///
@@ -33,11 +62,521 @@ pub struct SyncDotenvOptions {
     #[arg(long)]
     pub no_template: bool,
 
+    /// Only synchronize these variables (repeatable).
+    ///
+    /// Other variables are left alone, even if they're in the template file.
+    /// Variables passed here are synchronized even if they aren't.
+    #[arg(long = "var", value_name = "NAME")]
+    pub vars: Vec<String>,
+
+    /// Only synchronize variables whose names match these patterns, like
+    /// `DB_*`.
+    ///
+    /// Patterns can be separated by commas or passed more than once. `*`
+    /// matches any number of characters and `?` matches one. This narrows down
+    /// the variables from the template file, the dotenv file, or --var.
+    #[arg(long, value_name = "PATTERN", value_delimiter = ',')]
+    pub only: Vec<Pattern>,
+
+    /// Don't synchronize variables whose names match these patterns.
+    ///
+    /// Patterns are matched like --only, and exclusions take precedence.
+    #[arg(long, value_name = "PATTERN", value_delimiter = ',')]
+    pub exclude: Vec<Pattern>,
+
+    /// What to do with variables whose secrets were deleted from Key Vault.
+    ///
+    /// Deleted secrets are found using Key Vault's soft-delete, so they're
+    /// only detected until they're purged. Variables without a secret that
+    /// was deleted are pushed as usual.
+    #[arg(long, value_enum, default_value_t)]
+    pub on_remote_delete: RemoteDeleteStrategy,
+
+    /// How to handle variables that changed both locally and remotely.
+    ///
+    /// A variable is in conflict when it has different values locally and in
+    /// Key Vault, and both changed since it was last synchronized according to
+    /// `.azsync/manifest.json`. With `newest`, the newer value is kept. With
+    /// `prefer-local` or `prefer-remote`, that side is kept. With `fail`,
+    /// nothing is synchronized. With `prompt`, a word-level diff of the two
+    /// values is shown and you're asked whether to keep the local value, keep
+    /// the remote value, or skip it. Choices are limited by the sync mode and
+    /// directives. With `report`, they're skipped and written to the conflict
+    /// report instead. Only hashes of the values are reported.
+    #[arg(long, value_enum, default_value_t)]
+    pub on_conflict: ConflictStrategy,
+
+    /// Where to write conflicts with `--on-conflict report`.
+    ///
+    /// Any earlier report is replaced. Nothing is written if there are no
+    /// conflicts.
+    #[arg(long, value_name = "PATH", default_value = CONFLICTS_FILE)]
+    pub conflict_report: PathBuf,
+
+    /// How to resolve reported conflicts, when run by `azsync resolve`.
+    #[arg(skip)]
+    pub decisions: Decisions,
+
+    /// Show how each pulled, pushed, or deleted value changes.
+    ///
+    /// A word-level diff from the old value to the new one is shown under each
+    /// action, so changes can be reviewed before confirming or with
+    /// --check-only. Values are masked unless --show-values is passed.
+    #[arg(long)]
+    pub diff: bool,
+
+    /// Show values in conflict diffs and --diff instead of masking them.
+    #[arg(long)]
+    pub show_values: bool,
+
+    /// Push to secrets that are disabled in Key Vault, enabling them again.
+    ///
+    /// Disabled secrets can't be read, so variables whose secrets are disabled
+    /// are normally skipped rather than pulled or pushed. With this flag,
+    /// local values are pushed to them as new, enabled versions.
+    #[arg(long, conflicts_with = "disable_on_push")]
+    pub enabled: bool,
+
+    /// Disable secrets when pushing them.
+    ///
+    /// Values are pushed as disabled versions, so they can't be read until
+    /// they're enabled again. Variables whose secrets are disabled are skipped
+    /// on later runs unless --enabled is passed.
+    #[arg(long)]
+    pub disable_on_push: bool,
+
+    /// How to push empty and whitespace-only values to Key Vault.
+    ///
+    /// Key Vault can't store empty secrets, so pushing one would fail partway
+    /// through synchronizing. This is decided when planning instead: with
+    /// `error`, nothing is changed if any would be pushed. With `skip`, those
+    /// variables are skipped. With `placeholder`, --empty-placeholder is
+    /// pushed in their place, and pulled back as an empty value. App
+    /// Configuration stores empty values as they are.
+    #[arg(long, value_enum, default_value_t)]
+    pub empty_values: EmptyValues,
+
+    /// The value to push in place of empty values with `--empty-values
+    /// placeholder`.
+    #[arg(long, value_name = "VALUE", default_value = "<empty>")]
+    pub empty_placeholder: String,
+
+    /// Where the variables are stored in Azure.
+    ///
+    /// With `app-config`, each variable is stored as a key-value in an App
+    /// Configuration store instead of as a secret in Key Vault. App
+    /// Configuration doesn't soft-delete or disable key-values, so
+    /// --on-remote-delete and --enabled have no effect, and --disable-on-push
+    /// can't be used.
+    #[arg(long, value_enum, default_value_t)]
+    pub backend: DotenvBackend,
+
+    /// Another Key Vault to synchronize some variables with, like
+    /// `shared=https://shared.vault.azure.net`.
+    ///
+    /// Variables with a `# azsync: vault=NAME` directive are synchronized with
+    /// the vault passed with that name, and the rest with --key-vault-url.
+    /// The URL supports the `env:` scheme, like --key-vault-url. Can be passed
+    /// more than once.
+    #[arg(long = "vault", value_name = "NAME=URL")]
+    pub vaults: Vec<NamedVault>,
+
+    /// A replica of the Key Vault to pull from if --key-vault-url is
+    /// unavailable, like one kept in another region for disaster recovery.
+    ///
+    /// The replica is only used when values are only being pulled (with `-m
+    /// pull` or `-m pull-always`, or with --export) and loading secrets from
+    /// --key-vault-url still fails after retrying. Values pulled from the
+    /// replica are marked as such. The URL supports the `env:` scheme, like
+    /// --key-vault-url.
+    ///
+    /// If not provided, the value from the config file is used.
+    #[arg(long, value_name = "URL")]
+    pub key_vault_replica_url: Option<MaybeEnv<Url>>,
+
+    /// A tag to set on each pushed secret or key-value, like `project=foo`.
+    ///
+    /// Existing tags and content types are kept when pushing. Tags can also be
+    /// set for a single variable with a `# azsync: tag=KEY=VALUE` directive.
+    /// Can be passed more than once.
+    #[arg(long = "tag", value_name = "KEY=VALUE")]
+    pub tags: Vec<Tag>,
+
+    /// Record when the dotenv file was last synchronized in a comment block at
+    /// its end.
+    ///
+    /// The trailer lists the time, the profile, and the stores used, and is
+    /// updated in place each time changes are made.
+    #[arg(long)]
+    pub trailer: bool,
+
+    /// Write a comment above each variable pulled into the dotenv file for
+    /// the first time, recording where and when it was pulled from.
+    ///
+    /// For example, `# from Key Vault https://my-vault.vault.azure.net/,
+    /// pulled 2025-01-02`. Comments on variables that are already in the file
+    /// are always kept.
+    #[arg(long)]
+    pub provenance: bool,
+
+    /// Write the remote values to this file in another format instead of
+    /// synchronizing, or `-` for stdout.
+    ///
+    /// The variables are chosen the same way as when synchronizing, but only
+    /// their values in Azure are read, and the dotenv file isn't modified.
+    /// Variables that aren't stored in Azure are left out. Use this to hand
+    /// variables to tools that don't read dotenv files.
+    #[arg(long, value_name = "PATH")]
+    pub export: Option<PathBuf>,
+
+    /// The format to write with --export.
+    ///
+    /// `bash` and `powershell` write scripts that set each variable in the
+    /// environment when they're sourced.
+    #[arg(long, value_enum, default_value_t, requires = "export")]
+    pub format: ExportFormat,
+
+    /// The name of the Secret written with `--format k8s-secret`.
+    #[arg(
+        long,
+        value_name = "NAME",
+        default_value = "azsync",
+        requires = "export"
+    )]
+    pub secret_name: String,
+
     /// Options for configuring how to synchronize with Azure.
     #[command(flatten)]
     pub sync: SyncOptions,
 
+    /// Options for watching the dotenv file for changes.
+    #[command(flatten)]
+    pub watch: WatchOptions,
+
     /// Options for configuring the Key Vault.
     #[command(flatten)]
     pub key_vault: KeyVaultOptions,
+
+    /// Options for configuring the App Configuration store.
+    #[command(flatten)]
+    pub app_config: AppConfigOptions,
+}
+
+/// Where a dotenv template is loaded from.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum TemplateSource {
+    /// A local file.
+    Path(PathBuf),
+
+    /// A blob, by its URL.
+    BlobUrl(Url),
+
+    /// A blob in the configured container, by its name.
+    Blob(String),
+}
+
+impl SyncDotenvOptions {
+    /// Checks whether a variable is selected by --only and --exclude.
+    pub fn is_selected(&self, name: &str) -> bool {
+        (self.only.is_empty() || self.only.iter().any(|pattern| pattern.matches(name)))
+            && !self.exclude.iter().any(|pattern| pattern.matches(name))
+    }
+}
+
+impl FromStr for TemplateSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(name) = s.strip_prefix("blob:") {
+            ensure!(!name.is_empty(), "Missing blob name after `blob:`");
+            return Ok(Self::Blob(name.to_string()));
+        }
+
+        if s.starts_with("https://") || s.starts_with("http://") {
+            return Ok(Self::BlobUrl(s.parse()?));
+        }
+
+        Ok(Self::Path(s.into()))
+    }
+}
+
+impl Display for TemplateSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Path(path) => write!(f, "{}", path.display()),
+            Self::BlobUrl(url) => write!(f, "{url}"),
+            Self::Blob(name) => write!(f, "blob:{name}"),
+        }
+    }
+}
+
+/// A Key Vault that variables can be routed to by name.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct NamedVault {
+    /// The name used in `vault` directives.
+    pub name: String,
+
+    /// The URL of the Key Vault.
+    pub url: MaybeEnv<Url>,
+}
+
+impl FromStr for NamedVault {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, url) = s.split_once('=').context("Expected NAME=URL")?;
+        ensure!(!name.is_empty(), "Missing vault name");
+        ensure!(!url.is_empty(), "Missing URL for {name}");
+
+        Ok(Self {
+            name: name.to_string(),
+            url: url.parse()?,
+        })
+    }
+}
+
+/// A tag to set on pushed variables, passed as `KEY=VALUE`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Tag {
+    pub key: String,
+    pub value: String,
+}
+
+impl FromStr for Tag {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = s.split_once('=').context("Expected KEY=VALUE")?;
+        ensure!(!key.is_empty(), "Missing tag name");
+
+        Ok(Self {
+            key: key.to_string(),
+            value: value.to_string(),
+        })
+    }
+}
+
+/// How to resolve variables that exist locally but were deleted from Key
+/// Vault.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default, ValueEnum)]
+pub enum RemoteDeleteStrategy {
+    /// Leave the local value alone.
+    #[default]
+    #[value(name = "skip")]
+    Skip,
+
+    /// Push the local value to Key Vault again.
+    #[value(name = "push")]
+    Push,
+
+    /// Remove the variable from the dotenv file.
+    #[value(name = "delete-local")]
+    DeleteLocal,
+}
+
+/// How to push empty and whitespace-only values to stores that can't hold
+/// them.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default, ValueEnum)]
+pub enum EmptyValues {
+    /// Fail before making any changes.
+    #[default]
+    #[value(name = "error")]
+    Error,
+
+    /// Skip the variable.
+    #[value(name = "skip")]
+    Skip,
+
+    /// Push a placeholder instead.
+    #[value(name = "placeholder")]
+    Placeholder,
+}
+
+/// A format to export variables in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default, ValueEnum)]
+pub enum ExportFormat {
+    /// A JSON object of values by name.
+    #[default]
+    #[value(name = "json")]
+    Json,
+
+    /// A YAML mapping of values by name.
+    #[value(name = "yaml")]
+    Yaml,
+
+    /// A Kubernetes Secret manifest, with values encoded as base64.
+    #[value(name = "k8s-secret")]
+    K8sSecret,
+
+    /// A bash script exporting each variable.
+    #[value(name = "bash")]
+    Bash,
+
+    /// A PowerShell script setting each environment variable.
+    #[value(name = "powershell")]
+    Powershell,
+}
+
+/// An operation on a dotenv file.
+#[derive(Clone, Debug, Subcommand)]
+pub enum DotenvCommand {
+    /// Import deployment outputs into your dotenv file.
+    ///
+    /// Output names are converted to variable names, so `storageAccountUrl`
+    /// becomes `STORAGE_ACCOUNT_URL`. Existing variables are replaced in-place
+    /// and new ones are added to the end of the file.
+    Import(ImportOptions),
+
+    /// Check your dotenv and template files for problems.
+    ///
+    /// Reports lines that can't be parsed, variables defined more than once,
+    /// differences between the variables in the two files, names that can't
+    /// be used for Key Vault secrets, references to undefined variables, and
+    /// values too large for Key Vault. Fails if any problems are found.
+    Lint(LintOptions),
+}
+
+/// Options for linting a dotenv file.
+#[derive(Clone, Debug, Args)]
+pub struct LintOptions {
+    /// The dotenv template file.
+    ///
+    /// If the file does not exist, only the dotenv file is checked.
+    #[arg(long, short = 't', default_value = ".env.example")]
+    pub template_file: PathBuf,
+
+    /// The largest value allowed, in bytes.
+    #[arg(long, default_value_t = MAX_SECRET_VALUE_SIZE)]
+    pub max_value_size: u64,
+}
+
+/// Options for importing deployment outputs into a dotenv file.
+#[derive(Clone, Debug, Args)]
+pub struct ImportOptions {
+    /// The JSON file containing the outputs.
+    pub path: PathBuf,
+
+    /// The format of the outputs.
+    #[arg(long, value_enum, default_value_t)]
+    pub from: OutputsFormat,
+
+    /// A prefix to add to the name of each variable.
+    #[arg(long, default_value = "")]
+    pub prefix: String,
+
+    /// Also push the imported variables to Key Vault.
+    #[arg(long)]
+    pub push: bool,
+
+    /// Push without asking for confirmation.
+    #[arg(long, short = 'y', requires = "push")]
+    pub no_confirm: bool,
+
+    /// Options for configuring the Key Vault.
+    #[command(flatten)]
+    pub key_vault: KeyVaultOptions,
+}
+
+/// A format of deployment outputs.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default, ValueEnum)]
+pub enum OutputsFormat {
+    /// Outputs of an ARM or Bicep deployment.
+    ///
+    /// This can be a whole deployment (like from `az deployment group show`)
+    /// or just its outputs (with `--query properties.outputs`).
+    #[default]
+    #[value(name = "arm-outputs", alias = "bicep")]
+    ArmOutputs,
+
+    /// Outputs from `terraform output -json`.
+    #[value(name = "terraform")]
+    Terraform,
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use test_case::test_case;
+
+    use super::*;
+    use crate::cli::{Cli, CliCommand};
+
+    #[test]
+    fn template_sources() {
+        assert_eq!(
+            ".env.example".parse::<TemplateSource>().unwrap(),
+            TemplateSource::Path(".env.example".into())
+        );
+        assert_eq!(
+            "blob:templates/.env.example"
+                .parse::<TemplateSource>()
+                .unwrap(),
+            TemplateSource::Blob("templates/.env.example".to_string())
+        );
+        assert_eq!(
+            "https://account.blob.core.windows.net/config/.env.example"
+                .parse::<TemplateSource>()
+                .unwrap(),
+            TemplateSource::BlobUrl(
+                "https://account.blob.core.windows.net/config/.env.example"
+                    .parse()
+                    .unwrap()
+            )
+        );
+        assert!("blob:".parse::<TemplateSource>().is_err());
+    }
+
+    #[test]
+    fn named_vaults() {
+        assert_eq!(
+            "shared=https://shared.vault.azure.net"
+                .parse::<NamedVault>()
+                .unwrap(),
+            NamedVault {
+                name: "shared".to_string(),
+                url: MaybeEnv::Value("https://shared.vault.azure.net".parse().unwrap()),
+            }
+        );
+        assert_eq!(
+            "shared=env:SHARED_VAULT_URL".parse::<NamedVault>().unwrap(),
+            NamedVault {
+                name: "shared".to_string(),
+                url: MaybeEnv::EnvVar("SHARED_VAULT_URL".to_string()),
+            }
+        );
+        assert!("shared".parse::<NamedVault>().is_err());
+        assert!(
+            "=https://shared.vault.azure.net"
+                .parse::<NamedVault>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn tags() {
+        assert_eq!(
+            "project=foo=bar".parse::<Tag>().unwrap(),
+            Tag {
+                key: "project".to_string(),
+                value: "foo=bar".to_string(),
+            }
+        );
+        assert!("project".parse::<Tag>().is_err());
+        assert!("=foo".parse::<Tag>().is_err());
+    }
+
+    #[test_case(&[], "DB_HOST" => true; "no filters")]
+    #[test_case(&["--only", "DB_*"], "DB_HOST" => true; "only matching")]
+    #[test_case(&["--only", "DB_*"], "API_KEY" => false; "only not matching")]
+    #[test_case(&["--only", "API_KEY,DB_*"], "API_KEY" => true; "only comma separated")]
+    #[test_case(&["--exclude", "*_SECRET"], "DB_SECRET" => false; "excluded")]
+    #[test_case(&["--only", "DB_*", "--exclude", "DB_PASS?"], "DB_PASS1" => false; "exclude wins")]
+    #[test_case(&["--only", "DB_*", "--exclude", "DB_PASS?"], "DB_PASS" => true; "exclude needs a full match")]
+    fn selected(args: &[&str], name: &str) -> bool {
+        let cli = Cli::try_parse_from(["azsync", "dotenv"].iter().chain(args)).unwrap();
+        let CliCommand::Dotenv(options) = cli.subcommand else {
+            panic!("expected the dotenv command");
+        };
+        options.is_selected(name)
+    }
 }
@@ -0,0 +1,80 @@
+use anyhow::{Context, bail};
+use time::Duration;
+
+/// Parses a duration like `90s`, `15m`, `1h30m`, or `7d`.
+///
+/// A number without a unit is interpreted as a number of seconds.
+pub fn parse_duration(s: &str) -> anyhow::Result<Duration> {
+    if s.is_empty() {
+        bail!("Duration cannot be empty");
+    }
+
+    // Plain number of seconds
+    if let Ok(seconds) = s.parse::<u32>() {
+        return Ok(Duration::seconds(seconds.into()));
+    }
+
+    let mut total: i64 = 0;
+    let mut rest = s;
+    while !rest.is_empty() {
+        // Split off the number
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .context("Duration is missing a unit")?;
+        let (value, remaining) = rest.split_at(digits);
+        let value: i64 = value
+            .parse()
+            .with_context(|| format!("Invalid duration: {s:?}"))?;
+
+        // Split off the unit
+        let unit_len = remaining
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(remaining.len());
+        let (unit, remaining) = remaining.split_at(unit_len);
+        let multiplier: i64 = match unit {
+            "s" => 1,
+            "m" => 60,
+            "h" => 60 * 60,
+            "d" => 24 * 60 * 60,
+            other => bail!("Invalid duration unit: {other:?} (expected s, m, h, or d)"),
+        };
+        total = value
+            .checked_mul(multiplier)
+            .and_then(|seconds| total.checked_add(seconds))
+            .with_context(|| format!("Duration is too large: {s:?}"))?;
+
+        rest = remaining;
+    }
+
+    Ok(Duration::seconds(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("0" => Duration::ZERO; "zero")]
+    #[test_case("90" => Duration::seconds(90); "plain seconds")]
+    #[test_case("90s" => Duration::seconds(90); "seconds")]
+    #[test_case("15m" => Duration::minutes(15); "minutes")]
+    #[test_case("2h" => Duration::hours(2); "hours")]
+    #[test_case("7d" => Duration::days(7); "days")]
+    #[test_case("1h30m" => Duration::minutes(90); "combined")]
+    fn valid(s: &str) -> Duration {
+        parse_duration(s).unwrap()
+    }
+
+    #[test_case(""; "empty")]
+    #[test_case("h"; "missing value")]
+    #[test_case("10x"; "invalid unit")]
+    #[test_case("1h30"; "trailing number")]
+    #[test_case("-5"; "negative seconds")]
+    #[test_case("-5m"; "negative")]
+    #[test_case("999999999999999999d"; "overflowing unit")]
+    #[test_case("9223372036854775807s1s"; "overflowing total")]
+    fn invalid(s: &str) {
+        assert!(parse_duration(s).is_err());
+    }
+}
@@ -1,11 +1,22 @@
-use std::path::PathBuf;
+use std::{num::NonZeroUsize, path::PathBuf};
 
 use clap::Args;
+use clap_complete::ArgValueCompleter;
 
-use crate::cli::{AzureStorageOptions, SyncOptions};
+use crate::{
+    cli::{
+        AzureStorageOptions, Compression, ConflictStrategy, MaybeEnv, SyncOptions, WatchOptions,
+        complete_env_var, parse_size,
+    },
+    conflicts::{CONFLICTS_FILE, Decisions},
+};
 
 /// Options for synchronizing files.
 #[derive(Clone, Debug, Args)]
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "Each flag is an independent switch"
+)]
 pub struct SyncFileOptions {
     /// The files to sync.
     ///
@@ -23,10 +34,13 @@ pub struct SyncFileOptions {
     /// pull the file foo.json, you MUST specify foo.json because *.json will
     /// not be expanded by your shell to include it.
     ///
-    /// There is currently no way to pull all files matching a pattern from the
-    /// remote storage. IF YOU WANT TO SYNCHRONIZE A DIRECTORY, ARCHIVE IT
-    /// FIRST. You can synchronize foos.zip easily because it is only one file.
-    #[arg(required = true, num_args = 1..)]
+    /// To pull files that only exist remotely, pass --remote-prefix instead.
+    /// IF YOU WANT TO SYNCHRONIZE A DIRECTORY, USE `azsync dir` INSTEAD. It
+    /// finds files that only exist remotely as well.
+    ///
+    /// If no paths are given, the files declared in the config file are
+    /// synchronized instead. Those can each be stored in a different storage
+    /// account or container.
     pub paths: Vec<PathBuf>,
 
     // NOTE: clap doesn't format doc comments correctly for long help yet:
@@ -34,16 +48,256 @@ pub struct SyncFileOptions {
     #[doc = include_str!("file.blob_name.txt")]
     #[arg(
         long,
-        default_value = "#name#",
         help = "The name of the remote blob.",
         long_help = include_str!("file.blob_name.txt"),
     )]
-    pub blob_name: String,
+    pub blob_name: Option<String>,
+
+    /// Also synchronize blobs whose names start with this prefix.
+    ///
+    /// The container is listed, and each blob whose name starts with the
+    /// prefix is synchronized with a local file at the rest of its name,
+    /// relative to the current directory. For example, with `--remote-prefix
+    /// config/`, the blob `config/app.json` is synchronized with `app.json`.
+    /// This pulls blobs that don't exist locally yet without naming each one.
+    ///
+    /// Blobs already synchronized with one of the given paths aren't added
+    /// again. Files declared in the config file aren't synchronized when this
+    /// is passed.
+    #[arg(long)]
+    pub remote_prefix: Option<String>,
+
+    /// Write pulled files into this directory instead of in place.
+    ///
+    /// Each pulled file is written to the same path relative to this
+    /// directory as it has relative to the current directory, creating
+    /// subdirectories as needed. Local files are still compared and pushed in
+    /// place, but never modified. This is useful for read-only checkouts.
+    ///
+    /// Only pulled files are written there, and nothing is swapped in
+    /// afterwards, so the directory isn't a complete copy. Copy its files over
+    /// the local ones yourself once synchronizing is done.
+    #[arg(long)]
+    pub stage_dir: Option<PathBuf>,
+
+    /// Where to write pulled files while they're downloading.
+    ///
+    /// Pulled files are downloaded to a temporary file, then moved into place
+    /// once complete. By default, the temporary file is created next to the
+    /// destination, so it's on the same filesystem and can be moved
+    /// atomically. Use this when that directory can't hold a second copy of
+    /// the file, or to avoid a small `/tmp` mount.
+    #[arg(long, env = "AZSYNC_TEMP_DIR")]
+    pub temp_dir: Option<PathBuf>,
+
+    /// Keep interrupted downloads and continue them on the next run.
+    ///
+    /// Normally, a download that fails partway through is discarded. With
+    /// this flag, the partially downloaded file is kept along with a marker
+    /// recording how much of it was written, and the next pull with this flag
+    /// continues from there, as long as the blob hasn't changed since.
+    #[arg(long)]
+    pub resume: bool,
+
+    /// Replace parts of file names that aren't valid Unicode in blob names.
+    ///
+    /// Blob names must be valid Unicode. By default, files whose names aren't
+    /// are skipped with a warning. With this flag, invalid parts are replaced
+    /// with `\u{FFFD}` instead. Pulled blobs are saved under the replaced
+    /// name, not the original one.
+    #[arg(long)]
+    pub lossy_names: bool,
+
+    /// Write the properties of each pulled blob to a sidecar file.
+    ///
+    /// Properties like the blob's content type, metadata, and index tags
+    /// don't survive being saved as a plain file. With this flag, they're
+    /// written as JSON next to each pulled file, in a file with `.azmeta`
+    /// added to its name (like `file.json.azmeta`). They're also printed
+    /// when pulling with `-v`.
+    #[arg(long)]
+    pub sidecar: bool,
+
+    /// Preserve the owner and access control list of each file (Windows
+    /// only).
+    ///
+    /// When pushing, the file's owner and ACL are stored in the blob's
+    /// metadata. When pulling, they're restored from the blob's metadata if
+    /// it has them. Restoring an owner other than yourself usually needs
+    /// administrator rights.
+    #[arg(long)]
+    pub preserve_acl: bool,
+
+    /// Compare and record checksums of synchronized files.
+    ///
+    /// When pushing, a SHA-256 checksum of the file is stored in the blob's
+    /// metadata. The checksum is computed from the same data being uploaded,
+    /// so the file is only read once.
+    ///
+    /// When both the local file and the remote blob exist and the blob has a
+    /// checksum, the file is considered unchanged if its contents match that
+    /// checksum, regardless of when either was last modified.
+    #[arg(long)]
+    pub checksum: bool,
+
+    /// Ignore differences in line endings and trailing newlines when
+    /// comparing checksums of text files.
+    ///
+    /// When pushing, a second checksum is stored that hashes CRLF line
+    /// endings as LF and leaves out newlines at the end of the file. Files
+    /// whose contents match that checksum are considered unchanged, so the
+    /// same file checked out on Windows and Linux isn't pushed back and forth.
+    /// Blobs only have this checksum once they've been pushed with this flag.
+    #[arg(long, requires = "checksum")]
+    pub normalize_text: bool,
+
+    /// Encrypt pushed files with this key, and decrypt pulled ones.
+    ///
+    /// The key is read from a file, or from a variable with `env:VAR`, and
+    /// must be 32 random bytes encoded as base64, like the output of `openssl
+    /// rand -base64 32`. Files are encrypted with AES-256-GCM before they're
+    /// uploaded, and the nonce and an ID for the key are stored in the blob's
+    /// metadata. Encrypted files aren't scanned for secrets.
+    ///
+    /// Encrypted files are held in memory while they're transferred, so
+    /// they're never uploaded in blocks or resumed. Pulling an encrypted blob
+    /// without its key fails, and so does pulling a blob that isn't encrypted
+    /// with this key, since anyone who can write to the container could
+    /// replace an encrypted blob with one that isn't.
+    #[arg(long, value_name = "KEY_FILE", add = ArgValueCompleter::new(complete_env_var))]
+    pub encrypt_key: Option<MaybeEnv<PathBuf>>,
+
+    /// Compress pushed files.
+    ///
+    /// Blobs are stored compressed with their `Content-Encoding` set, and
+    /// how they were compressed is recorded in their metadata. Compressed
+    /// blobs are decompressed when they're pulled, whether or not this is
+    /// passed, and checksums are of the uncompressed contents, so they're
+    /// compared with local files as usual. Encrypted files are compressed
+    /// before they're encrypted, and have no `Content-Encoding`, since what's
+    /// stored can't be decompressed without the key.
+    ///
+    /// Compressed files are held in memory while they're transferred, so
+    /// they're never uploaded in blocks or resumed.
+    #[arg(long, value_enum, value_name = "ALGORITHM")]
+    pub compress: Option<Compression>,
+
+    /// Mirror deletions, so one side ends up matching the other.
+    ///
+    /// When pushing, blobs whose local files don't exist are deleted. When
+    /// pulling, local files whose blobs don't exist are deleted. Combine this
+    /// with --remote-prefix to delete blobs that were never named locally.
+    /// Only files being synchronized are considered, and deletions are
+    /// confirmed separately from the rest of the plan.
+    ///
+    /// The sync mode must be `push`, `push-always`, `pull`, or `pull-always`,
+    /// since a missing copy can't be told apart from a new one otherwise.
+    #[arg(long)]
+    pub delete: bool,
+
+    /// Delete local files outright when mirroring deletions.
+    ///
+    /// Otherwise, they're moved to `.azsync/trash/<timestamp>/`, keeping their
+    /// paths relative to the current directory, so they can be restored. Use
+    /// `azsync trash empty` to delete them for good.
+    #[arg(long, requires = "delete")]
+    pub hard_delete: bool,
+
+    /// How to handle files that changed both locally and remotely.
+    ///
+    /// A file is in conflict when both the local file and the remote blob
+    /// exist and differ, and both changed since it was last synchronized
+    /// according to `.azsync/manifest.json`. With `newest`, the newer copy is
+    /// kept. With `prefer-local` or `prefer-remote`, that side is kept. With
+    /// `fail`, nothing is synchronized. With `prompt`, the differences are
+    /// shown (as a diff for text files) and you're asked whether to keep the
+    /// local file, keep the remote blob, or skip it. Choices are limited by
+    /// the sync mode. With `report`, they're skipped and written to the
+    /// conflict report instead.
+    #[arg(long, value_enum, default_value_t)]
+    pub on_conflict: ConflictStrategy,
+
+    /// Where to write conflicts with `--on-conflict report`.
+    ///
+    /// Any earlier report is replaced. Nothing is written if there are no
+    /// conflicts.
+    #[arg(long, value_name = "PATH", default_value = CONFLICTS_FILE)]
+    pub conflict_report: PathBuf,
+
+    /// How to resolve reported conflicts, when run by `azsync resolve`.
+    #[arg(skip)]
+    pub decisions: Decisions,
+
+    /// Push files even if they appear to contain secrets.
+    ///
+    /// Files are scanned for private keys, connection strings, and other
+    /// credentials before being pushed. Key stores like `.pfx` files are always
+    /// considered to contain secrets. By default, nothing is pushed if any
+    /// secrets are found. Set `secret-scan = "warn"` in the config file to only
+    /// warn about them instead.
+    #[arg(long)]
+    pub allow_secrets: bool,
+
+    /// An Azure Blob Inventory report to read the state of remote blobs from.
+    ///
+    /// Normally, each remote blob is queried individually to determine which
+    /// files need to be synchronized. For containers with many blobs, an
+    /// inventory report can be used to plan much more quickly. The report may be
+    /// in CSV or Parquet format, read as Parquet if its name ends in .parquet,
+    /// and must include the Name and Last-Modified fields. If the Etag,
+    /// Content-Length, and Metadata fields are included, they're used as well.
+    ///
+    /// Reports are only as current as when they were generated. Blobs that
+    /// changed since then will fail to be pushed or pulled rather than being
+    /// overwritten.
+    #[arg(long)]
+    pub inventory: Option<PathBuf>,
+
+    /// Report progress while pushing and pulling files.
+    ///
+    /// When stderr is a terminal, a progress bar is drawn for each file and
+    /// for all files together, showing how much has been transferred, the
+    /// transfer rate, and an estimate of how much longer it will take.
+    /// Otherwise, or with a structured --log-format, the same is logged every
+    /// few seconds instead. Files are uploaded in blocks so their progress can
+    /// be tracked.
+    #[arg(long)]
+    pub progress: bool,
+
+    /// The size of each block when uploading files in blocks, like `8MiB`.
+    ///
+    /// Files larger than 256 MiB are uploaded as a list of blocks instead of
+    /// in a single request, as are files pushed with --checksum or
+    /// --progress. A blob can have at most 50,000 blocks, so larger blocks
+    /// are needed for files over about 195 GiB. Blocks can be up to 4000 MiB.
+    #[arg(long, value_name = "SIZE", default_value = "4MiB", value_parser = parse_size)]
+    pub block_size: u64,
+
+    /// How many blocks of each file to upload at once.
+    ///
+    /// Each block being uploaded is held in memory, so this times
+    /// --block-size is roughly how much memory each upload uses.
+    #[arg(long, value_name = "N", default_value = "4")]
+    pub block_concurrency: NonZeroUsize,
+
+    /// Skip planning if nothing changed since the last identical run.
+    ///
+    /// After a successful run, the state of each local file and remote blob is
+    /// recorded in `.azsync/manifest.json`. When run again with the same
+    /// arguments, only the local files and the `ETag` of each blob are
+    /// checked, and nothing else is done if none of them changed. This is
+    /// useful when running the same command several times, like in CI.
+    #[arg(long)]
+    pub idempotent: bool,
 
     /// Options for configuring how to synchronize with Azure.
     #[command(flatten)]
     pub sync: SyncOptions,
 
+    /// Options for watching files for changes.
+    #[command(flatten)]
+    pub watch: WatchOptions,
+
     /// Options for configuring the Storage Account.
     #[command(flatten)]
     pub azure_storage: AzureStorageOptions,
@@ -0,0 +1,43 @@
+use std::num::NonZeroUsize;
+
+use clap::Args;
+use time::Duration;
+
+use crate::cli::{AzureStorageOptions, parse_duration};
+
+/// Options for cleaning up blocks left behind by failed uploads.
+#[derive(Clone, Debug, Args)]
+pub struct GcOptions {
+    /// Only clean up blobs that haven't been modified for this long.
+    ///
+    /// This avoids interfering with uploads that are still in progress.
+    /// Durations can be written like `90s`, `15m`, `1h30m`, or `7d`.
+    #[arg(long, default_value = "1d", value_parser = parse_duration)]
+    pub min_age: Duration,
+
+    /// Only check if anything needs to be cleaned up.
+    ///
+    /// Any changes that need to be made are printed to stdout.
+    ///
+    /// The application exits with status 1 if anything can be cleaned up.
+    #[arg(long, short = 'c')]
+    pub check_only: bool,
+
+    /// Don't ask for confirmation before cleaning up.
+    ///
+    /// This is a potentially destructive action. Use with caution.
+    #[arg(long, short = 'y')]
+    pub no_confirm: bool,
+
+    /// The most requests to make to Azure at once.
+    ///
+    /// Limits how many blobs are inspected, and batches of blobs deleted, at
+    /// the same time. Lower this if Azure throttles requests (with 429
+    /// errors). By default, 16 requests are made at once.
+    #[arg(long, value_name = "N")]
+    pub max_concurrency: Option<NonZeroUsize>,
+
+    /// Options for configuring the Storage Account.
+    #[command(flatten)]
+    pub azure_storage: AzureStorageOptions,
+}
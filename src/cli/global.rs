@@ -1,11 +1,26 @@
 use std::path::PathBuf;
 
-use clap::{ArgAction, Args};
+use clap::{ArgAction, ArgMatches, Args, ValueEnum, parser::ValueSource};
 
 /// Global options that are always relevant.
 #[derive(Clone, Debug, Args)]
 #[command(version, next_help_heading = "Global")]
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "Each flag is an independent switch"
+)]
 pub struct GlobalOptions {
+    /// The config file to load (if present).
+    #[arg(global = true, long, default_value = "azsync.toml")]
+    pub config: PathBuf,
+
+    /// The profile from the config file to use.
+    ///
+    /// Profiles can override any of the options set at the top level of the
+    /// config file.
+    #[arg(global = true, long, short = 'p', env = "AZSYNC_PROFILE")]
+    pub profile: Option<String>,
+
     /// The dotenv file to load (if present).
     ///
     /// Some options can load values from your environment. If this dotenv file
@@ -25,9 +40,153 @@ pub struct GlobalOptions {
     #[arg(global = true, long)]
     pub no_env_file: bool,
 
+    /// Fail on problems in dotenv files instead of warning about them.
+    ///
+    /// Normally, variables defined more than once use their last definition
+    /// and lines that can't be parsed are ignored, with a warning for each.
+    #[arg(global = true, long)]
+    pub strict_parse: bool,
+
+    /// Never modify anything in Azure.
+    ///
+    /// Anything that would be pushed to or deleted from Azure is skipped
+    /// instead, regardless of the sync mode. Commands that can only modify
+    /// Azure fail instead.
+    ///
+    /// This can also be set with `read-only = true` in the config file.
+    #[arg(global = true, long)]
+    pub read_only: bool,
+
+    /// Ignore the policy in the config file.
+    ///
+    /// Normally, azsync refuses to synchronize with storage accounts,
+    /// containers, or Key Vaults that aren't permitted by the config file's
+    /// policy.
+    #[arg(global = true, long)]
+    pub override_policy: bool,
+
+    /// Wait for other azsync processes in this directory to finish.
+    ///
+    /// Commands that modify local files, like your dotenv file, lock the
+    /// directory while they run. By default, they fail if another process
+    /// holds the lock.
+    #[arg(global = true, long)]
+    pub wait_for_lock: bool,
+
     /// Enable more verbose output (repeatable up to 3 times).
     ///
     /// Output is emitted via stderr.
     #[arg(global = true, long, short = 'v', action = ArgAction::Count)]
     pub verbose: u8,
+
+    /// How to format output.
+    ///
+    /// Structured formats always include timestamps and targets, so log
+    /// aggregators can parse them.
+    #[arg(
+        global = true,
+        long,
+        value_enum,
+        default_value_t,
+        env = "AZSYNC_LOG_FORMAT"
+    )]
+    pub log_format: LogFormat,
+}
+
+impl GlobalOptions {
+    /// Uses the options azsync was run with for any that weren't passed on
+    /// a job's own command line, given the matches it was parsed from.
+    pub fn inherit(&mut self, parent: &Self, matches: &ArgMatches) {
+        let passed = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+        if !passed("config") {
+            self.config.clone_from(&parent.config);
+        }
+        if !passed("profile") {
+            self.profile.clone_from(&parent.profile);
+        }
+        if !passed("env_file") {
+            self.env_file.clone_from(&parent.env_file);
+        }
+        if !passed("no_env_file") {
+            self.no_env_file = parent.no_env_file;
+        }
+        if !passed("strict_parse") {
+            self.strict_parse = parent.strict_parse;
+        }
+        if !passed("read_only") {
+            self.read_only = parent.read_only;
+        }
+        if !passed("override_policy") {
+            self.override_policy = parent.override_policy;
+        }
+        if !passed("wait_for_lock") {
+            self.wait_for_lock = parent.wait_for_lock;
+        }
+        if !passed("verbose") {
+            self.verbose = parent.verbose;
+        }
+        if !passed("log_format") {
+            self.log_format = parent.log_format;
+        }
+    }
+}
+
+/// How log messages are formatted.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default, ValueEnum)]
+pub enum LogFormat {
+    /// Compact, human-readable lines.
+    #[default]
+    #[value(name = "text")]
+    Text,
+
+    /// One JSON object per line.
+    #[value(name = "json")]
+    Json,
+
+    /// `key=value` pairs, one line per message.
+    #[value(name = "logfmt")]
+    Logfmt,
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{CommandFactory, FromArgMatches};
+
+    use super::*;
+    use crate::cli::Cli;
+
+    fn parse(args: &[&str]) -> (GlobalOptions, ArgMatches) {
+        let matches = Cli::command().try_get_matches_from(args).unwrap();
+        let cli = Cli::from_arg_matches(&matches).unwrap();
+        (cli.global, matches)
+    }
+
+    #[test]
+    fn inherit() {
+        let (parent, _) = parse(&[
+            "azsync",
+            "--config",
+            "ci.toml",
+            "--read-only",
+            "--env-file",
+            ".env.ci",
+            "batch",
+            "jobs.yaml",
+        ]);
+        let (mut job, matches) = parse(&[
+            "azsync",
+            "--profile",
+            "dev",
+            "dotenv",
+            "--env-file",
+            ".env.dev",
+        ]);
+
+        job.inherit(&parent, &matches);
+
+        assert_eq!(job.config, PathBuf::from("ci.toml"));
+        assert_eq!(job.profile.as_deref(), Some("dev"));
+        assert_eq!(job.env_file, PathBuf::from(".env.dev"));
+        assert!(job.read_only);
+    }
 }
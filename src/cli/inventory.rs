@@ -0,0 +1,36 @@
+use clap::{Args, ValueEnum};
+
+use crate::cli::{AzureStorageOptions, KeyVaultOptions};
+
+/// Options for listing the resources azsync tracks.
+#[derive(Clone, Debug, Args)]
+pub struct InventoryOptions {
+    /// How to write the inventory to stdout.
+    ///
+    /// `json` writes a single document with when it was generated and each
+    /// resource's kind, local path or name, remote URL, last synchronized
+    /// time (from `.azsync/manifest.json`), hash, and size in bytes.
+    #[arg(long, value_enum, default_value_t)]
+    pub output: InventoryFormat,
+
+    /// Options for configuring the Storage Account.
+    #[command(flatten)]
+    pub azure_storage: AzureStorageOptions,
+
+    /// Options for configuring the Key Vault.
+    #[command(flatten)]
+    pub key_vault: KeyVaultOptions,
+}
+
+/// How to write the inventory of tracked resources.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default, ValueEnum)]
+pub enum InventoryFormat {
+    /// A table for reading.
+    #[default]
+    #[value(name = "table")]
+    Table,
+
+    /// A JSON document for other tools to ingest.
+    #[value(name = "json")]
+    Json,
+}
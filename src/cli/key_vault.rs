@@ -1,7 +1,12 @@
 use clap::Args;
+use clap_complete::ArgValueCompleter;
 use url::Url;
 
-use crate::cli::MaybeEnv;
+use crate::{
+    cli::{MaybeEnv, complete_env_var},
+    config::Config,
+    dotenv::DotenvFile,
+};
 
 /// Options for configuring the Key Vault instance.
 #[derive(Clone, Debug, Args)]
@@ -17,6 +22,33 @@ pub struct KeyVaultOptions {
     /// that file for a value. If it's not found in that file, or if no dotenv
     /// file is present, then the program's environment variables will be
     /// searched instead.
-    #[arg(long, default_value = "env:KEY_VAULT_URL")]
-    pub key_vault_url: MaybeEnv<Url>,
+    ///
+    /// If not provided, the value from the config file is used, or
+    /// `env:KEY_VAULT_URL` if it's not configured either.
+    #[arg(long, add = ArgValueCompleter::new(complete_env_var))]
+    pub key_vault_url: Option<MaybeEnv<Url>>,
+}
+
+impl KeyVaultOptions {
+    /// Whether no Key Vault was passed on the command line.
+    pub fn is_unset(&self) -> bool {
+        self.key_vault_url.is_none()
+    }
+
+    /// Resolves the Key Vault URL.
+    ///
+    /// Values passed on the command line take precedence over values from the
+    /// config file.
+    pub fn resolve(&self, config: &Config, dotenv: Option<&DotenvFile>) -> anyhow::Result<Url> {
+        let url = self
+            .key_vault_url
+            .as_ref()
+            .or(config.key_vault_url.as_ref())
+            .cloned()
+            .unwrap_or_else(|| MaybeEnv::EnvVar("KEY_VAULT_URL".to_string()))
+            .resolve(dotenv)?
+            .into_owned();
+
+        Ok(url)
+    }
 }
@@ -0,0 +1,82 @@
+use std::{path::PathBuf, str::FromStr};
+
+use anyhow::ensure;
+use clap::Args;
+
+use crate::cli::{KeyVaultOptions, SyncOptions};
+
+/// Options for synchronizing Key Vault secrets with local files.
+#[derive(Clone, Debug, Args)]
+pub struct SyncSecretsOptions {
+    /// The secrets to sync, each with the file to store it in.
+    ///
+    /// Each secret is given as `NAME=PATH`, like `tls-cert=certs/tls.pem`. If
+    /// the path is left out, the secret is stored in a file named after it in
+    /// the current directory.
+    ///
+    /// Each file holds the secret's value as-is. Secrets with the content type
+    /// `application/x-pkcs12`, like certificates imported into Key Vault, are
+    /// stored base64-encoded, so they're decoded when pulled. `.pfx` and `.p12`
+    /// files are encoded again when pushed.
+    #[arg(required = true, value_name = "NAME[=PATH]")]
+    pub secrets: Vec<SecretFile>,
+
+    /// Options for configuring how to synchronize with Azure.
+    #[command(flatten)]
+    pub sync: SyncOptions,
+
+    /// Options for configuring the Key Vault.
+    #[command(flatten)]
+    pub key_vault: KeyVaultOptions,
+}
+
+/// A secret and the local file it's synchronized with.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct SecretFile {
+    /// The name of the secret in Key Vault.
+    pub name: String,
+
+    /// The local file.
+    pub path: PathBuf,
+}
+
+impl FromStr for SecretFile {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, path) = s.split_once('=').unwrap_or((s, s));
+        ensure!(!name.is_empty(), "Missing secret name");
+        ensure!(
+            name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'),
+            "Secret names can only contain letters, digits, and '-'"
+        );
+        ensure!(!path.is_empty(), "Missing path for {name}");
+
+        Ok(Self {
+            name: name.to_string(),
+            path: path.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("tls-cert", "tls-cert", "tls-cert"; "name only")]
+    #[test_case("tls-cert=certs/tls.pem", "tls-cert", "certs/tls.pem"; "name and path")]
+    fn secret_files(arg: &str, name: &str, path: &str) {
+        let secret: SecretFile = arg.parse().unwrap();
+        assert_eq!(secret.name, name);
+        assert_eq!(secret.path, PathBuf::from(path));
+    }
+
+    #[test_case("=tls.pem"; "missing name")]
+    #[test_case("tls-cert="; "missing path")]
+    #[test_case("tls_cert"; "invalid name")]
+    fn invalid_secret_files(arg: &str) {
+        assert!(arg.parse::<SecretFile>().is_err());
+    }
+}
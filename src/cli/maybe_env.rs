@@ -1,10 +1,12 @@
 use std::{
     borrow::Cow,
+    collections::BTreeSet,
     env::var,
     error::Error,
     ffi::OsStr,
     fmt::{Display, Formatter},
     marker::PhantomData,
+    path::Path,
     str::FromStr,
 };
 
@@ -13,6 +15,8 @@ use clap::{
     Arg, Command,
     builder::{NonEmptyStringValueParser, TypedValueParser, ValueParserFactory},
 };
+use clap_complete::CompletionCandidate;
+use serde::{Deserialize, Deserializer, de};
 use url::Url;
 
 use crate::dotenv::DotenvFile;
@@ -63,6 +67,16 @@ where
     }
 }
 
+impl<T> MaybeEnv<T> {
+    /// Gets the name of the environment variable referred to by a value in the
+    /// format `env:VAR_NAME`, if it is one.
+    fn env_var_name(value: &str) -> Option<String> {
+        let value = Url::from_str(value).ok()?;
+        (value.scheme() == "env" && value.cannot_be_a_base() && !value.path().is_empty())
+            .then(|| value.path().to_string())
+    }
+}
+
 impl<T> Display for MaybeEnv<T>
 where
     T: Display,
@@ -84,6 +98,21 @@ where
     }
 }
 
+impl<T> FromStr for MaybeEnv<T>
+where
+    T: FromStr,
+{
+    type Err = T::Err;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(var_name) = Self::env_var_name(s) {
+            return Ok(MaybeEnv::EnvVar(var_name));
+        }
+
+        s.parse().map(MaybeEnv::Value)
+    }
+}
+
 impl<T> ValueParserFactory for MaybeEnv<T> {
     type Parser = MaybeEnvParser<T>;
 
@@ -114,15 +143,78 @@ where
         let value2 = inner.parse_ref(cmd, arg, value)?;
 
         // Parse the string as `env:VAR_NAME` if possible
-        if let Ok(value) = Url::from_str(&value2)
-            && value.scheme() == "env"
-            && value.cannot_be_a_base()
-            && !value.path().is_empty()
-        {
-            let var_name = value.path();
-            return Ok(MaybeEnv::EnvVar(var_name.to_string()));
+        if let Some(var_name) = MaybeEnv::<T>::env_var_name(&value2) {
+            return Ok(MaybeEnv::EnvVar(var_name));
         }
 
         TypedValueParser::parse_ref(&T::from_str, cmd, arg, value).map(MaybeEnv::Value)
     }
 }
+
+impl<'de, T> Deserialize<'de> for MaybeEnv<T>
+where
+    T: FromStr<Err: Display>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        if let Some(var_name) = Self::env_var_name(&value) {
+            return Ok(MaybeEnv::EnvVar(var_name));
+        }
+
+        value
+            .parse()
+            .map(MaybeEnv::Value)
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Completes `env:` values with the names of variables in local dotenv files.
+///
+/// Variables are read from `.env` and `.env.example` in the current directory.
+pub fn complete_env_var(current: &OsStr) -> Vec<CompletionCandidate> {
+    let Some(current) = current.to_str() else {
+        return Vec::new();
+    };
+
+    // Collect variable names, ignoring files that can't be read
+    let mut names = BTreeSet::new();
+    for path in [".env", ".env.example"] {
+        if let Ok(Some(file)) = DotenvFile::from_path_exists(Path::new(path), false) {
+            names.extend(file.parameters.into_keys());
+        }
+    }
+
+    env_var_candidates(current, names)
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+/// Gets the `env:` values for the given variable names that start with the
+/// value being completed.
+fn env_var_candidates(current: &str, names: impl IntoIterator<Item = String>) -> Vec<String> {
+    names
+        .into_iter()
+        .map(|name| format!("env:{name}"))
+        .filter(|value| value.starts_with(current))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("" => vec!["env:KEY_VAULT_URL", "env:STORAGE_ACCOUNT_URL"]; "empty")]
+    #[test_case("en" => vec!["env:KEY_VAULT_URL", "env:STORAGE_ACCOUNT_URL"]; "partial scheme")]
+    #[test_case("env:S" => vec!["env:STORAGE_ACCOUNT_URL"]; "partial name")]
+    #[test_case("https://" => Vec::<String>::new(); "url")]
+    fn candidates(current: &str) -> Vec<String> {
+        let names = ["KEY_VAULT_URL", "STORAGE_ACCOUNT_URL"].map(String::from);
+        env_var_candidates(current, names)
+    }
+}
@@ -0,0 +1,24 @@
+use clap::Args;
+
+use crate::cli::AzureStorageOptions;
+
+/// Options for renaming a remote blob.
+#[derive(Clone, Debug, Args)]
+pub struct MoveOptions {
+    /// The name of the blob to rename.
+    pub source: String,
+
+    /// The new name of the blob.
+    ///
+    /// If a blob with this name already exists, it's left alone and the
+    /// rename fails.
+    pub destination: String,
+
+    /// Don't ask for confirmation before renaming.
+    #[arg(long, short = 'y')]
+    pub no_confirm: bool,
+
+    /// Options for configuring the Storage Account.
+    #[command(flatten)]
+    pub azure_storage: AzureStorageOptions,
+}
@@ -0,0 +1,27 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use crate::{conflicts::CONFLICTS_FILE, glob::Pattern};
+
+/// Options for resolving reported conflicts.
+#[derive(Clone, Debug, Args)]
+pub struct ResolveOptions {
+    /// The conflict report to resolve.
+    ///
+    /// Conflicts with a `resolution` set in the report (`keep-local`,
+    /// `keep-remote`, or `skip`) are resolved that way.
+    #[arg(long, value_name = "PATH", default_value = CONFLICTS_FILE)]
+    pub from: PathBuf,
+
+    /// Keep the local copy of conflicts whose names match this pattern.
+    ///
+    /// Patterns are matched like --exclude for `azsync dir`, and take
+    /// precedence over resolutions set in the report.
+    #[arg(long, value_name = "PATTERN")]
+    pub accept_local: Vec<Pattern>,
+
+    /// Keep the remote copy of conflicts whose names match this pattern.
+    #[arg(long, value_name = "PATTERN")]
+    pub accept_remote: Vec<Pattern>,
+}
@@ -0,0 +1,22 @@
+use clap::Args;
+use time::Duration;
+
+use crate::cli::{AzureStorageOptions, parse_duration};
+
+/// Options for sharing a remote blob.
+#[derive(Clone, Debug, Args)]
+pub struct ShareOptions {
+    /// The name of the blob to share.
+    pub blob_name: String,
+
+    /// How long the link is valid for.
+    ///
+    /// Durations can be written like `90s`, `15m`, `1h30m`, or `7d`. Links can
+    /// be valid for at most seven days.
+    #[arg(long, default_value = "1h", value_parser = parse_duration)]
+    pub expiry: Duration,
+
+    /// Options for configuring the Storage Account.
+    #[command(flatten)]
+    pub azure_storage: AzureStorageOptions,
+}
@@ -0,0 +1,52 @@
+use anyhow::{Context, bail};
+
+/// Parses a number of bytes like `512`, `64K`, `8MiB`, or `1G`.
+///
+/// Units are powers of 1024, with or without the `iB` suffix.
+pub fn parse_size(s: &str) -> anyhow::Result<u64> {
+    let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(digits);
+    if value.is_empty() {
+        bail!("Invalid size: {s:?}");
+    }
+    let value: u64 = value
+        .parse()
+        .with_context(|| format!("Invalid size: {s:?}"))?;
+
+    let multiplier: u64 = match unit.trim() {
+        "" | "B" => 1,
+        "K" | "KiB" => 1024,
+        "M" | "MiB" => 1024 * 1024,
+        "G" | "GiB" => 1024 * 1024 * 1024,
+        other => bail!("Invalid size unit: {other:?} (expected B, KiB, MiB, or GiB)"),
+    };
+
+    value
+        .checked_mul(multiplier)
+        .with_context(|| format!("Size is too large: {s:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("512" => 512; "plain bytes")]
+    #[test_case("512B" => 512; "bytes")]
+    #[test_case("64K" => 64 * 1024; "kibibytes")]
+    #[test_case("8MiB" => 8 * 1024 * 1024; "mebibytes")]
+    #[test_case("1 G" => 1024 * 1024 * 1024; "spaced gibibytes")]
+    fn valid(s: &str) -> u64 {
+        parse_size(s).unwrap()
+    }
+
+    #[test_case(""; "empty")]
+    #[test_case("MiB"; "missing value")]
+    #[test_case("8MB"; "decimal unit")]
+    #[test_case("-1"; "negative")]
+    #[test_case("99999999999999G"; "overflow")]
+    fn invalid(s: &str) {
+        assert!(parse_size(s).is_err());
+    }
+}
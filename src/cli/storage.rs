@@ -1,7 +1,14 @@
+use anyhow::Context;
 use clap::Args;
+use clap_complete::ArgValueCompleter;
 use url::Url;
 
-use crate::cli::MaybeEnv;
+use crate::{
+    cli::{MaybeEnv, complete_env_var},
+    config::{Config, FileEntry},
+    dotenv::DotenvFile,
+    storage::{ConnectionString, SasToken, StorageCredential},
+};
 
 /// Options for configuring the Azure Storage instance.
 #[derive(Clone, Debug, Args)]
@@ -19,8 +26,12 @@ pub struct AzureStorageOptions {
     /// that file for a value. If it's not found in that file, or if no dotenv
     /// file is present, then the program's environment variables will be
     /// searched instead.
-    #[arg(long, default_value = "env:STORAGE_ACCOUNT_URL")]
-    pub storage_account_url: MaybeEnv<Url>,
+    ///
+    /// If not provided, the endpoint from --connection-string is used. Without
+    /// one, the value from the config file is used, or
+    /// `env:STORAGE_ACCOUNT_URL` if it's not configured either.
+    #[arg(long, add = ArgValueCompleter::new(complete_env_var))]
+    pub storage_account_url: Option<MaybeEnv<Url>>,
 
     /// The name of the container in the storage account.
     ///
@@ -32,6 +43,150 @@ pub struct AzureStorageOptions {
     /// that file for a value. If it's not found in that file, or if no dotenv
     /// file is present, then the program's environment variables will be
     /// searched instead.
-    #[arg(long, default_value = "env:STORAGE_ACCOUNT_CONTAINER")]
-    pub container_name: MaybeEnv<String>,
+    ///
+    /// If not provided, the value from the config file is used, or
+    /// `env:STORAGE_ACCOUNT_CONTAINER` if it's not configured either.
+    #[arg(long, add = ArgValueCompleter::new(complete_env_var))]
+    pub container_name: Option<MaybeEnv<String>>,
+
+    /// A connection string for the storage account.
+    ///
+    /// Requests to the storage account are authenticated with the account key
+    /// or SAS token in the connection string instead of an Azure credential.
+    /// Its blob endpoint is used if --storage-account-url isn't passed.
+    ///
+    /// Connection strings are secret, so load them from the environment with
+    /// the `env:` scheme, like `env:AZURE_STORAGE_CONNECTION_STRING`. As with
+    /// the other options, the local dotenv file is searched first.
+    #[arg(long, add = ArgValueCompleter::new(complete_env_var))]
+    pub connection_string: Option<MaybeEnv<String>>,
+
+    /// A SAS token to authenticate with (repeatable).
+    ///
+    /// This can be a token on its own, like `sv=...&sig=...`, which is used for
+    /// every request to Blob Storage. It can also be the full SAS URL of a
+    /// storage account, container, or blob, which is only used for what it
+    /// grants access to. When several apply, the narrowest is used, so each
+    /// blob can have its own SAS URL. The storage account and container of the
+    /// first URL are used if they aren't passed otherwise.
+    ///
+    /// SAS tokens are secret, so load them from the environment with the
+    /// `env:` scheme, like `env:AZURE_STORAGE_SAS_TOKEN`.
+    #[arg(
+        long = "sas-token",
+        value_name = "TOKEN",
+        conflicts_with = "connection_string",
+        add = ArgValueCompleter::new(complete_env_var),
+    )]
+    pub sas_tokens: Vec<MaybeEnv<String>>,
+}
+
+impl AzureStorageOptions {
+    /// Whether no storage account was passed on the command line, in any form.
+    pub fn is_unset(&self) -> bool {
+        self.storage_account_url.is_none()
+            && self.connection_string.is_none()
+            && self.sas_tokens.is_empty()
+    }
+
+    /// Resolves the connection string, if one was passed.
+    pub fn connection_string(
+        &self,
+        dotenv: Option<&DotenvFile>,
+    ) -> anyhow::Result<Option<ConnectionString>> {
+        self.connection_string
+            .as_ref()
+            .map(|connection_string| {
+                connection_string
+                    .resolve(dotenv)?
+                    .parse()
+                    .context("Invalid connection string")
+            })
+            .transpose()
+    }
+
+    /// Resolves the SAS tokens that were passed.
+    pub fn sas_tokens(&self, dotenv: Option<&DotenvFile>) -> anyhow::Result<Vec<SasToken>> {
+        self.sas_tokens
+            .iter()
+            .map(|token| token.resolve(dotenv)?.parse().context("Invalid SAS token"))
+            .collect()
+    }
+
+    /// Creates the credential used to authenticate with Blob Storage.
+    pub fn credential(&self, dotenv: Option<&DotenvFile>) -> anyhow::Result<StorageCredential> {
+        StorageCredential::new(self.connection_string(dotenv)?, self.sas_tokens(dotenv)?)
+    }
+
+    /// Resolves the storage account endpoint and container name.
+    ///
+    /// Values passed on the command line take precedence over values from the
+    /// config file.
+    pub fn resolve(
+        &self,
+        config: &Config,
+        dotenv: Option<&DotenvFile>,
+    ) -> anyhow::Result<(Url, String)> {
+        self.resolve_with(None, None, config, dotenv)
+    }
+
+    /// Resolves the storage account endpoint and container name for a file
+    /// declared in the config file.
+    ///
+    /// Values set for the file take precedence over everything else.
+    pub fn resolve_file(
+        &self,
+        file: &FileEntry,
+        config: &Config,
+        dotenv: Option<&DotenvFile>,
+    ) -> anyhow::Result<(Url, String)> {
+        self.resolve_with(
+            file.storage_account_url.as_ref(),
+            file.container_name.as_ref(),
+            config,
+            dotenv,
+        )
+    }
+
+    fn resolve_with(
+        &self,
+        storage_account_url: Option<&MaybeEnv<Url>>,
+        container_name: Option<&MaybeEnv<String>>,
+        config: &Config,
+        dotenv: Option<&DotenvFile>,
+    ) -> anyhow::Result<(Url, String)> {
+        // SAS URLs say which account and container they're for
+        let sas_scope = self
+            .sas_tokens(dotenv)?
+            .into_iter()
+            .find_map(|token| token.scope);
+        let endpoint = match storage_account_url.or(self.storage_account_url.as_ref()) {
+            Some(url) => url.resolve(dotenv)?.into_owned(),
+            None => match (self.connection_string(dotenv)?, &sas_scope) {
+                (Some(connection_string), _) => connection_string.endpoint,
+                (None, Some(scope)) => scope.endpoint.clone(),
+                (None, None) => config
+                    .storage_account_url
+                    .clone()
+                    .unwrap_or_else(|| MaybeEnv::EnvVar("STORAGE_ACCOUNT_URL".to_string()))
+                    .resolve(dotenv)?
+                    .into_owned(),
+            },
+        };
+        let sas_container = sas_scope.and_then(|scope| scope.container_name);
+        let container_name = match container_name.or(self.container_name.as_ref()) {
+            Some(name) => name.resolve(dotenv)?.into_owned(),
+            None => match sas_container {
+                Some(name) => name,
+                None => config
+                    .container_name
+                    .clone()
+                    .unwrap_or_else(|| MaybeEnv::EnvVar("STORAGE_ACCOUNT_CONTAINER".to_string()))
+                    .resolve(dotenv)?
+                    .into_owned(),
+            },
+        };
+
+        Ok((endpoint, container_name))
+    }
 }
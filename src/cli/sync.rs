@@ -1,7 +1,22 @@
+use std::{num::NonZeroUsize, sync::Arc};
+
 use clap::{Args, ValueEnum};
+use serde::Deserialize;
+use time::Duration;
+use tracing::warn;
+
+use crate::{
+    cli::parse_duration,
+    config::{Config, SyncCommand},
+    sync::{ConfirmedPlan, MODIFIED_TOLERANCE, PlanSummary, confirm},
+};
 
 /// Options for synchronizing between local and remote.
 #[derive(Clone, Debug, Args)]
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "Each flag is an independent switch"
+)]
 pub struct SyncOptions {
     /// How to synchronize values.
     ///
@@ -17,18 +32,105 @@ pub struct SyncOptions {
     /// `pull` always indicates reading from Azure and storing locally. `push`
     /// always indicates reading locally and sending to Azure. Values that end
     /// in `-always` will always push/pull relevant values.
-    #[arg(long, short = 'm', value_enum, default_value_t)]
-    pub sync_mode: SyncMode,
+    ///
+    /// If not provided, the value from the config file is used (preferring
+    /// the one set for this command in `sync-modes`), or `sync` if it's not
+    /// configured either.
+    #[arg(long, short = 'm', value_enum)]
+    pub sync_mode: Option<SyncMode>,
 
     /// Only check if anything needs to be synchronized.
     ///
     /// Any changes that need to be made are printed to stdout.
     ///
-    /// The application returns an error status if the local dotenv is out
-    /// of sync.
+    /// The application exits with status 1 if anything is out of sync, 3 if
+    /// anything diverged, and 2 if checking fails.
     #[arg(long, short = 'c')]
     pub check_only: bool,
 
+    /// Check whether each change would succeed, without making any changes.
+    ///
+    /// Unlike --check-only, this goes through the same steps as actually
+    /// synchronizing, like opening files and checking that remote values
+    /// haven't changed, but skips anything that would modify them. Changes
+    /// that would fail are reported, and the application returns an error
+    /// status if any would.
+    #[arg(long, conflicts_with = "check_only")]
+    pub dry_run: bool,
+
+    /// With --check-only, only return an error status if something has been
+    /// out of sync for longer than this, like `1h` or `2d`.
+    ///
+    /// How long something has been out of sync is measured from when the copy
+    /// that would be synchronized from was modified. This is useful for
+    /// scheduled checks that should only alert when values stay out of sync,
+    /// not every time one changes.
+    #[arg(long, value_parser = parse_duration, requires = "check_only")]
+    pub max_drift: Option<Duration>,
+
+    /// How many seconds apart modified times can be while still being
+    /// considered the same.
+    ///
+    /// Local and remote clocks rarely agree exactly, so values modified within
+    /// a minute of each other are considered unchanged by default. Raise this
+    /// if clocks drift further apart, or pass 0 to only consider identical
+    /// times the same. This only applies when modified times are compared,
+    /// not when version counters or recorded sync state decide.
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value_t = MODIFIED_TOLERANCE.whole_seconds().unsigned_abs(),
+    )]
+    pub time_tolerance: u64,
+
+    /// Explain why each value will be pushed, pulled, or skipped.
+    ///
+    /// For each value, the modified times (or version counters) that were
+    /// compared are shown, along with the rule that decided what to do, like
+    /// when the times are too close together to tell which is newer or when
+    /// the sync mode doesn't allow pulling.
+    #[arg(long)]
+    pub explain: bool,
+
+    /// Print a summary of the plan to stdout as JSON.
+    ///
+    /// The summary counts how many values will be pushed, pulled, or deleted,
+    /// and how many will be skipped for each reason. This is useful with
+    /// --check-only for reporting on how far out of sync values are.
+    #[arg(long)]
+    pub json: bool,
+
+    /// Only make the first N changes, then stop.
+    ///
+    /// The whole plan is printed as usual, but only the first N pushes,
+    /// pulls, or deletions in it are made. The rest are reported as held
+    /// back. This is useful for trying out risky changes against production
+    /// a few at a time, running again to continue once they've been checked.
+    #[arg(long, value_name = "N", conflicts_with_all = ["check_only", "dry_run"])]
+    pub canary: Option<NonZeroUsize>,
+
+    /// The most requests to make to Azure at once.
+    ///
+    /// Limits how many values are planned, checked, and loaded and how many
+    /// changes are made at the same time. Lower this if Azure throttles
+    /// requests (with 429 errors) when synchronizing many values. By default,
+    /// a few dozen values are planned at once and every change is made at
+    /// once.
+    #[arg(long, value_name = "N")]
+    pub max_concurrency: Option<NonZeroUsize>,
+
+    /// Fail if a change makes no progress for this long, like `30s` or `5m`.
+    ///
+    /// A transfer can stall without ever failing, for example when a network
+    /// connection drops silently, and would otherwise be waited on forever.
+    /// Transfers make progress with each chunk downloaded or block uploaded,
+    /// and other changes need to finish within this time. When a change
+    /// stalls, the remaining changes are stopped and the application returns
+    /// an error status, so running again retries anything that wasn't made.
+    /// By default, changes are waited on as long as they take.
+    #[arg(long, value_name = "DURATION", default_value = "0", value_parser = parse_duration)]
+    pub action_timeout: Duration,
+
     /// Don't ask for confirmation before synchronizing.
     ///
     /// Normally, you will be asked before any changes are made locally or in
@@ -38,10 +140,64 @@ pub struct SyncOptions {
     /// This is a potentially destructive action. Use with caution.
     #[arg(long, short = 'y')]
     pub no_confirm: bool,
+
+    /// Where `azsync batch` records what this command plans to do, so it's
+    /// only confirmed again when it's run if the plan changed.
+    #[arg(skip)]
+    pub batch_plan: Option<Arc<ConfirmedPlan>>,
+}
+
+impl SyncOptions {
+    /// Records what this command plans to do, if it's part of a batch.
+    pub fn record_plan(&self, summary: &PlanSummary) {
+        if let Some(plan) = &self.batch_plan {
+            plan.record(summary);
+        }
+    }
+
+    /// Asks for confirmation before making the changes in a plan, unless
+    /// `--no-confirm` was passed or the batch this is part of already
+    /// confirmed the same plan.
+    pub fn confirm(&self, summary: &PlanSummary) -> anyhow::Result<()> {
+        if self.no_confirm {
+            return Ok(());
+        }
+        if let Some(plan) = &self.batch_plan {
+            if plan.get().as_ref() == Some(summary) {
+                return Ok(());
+            }
+            warn!("The plan changed since the batch was confirmed");
+        }
+
+        confirm()
+    }
+
+    /// Gets the sync mode to use for a command.
+    ///
+    /// The mode passed on the command line takes precedence over the mode from
+    /// the config file.
+    pub fn sync_mode(&self, config: &Config, command: SyncCommand) -> SyncMode {
+        self.sync_mode
+            .or(config.sync_mode_for(command))
+            .unwrap_or_default()
+    }
+
+    /// Gets how far apart modified times can be while still being considered
+    /// the same.
+    pub fn time_tolerance(&self) -> Duration {
+        Duration::seconds(self.time_tolerance.try_into().unwrap_or(i64::MAX))
+    }
+
+    /// Gets how many values to plan at once, which is `default` unless
+    /// --max-concurrency is passed.
+    pub fn plan_concurrency(&self, default: usize) -> usize {
+        self.max_concurrency.map_or(default, NonZeroUsize::get)
+    }
 }
 
 /// Mode for synchronizing between local and remote.
-#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default, ValueEnum)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum SyncMode {
     /// Push if local is newer, pull if remote is newer.
     ///
@@ -74,3 +230,80 @@ pub enum SyncMode {
     #[value(name = "pull-always")]
     PullAlways,
 }
+
+impl SyncMode {
+    /// Checks if this mode ever pushes values.
+    pub fn can_push(self) -> bool {
+        matches!(self, Self::Sync | Self::Push | Self::PushAlways)
+    }
+
+    /// Checks if this mode ever pulls values.
+    pub fn can_pull(self) -> bool {
+        matches!(self, Self::Sync | Self::Pull | Self::PullAlways)
+    }
+}
+
+/// How to handle values that changed both locally and remotely.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictStrategy {
+    /// Keep whichever value was modified most recently.
+    ///
+    /// Which direction values can be synchronized in is still limited by the
+    /// sync mode.
+    #[default]
+    #[value(name = "newest")]
+    Newest,
+
+    /// Keep the local copy.
+    ///
+    /// Values the sync mode or directives don't allow pushing are skipped.
+    #[value(name = "prefer-local")]
+    PreferLocal,
+
+    /// Keep the remote copy.
+    ///
+    /// Values the sync mode or directives don't allow pulling are skipped.
+    #[value(name = "prefer-remote")]
+    PreferRemote,
+
+    /// Show the differences and ask which value to keep.
+    #[value(name = "prompt")]
+    Prompt,
+
+    /// Skip values in conflict and write them to a conflict report.
+    ///
+    /// The report is JSON, listing when each copy was modified, hashes of
+    /// their contents, and which copy to keep is suggested. Decide on them
+    /// and run `azsync resolve` to resolve them later.
+    #[value(name = "report")]
+    Report,
+
+    /// Fail without synchronizing anything, listing the values in conflict.
+    #[value(name = "fail")]
+    Fail,
+}
+
+/// How to compress pushed files.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, ValueEnum)]
+pub enum Compression {
+    /// Compress with gzip.
+    #[value(name = "gzip")]
+    Gzip,
+
+    /// Compress with zstd, which is faster and usually smaller, but can't be
+    /// decompressed by most browsers.
+    #[value(name = "zstd")]
+    Zstd,
+}
+
+impl Compression {
+    /// Gets the name of the compression, as used in the blob's
+    /// `Content-Encoding`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+        }
+    }
+}
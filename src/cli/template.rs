@@ -0,0 +1,33 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use crate::cli::KeyVaultOptions;
+
+/// Options for rendering a template with the values of variables.
+#[derive(Clone, Debug, Args)]
+pub struct ExecTemplateOptions {
+    /// The template file to render.
+    pub template: PathBuf,
+
+    /// Where to write the rendered file.
+    ///
+    /// If not provided, the rendered file is written to stdout.
+    #[arg(long, short = 'o')]
+    pub output: Option<PathBuf>,
+
+    /// Pull the variables used by the template from Key Vault.
+    ///
+    /// Values from Key Vault take precedence over the ones in your dotenv
+    /// file. Your dotenv file is not modified.
+    #[arg(long)]
+    pub pull: bool,
+
+    /// Replace variables that aren't defined with nothing instead of failing.
+    #[arg(long)]
+    pub allow_missing: bool,
+
+    /// Options for configuring the Key Vault.
+    #[command(flatten)]
+    pub key_vault: KeyVaultOptions,
+}
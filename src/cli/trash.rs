@@ -0,0 +1,38 @@
+use clap::{Args, Subcommand};
+use time::Duration;
+
+use crate::cli::parse_duration;
+
+/// Options for managing local files moved to the trash.
+#[derive(Clone, Debug, Args)]
+pub struct TrashOptions {
+    /// The operation to perform.
+    #[command(subcommand)]
+    pub command: TrashCommand,
+}
+
+/// An operation on the trash.
+#[derive(Clone, Debug, Subcommand)]
+pub enum TrashCommand {
+    /// Permanently delete files in the trash.
+    ///
+    /// Each run's deleted files are kept in their own directory, named after
+    /// when the run happened. Whole directories are deleted at once.
+    Empty(EmptyTrashOptions),
+}
+
+/// Options for emptying the trash.
+#[derive(Clone, Debug, Args)]
+pub struct EmptyTrashOptions {
+    /// Only delete files moved to the trash at least this long ago.
+    ///
+    /// Durations can be written like `90s`, `15m`, `1h30m`, or `7d`.
+    #[arg(long, value_parser = parse_duration)]
+    pub older_than: Option<Duration>,
+
+    /// Don't ask for confirmation before deleting.
+    ///
+    /// This is a potentially destructive action. Use with caution.
+    #[arg(long, short = 'y')]
+    pub no_confirm: bool,
+}
@@ -0,0 +1,41 @@
+use clap::{Args, ValueEnum};
+
+use crate::cli::AzureStorageOptions;
+
+/// Options for reporting how much space blobs take up.
+#[derive(Clone, Debug, Args)]
+pub struct UsageOptions {
+    /// Only report on blobs whose names start with this prefix, like
+    /// `config/`.
+    ///
+    /// Folders are grouped relative to the prefix.
+    #[arg(long)]
+    pub prefix: Option<String>,
+
+    /// How to group blobs.
+    #[arg(long, value_enum, default_value_t)]
+    pub group_by: GroupBy,
+
+    /// Print the report to stdout as JSON instead of as a table.
+    ///
+    /// Sizes are in bytes, and each group is keyed by its name.
+    #[arg(long)]
+    pub json: bool,
+
+    /// Options for configuring the Storage Account.
+    #[command(flatten)]
+    pub azure_storage: AzureStorageOptions,
+}
+
+/// How to group blobs when reporting how much space they take up.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default, ValueEnum)]
+pub enum GroupBy {
+    /// By top-level folder, like `config/`.
+    #[default]
+    #[value(name = "folder")]
+    Folder,
+
+    /// By extension, like `.json`.
+    #[value(name = "extension")]
+    Extension,
+}
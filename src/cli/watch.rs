@@ -0,0 +1,44 @@
+use std::net::{IpAddr, Ipv4Addr};
+
+use clap::Args;
+use time::Duration;
+
+use crate::cli::parse_duration;
+
+/// Options for continuously synchronizing as local files change.
+#[derive(Clone, Debug, Args)]
+#[command(next_help_heading = "Watching")]
+pub struct WatchOptions {
+    /// Keep running, and synchronize again whenever local files change.
+    ///
+    /// The local files are watched through the directories they're in, so
+    /// they're noticed when they're created, deleted, or replaced. With
+    /// `azsync dir`, the whole directory is watched, so new files are
+    /// synchronized too. Changes on network drives may not be noticed. Changes
+    /// are only pushed by default (as if `-m push` was passed), and never need
+    /// to be confirmed. Failed runs are reported without stopping. Press
+    /// Ctrl+C to stop watching.
+    #[arg(long, conflicts_with_all = ["check_only", "dry_run"])]
+    pub watch: bool,
+
+    /// With --watch, how long files need to stop changing before they're
+    /// synchronized, like `5s` or `1m`.
+    #[arg(long, value_parser = parse_duration, default_value = "2s", requires = "watch")]
+    pub debounce: Duration,
+
+    /// With --watch, serve Prometheus metrics on this port.
+    ///
+    /// The metrics count the synchronizations run and how many failed, the
+    /// actions taken by type, and the bytes transferred, and record when a
+    /// synchronization last succeeded.
+    #[arg(long, value_name = "PORT", requires = "watch")]
+    pub metrics_port: Option<u16>,
+
+    /// With --metrics-port, the address to serve metrics on.
+    ///
+    /// By default, metrics are only served to this machine. Pass `0.0.0.0` to
+    /// serve them on all interfaces, like when Prometheus scrapes from
+    /// elsewhere.
+    #[arg(long, value_name = "ADDRESS", default_value_t = IpAddr::V4(Ipv4Addr::LOCALHOST))]
+    pub metrics_addr: IpAddr,
+}
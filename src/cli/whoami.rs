@@ -0,0 +1,15 @@
+use clap::Args;
+
+use crate::cli::{AzureStorageOptions, KeyVaultOptions};
+
+/// Options for reporting the current Azure identity.
+#[derive(Clone, Debug, Args)]
+pub struct WhoamiOptions {
+    /// Options for configuring the Storage Account.
+    #[command(flatten)]
+    pub azure_storage: AzureStorageOptions,
+
+    /// Options for configuring the Key Vault.
+    #[command(flatten)]
+    pub key_vault: KeyVaultOptions,
+}
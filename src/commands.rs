@@ -1,9 +1,21 @@
 #[macro_use]
 mod macros;
 
+mod batch;
 mod command;
 mod completions;
+mod dir;
 mod dotenv;
 mod file;
+mod gc;
+mod inventory;
+mod keyvault;
+mod mv;
+mod resolve;
+mod share;
+mod template;
+mod trash;
+mod usage;
+mod whoami;
 
 pub use command::*;
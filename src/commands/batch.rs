@@ -0,0 +1,174 @@
+use std::{num::NonZeroUsize, sync::Arc};
+
+use anyhow::{Context, bail};
+use clap::{CommandFactory, FromArgMatches};
+use futures::{StreamExt, stream};
+use tracing::{error, info, warn};
+
+use crate::{
+    batch::JobFile,
+    cli::{BatchOptions, Cli, CliCommand, GlobalOptions, SyncOptions},
+    commands::{Command, Status},
+    sync::{ConfirmedPlan, PlanSummary, confirm},
+};
+
+/// A job from the job file, parsed like its command line.
+struct ParsedJob<'a> {
+    name: &'a str,
+    command: CliCommand,
+    global: GlobalOptions,
+
+    /// What the job planned to do, to only confirm it again if it changed.
+    plan: Arc<ConfirmedPlan>,
+}
+
+impl Command for BatchOptions {
+    async fn execute(self, global_options: &GlobalOptions) -> anyhow::Result<Status> {
+        let file = JobFile::load(&self.jobs)?;
+        let jobs = file
+            .jobs
+            .iter()
+            .map(|job| {
+                let matches = Cli::command()
+                    .try_get_matches_from(job.command_line()?)
+                    .with_context(|| format!("Invalid job {}", job.name()))?;
+                let Cli {
+                    subcommand,
+                    mut global,
+                } = Cli::from_arg_matches(&matches)
+                    .with_context(|| format!("Invalid job {}", job.name()))?;
+                check_job(&subcommand).with_context(|| format!("Invalid job {}", job.name()))?;
+
+                // Options passed to the batch apply to each job unless it
+                // sets them itself
+                global.inherit(global_options, &matches);
+                Ok(ParsedJob {
+                    name: job.name(),
+                    command: subcommand,
+                    global,
+                    plan: Arc::default(),
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        // Plan every job first, checking that each change would succeed
+        info!("Planning {} job(s)", jobs.len());
+        let planned = run_jobs(&jobs, self.parallel_jobs, true, true).await;
+        let failed = report(&jobs, &planned);
+        if failed > 0 {
+            bail!(
+                "{failed} of {} job(s) couldn't be planned, so nothing was changed",
+                jobs.len()
+            );
+        }
+        let mut summary = PlanSummary::default();
+        for job in &jobs {
+            if let Some(plan) = job.plan.get() {
+                summary.add(&plan);
+            }
+        }
+        info!("Summary of all jobs: {summary}");
+        if self.dry_run {
+            return Ok(Status::Success);
+        }
+
+        // Ask once for the whole batch. Each job still asks before deleting
+        // anything, and asks again if its plan changed since
+        if !self.no_confirm {
+            confirm()?;
+        }
+
+        // Run every job, even if others fail
+        let results = run_jobs(&jobs, self.parallel_jobs, false, self.no_confirm).await;
+        let failed = report(&jobs, &results);
+        if failed > 0 {
+            bail!("{failed} of {} job(s) failed", jobs.len());
+        }
+
+        // Exit with the worst status of any job
+        Ok(results.into_iter().flatten().max().unwrap_or_default())
+    }
+}
+
+/// Checks that a job only synchronizes, so it can be planned and run without
+/// asking for confirmation on its own.
+fn check_job(command: &CliCommand) -> anyhow::Result<()> {
+    match command {
+        CliCommand::Dir(_) | CliCommand::Keyvault(_) => {}
+        CliCommand::Dotenv(options) => {
+            if options.command.is_some() || options.export.is_some() || options.watch.watch {
+                bail!("Jobs can only synchronize dotenv files, without --export or --watch");
+            }
+        }
+        CliCommand::File(options) => {
+            if options.watch.watch {
+                bail!("Jobs can't use --watch");
+            }
+        }
+        _ => bail!("Jobs can only run `azsync dir`, `dotenv`, `file`, or `keyvault`"),
+    }
+
+    Ok(())
+}
+
+/// Gets the synchronization options of a job's command.
+fn sync_options(command: &mut CliCommand) -> Option<&mut SyncOptions> {
+    match command {
+        CliCommand::Dir(options) => Some(&mut options.sync),
+        CliCommand::Dotenv(options) => Some(&mut options.sync),
+        CliCommand::File(options) => Some(&mut options.sync),
+        CliCommand::Keyvault(options) => Some(&mut options.sync),
+        _ => None,
+    }
+}
+
+/// Runs each job, either as a dry run or for real, returning the results in
+/// the same order as the jobs.
+async fn run_jobs(
+    jobs: &[ParsedJob<'_>],
+    parallel_jobs: NonZeroUsize,
+    dry_run: bool,
+    no_confirm: bool,
+) -> Vec<anyhow::Result<Status>> {
+    let total = jobs.len();
+    stream::iter(jobs.iter().enumerate())
+        .map(|(index, job)| async move {
+            info!("Job {}/{total}: {}", index + 1, job.name);
+            let mut command = job.command.clone();
+            if let Some(sync) = sync_options(&mut command) {
+                sync.check_only = false;
+                sync.dry_run = dry_run;
+                sync.no_confirm = no_confirm;
+                sync.batch_plan = Some(job.plan.clone());
+            }
+            match command {
+                CliCommand::Dir(options) => Box::pin(options.execute(&job.global)).await,
+                CliCommand::Dotenv(options) => Box::pin(options.execute(&job.global)).await,
+                CliCommand::File(options) => Box::pin(options.execute(&job.global)).await,
+                CliCommand::Keyvault(options) => Box::pin(options.execute(&job.global)).await,
+                _ => unreachable!("jobs are checked before they're run"),
+            }
+        })
+        .buffered(parallel_jobs.get())
+        .collect()
+        .await
+}
+
+/// Logs how each job went, returning how many failed.
+fn report(jobs: &[ParsedJob<'_>], results: &[anyhow::Result<Status>]) -> usize {
+    info!("Jobs:");
+    let mut failed = 0;
+    for (job, result) in jobs.iter().zip(results) {
+        match result {
+            Ok(Status::Success) => info!("  {}: ok", job.name),
+            Ok(Status::Drifted) => warn!("  {}: out of sync", job.name),
+            Ok(Status::Diverged) => warn!("  {}: diverged", job.name),
+            Err(error) => {
+                error!("  {}: {error:#}", job.name);
+                failed += 1;
+            }
+        }
+    }
+
+    failed
+}
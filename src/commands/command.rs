@@ -1,7 +1,40 @@
-use crate::cli::GlobalOptions;
+use std::process::ExitCode;
+
+use crate::{
+    cli::GlobalOptions,
+    sync::{EXIT_DIVERGED, EXIT_DRIFTED},
+};
 
 /// An executable CLI subcommand.
 pub trait Command: Sized {
-    /// Execute this command.
-    async fn execute(self, global_options: &GlobalOptions) -> anyhow::Result<()>;
+    /// Execute this command, returning how it finished if it didn't fail.
+    async fn execute(self, global_options: &GlobalOptions) -> anyhow::Result<Status>;
+}
+
+/// How a command finished without failing, which decides the status azsync
+/// exits with.
+///
+/// Statuses are ordered from best to worst, so the worst of several can be
+/// found with [`Ord::max`].
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub enum Status {
+    /// The command did what it was asked to.
+    #[default]
+    Success,
+
+    /// Something is out of sync, like with `--check-only`.
+    Drifted,
+
+    /// Something diverged, with `--check-only`.
+    Diverged,
+}
+
+impl From<Status> for ExitCode {
+    fn from(status: Status) -> Self {
+        match status {
+            Status::Success => ExitCode::SUCCESS,
+            Status::Drifted => ExitCode::from(EXIT_DRIFTED),
+            Status::Diverged => ExitCode::from(EXIT_DIVERGED),
+        }
+    }
 }
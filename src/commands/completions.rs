@@ -1,18 +1,24 @@
 use std::io::stdout;
 
+use anyhow::{Context, bail};
 use clap::CommandFactory;
-use clap_complete::generate;
+use clap_complete::{env::Shells, generate};
 use clap_complete_nushell::Nushell;
 
 use crate::{
     cli::{Cli, CompletionsOptions, GlobalOptions, Shell},
-    commands::Command,
+    commands::{Command, Status},
 };
 
 impl Command for CompletionsOptions {
-    async fn execute(self, _global_options: &GlobalOptions) -> anyhow::Result<()> {
+    async fn execute(self, _global_options: &GlobalOptions) -> anyhow::Result<Status> {
         let mut cmd = Cli::command();
-        let bin_name = cmd.get_name().to_string();
+        let bin_name = self.bin_name.unwrap_or_else(|| cmd.get_name().to_string());
+
+        if self.dynamic {
+            write_registration(self.shell, &bin_name)?;
+            return Ok(Status::Success);
+        }
 
         // Map shell to clap_complete shell type
         let shell = match self.shell {
@@ -24,13 +30,36 @@ impl Command for CompletionsOptions {
             Shell::Nushell => {
                 // This uses clap_complete_nushell's generator instead
                 generate(Nushell, &mut cmd, bin_name, &mut stdout());
-                return Ok(());
+                return Ok(Status::Success);
             }
         };
 
         // Generate completions
         generate(shell, &mut cmd, bin_name, &mut stdout());
 
-        Ok(())
+        Ok(Status::Success)
     }
 }
+
+/// Writes a script that registers dynamic completions with the shell.
+///
+/// The shell calls back into azsync with `COMPLETE=<shell>` set to get
+/// completions.
+fn write_registration(shell: Shell, bin_name: &str) -> anyhow::Result<()> {
+    let shell_name = match shell {
+        Shell::Bash => "bash",
+        Shell::PowerShell => "powershell",
+        Shell::Zsh => "zsh",
+        Shell::Elvish => "elvish",
+        Shell::Fish => "fish",
+        Shell::Nushell => bail!("Dynamic completions are not supported for Nushell"),
+    };
+
+    Shells::builtins()
+        .completer(shell_name)
+        .with_context(|| format!("Dynamic completions are not supported for {shell_name}"))?
+        .write_registration("COMPLETE", bin_name, bin_name, bin_name, &mut stdout())
+        .context("Failed to write completions")?;
+
+    Ok(())
+}
@@ -0,0 +1,297 @@
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    fs::{create_dir_all, read_dir},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context as _, bail};
+use time::Duration;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    cli::{GlobalOptions, SyncDirOptions, SyncFileOptions, SyncMode, WatchOptions},
+    commands::{
+        Command, Status,
+        file::{Container, Context},
+    },
+    config::Config,
+    dotenv::DotenvFile,
+    glob::Pattern,
+    metrics,
+    storage::{EncryptionKey, is_sidecar, is_temp_file, list_blobs, local_path_in},
+    watch::{Watched, wait_for_changes},
+};
+
+impl Command for SyncDirOptions {
+    async fn execute(mut self, global_options: &GlobalOptions) -> anyhow::Result<Status> {
+        if !self.watch.watch {
+            return self.sync_once(global_options).await;
+        }
+
+        // Nobody is around to confirm each run
+        self.sync.sync_mode.get_or_insert(SyncMode::Push);
+        self.sync.no_confirm = true;
+        if let Some(port) = self.watch.metrics_port {
+            metrics::serve(self.watch.metrics_addr, port).await?;
+        }
+        let mut succeeded = false;
+        loop {
+            let result = self.clone().sync_once(global_options).await;
+            metrics::record_sync(result.is_ok());
+            match result {
+                Ok(_) => succeeded = true,
+                // Give up if it never worked
+                Err(error) if !succeeded => return Err(error),
+                Err(error) => error!("{error:#}"),
+            }
+
+            // Watch the directory itself, so new files are noticed too
+            create_dir_all(&self.path)
+                .with_context(|| format!("Can't create {}", self.path.display()))?;
+            let root = self.path.canonicalize()?;
+            let filter = FileFilter {
+                exclude: &self.exclude,
+                sidecar: self.sidecar,
+                lossy_names: self.lossy_names,
+            };
+            let is_watched = |path: &Path| filter.watches(&root, path);
+            info!("Watching {} for changes", root.display());
+            wait_for_changes(
+                &Watched::Directory {
+                    root: &root,
+                    filter: &is_watched,
+                },
+                self.watch.debounce,
+            )
+            .await?;
+        }
+    }
+}
+
+impl SyncDirOptions {
+    /// Finds the files and blobs in the directory and synchronizes them once.
+    async fn sync_once(self, global_options: &GlobalOptions) -> anyhow::Result<Status> {
+        let config = Config::load(global_options)?;
+
+        // Load dotenv file
+        let dotenv = if global_options.no_env_file {
+            None
+        } else {
+            DotenvFile::from_path_exists(&global_options.env_file, global_options.strict_parse)?
+        };
+
+        // Find the directory
+        let root = match self.path.canonicalize() {
+            Ok(path) => path,
+            // Directory doesn't exist yet, so everything is pulled into it
+            Err(error) if error.kind() == ErrorKind::NotFound => self.path.clone(),
+            Err(error) => {
+                return Err(error).with_context(|| format!("Can't read {}", self.path.display()));
+            }
+        };
+        if root.exists() && !root.is_dir() {
+            bail!("{} is not a directory", root.display());
+        }
+        let prefix = match self.prefix.trim_matches('/') {
+            "" => String::new(),
+            prefix => format!("{prefix}/"),
+        };
+
+        // Find local files and remote blobs
+        let mut paths = BTreeMap::new();
+        if root.exists() {
+            let filter = FileFilter {
+                exclude: &self.exclude,
+                sidecar: self.sidecar,
+                lossy_names: self.lossy_names,
+            };
+            find_files(&root, &root, &filter, &mut paths)?;
+        }
+        let (endpoint, name) = self.azure_storage.resolve(&config, dotenv.as_ref())?;
+        config.policy.check_storage(&endpoint, &name)?;
+        let credential = self.azure_storage.credential(dotenv.as_ref())?;
+        let client = credential.container_client(&endpoint, name.clone())?;
+        let blobs = list_blobs(
+            &client,
+            Some(prefix.as_str()).filter(|prefix| !prefix.is_empty()),
+            false,
+        )
+        .await
+        .context("Failed to list blobs")?;
+        let blob_paths = blobs
+            .into_iter()
+            .filter_map(|blob| blob.name.strip_prefix(&prefix).map(ToString::to_string))
+            .filter(|path| !path.is_empty() && !is_excluded(path, &self.exclude));
+        for path in blob_paths {
+            // Blob names could point anywhere, so keep them in the directory
+            let Some(local_path) = local_path_in(&root, &path) else {
+                warn!("Skipping {prefix}{path} (no local path for it)");
+                continue;
+            };
+            paths.entry(path).or_insert(local_path);
+        }
+        debug!(?paths);
+
+        // Synchronize each path like a file
+        let container = Container { endpoint, name };
+        let contexts = paths
+            .into_iter()
+            .map(|(path, local_path)| Context {
+                container: container.clone(),
+                blob_name: format!("{prefix}{path}"),
+                local_path,
+            })
+            .collect();
+        let options = SyncFileOptions {
+            paths: Vec::new(),
+            blob_name: None,
+            remote_prefix: None,
+            stage_dir: self.stage_dir,
+            temp_dir: self.temp_dir,
+            resume: self.resume,
+            lossy_names: self.lossy_names,
+            sidecar: self.sidecar,
+            preserve_acl: self.preserve_acl,
+            checksum: self.checksum,
+            normalize_text: self.normalize_text,
+            encrypt_key: None,
+            compress: self.compress,
+            delete: self.delete,
+            hard_delete: self.hard_delete,
+            on_conflict: self.on_conflict,
+            conflict_report: self.conflict_report,
+            decisions: self.decisions,
+            allow_secrets: self.allow_secrets,
+            inventory: None,
+            progress: self.progress,
+            block_size: self.block_size,
+            block_concurrency: self.block_concurrency,
+            idempotent: self.idempotent,
+            sync: self.sync,
+            watch: WatchOptions {
+                watch: false,
+                debounce: Duration::ZERO,
+                metrics_port: None,
+                metrics_addr: self.watch.metrics_addr,
+            },
+            azure_storage: self.azure_storage,
+        };
+        let encryption = self
+            .encrypt_key
+            .as_ref()
+            .map(|key| EncryptionKey::load(key, dotenv.as_ref()))
+            .transpose()?;
+        options
+            .sync_contexts(
+                contexts,
+                &root,
+                credential,
+                encryption,
+                &config,
+                global_options,
+            )
+            .await
+    }
+}
+
+/// Which local files to synchronize.
+struct FileFilter<'a> {
+    /// Patterns for paths to skip.
+    exclude: &'a [Pattern],
+
+    /// Whether to skip sidecar files.
+    sidecar: bool,
+
+    /// Whether to replace parts of names that aren't valid Unicode instead of
+    /// skipping those files.
+    lossy_names: bool,
+}
+
+impl FileFilter<'_> {
+    /// Checks whether changing a path in `root` could change what's
+    /// synchronized, ignoring what azsync writes there itself, like temporary
+    /// files and its state in `.azsync`.
+    fn watches(&self, root: &Path, path: &Path) -> bool {
+        relative_path(root, path, self.lossy_names).is_some_and(|relative| {
+            !is_excluded(&relative, self.exclude)
+                && !relative.split('/').any(|component| component == ".azsync")
+        }) && !(self.sidecar && is_sidecar(path))
+            && !is_temp_file(path)
+    }
+}
+
+/// Finds the files in a directory and its subdirectories, by their paths
+/// relative to `root` using `/` as the separator.
+///
+/// Symbolic links to files are followed, but links to directories are not.
+/// Files are skipped as set by `filter`.
+fn find_files(
+    root: &Path,
+    directory: &Path,
+    filter: &FileFilter<'_>,
+    paths: &mut BTreeMap<String, PathBuf>,
+) -> anyhow::Result<()> {
+    let entries =
+        read_dir(directory).with_context(|| format!("Can't read {}", directory.display()))?;
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        let Some(relative) = relative_path(root, &path, filter.lossy_names) else {
+            warn!(
+                "Skipping {} (path isn't valid Unicode, pass --lossy-names to replace invalid characters)",
+                path.display()
+            );
+            continue;
+        };
+        if is_excluded(&relative, filter.exclude)
+            || (filter.sidecar && is_sidecar(&path))
+            || is_temp_file(&path)
+        {
+            continue;
+        }
+
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            find_files(root, &path, filter, paths)?;
+        } else if file_type.is_file() || path.is_file() {
+            if paths.contains_key(&relative) {
+                warn!(
+                    "Skipping {} (another file has the same name with --lossy-names)",
+                    path.display()
+                );
+                continue;
+            }
+            paths.insert(relative, path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Gets a path relative to `root` using `/` as the separator.
+///
+/// Returns `None` if the path isn't valid Unicode, unless `lossy` is set, in
+/// which case invalid parts are replaced with `\u{FFFD}`.
+pub(super) fn relative_path(root: &Path, path: &Path, lossy: bool) -> Option<String> {
+    let components: Option<Vec<_>> = path
+        .strip_prefix(root)
+        .ok()?
+        .components()
+        .map(|component| {
+            let component = component.as_os_str();
+            if lossy {
+                Some(component.to_string_lossy())
+            } else {
+                component.to_str().map(Cow::Borrowed)
+            }
+        })
+        .collect();
+    Some(components?.join("/"))
+}
+
+/// Checks whether a relative path matches any of the excluded patterns.
+fn is_excluded(path: &str, exclude: &[Pattern]) -> bool {
+    exclude.iter().any(|pattern| pattern.matches(path))
+}
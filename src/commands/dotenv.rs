@@ -1,113 +1,868 @@
 use std::{
     cmp::max,
-    collections::{HashMap, HashSet},
-    fs::File,
-    future::ready,
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::{File, OpenOptions},
     io::Write,
-    process::exit,
+    path::Path,
     sync::{
         Arc,
         mpsc::{Sender, channel},
     },
 };
 
-use anyhow::Context;
+use anyhow::{Context, bail};
 use azure_identity::DefaultAzureCredential;
-use azure_security_keyvault_secrets::{SecretClient, models::SetSecretParameters};
-use futures::{StreamExt, TryStreamExt, future::ok, stream::FuturesUnordered};
-use time::OffsetDateTime;
-use tracing::{debug, info};
+use azure_security_keyvault_secrets::{SecretClient, models::Secret};
+use azure_storage_blob::BlobClient;
+use futures::{StreamExt, TryStreamExt, future::ok, stream};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use tracing::{debug, error, info, warn};
+
+use url::Url;
 
 use crate::{
-    cli::{GlobalOptions, SyncDotenvOptions, SyncMode},
-    commands::Command,
-    dotenv::DotenvFile,
-    sync::{SyncAction, SyncType, confirm},
+    app_config::AppConfigClient,
+    audit::{AuditEntry, AuditLog, hash_secret, load_hash_key},
+    cli::{
+        AzureStorageOptions, ConflictStrategy, DotenvBackend, DotenvCommand, EmptyValues,
+        GlobalOptions, RemoteDeleteStrategy, SyncDotenvOptions, SyncMode, Tag, TemplateSource,
+    },
+    commands::{Command, Status},
+    config::{Config, SyncCommand},
+    conflicts::{ConflictReport, ConflictSide, ReportedConflict, Resolution},
+    dotenv::{Directives, DotenvFile, Trailer, render_export},
+    format::word_diff,
+    manifest::MANIFEST_FILE,
+    metrics,
+    storage::{blob_url, parse_blob_url},
+    sync::{
+        Changed, MAX_REPLANS, PlanSummary, SkipReason, SyncAction, SyncType, check_writable,
+        choose, dry_run, execute_all, explain_modified, explain_ordering, is_drifted,
+        modified_time, report_canary, split_stale, take_canary,
+    },
+    synced::{Changes, SyncHistory, SyncedState, explain_changes},
+    versions::{
+        SyncedVersion, VERSION_KEY, VersionUpdate, compare_versions, explain_versions,
+        parse_version,
+    },
+    watch::{Watched, wait_for_changes},
 };
 
+mod import;
+mod lint;
+mod store;
+
+use store::{Replica, VarStore, VarStores};
+
+type VarAction = SyncType<PushVar, PullVar, String, DeleteVar>;
+
+/// A variable's name, local value, remote value, and secret URL.
+type VarSeed<'a> = (&'a str, Option<String>, Option<String>, String);
+
+impl VarAction {
+    /// Gets the name of the variable this action is for.
+    fn name(&self) -> &str {
+        match self {
+            SyncType::Push(inner) => &inner.name,
+            SyncType::Pull(inner) => &inner.name,
+            SyncType::DeleteLocal(inner) => &inner.name,
+            SyncType::DeleteRemote(never) => match *never {},
+            SyncType::Skip { data, .. } => data,
+        }
+    }
+}
+
 impl Command for SyncDotenvOptions {
-    async fn execute(self, global_options: &GlobalOptions) -> anyhow::Result<()> {
+    async fn execute(mut self, global_options: &GlobalOptions) -> anyhow::Result<Status> {
+        load_hash_key()?;
+        match self.command.take() {
+            Some(DotenvCommand::Import(command)) => return command.execute(global_options).await,
+            Some(DotenvCommand::Lint(command)) => return command.execute(global_options).await,
+            None => {}
+        }
+
+        if !self.watch.watch {
+            return self.synchronize(global_options).await;
+        }
+
+        // Nobody is around to confirm each run
+        self.sync.sync_mode.get_or_insert(SyncMode::Push);
+        self.sync.no_confirm = true;
+        if let Some(port) = self.watch.metrics_port {
+            metrics::serve(self.watch.metrics_addr, port).await?;
+        }
+        let mut watched = vec![global_options.env_file.clone()];
+        if let TemplateSource::Path(path) = &self.template_file
+            && !self.no_template
+        {
+            watched.push(path.clone());
+        }
+        let mut succeeded = false;
+        loop {
+            let result = self.synchronize(global_options).await;
+            metrics::record_sync(result.is_ok());
+            match result {
+                Ok(_) => succeeded = true,
+                // Give up if it never worked
+                Err(error) if !succeeded => return Err(error),
+                Err(error) => error!("{error:#}"),
+            }
+            info!("Watching {} for changes", global_options.env_file.display());
+            wait_for_changes(&Watched::Files(&watched), self.watch.debounce).await?;
+        }
+    }
+}
+
+impl SyncDotenvOptions {
+    /// Synchronizes the dotenv file, planning again if anything changes while
+    /// waiting for confirmation.
+    async fn synchronize(&self, global_options: &GlobalOptions) -> anyhow::Result<Status> {
+        for _ in 0..=MAX_REPLANS {
+            if let Some(status) = self.sync_once(global_options).await? {
+                return Ok(status);
+            }
+            warn!("Variables changed since planning, planning again");
+        }
+
+        bail!("Variables kept changing after planning, try again later");
+    }
+
+    /// Gets the value of a remote variable to compare and pull, turning
+    /// placeholders for empty values back into empty values.
+    ///
+    /// A placeholder matches a local value that's only whitespace too, since
+    /// that's what would have been pushed for it.
+    fn unmask_empty(&self, store: &VarStore, remote: &str, local: Option<&str>) -> String {
+        if self.empty_values != EmptyValues::Placeholder
+            || store.stores_empty_values()
+            || remote != self.empty_placeholder
+        {
+            return remote.to_string();
+        }
+
+        local
+            .filter(|local| is_blank(local))
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    /// Applies --empty-values to pushes of empty or whitespace-only values to
+    /// stores that can't hold them.
+    fn handle_empty_values(&self, actions: Vec<VarAction>) -> anyhow::Result<Vec<VarAction>> {
+        let mut empty = Vec::new();
+        let actions = actions
+            .into_iter()
+            .map(|action| match action {
+                SyncType::Push(mut push)
+                    if is_blank(&push.value) && !push.store.stores_empty_values() =>
+                {
+                    match self.empty_values {
+                        EmptyValues::Error => {
+                            empty.push(push.name.clone());
+                            SyncType::Push(push)
+                        }
+                        EmptyValues::Skip => SyncType::Skip {
+                            reason: SkipReason::EmptyValue,
+                            data: push.name,
+                        },
+                        EmptyValues::Placeholder => {
+                            push.value.clone_from(&self.empty_placeholder);
+                            push.placeholder = true;
+                            SyncType::Push(push)
+                        }
+                    }
+                }
+                action => action,
+            })
+            .collect();
+
+        if !empty.is_empty() {
+            empty.sort_unstable();
+            bail!(
+                "Key Vault can't store empty values, so {} can't be pushed (pass --empty-values to skip them or push a placeholder)",
+                empty.join(", ")
+            );
+        }
+
+        Ok(actions)
+    }
+
+    /// Writes the remote values of variables to --export in another format.
+    ///
+    /// Variables that aren't stored remotely are left out.
+    async fn export_vars(
+        &self,
+        path: &Path,
+        stores: &VarStores,
+        names: &HashSet<&str>,
+        dotenv: Option<&DotenvFile>,
+    ) -> anyhow::Result<()> {
+        let remote_vars = stores
+            .get_remote_vars(SyncMode::Pull, names.iter().copied())
+            .await?;
+
+        let mut vars = BTreeMap::new();
+        for &name in names {
+            let Some(remote) = remote_vars.values.get(name) else {
+                if remote_vars.disabled.contains(name) {
+                    warn!("{name} is disabled in Key Vault, leaving it out");
+                } else {
+                    warn!("{name} isn't stored in Azure, leaving it out");
+                }
+                continue;
+            };
+            let local = dotenv
+                .and_then(|dotenv| dotenv.parameters.get(name))
+                .map(String::as_str);
+            let value = self.unmask_empty(stores.get(name), &remote.value, local);
+            vars.insert(name.to_string(), value);
+        }
+
+        let output = render_export(&vars, self.format, &self.secret_name)?;
+        if path == Path::new("-") {
+            print!("{output}");
+        } else {
+            // Only the owner should be able to read exported secrets
+            let mut options = OpenOptions::new();
+            options.write(true).create(true).truncate(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            options
+                .open(path)
+                .and_then(|mut file| file.write_all(output.as_bytes()))
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            info!("Exported {} variable(s) to {}", vars.len(), path.display());
+        }
+
+        Ok(())
+    }
+
+    /// Loads the template file, downloading it first if it's stored in Blob
+    /// Storage.
+    async fn load_template(
+        &self,
+        config: &Config,
+        dotenv: Option<&DotenvFile>,
+        global_options: &GlobalOptions,
+    ) -> anyhow::Result<Option<DotenvFile>> {
+        let (endpoint, container_name, blob_name) = match &self.template_file {
+            TemplateSource::Path(path) => {
+                return DotenvFile::from_path_exists(path, global_options.strict_parse);
+            }
+            TemplateSource::BlobUrl(url) => parse_blob_url(url)?,
+            TemplateSource::Blob(name) => {
+                let storage = AzureStorageOptions {
+                    storage_account_url: None,
+                    container_name: None,
+                    connection_string: None,
+                    sas_tokens: Vec::new(),
+                };
+                let (endpoint, container_name) =
+                    storage.resolve(config, dotenv.filter(|_| !global_options.no_env_file))?;
+                (endpoint.to_string(), container_name, name.clone())
+            }
+        };
+        config
+            .policy
+            .check_storage(&endpoint.parse()?, &container_name)?;
+
+        // Download the template
+        let credential =
+            DefaultAzureCredential::new().context("Failed to get default Azure credential")?;
+        let client = BlobClient::new(&endpoint, container_name, blob_name, credential, None)?;
+        let url = blob_url(&client);
+        info!("Using template: {url}");
+        let mut body = client
+            .download(None)
+            .await
+            .with_context(|| format!("Failed to download template {url}"))?
+            .into_raw_body();
+        let mut bytes = Vec::new();
+        while let Some(chunk) = body.try_next().await? {
+            bytes.extend_from_slice(&chunk);
+        }
+
+        DotenvFile::from_bytes(&bytes, &url, global_options.strict_parse).map(Some)
+    }
+
+    /// Plans and executes a synchronization, returning how it finished.
+    ///
+    /// Returns `None` without making any changes if anything changed after
+    /// planning.
+    async fn sync_once(&self, global_options: &GlobalOptions) -> anyhow::Result<Option<Status>> {
+        let config = Config::load(global_options)?;
+
         // Load dotenv file
-        let dotenv = DotenvFile::from_path_exists(&global_options.env_file)?;
+        let dotenv =
+            DotenvFile::from_path_exists(&global_options.env_file, global_options.strict_parse)?;
         let template = if self.no_template {
             None
         } else {
-            DotenvFile::from_path_exists(&self.template_file)?
+            self.load_template(&config, dotenv.as_ref(), global_options)
+                .await?
         };
 
         // Collect list of variables to synchronize
-        let vars_to_sync: HashSet<_> = template
+        let listed = template
             .as_ref()
-            .map(|template| template.parameters.keys())
-            .or_else(|| Some(dotenv.as_ref()?.parameters.keys()))
-            .context("Cannot synchronize without a dotenv or dotenv template file")?
-            .map(String::as_str)
-            .collect();
+            .or(dotenv.as_ref())
+            .map(|file| &file.parameters);
+        let mut vars_to_sync: HashSet<_> = if self.vars.is_empty() {
+            listed
+                .context("Cannot synchronize without a dotenv or dotenv template file")?
+                .keys()
+                .map(String::as_str)
+                .collect()
+        } else {
+            for name in &self.vars {
+                if !listed.is_some_and(|listed| listed.contains_key(name)) {
+                    warn!(
+                        "{name} isn't defined in the template or dotenv file, synchronizing it anyway"
+                    );
+                }
+            }
+            self.vars.iter().map(String::as_str).collect()
+        };
+        vars_to_sync.retain(|name| self.is_selected(name));
+        if vars_to_sync.is_empty() && !(self.only.is_empty() && self.exclude.is_empty()) {
+            warn!("No variables match --only and --exclude");
+        }
         debug!(local_vars=?vars_to_sync.iter());
 
+        // Directives come from the same file as the list of variables
+        let directives = template
+            .as_ref()
+            .or(dotenv.as_ref())
+            .map(|file| &file.directives);
+
+        // Variables that are never synchronized aren't looked up remotely, but
+        // are still listed as skipped
+        let mut never_synced = Vec::new();
+        vars_to_sync.retain(|&name| {
+            let skip = match directives.and_then(|directives| directives.get(name)) {
+                Some(directives) if directives.template_only => (
+                    SkipReason::TemplateOnly,
+                    "the variable has a template-only directive",
+                ),
+                Some(directives) if directives.skip => (
+                    SkipReason::SkipDirective,
+                    "the variable has a skip directive",
+                ),
+                _ => return true,
+            };
+            never_synced.push((name, skip));
+            false
+        });
+
         // Create client
         let credential =
             DefaultAzureCredential::new().context("Failed to get default Azure credential")?;
-        let key_vault_url = self
-            .key_vault
-            .key_vault_url
-            .resolve(dotenv.as_ref().filter(|_| !global_options.no_env_file))?;
+        let env_file = dotenv.as_ref().filter(|_| !global_options.no_env_file);
         info!("Using:");
-        info!("  Key Vault: {key_vault_url}");
-        let client = SecretClient::new(key_vault_url.as_str(), credential, None)
-            .context("Failed to create Key Vault secrets client")?;
+        let routes: Vec<_> = vars_to_sync
+            .iter()
+            .map(|&name| {
+                let vault = directives
+                    .and_then(|directives| directives.get(name))
+                    .and_then(|directives| directives.vault.as_deref());
+                (name, vault)
+            })
+            .collect();
+        let needs_default = routes.iter().any(|(_, vault)| vault.is_none());
+        let default_store = if needs_default {
+            Some(match self.backend {
+                DotenvBackend::KeyVault => {
+                    let key_vault_url = self.key_vault.resolve(&config, env_file)?;
+                    config.policy.check_key_vault(&key_vault_url)?;
+                    info!("  Key Vault: {key_vault_url}");
+                    let client =
+                        SecretClient::new(key_vault_url.as_str(), credential.clone(), None)
+                            .context("Failed to create Key Vault secrets client")?;
+                    let replica = match self
+                        .key_vault_replica_url
+                        .as_ref()
+                        .or(config.key_vault_replica_url.as_ref())
+                    {
+                        Some(url) => {
+                            let url = url.resolve(env_file)?.into_owned();
+                            config.policy.check_key_vault(&url)?;
+                            info!("  Key Vault replica: {url}");
+                            let client = SecretClient::new(url.as_str(), credential.clone(), None)
+                                .context("Failed to create Key Vault secrets client")?;
+                            Some(Box::new(Replica::new(client, url)))
+                        }
+                        None => None,
+                    };
+                    VarStore::KeyVault {
+                        client,
+                        url: key_vault_url,
+                        replica,
+                    }
+                }
+                DotenvBackend::AppConfig => {
+                    if self.disable_on_push {
+                        bail!("--disable-on-push can't be used with App Configuration");
+                    }
+                    let url = self.app_config.resolve(env_file)?;
+                    info!("  App Configuration: {url}");
+                    if let Some(label) = &self.app_config.label {
+                        info!("  Label: {label}");
+                    }
+                    VarStore::AppConfig(AppConfigClient::new(
+                        url,
+                        credential.clone(),
+                        self.app_config.label.clone(),
+                        self.app_config.key_prefix.clone(),
+                    ))
+                }
+            })
+        } else {
+            None
+        };
+        let mut named_stores = HashMap::with_capacity(self.vaults.len());
+        for vault in &self.vaults {
+            if self.backend != DotenvBackend::KeyVault {
+                bail!("--vault can only be used with Key Vault");
+            }
+            let url = vault.url.resolve(env_file)?.into_owned();
+            config.policy.check_key_vault(&url)?;
+            info!("  Key Vault ({}): {url}", vault.name);
+            let client = SecretClient::new(url.as_str(), credential.clone(), None)
+                .context("Failed to create Key Vault secrets client")?;
+            named_stores.insert(
+                vault.name.clone(),
+                VarStore::KeyVault {
+                    client,
+                    url,
+                    replica: None,
+                },
+            );
+        }
+        let stores = VarStores::new(
+            default_store,
+            named_stores,
+            routes,
+            self.sync.plan_concurrency(MAX_CONCURRENT_REQUESTS),
+        )?;
+
+        // Only synchronize variables whose conflicts are being resolved
+        if !self.decisions.is_empty() {
+            vars_to_sync.retain(|name| self.decisions.contains(&stores.get(name).resource(name)));
+            never_synced.clear();
+        }
+
+        // Write the remote values elsewhere instead of synchronizing them
+        if let Some(path) = &self.export {
+            self.export_vars(path, &stores, &vars_to_sync, dotenv.as_ref())
+                .await?;
+            return Ok(Some(Status::Success));
+        }
+
+        // Get synchronized values from Azure
+        let sync_mode = self.sync.sync_mode(&config, SyncCommand::Dotenv);
+        let time_tolerance = self.sync.time_tolerance();
+        let remote_vars = stores
+            .get_remote_vars(sync_mode, vars_to_sync.iter().copied())
+            .await?;
+        debug!(remote_vars=?remote_vars.values.keys(), disabled_vars=?remote_vars.disabled);
 
-        // Get synchronized secrets from Key Vault
-        let remote_vars =
-            get_remote_vars(&client, self.sync.sync_mode, vars_to_sync.iter().copied()).await?;
-        debug!(remote_vars=?remote_vars.keys());
+        // Find local variables whose secrets were deleted from Key Vault
+        let deleted_vars = if self.on_remote_delete == RemoteDeleteStrategy::Push
+            || sync_mode == SyncMode::PushAlways
+        {
+            HashSet::new()
+        } else {
+            let missing = vars_to_sync.iter().copied().filter(|name| {
+                !remote_vars.values.contains_key(*name)
+                    && !remote_vars.disabled.contains(*name)
+                    && dotenv
+                        .as_ref()
+                        .is_some_and(|dotenv| dotenv.parameters.contains_key(*name))
+            });
+            stores.get_deleted_vars(missing).await?
+        };
+        debug!(deleted_vars=?deleted_vars.iter());
 
         // Create a list of actions to execute
-        let client = Arc::new(client);
+        let versions = config.version_store()?;
         let (pairs_tx, pairs_rx) = channel();
         let local_modified = dotenv.as_ref().and_then(|dotenv| dotenv.last_modified);
-        let mut actions: Vec<_> = vars_to_sync
+        // There's nobody to ask when only checking
+        let on_conflict = match self.on_conflict {
+            ConflictStrategy::Prompt if self.sync.check_only => ConflictStrategy::Newest,
+            strategy => strategy,
+        };
+        let history = SyncHistory::load(Path::new(MANIFEST_FILE))?;
+        let mut in_sync = Vec::new();
+        let skipped = never_synced.into_iter().map(|(name, (reason, why))| {
+            PlannedVar::Action(
+                SyncType::Skip {
+                    reason,
+                    data: name.to_string(),
+                },
+                why.to_string(),
+            )
+        });
+        let planned: Vec<PlannedVar> = vars_to_sync
             .into_iter()
             .map(|name| {
+                let directives = directives
+                    .and_then(|directives| directives.get(name))
+                    .cloned()
+                    .unwrap_or_default();
+
                 let local_value = dotenv
                     .as_ref()
                     .and_then(|dotenv| dotenv.parameters.get(name))
                     .cloned();
-                let (remote_value, remote_modified) = remote_vars
-                    .get(name)
-                    .map(|&(ref value, modified)| (value.clone(), modified))
-                    .unzip();
+                // Disabled secrets can't be read, so only push to them deliberately
+                let disabled = remote_vars.disabled.contains(name);
+                if disabled && !self.enabled {
+                    return PlannedVar::Action(
+                        SyncType::Skip {
+                            reason: SkipReason::Disabled,
+                            data: name.to_string(),
+                        },
+                        "the secret is disabled in Key Vault (pass --enabled to push to it)"
+                            .to_string(),
+                    );
+                }
+
+                let store = stores.get(name);
+                let resource = store.resource(name);
+                let remote = remote_vars.values.get(name);
+                let remote_value = remote
+                    .map(|remote| self.unmask_empty(store, &remote.value, local_value.as_deref()));
+                let remote_updated = remote.and_then(|remote| remote.updated);
+                let remote_version = remote.and_then(|remote| remote.version);
+                let (content_type, tags) = push_attributes(remote, &self.tags, &directives);
 
                 // Check if values are equal
-                if local_value
+                if let (Some(local_value), Some(remote_value)) = (&local_value, &remote_value)
+                    && local_value == remote_value
+                {
+                    // Start tracking versions from here
+                    in_sync.push((resource.clone(), hash_secret(local_value)));
+                    if let (Some(versions), Some(version)) = (&versions, remote_version) {
+                        versions.record(
+                            resource,
+                            SyncedVersion {
+                                version,
+                                local: hash_secret(local_value),
+                            },
+                        );
+                    }
+
+                    return PlannedVar::Action(
+                        SyncType::Skip {
+                            reason: SkipReason::Unchanged,
+                            data: name.to_string(),
+                        },
+                        "the local and remote values are the same".to_string(),
+                    );
+                }
+
+                // Resolve variables whose secrets were deleted remotely
+                if let Some(local_value) =
+                    local_value.as_ref().filter(|_| deleted_vars.contains(name))
+                {
+                    let (reason, why) = match (self.on_remote_delete, sync_mode) {
+                        (RemoteDeleteStrategy::Skip, _) => {
+                            (SkipReason::DeletedRemotely, "--on-remote-delete is `skip`")
+                        }
+                        (RemoteDeleteStrategy::DeleteLocal, _) if directives.push_only => (
+                            SkipReason::PushOnlyDirective,
+                            "the push-only directive prevents deleting it locally",
+                        ),
+                        (RemoteDeleteStrategy::DeleteLocal, SyncMode::Push) => (
+                            SkipReason::PushDisabled,
+                            "sync mode `push` never deletes it locally",
+                        ),
+                        (RemoteDeleteStrategy::DeleteLocal, _) => {
+                            return PlannedVar::Action(
+                                SyncType::DeleteLocal(DeleteVar {
+                                    name: name.to_string(),
+                                    old_value: local_value.clone(),
+                                    resource,
+                                    store: store.clone(),
+                                    pairs_tx: pairs_tx.clone(),
+                                }),
+                                "the secret was deleted from Key Vault, so it's deleted locally"
+                                    .to_string(),
+                            );
+                        }
+                        (RemoteDeleteStrategy::Push, _) => {
+                            unreachable!("deleted secrets aren't looked up when re-pushing")
+                        }
+                    };
+                    return PlannedVar::Action(
+                        SyncType::Skip {
+                            reason,
+                            data: name.to_string(),
+                        },
+                        format!("the secret was deleted from Key Vault, but {why}"),
+                    );
+                }
+
+                // Compare version counters if they're used
+                let version_order = match (&versions, &local_value) {
+                    (Some(versions), Some(local_value)) => compare_versions(
+                        versions.get(&resource).as_ref(),
+                        &hash_secret(local_value),
+                        remote_version,
+                    ),
+                    _ => None,
+                };
+                let push_version = versions.as_ref().map(|store| VersionUpdate {
+                    store: store.clone(),
+                    resource: resource.clone(),
+                    version: remote_version.map_or(1, |version| version + 1),
+                });
+                let pull_version = versions
                     .as_ref()
-                    .zip(remote_value.as_ref())
-                    .is_some_and(|(a, b)| a == b)
+                    .zip(remote_version)
+                    .map(|(store, version)| VersionUpdate {
+                        store: store.clone(),
+                        resource: resource.clone(),
+                        version,
+                    });
+
+                // Otherwise, compare both values with the one last synchronized
+                let changes = match (history.get(&resource), &local_value, &remote_value) {
+                    (Some(state), Some(local_value), Some(remote_value)) => Some(
+                        state.value_changes(&hash_secret(local_value), &hash_secret(remote_value)),
+                    ),
+                    _ => None,
+                };
+                let order = match version_order {
+                    Some(ordering) => Some((ordering, explain_versions(ordering))),
+                    None => changes
+                        .and_then(Changes::ordering)
+                        .map(|ordering| (ordering, explain_changes(ordering))),
+                };
+
+                // Leave values that changed on both sides for the user to decide on
+                if on_conflict != ConflictStrategy::Newest
+                    && order.is_none()
+                    && let (Some(local_value), Some(remote_value)) = (&local_value, &remote_value)
+                    && changes.is_none_or(Changes::both)
                 {
-                    return SyncType::Skip {
-                        reason: "unchanged",
-                        data: name.to_string(),
+                    return PlannedVar::Conflict(Conflict {
+                        push: PushVar {
+                            name: name.to_string(),
+                            value: local_value.clone(),
+                            old_value: Some(remote_value.clone()),
+                            resource: resource.clone(),
+                            content_type: content_type.clone(),
+                            tags: tags.clone(),
+                            local_modified: local_modified.unwrap_or_else(OffsetDateTime::now_utc),
+                            remote_updated,
+                            store: store.clone(),
+                            version: push_version,
+                            disable: self.disable_on_push,
+                            placeholder: false,
+                        },
+                        pull: PullVar {
+                            name: name.to_string(),
+                            value: remote_value.clone(),
+                            old_value: Some(local_value.clone()),
+                            resource,
+                            remote_modified: remote_updated.unwrap_or_else(OffsetDateTime::now_utc),
+                            remote_updated,
+                            attributes: remote.and_then(describe_attributes),
+                            store: store.clone(),
+                            pairs_tx: pairs_tx.clone(),
+                            version: pull_version,
+                        },
+                        local_modified,
+                        can_push: sync_mode.can_push()
+                            && !directives.pull_only
+                            && !config.read_only,
+                        can_pull: sync_mode.can_pull() && !directives.push_only,
+                    });
+                }
+
+                let local_modified = local_value.as_ref().and(local_modified);
+                let seed = (name, local_value, remote_value, resource);
+                let push =
+                    |local_modified, (name, local_value, remote_value, resource): VarSeed<'_>| {
+                        PushVar {
+                            name: name.to_string(),
+                            value: local_value.expect("local value should be Some"),
+                            old_value: remote_value,
+                            resource,
+                            content_type: content_type.clone(),
+                            tags: tags.clone(),
+                            local_modified,
+                            remote_updated,
+                            store: store.clone(),
+                            version: push_version,
+                            disable: self.disable_on_push,
+                            placeholder: false,
+                        }
+                    };
+                let pull =
+                    |remote_modified, (name, local_value, remote_value, resource): VarSeed<'_>| {
+                        PullVar {
+                            name: name.to_string(),
+                            value: remote_value.expect("remote value should be Some"),
+                            old_value: local_value,
+                            resource,
+                            remote_modified,
+                            remote_updated,
+                            attributes: remote.and_then(describe_attributes),
+                            store: store.clone(),
+                            pairs_tx: pairs_tx.clone(),
+                            version: pull_version,
+                        }
                     };
+                let skip = |(name, ..): VarSeed<'_>| name.to_string();
+
+                // Prefer version counters and recorded states to modified times
+                // when they're conclusive
+                let (action, explanation) = match order {
+                    Some((ordering, why)) => (
+                        SyncType::from_ordering(
+                            sync_mode,
+                            ordering,
+                            local_modified.unwrap_or_else(OffsetDateTime::now_utc),
+                            remote_updated.unwrap_or_else(OffsetDateTime::now_utc),
+                            seed,
+                            push,
+                            pull,
+                            skip,
+                        ),
+                        explain_ordering(sync_mode, ordering, why),
+                    ),
+                    None => (
+                        SyncType::from_modified(
+                            sync_mode,
+                            local_modified,
+                            remote_updated,
+                            time_tolerance,
+                            seed,
+                            push,
+                            pull,
+                            skip,
+                        ),
+                        explain_modified(sync_mode, local_modified, remote_updated, time_tolerance),
+                    ),
+                };
+
+                // Only synchronize in the direction allowed by directives
+                match action {
+                    SyncType::Push(push) if directives.pull_only => PlannedVar::Action(
+                        SyncType::Skip {
+                            reason: SkipReason::PullOnlyDirective,
+                            data: push.name,
+                        },
+                        format!("{explanation}, but the pull-only directive prevents pushing"),
+                    ),
+                    SyncType::Pull(pull) if directives.push_only => PlannedVar::Action(
+                        SyncType::Skip {
+                            reason: SkipReason::PushOnlyDirective,
+                            data: pull.name,
+                        },
+                        format!("{explanation}, but the push-only directive prevents pulling"),
+                    ),
+                    action => PlannedVar::Action(action, explanation),
                 }
+            })
+            .chain(skipped)
+            .collect();
 
-                SyncType::from_modified(
-                    self.sync.sync_mode,
-                    local_value.as_ref().and(local_modified),
-                    remote_modified.flatten(),
-                    name,
-                    |_, name| PushVar {
-                        name: name.to_string(),
-                        value: local_value.expect("local value should be Some"),
-                        client: client.clone(),
-                    },
-                    |remote_modified, name| PullVar {
-                        name: name.to_string(),
-                        value: remote_value.expect("remote value should be Some"),
-                        remote_modified,
-                        pairs_tx: pairs_tx.clone(),
-                    },
-                    ToString::to_string,
-                )
+        // Ask the user how to resolve any conflicts
+        let mut actions = Vec::with_capacity(planned.len());
+        let mut explanations = Vec::with_capacity(planned.len());
+        let mut conflicts = Vec::new();
+        for planned in planned {
+            match planned {
+                // Only resolve conflicts when resolving them
+                PlannedVar::Action(action, _)
+                    if !self.decisions.is_empty() && !matches!(action, SyncType::Skip { .. }) =>
+                {
+                    let name = action.name().to_string();
+                    warn!("{name} is no longer in conflict, skipping it");
+                    actions.push(SyncType::Skip {
+                        reason: SkipReason::Conflict,
+                        data: name,
+                    });
+                }
+                PlannedVar::Action(action, explanation) => {
+                    if self.sync.explain {
+                        let explanation = if config.read_only && matches!(action, SyncType::Push(_))
+                        {
+                            format!("{explanation}, but nothing is pushed in read-only mode")
+                        } else {
+                            explanation
+                        };
+                        explanations.push((action.name().to_string(), explanation));
+                    }
+                    actions.push(action);
+                }
+                PlannedVar::Conflict(conflict) => conflicts.push(conflict),
+            }
+        }
+
+        // Explain the decisions that were made automatically
+        if self.sync.explain {
+            explanations.sort_unstable();
+            info!("Explanations:");
+            for (name, explanation) in explanations {
+                info!("  {name}: {explanation}");
+            }
+        }
+        conflicts.sort_unstable_by(|a, b| a.push.name.cmp(&b.push.name));
+        let mut reported = Vec::new();
+        let mut failed = Vec::new();
+        for conflict in conflicts {
+            match on_conflict {
+                ConflictStrategy::Newest => unreachable!("conflicts aren't planned with `newest`"),
+                ConflictStrategy::PreferLocal => {
+                    actions.push(conflict.prefer(Resolution::KeepLocal));
+                }
+                ConflictStrategy::PreferRemote => {
+                    actions.push(conflict.prefer(Resolution::KeepRemote));
+                }
+                ConflictStrategy::Prompt => actions.push(conflict.resolve(self.show_values)?),
+                ConflictStrategy::Fail => failed.push(conflict.push.name),
+                ConflictStrategy::Report => {
+                    // Resolve conflicts that were decided on, and report the rest
+                    let current = conflict.describe()?;
+                    if self.decisions.is_empty() {
+                        reported.push(current);
+                        actions.push(conflict.into_action(Resolution::Skip));
+                    } else {
+                        let resolution = self.decisions.resolve(&current);
+                        actions.push(conflict.into_action(resolution));
+                    }
+                }
+            }
+        }
+        if !failed.is_empty() {
+            bail!(
+                "Variables changed both locally and remotely (--on-conflict fail): {}",
+                failed.join(", ")
+            );
+        }
+        if !reported.is_empty() {
+            let count = reported.len();
+            ConflictReport::new(reported).save(&self.conflict_report)?;
+            warn!(
+                "Reported {count} conflict(s) in {}",
+                self.conflict_report.display()
+            );
+        }
+
+        let mut actions: Vec<_> = self
+            .handle_empty_values(actions)?
+            .into_iter()
+            .map(|action| {
+                if config.read_only {
+                    action.read_only(|push| push.name)
+                } else {
+                    action
+                }
             })
             .collect();
         actions.sort_unstable();
@@ -115,26 +870,105 @@ impl Command for SyncDotenvOptions {
         // Print actions to the user
         info!("Actions:");
         for action in &actions {
-            match action {
-                SyncType::Pull(PullVar { name, .. }) => info!("-> PULL: {name}"),
-                SyncType::Push(PushVar { name, .. }) => info!("<- PUSH: {name}"),
-                SyncType::Skip { reason, data } => info!("   SKIP: {data} ({reason})"),
+            let change = match action {
+                SyncType::Pull(PullVar {
+                    name,
+                    value,
+                    old_value,
+                    attributes,
+                    ..
+                }) => {
+                    let attributes = attributes
+                        .as_ref()
+                        .map(|attributes| format!(" ({attributes})"))
+                        .unwrap_or_default();
+                    info!("-> PULL: {name}{attributes}");
+                    Some((old_value.as_deref(), value.as_str()))
+                }
+                SyncType::Push(PushVar {
+                    name,
+                    value,
+                    old_value,
+                    placeholder,
+                    ..
+                }) => {
+                    if *placeholder {
+                        info!("<- PUSH: {name} (empty, pushed as a placeholder)");
+                    } else {
+                        info!("<- PUSH: {name}");
+                    }
+                    Some((old_value.as_deref(), value.as_str()))
+                }
+                SyncType::DeleteLocal(DeleteVar {
+                    name, old_value, ..
+                }) => {
+                    info!("-> DELETE: {name} (deleted remotely)");
+                    Some((Some(old_value.as_str()), ""))
+                }
+                SyncType::DeleteRemote(never) => match *never {},
+                SyncType::Skip { reason, data } => {
+                    info!("   SKIP: {data} ({reason})");
+                    None
+                }
+            };
+
+            // Show how the value changes if requested
+            if self.diff
+                && let Some((old_value, value)) = change
+            {
+                info!(
+                    "     {}",
+                    word_diff(old_value.unwrap_or_default(), value, self.show_values)
+                );
             }
         }
+        let summary = PlanSummary::new(&actions);
+        summary.report(self.sync.json)?;
+        self.sync.record_plan(&summary);
+
+        // Make sure each action would succeed
+        if self.sync.dry_run {
+            if actions
+                .iter()
+                .any(|action| matches!(action, SyncType::Pull(_) | SyncType::DeleteLocal(_)))
+            {
+                check_writable(&global_options.env_file)?;
+            }
+            dry_run(&actions, &self.sync).await?;
+            return Ok(Some(Status::Success));
+        }
 
         // If we're only checking, make no changes
         let unchanged = actions
             .iter()
             .all(|action| matches!(action, SyncType::Skip { .. }));
         if self.sync.check_only || unchanged {
-            exit(i32::from(!unchanged));
+            if self.sync.check_only && summary.has_diverged() {
+                return Ok(Some(Status::Diverged));
+            }
+            let failed = self.sync.check_only && summary.has_failed_skips(&config.fail_on_skip);
+            if is_drifted(&actions, self.sync.max_drift) | failed {
+                return Ok(Some(Status::Drifted));
+            }
+            if !self.sync.check_only {
+                record_synced(&history, in_sync)?;
+            }
+            return Ok(Some(Status::Success));
         }
 
         // Ask for confirmation
-        if !self.sync.no_confirm {
-            confirm()?;
+        self.sync.confirm(&summary)?;
+
+        // Make sure nothing changed while waiting for confirmation
+        let dotenv_changed = modified_time(&global_options.env_file)? != local_modified;
+        let (actions, stale) = split_stale(actions, &self.sync).await?;
+        if dotenv_changed || !stale.is_empty() {
+            return Ok(None);
         }
 
+        // Only make the first few changes if they're being tried out
+        let (actions, held_back) = take_canary(actions, self.sync.canary);
+
         // Get the latest that the remote was modified for the dotenv
         let new_modified = actions
             .iter()
@@ -151,20 +985,63 @@ impl Command for SyncDotenvOptions {
             .max();
 
         // Execute the actions
-        let actions: FuturesUnordered<_> = actions.into_iter().map(SyncAction::execute).collect();
-        actions.try_collect::<()>().await?;
+        let synced: Vec<_> = actions
+            .iter()
+            .filter_map(|action| match action {
+                // Placeholders are pulled back as empty values
+                SyncType::Push(push) if push.placeholder => {
+                    Some((push.resource.clone(), hash_secret("")))
+                }
+                SyncType::Push(push) => Some((push.resource.clone(), hash_secret(&push.value))),
+                SyncType::Pull(pull) => Some((pull.resource.clone(), hash_secret(&pull.value))),
+                _ => None,
+            })
+            .chain(in_sync)
+            .collect();
+        let audit = AuditLog::new(&config.audit, credential)?;
+        execute_all(actions, &audit, &self.sync).await?;
 
         // Update local file
         drop(pairs_tx); // to allow the channel to close after actions complete
-        let replacements: HashMap<_, _> = pairs_rx.into_iter().collect();
-        if !replacements.is_empty() {
-            let new_source = if let Some(dotenv) = dotenv {
-                dotenv.replace(replacements)
+        let mut replacements = HashMap::new();
+        let mut removals = HashSet::new();
+        for (name, value) in pairs_rx {
+            if let Some(value) = value {
+                replacements.insert(name, value);
             } else {
-                DotenvFile::default().replace(replacements)
+                removals.insert(name);
+            }
+        }
+        if !replacements.is_empty() || !removals.is_empty() || self.trailer {
+            let dotenv = dotenv.unwrap_or_default();
+            let comments = if self.provenance {
+                let pulled = OffsetDateTime::now_utc().date();
+                replacements
+                    .keys()
+                    .map(|name| {
+                        let comment =
+                            format!("from {}, pulled {pulled}", stores.get(name).pulled_from());
+                        (name.clone(), comment)
+                    })
+                    .collect()
+            } else {
+                HashMap::new()
             };
+            let mut new_source =
+                dotenv.replace_with_comments(replacements, &comments, template.as_ref())?;
+            if !removals.is_empty() {
+                new_source = DotenvFile::parse(new_source)?.remove(&removals);
+            }
+            if self.trailer {
+                let trailer = Trailer {
+                    synchronized: OffsetDateTime::now_utc(),
+                    profile: global_options.profile.clone(),
+                    stores: stores.describe(),
+                };
+                new_source = DotenvFile::parse(new_source)?.set_trailer(&trailer)?;
+            }
             let mut file = File::create(&global_options.env_file)?;
-            write!(file, "{new_source}")?;
+            file.write_all(&dotenv.encoding.encode(&new_source))?;
             file.flush()?;
 
             // Track the new modified time if it's later than the current modified time
@@ -178,90 +1055,570 @@ impl Command for SyncDotenvOptions {
             }
         }
 
-        Ok(())
+        if let Some(versions) = &versions {
+            versions.save()?;
+        }
+        record_synced(&history, synced)?;
+        report_canary(held_back);
+
+        Ok(Some(Status::Success))
+    }
+}
+
+/// Records the hashes of the values that were just synchronized, by the URL of
+/// each secret or key-value.
+fn record_synced(history: &SyncHistory, synced: Vec<(String, String)>) -> anyhow::Result<()> {
+    for (resource, hash) in synced {
+        history.record(resource, SyncedState::now(None, Some(hash), None)?);
     }
+    history.save()
+}
+
+/// The result of planning what to do with a variable.
+#[expect(
+    clippy::large_enum_variant,
+    reason = "Conflicts are rare and short-lived"
+)]
+enum PlannedVar {
+    /// The action to take, and an explanation of why.
+    Action(VarAction, String),
+
+    /// The variable differs on both sides and the user needs to decide what to
+    /// do.
+    Conflict(Conflict),
 }
 
-async fn get_remote_vars(
+/// A variable with different values locally and in Key Vault.
+struct Conflict {
+    push: PushVar,
+    pull: PullVar,
+    local_modified: Option<OffsetDateTime>,
+    can_push: bool,
+    can_pull: bool,
+}
+
+impl Conflict {
+    /// Shows the user how the local and remote values differ and asks them
+    /// which one to keep.
+    fn resolve(self, show_values: bool) -> anyhow::Result<VarAction> {
+        let format_time = |time: Option<OffsetDateTime>| {
+            time.map_or_else(|| Ok("unknown".to_string()), |time| time.format(&Rfc3339))
+        };
+        println!("CONFLICT: {}", self.push.name);
+        println!(
+            "  {}",
+            word_diff(&self.pull.value, &self.push.value, show_values)
+        );
+        println!("  local:  modified {}", format_time(self.local_modified)?);
+        println!(
+            "  remote: modified {}",
+            format_time(self.push.remote_updated)?
+        );
+
+        // Only offer choices allowed by the sync mode and directives
+        let mut choices = Vec::with_capacity(3);
+        if self.can_push {
+            choices.push(("l", "keep local"));
+        }
+        if self.can_pull {
+            choices.push(("r", "keep remote"));
+        }
+        choices.push(("s", "skip"));
+
+        let resolution = match choices[choose(&choices)?].0 {
+            "l" => Resolution::KeepLocal,
+            "r" => Resolution::KeepRemote,
+            _ => Resolution::Skip,
+        };
+
+        Ok(self.into_action(resolution))
+    }
+
+    /// Resolves the conflict by keeping one copy, or skips it if the sync mode
+    /// or directives don't allow that.
+    fn prefer(self, resolution: Resolution) -> VarAction {
+        let allowed = match resolution {
+            Resolution::KeepLocal => self.can_push,
+            Resolution::KeepRemote => self.can_pull,
+            Resolution::Skip => true,
+        };
+        self.into_action(if allowed {
+            resolution
+        } else {
+            Resolution::Skip
+        })
+    }
+
+    /// Converts the conflict into the action that resolves it.
+    fn into_action(self, resolution: Resolution) -> VarAction {
+        match resolution {
+            Resolution::KeepLocal => SyncType::Push(self.push),
+            Resolution::KeepRemote => SyncType::Pull(self.pull),
+            Resolution::Skip => SyncType::Skip {
+                reason: SkipReason::Conflict,
+                data: self.push.name,
+            },
+        }
+    }
+
+    /// Describes the conflict for the conflict report.
+    ///
+    /// Only hashes of the values are reported, so the report can be shared.
+    fn describe(&self) -> anyhow::Result<ReportedConflict> {
+        let local_side =
+            ConflictSide::new(self.local_modified, Some(hash_secret(&self.push.value)))?;
+        let remote_side = ConflictSide::new(
+            self.push.remote_updated,
+            Some(hash_secret(&self.pull.value)),
+        )?;
+        ReportedConflict::new(
+            self.push.name.clone(),
+            self.push.resource.clone(),
+            local_side,
+            remote_side,
+            self.can_push,
+            self.can_pull,
+        )
+    }
+}
+
+/// Gets the URL of the secret a variable is synchronized with, for the audit
+/// log.
+pub(super) fn secret_resource(key_vault_url: &Url, name: &str) -> String {
+    format!(
+        "{}/secrets/{}",
+        key_vault_url.as_str().trim_end_matches('/'),
+        name.replace('_', "-")
+    )
+}
+
+/// Secrets loaded from Key Vault.
+pub(super) struct RemoteVars {
+    /// The enabled secrets, by variable name.
+    pub values: HashMap<String, RemoteVar>,
+
+    /// The names of variables whose secrets are disabled.
+    pub disabled: HashSet<String>,
+}
+
+/// A secret loaded from Key Vault.
+pub(super) struct RemoteVar {
+    /// The secret's value.
+    pub value: String,
+
+    /// When the secret was last updated.
+    pub updated: Option<OffsetDateTime>,
+
+    /// The secret's version counter, if it has one.
+    pub version: Option<u64>,
+
+    /// The secret's content type, if it has one.
+    pub content_type: Option<String>,
+
+    /// The secret's tags, not including its version counter.
+    pub tags: HashMap<String, String>,
+
+    /// Whether the secret was loaded from a replica because the Key Vault was
+    /// unavailable.
+    pub from_replica: bool,
+}
+
+/// Loads the secrets of variables from Key Vault, making at most
+/// `concurrency` requests at once.
+pub(super) async fn get_remote_vars(
     client: &SecretClient,
     mode: SyncMode,
     var_names: impl IntoIterator<Item = &str>,
-) -> anyhow::Result<HashMap<String, (String, Option<OffsetDateTime>)>> {
+    concurrency: usize,
+) -> anyhow::Result<RemoteVars> {
     if let SyncMode::PushAlways = mode {
         // Don't pull any values
-        return Ok(HashMap::new());
+        return Ok(RemoteVars {
+            values: HashMap::new(),
+            disabled: HashSet::new(),
+        });
     }
 
     // Get synchronized secrets from Key Vault
-    let remote_vars: Vec<_> = var_names
-        .into_iter()
-        .map(|name| name.replace('_', "-"))
-        .collect();
-    let remote_vars: FuturesUnordered<_> = remote_vars
-        .iter()
-        .map(|var_name| client.get_secret(var_name, "", None))
-        .collect();
-
-    #[expect(clippy::redundant_closure_for_method_calls, reason = "Opaque type")]
-    let remote_vars: HashMap<_, _> = remote_vars
-        .filter(|result| match result {
-            Ok(_) => ready(true),
-            Err(error) => ready(error.http_status() != Some(404.into())),
-        })
-        .and_then(|response| response.into_body())
-        .map_ok(|secret| {
-            let name = secret.id?.split('/').nth_back(1)?.replace('-', "_");
-            let value = secret.value?;
-            let modified = secret
-                .attributes
-                .and_then(|attributes| attributes.updated.or(attributes.created));
-            Some((name, (value, modified)))
+    let fetched: Vec<_> = stream::iter(var_names)
+        .map(|name| async move {
+            match client.get_secret(&name.replace('_', "-"), "", None).await {
+                Ok(response) => Ok(Fetched::Found(response.into_body().await?)),
+                Err(error) if error.http_status() == Some(404.into()) => Ok(Fetched::Missing),
+                // Disabled secrets can't be read
+                Err(error) if error.http_status() == Some(403.into()) => {
+                    Ok(Fetched::Forbidden(name.to_string(), error))
+                }
+                Err(error) => Err(error),
+            }
         })
-        .try_filter_map(ok)
+        .buffer_unordered(concurrency)
         .try_collect()
         .await
         .context("Failed to load secrets from Key Vault")?;
 
-    Ok(remote_vars)
+    let mut values = HashMap::with_capacity(fetched.len());
+    let mut forbidden = Vec::new();
+    for fetched in fetched {
+        match fetched {
+            Fetched::Found(secret) => {
+                let Some(name) = secret
+                    .id
+                    .as_deref()
+                    .and_then(|id| id.split('/').nth_back(1))
+                    .map(|name| name.replace('-', "_"))
+                else {
+                    continue;
+                };
+                let Some(value) = secret.value else {
+                    continue;
+                };
+                let updated = secret
+                    .attributes
+                    .and_then(|attributes| attributes.updated.or(attributes.created));
+                let mut tags = secret.tags.unwrap_or_default();
+                let version = parse_version(tags.remove(VERSION_KEY).as_ref());
+                values.insert(
+                    name,
+                    RemoteVar {
+                        value,
+                        updated,
+                        version,
+                        content_type: secret.content_type,
+                        tags,
+                        from_replica: false,
+                    },
+                );
+            }
+            Fetched::Missing => {}
+            Fetched::Forbidden(name, error) => forbidden.push((name, error)),
+        }
+    }
+
+    // Tell disabled secrets apart from ones we aren't allowed to read
+    let mut disabled = HashSet::new();
+    if !forbidden.is_empty() {
+        let all_disabled = get_disabled_secrets(client).await?;
+        for (name, error) in forbidden {
+            if !all_disabled.contains(&name.replace('_', "-")) {
+                return Err(error).context(format!("Failed to load {name} from Key Vault"));
+            }
+            disabled.insert(name);
+        }
+    }
+
+    Ok(RemoteVars { values, disabled })
+}
+
+/// The result of getting a secret from Key Vault.
+enum Fetched {
+    /// The secret exists and could be read.
+    Found(Secret),
+
+    /// The secret doesn't exist.
+    Missing,
+
+    /// Reading the secret was forbidden, which may mean it's disabled.
+    Forbidden(String, azure_core::Error),
+}
+
+/// Gets the names of all disabled secrets in Key Vault.
+async fn get_disabled_secrets(client: &SecretClient) -> anyhow::Result<HashSet<String>> {
+    let mut secrets = client.list_secret_properties(None)?;
+    let mut disabled = HashSet::new();
+    while let Some(secret) = secrets
+        .try_next()
+        .await
+        .context("Failed to list secrets in Key Vault")?
+    {
+        let enabled = secret
+            .attributes
+            .and_then(|attributes| attributes.enabled)
+            .unwrap_or(true);
+        if let Some(name) = secret
+            .id
+            .as_deref()
+            .and_then(|id| id.split('/').nth_back(0))
+            && !enabled
+        {
+            disabled.insert(name.to_string());
+        }
+    }
+
+    Ok(disabled)
+}
+
+/// Gets the names of variables whose secrets were deleted from Key Vault but
+/// not purged yet, making at most `concurrency` requests at once.
+async fn get_deleted_vars(
+    client: &SecretClient,
+    var_names: impl IntoIterator<Item = &str>,
+    concurrency: usize,
+) -> anyhow::Result<HashSet<String>> {
+    stream::iter(var_names)
+        .map(|name| async move {
+            match client
+                .get_deleted_secret(&name.replace('_', "-"), None)
+                .await
+            {
+                Ok(_) => Ok(Some(name.to_string())),
+                // Forbidden if soft-delete is disabled or we can't list deleted secrets
+                Err(error)
+                    if error.http_status() == Some(404.into())
+                        || error.http_status() == Some(403.into()) =>
+                {
+                    Ok(None)
+                }
+                Err(error) => Err(error),
+            }
+        })
+        .buffer_unordered(concurrency)
+        .try_filter_map(ok)
+        .try_collect()
+        .await
+        .context("Failed to check for deleted secrets in Key Vault")
 }
 
 pub struct PullVar {
     name: String,
     value: String,
+    old_value: Option<String>,
+    resource: String,
     remote_modified: OffsetDateTime,
-    pairs_tx: Sender<(String, String)>,
+
+    /// When the secret was last updated when planning.
+    remote_updated: Option<OffsetDateTime>,
+
+    /// The secret's content type and tags, if it has any worth showing.
+    attributes: Option<String>,
+    store: Arc<VarStore>,
+    pairs_tx: Sender<(String, Option<String>)>,
+
+    /// The version to record once pulled, if version counters are used.
+    version: Option<VersionUpdate>,
 }
 
 sortable_by_key!(PullVar, str, |action| &action.name);
 
 impl SyncAction for PullVar {
-    async fn execute(self) -> anyhow::Result<()> {
-        self.pairs_tx.send((self.name, self.value))?;
+    async fn execute(self, audit: &AuditLog) -> anyhow::Result<()> {
+        let entry = AuditEntry {
+            action: "pull",
+            resource: self.resource,
+            before: self.old_value.as_deref().map(hash_secret),
+            after: Some(hash_secret(&self.value)),
+            bytes: Some(self.value.len() as u64),
+        };
+        if let Some(version) = self.version {
+            version.record(hash_secret(&self.value));
+        }
+        self.pairs_tx.send((self.name, Some(self.value)))?;
+        audit.record(entry).await
+    }
+
+    async fn dry_run(&self) -> anyhow::Result<()> {
+        // Values are only written locally once all actions complete
         Ok(())
     }
+
+    async fn check_fresh(&self) -> anyhow::Result<()> {
+        self.store
+            .check_unchanged(&self.name, self.remote_updated)
+            .await
+    }
+
+    fn changed_at(&self) -> Option<OffsetDateTime> {
+        Some(self.remote_modified)
+    }
 }
 
 pub struct PushVar {
     name: String,
     value: String,
-    client: Arc<SecretClient>,
+    old_value: Option<String>,
+    resource: String,
+    content_type: Option<String>,
+
+    /// Tags to store the secret with, not including its version counter.
+    tags: HashMap<String, String>,
+
+    /// When the dotenv file was last modified.
+    local_modified: OffsetDateTime,
+
+    /// When the secret was last updated when planning, if it existed.
+    remote_updated: Option<OffsetDateTime>,
+    store: Arc<VarStore>,
+
+    /// The version to tag the secret with, if version counters are used.
+    version: Option<VersionUpdate>,
+
+    /// Whether to push the secret disabled.
+    disable: bool,
+
+    /// Whether the value is a placeholder for an empty value.
+    placeholder: bool,
 }
 
 sortable_by_key!(PushVar, str, |action| &action.name);
 
 impl SyncAction for PushVar {
-    async fn execute(self) -> anyhow::Result<()> {
-        let params = SetSecretParameters {
-            content_type: Some("text/plain".into()),
-            value: Some(self.value),
-            ..Default::default()
+    async fn execute(self, audit: &AuditLog) -> anyhow::Result<()> {
+        let entry = AuditEntry {
+            action: "push",
+            resource: self.resource,
+            before: self.old_value.as_deref().map(hash_secret),
+            after: Some(hash_secret(&self.value)),
+            bytes: Some(self.value.len() as u64),
         };
-
-        let name = self.name.replace('_', "-");
-        self.client
-            .set_secret(&name, params.try_into()?, None)
+        let local = hash_secret(&self.value);
+        self.store
+            .set(
+                &self.name,
+                self.value,
+                self.content_type,
+                self.tags,
+                self.version.as_ref().map(|version| version.version),
+                self.disable,
+            )
             .await?;
+        if let Some(version) = self.version {
+            version.record(local);
+        }
 
+        audit.record(entry).await
+    }
+
+    async fn dry_run(&self) -> anyhow::Result<()> {
+        self.store.check_storable(&self.name, &self.value)
+    }
+
+    async fn check_fresh(&self) -> anyhow::Result<()> {
+        self.store
+            .check_unchanged(&self.name, self.remote_updated)
+            .await
+    }
+
+    fn changed_at(&self) -> Option<OffsetDateTime> {
+        Some(self.local_modified)
+    }
+}
+
+pub struct DeleteVar {
+    name: String,
+    old_value: String,
+    resource: String,
+    store: Arc<VarStore>,
+    pairs_tx: Sender<(String, Option<String>)>,
+}
+
+sortable_by_key!(DeleteVar, str, |action| &action.name);
+
+impl SyncAction for DeleteVar {
+    async fn execute(self, audit: &AuditLog) -> anyhow::Result<()> {
+        let entry = AuditEntry {
+            action: "delete-local",
+            resource: self.resource,
+            before: Some(hash_secret(&self.old_value)),
+            after: None,
+            bytes: None,
+        };
+        self.pairs_tx.send((self.name, None))?;
+        audit.record(entry).await
+    }
+
+    async fn dry_run(&self) -> anyhow::Result<()> {
+        // Variables are only removed locally once all actions complete
         Ok(())
     }
+
+    async fn check_fresh(&self) -> anyhow::Result<()> {
+        // The secret must still be missing
+        self.store.check_unchanged(&self.name, None).await
+    }
+}
+
+/// Checks whether a value is empty or only whitespace.
+fn is_blank(value: &str) -> bool {
+    value.trim().is_empty()
 }
+
+/// Gets the content type and tags to push a variable with.
+///
+/// The remote secret's content type and tags are kept unless a directive
+/// overrides them. Tags from `--tag` are added, then tags from directives.
+fn push_attributes(
+    remote: Option<&RemoteVar>,
+    cli_tags: &[Tag],
+    directives: &Directives,
+) -> (Option<String>, HashMap<String, String>) {
+    let content_type = directives
+        .content_type
+        .clone()
+        .or_else(|| remote.and_then(|remote| remote.content_type.clone()));
+    let mut tags = remote.map(|remote| remote.tags.clone()).unwrap_or_default();
+    tags.extend(
+        cli_tags
+            .iter()
+            .map(|tag| (tag.key.clone(), tag.value.clone())),
+    );
+    tags.extend(directives.tags.clone());
+
+    (content_type, tags)
+}
+
+/// Describes a secret's content type and tags for showing when it's pulled,
+/// if it has a content type other than the default or any tags.
+fn describe_attributes(remote: &RemoteVar) -> Option<String> {
+    let mut parts = Vec::new();
+    if remote.from_replica {
+        parts.push("from replica".to_string());
+    }
+    if let Some(content_type) = remote
+        .content_type
+        .as_deref()
+        .filter(|&content_type| content_type != "text/plain")
+    {
+        parts.push(format!("content type: {content_type}"));
+    }
+    if !remote.tags.is_empty() {
+        let mut tags: Vec<_> = remote
+            .tags
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect();
+        tags.sort_unstable();
+        parts.push(format!("tags: {}", tags.join(", ")));
+    }
+
+    (!parts.is_empty()).then(|| parts.join("; "))
+}
+
+/// Fails if a secret was updated since it was planned against.
+pub(super) async fn check_secret_unchanged(
+    client: &SecretClient,
+    name: &str,
+    planned_updated: Option<OffsetDateTime>,
+) -> anyhow::Result<()> {
+    let updated = match client.get_secret(&name.replace('_', "-"), "", None).await {
+        Ok(response) => response
+            .into_body()
+            .await?
+            .attributes
+            .and_then(|attributes| attributes.updated.or(attributes.created)),
+        Err(error) if error.http_status() == Some(404.into()) => None,
+        // Disabled secrets can't be read, so they're planned as if they're missing
+        Err(error) if error.http_status() == Some(403.into()) && planned_updated.is_none() => None,
+        Err(error) => return Err(error.into()),
+    };
+    if updated != planned_updated {
+        bail!(Changed(format!(
+            "{name} changed in Key Vault since planning"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Maximum number of requests to make to a store at once, unless
+/// --max-concurrency is passed.
+pub(super) const MAX_CONCURRENT_REQUESTS: usize = 16;
@@ -0,0 +1,120 @@
+use std::{collections::HashMap, fs::read_to_string, sync::Arc};
+
+use anyhow::{Context, bail};
+use azure_identity::DefaultAzureCredential;
+use azure_security_keyvault_secrets::SecretClient;
+use futures::{StreamExt, TryStreamExt, stream};
+use time::OffsetDateTime;
+use tracing::info;
+
+use crate::{
+    audit::AuditLog,
+    cli::{GlobalOptions, ImportOptions, OutputsFormat},
+    commands::{
+        Command, Status,
+        dotenv::{MAX_CONCURRENT_REQUESTS, PushVar, VarStore},
+    },
+    config::Config,
+    dotenv::{DotenvFile, parse_outputs, variable_name},
+    sync::{SyncAction, confirm},
+};
+
+impl Command for ImportOptions {
+    async fn execute(self, global_options: &GlobalOptions) -> anyhow::Result<Status> {
+        let config = Config::load(global_options)?;
+
+        // Read the outputs
+        let format = match self.from {
+            OutputsFormat::ArmOutputs => "deployment outputs",
+            OutputsFormat::Terraform => "Terraform outputs",
+        };
+        let json = read_to_string(&self.path)
+            .with_context(|| format!("Failed to read {}", self.path.display()))?;
+        let outputs = parse_outputs(&json)
+            .with_context(|| format!("Failed to parse {format} in {}", self.path.display()))?;
+        if outputs.is_empty() {
+            bail!("No outputs found in {}", self.path.display());
+        }
+        let variables: HashMap<_, _> = outputs
+            .into_iter()
+            .map(|(name, value)| (format!("{}{}", self.prefix, variable_name(&name)), value))
+            .collect();
+
+        // Write them to the dotenv file
+        let dotenv =
+            DotenvFile::from_path_exists(&global_options.env_file, global_options.strict_parse)?;
+        let mut names: Vec<_> = variables.keys().cloned().collect();
+        names.sort_unstable();
+        info!("Importing:");
+        for name in &names {
+            info!("  {name}");
+        }
+        let dotenv = dotenv.unwrap_or_default();
+        let new_source = dotenv.replace(variables.clone())?;
+        std::fs::write(
+            &global_options.env_file,
+            dotenv.encoding.encode(&new_source),
+        )
+        .with_context(|| format!("Failed to write {}", global_options.env_file.display()))?;
+
+        if !self.push {
+            return Ok(Status::Success);
+        }
+        if config.read_only {
+            bail!("Variables can't be pushed in read-only mode");
+        }
+
+        // Push them to Key Vault
+        let dotenv =
+            DotenvFile::from_path_exists(&global_options.env_file, global_options.strict_parse)?;
+        let credential =
+            DefaultAzureCredential::new().context("Failed to get default Azure credential")?;
+        let key_vault_url = self.key_vault.resolve(
+            &config,
+            dotenv.as_ref().filter(|_| !global_options.no_env_file),
+        )?;
+        config.policy.check_key_vault(&key_vault_url)?;
+        info!("Using:");
+        info!("  Key Vault: {key_vault_url}");
+        info!("Actions:");
+        for name in &names {
+            info!("<- PUSH: {name}");
+        }
+
+        if !self.no_confirm {
+            confirm()?;
+        }
+
+        let client = SecretClient::new(key_vault_url.as_str(), credential.clone(), None)
+            .context("Failed to create Key Vault secrets client")?;
+        let store = Arc::new(VarStore::KeyVault {
+            client,
+            url: key_vault_url,
+            replica: None,
+        });
+        let audit = AuditLog::new(&config.audit, credential)?;
+        stream::iter(variables)
+            .map(|(name, value)| {
+                PushVar {
+                    resource: store.resource(&name),
+                    name,
+                    value,
+                    old_value: None,
+                    content_type: None,
+                    tags: HashMap::new(),
+                    local_modified: OffsetDateTime::now_utc(),
+                    remote_updated: None,
+                    store: store.clone(),
+                    version: None,
+                    disable: false,
+                    placeholder: false,
+                }
+                .execute(&audit)
+            })
+            .buffer_unordered(MAX_CONCURRENT_REQUESTS)
+            .try_collect::<()>()
+            .await?;
+
+        Ok(Status::Success)
+    }
+}
@@ -0,0 +1,43 @@
+use anyhow::bail;
+use tracing::{info, warn};
+
+use crate::{
+    cli::{GlobalOptions, LintOptions},
+    commands::{Command, Status},
+    dotenv::{DotenvFile, LintFile, lint},
+};
+
+impl Command for LintOptions {
+    async fn execute(self, global_options: &GlobalOptions) -> anyhow::Result<Status> {
+        // Parse issues are reported as lints instead of warnings
+        let dotenv = DotenvFile::read_path_exists(&global_options.env_file)?;
+        let template = DotenvFile::read_path_exists(&self.template_file)?;
+        if dotenv.is_none() && template.is_none() {
+            bail!(
+                "Neither {} nor {} exist",
+                global_options.env_file.display(),
+                self.template_file.display()
+            );
+        }
+
+        let lints = lint(dotenv.as_ref(), template.as_ref(), self.max_value_size);
+        for lint in &lints {
+            let path = match lint.file {
+                LintFile::Dotenv => &global_options.env_file,
+                LintFile::Template => &self.template_file,
+            };
+            if let Some(line) = lint.line {
+                warn!("{}:{line}: {}", path.display(), lint.issue);
+            } else {
+                warn!("{}: {}", path.display(), lint.issue);
+            }
+        }
+
+        if !lints.is_empty() {
+            bail!("Found {} problem(s) in dotenv files", lints.len());
+        }
+
+        info!("No problems found");
+        Ok(Status::Success)
+    }
+}
@@ -0,0 +1,481 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use anyhow::{Context, bail};
+use azure_core::error::ErrorKind;
+use azure_security_keyvault_secrets::{
+    SecretClient,
+    models::{SecretAttributes, SetSecretParameters},
+};
+use futures::{StreamExt, TryStreamExt, future::ok, stream};
+use time::OffsetDateTime;
+use tracing::warn;
+use typespec_client_core::http::StatusCode;
+use url::Url;
+
+use crate::{
+    app_config::{AppConfigClient, MAX_KEY_VALUE_SIZE},
+    cli::SyncMode,
+    commands::dotenv::{
+        RemoteVar, RemoteVars, check_secret_unchanged, get_deleted_vars, get_remote_vars,
+        secret_resource,
+    },
+    dotenv::{MAX_SECRET_NAME_LEN, MAX_SECRET_VALUE_SIZE},
+    sync::Changed,
+    versions::{VERSION_KEY, parse_version},
+};
+
+/// Where dotenv variables are stored remotely.
+pub enum VarStore {
+    /// Secrets in a Key Vault.
+    KeyVault {
+        client: SecretClient,
+        url: Url,
+
+        /// A replica to pull from if the Key Vault is unavailable.
+        replica: Option<Box<Replica>>,
+    },
+
+    /// Key-values in an App Configuration store.
+    AppConfig(AppConfigClient),
+}
+
+impl VarStore {
+    /// Describes the kind of store, like `Key Vault`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::KeyVault { .. } => "Key Vault",
+            Self::AppConfig(_) => "App Configuration",
+        }
+    }
+
+    /// Describes the store, like `https://my-vault.vault.azure.net/`.
+    pub fn name(&self) -> String {
+        match self {
+            Self::KeyVault { url, .. } => url.to_string(),
+            Self::AppConfig(client) => client.endpoint().to_string(),
+        }
+    }
+
+    /// Describes where values were pulled from, which is the replica once the
+    /// Key Vault was unavailable.
+    pub fn pulled_from(&self) -> String {
+        match self {
+            Self::KeyVault {
+                replica: Some(replica),
+                ..
+            } if replica.active.load(Ordering::Relaxed) => {
+                format!("{} replica {}", self.kind(), replica.url)
+            }
+            _ => format!("{} {}", self.kind(), self.name()),
+        }
+    }
+
+    /// Gets the URL of the secret or key-value a variable is synchronized
+    /// with, for the audit log.
+    pub fn resource(&self, name: &str) -> String {
+        match self {
+            Self::KeyVault { url, .. } => secret_resource(url, name),
+            Self::AppConfig(client) => client
+                .key_url(name)
+                .map_or_else(|_| client.key(name), |url| url.to_string()),
+        }
+    }
+
+    /// Loads the remote values of variables, making at most `concurrency`
+    /// requests at once.
+    ///
+    /// If the Key Vault is unavailable and values are only being pulled,
+    /// they're loaded from its replica instead, if it has one.
+    pub async fn get_remote_vars(
+        &self,
+        mode: SyncMode,
+        var_names: impl IntoIterator<Item = &str>,
+        concurrency: usize,
+    ) -> anyhow::Result<RemoteVars> {
+        let client = match self {
+            Self::KeyVault {
+                client,
+                url,
+                replica,
+            } => {
+                let var_names: Vec<_> = var_names.into_iter().collect();
+                let error =
+                    match get_remote_vars(client, mode, var_names.iter().copied(), concurrency)
+                        .await
+                    {
+                        Ok(remote_vars) => return Ok(remote_vars),
+                        Err(error) => error,
+                    };
+                let Some(replica) = replica.as_ref().filter(|_| !mode.can_push()) else {
+                    return Err(error);
+                };
+                if !is_unavailable(&error) {
+                    return Err(error);
+                }
+
+                warn!(
+                    "Key Vault {url} is unavailable, pulling from replica {}: {error:#}",
+                    replica.url
+                );
+                replica.active.store(true, Ordering::Relaxed);
+                let mut remote_vars = Box::pin(get_remote_vars(
+                    &replica.client,
+                    mode,
+                    var_names,
+                    concurrency,
+                ))
+                .await
+                .with_context(|| format!("Failed to load variables from {}", replica.url))?;
+                for remote_var in remote_vars.values.values_mut() {
+                    remote_var.from_replica = true;
+                }
+                return Ok(remote_vars);
+            }
+            Self::AppConfig(client) => client,
+        };
+
+        let mut values = HashMap::new();
+        if mode != SyncMode::PushAlways {
+            let key_values: Vec<_> = stream::iter(var_names)
+                .map(|name| async move {
+                    anyhow::Ok(client.get(name).await?.map(|kv| (name.to_string(), kv)))
+                })
+                .buffer_unordered(concurrency)
+                .try_filter_map(ok)
+                .try_collect()
+                .await?;
+            for (name, key_value) in key_values {
+                let Some(value) = key_value.value else {
+                    continue;
+                };
+                let mut tags = key_value.tags;
+                values.insert(
+                    name,
+                    RemoteVar {
+                        value,
+                        updated: key_value.last_modified,
+                        version: parse_version(tags.remove(VERSION_KEY).as_ref()),
+                        content_type: key_value.content_type,
+                        tags,
+                        from_replica: false,
+                    },
+                );
+            }
+        }
+
+        Ok(RemoteVars {
+            values,
+            disabled: HashSet::new(),
+        })
+    }
+
+    /// Gets the names of variables that were deleted remotely but can still
+    /// be recovered, making at most `concurrency` requests at once.
+    ///
+    /// Only Key Vault keeps deleted values around.
+    pub async fn get_deleted_vars(
+        &self,
+        var_names: impl IntoIterator<Item = &str>,
+        concurrency: usize,
+    ) -> anyhow::Result<HashSet<String>> {
+        match self {
+            Self::KeyVault {
+                client, replica, ..
+            } => {
+                // Keep reading from the replica once the Key Vault was unavailable
+                let client = replica
+                    .as_ref()
+                    .filter(|replica| replica.active.load(Ordering::Relaxed))
+                    .map_or(client, |replica| &replica.client);
+                get_deleted_vars(client, var_names, concurrency).await
+            }
+            Self::AppConfig(_) => Ok(HashSet::new()),
+        }
+    }
+
+    /// Sets the remote value of a variable.
+    pub async fn set(
+        &self,
+        name: &str,
+        value: String,
+        content_type: Option<String>,
+        mut tags: HashMap<String, String>,
+        version: Option<u64>,
+        disable: bool,
+    ) -> anyhow::Result<()> {
+        if let Some(version) = version {
+            tags.insert(VERSION_KEY.to_string(), version.to_string());
+        }
+        match self {
+            Self::KeyVault { client, .. } => {
+                let params = SetSecretParameters {
+                    content_type: Some(content_type.unwrap_or_else(|| "text/plain".into())),
+                    secret_attributes: disable.then(|| SecretAttributes {
+                        enabled: Some(false),
+                        ..Default::default()
+                    }),
+                    tags: Some(tags),
+                    value: Some(value),
+                };
+                client
+                    .set_secret(&name.replace('_', "-"), params.try_into()?, None)
+                    .await?;
+            }
+            Self::AppConfig(client) => {
+                client
+                    .set(name, &value, content_type.as_deref(), tags)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks whether empty values can be stored as they are.
+    pub fn stores_empty_values(&self) -> bool {
+        matches!(self, Self::AppConfig(_))
+    }
+
+    /// Fails if a variable can't be stored remotely.
+    pub fn check_storable(&self, name: &str, value: &str) -> anyhow::Result<()> {
+        match self {
+            Self::KeyVault { .. } => {
+                let secret_name = name.replace('_', "-");
+                if secret_name.len() > MAX_SECRET_NAME_LEN
+                    || !secret_name
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-')
+                {
+                    bail!("Can't push {name}: invalid secret name {secret_name:?}");
+                }
+                if value.len() as u64 > MAX_SECRET_VALUE_SIZE {
+                    bail!(
+                        "Can't push {name}: value is too large ({} bytes, limit {MAX_SECRET_VALUE_SIZE})",
+                        value.len(),
+                    );
+                }
+            }
+            Self::AppConfig(client) => {
+                let key = client.key(name);
+                if key.contains('%') || key == "." || key == ".." {
+                    bail!("Can't push {name}: invalid key {key:?}");
+                }
+                let size = key.len() + value.len();
+                if size > MAX_KEY_VALUE_SIZE {
+                    bail!(
+                        "Can't push {name}: key and value are too large ({size} bytes, limit {MAX_KEY_VALUE_SIZE})"
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fails if a variable was updated remotely since it was planned against.
+    pub async fn check_unchanged(
+        &self,
+        name: &str,
+        planned_updated: Option<OffsetDateTime>,
+    ) -> anyhow::Result<()> {
+        let client = match self {
+            Self::KeyVault { client, .. } => {
+                return check_secret_unchanged(client, name, planned_updated).await;
+            }
+            Self::AppConfig(client) => client,
+        };
+
+        let updated = client
+            .get(name)
+            .await?
+            .and_then(|key_value| key_value.last_modified);
+        if updated != planned_updated {
+            bail!(Changed(format!(
+                "{name} changed in App Configuration since planning"
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// A replica of a Key Vault, like one kept in another region for disaster
+/// recovery.
+pub struct Replica {
+    pub client: SecretClient,
+    pub url: Url,
+
+    /// Whether the Key Vault was unavailable, so values were pulled from the
+    /// replica instead.
+    pub active: AtomicBool,
+}
+
+impl Replica {
+    /// Creates a client for a replica.
+    pub fn new(client: SecretClient, url: Url) -> Self {
+        Self {
+            client,
+            url,
+            active: AtomicBool::new(false),
+        }
+    }
+}
+
+/// Checks whether loading secrets failed because Key Vault couldn't be reached
+/// or kept failing on its end, rather than because the request was rejected.
+/// Only I/O errors, server errors, and throttling count; errors like failing
+/// to get a token or parse a response don't.
+///
+/// Requests are already retried by the client, so these failures are
+/// persistent.
+fn is_unavailable(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .find_map(|error| error.downcast_ref::<azure_core::Error>())
+        .is_some_and(|error| match error.kind() {
+            ErrorKind::Io => true,
+            ErrorKind::HttpResponse { status, .. } => {
+                status.is_server_error() || *status == StatusCode::TooManyRequests
+            }
+            _ => false,
+        })
+}
+
+/// The stores variables are synchronized with, when some are routed to other
+/// Key Vaults with `vault` directives.
+pub struct VarStores {
+    /// The store for variables without a `vault` directive, if any need it.
+    default: Option<Arc<VarStore>>,
+
+    /// The stores passed with `--vault`, by name.
+    named: HashMap<String, Arc<VarStore>>,
+
+    /// The name of the store each routed variable is synchronized with.
+    routes: HashMap<String, String>,
+
+    /// The most requests to make to a store at once.
+    concurrency: usize,
+}
+
+impl VarStores {
+    /// Routes variables to stores, which are sent at most `concurrency`
+    /// requests at once.
+    ///
+    /// Fails if a variable is routed to a store that wasn't passed, or isn't
+    /// routed anywhere without a default store.
+    pub fn new<'a>(
+        default: Option<VarStore>,
+        named: HashMap<String, VarStore>,
+        routes: impl IntoIterator<Item = (&'a str, Option<&'a str>)>,
+        concurrency: usize,
+    ) -> anyhow::Result<Self> {
+        let mut stores = Self {
+            default: default.map(Arc::new),
+            named: named
+                .into_iter()
+                .map(|(name, store)| (name, Arc::new(store)))
+                .collect(),
+            routes: HashMap::new(),
+            concurrency,
+        };
+        for (var, vault) in routes {
+            match vault {
+                Some(vault) if stores.named.contains_key(vault) => {
+                    stores.routes.insert(var.to_string(), vault.to_string());
+                }
+                Some(vault) => {
+                    bail!("{var} is stored in vault {vault:?}, but it wasn't passed with --vault")
+                }
+                None if stores.default.is_none() => bail!("No store for {var}"),
+                None => {}
+            }
+        }
+
+        Ok(stores)
+    }
+
+    /// Gets the store a variable is synchronized with.
+    pub fn get(&self, name: &str) -> &Arc<VarStore> {
+        self.routes
+            .get(name)
+            .and_then(|vault| self.named.get(vault))
+            .or(self.default.as_ref())
+            .expect("every variable is routed to a store")
+    }
+
+    /// Describes each store, like `Key Vault: https://my-vault.vault.azure.net/`.
+    pub fn describe(&self) -> Vec<String> {
+        let mut named: Vec<_> = self.named.iter().collect();
+        named.sort_unstable_by_key(|(name, _)| *name);
+        self.default
+            .iter()
+            .map(|store| format!("{}: {}", store.kind(), store.name()))
+            .chain(
+                named
+                    .into_iter()
+                    .map(|(name, store)| format!("{} ({name}): {}", store.kind(), store.name())),
+            )
+            .collect()
+    }
+
+    /// Groups variables by the store they're synchronized with.
+    fn group<'a>(
+        &self,
+        var_names: impl IntoIterator<Item = &'a str>,
+    ) -> Vec<(&Arc<VarStore>, Vec<&'a str>)> {
+        let mut groups: Vec<(&Arc<VarStore>, Vec<&str>)> = Vec::new();
+        for name in var_names {
+            let store = self.get(name);
+            match groups
+                .iter_mut()
+                .find(|(other, _)| Arc::ptr_eq(other, store))
+            {
+                Some((_, names)) => names.push(name),
+                None => groups.push((store, vec![name])),
+            }
+        }
+        groups
+    }
+
+    /// Loads the remote values of variables from each of their stores.
+    pub async fn get_remote_vars(
+        &self,
+        mode: SyncMode,
+        var_names: impl IntoIterator<Item = &str>,
+    ) -> anyhow::Result<RemoteVars> {
+        let mut remote_vars = RemoteVars {
+            values: HashMap::new(),
+            disabled: HashSet::new(),
+        };
+        for (store, names) in self.group(var_names) {
+            let RemoteVars { values, disabled } = store
+                .get_remote_vars(mode, names, self.concurrency)
+                .await
+                .with_context(|| format!("Failed to load variables from {}", store.name()))?;
+            remote_vars.values.extend(values);
+            remote_vars.disabled.extend(disabled);
+        }
+
+        Ok(remote_vars)
+    }
+
+    /// Gets the names of variables that were deleted from each of their
+    /// stores but can still be recovered.
+    pub async fn get_deleted_vars(
+        &self,
+        var_names: impl IntoIterator<Item = &str>,
+    ) -> anyhow::Result<HashSet<String>> {
+        let mut deleted = HashSet::new();
+        for (store, names) in self.group(var_names) {
+            deleted.extend(store.get_deleted_vars(names, self.concurrency).await?);
+        }
+
+        Ok(deleted)
+    }
+}
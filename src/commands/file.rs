@@ -1,140 +1,231 @@
 use std::{
     borrow::Cow,
-    collections::HashSet,
-    fs::File,
-    io::{ErrorKind, Write},
-    path::{Path, PathBuf},
-    process::exit,
-    sync::Arc,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    env::{current_dir, vars},
+    fs::{File, Metadata, create_dir_all, metadata, remove_file},
+    io::ErrorKind,
+    num::NonZeroUsize,
+    ops::Range,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
 };
 
 use anyhow::{Context as _, bail};
-use azure_identity::DefaultAzureCredential;
 use azure_storage_blob::{
     BlobClient,
-    models::{BlobClientDownloadResultHeaders, BlockBlobClientUploadOptions},
+    models::{
+        BlobClientDownloadOptions, BlobClientDownloadResultHeaders, BlobType,
+        BlockBlobClientCommitBlockListOptions, BlockBlobClientCommitBlockListResultHeaders,
+        BlockBlobClientUploadOptions, BlockBlobClientUploadResultHeaders, BlockListType,
+        BlockLookupList,
+    },
 };
-use futures::{TryStreamExt, stream::FuturesUnordered};
-use time::{OffsetDateTime, format_description::well_known::Rfc3339};
-use tokio::fs::File as AsyncFile;
-use tracing::info;
-use typespec_client_core::{
-    fs::FileStreamBuilder,
-    http::{StatusCode, response::ResponseBody},
+use futures::{StreamExt, TryStreamExt, stream};
+use sha2::{Digest, Sha256};
+use similar::TextDiff;
+use time::{Duration, OffsetDateTime, format_description::well_known::Rfc3339};
+use tokio::{
+    fs::File as AsyncFile,
+    io::{AsyncReadExt, BufReader},
 };
+use tracing::{debug, error, info, warn};
+use typespec_client_core::{fs::FileStreamBuilder, http::StatusCode};
 use url::Url;
 
 use crate::{
-    cli::{GlobalOptions, SyncFileOptions, SyncMode},
-    commands::Command,
+    acl::{check_supported, read_acl, write_acl},
+    annotation::read_blob_annotation,
+    audit::{AuditEntry, AuditLog, hash_value},
+    cli::{
+        Compression, ConflictStrategy, GlobalOptions, LogFormat, SyncFileOptions, SyncMode,
+        SyncOptions,
+    },
+    commands::{Command, Status, dir::relative_path},
+    config::{Config, SyncCommand},
+    conflicts::{ConflictReport, ConflictSide, Decisions, ReportedConflict, Resolution},
     dotenv::DotenvFile,
-    sync::{SyncAction, SyncType, confirm},
+    firewall,
+    format::{Size, newer_side},
+    manifest::MANIFEST_FILE,
+    metrics,
+    progress::{ItemProgress, Progress},
+    secrets::{SecretScan, scan_file},
+    state::{PlanMarker, StateCache, fingerprint_files},
+    storage::{
+        ACL_META, BLOCK_SIZE, BlobDeletion, BlobProperties, CHECKSUM_META, COMPRESSION_META,
+        EncryptionKey, Inventory, MAX_BLOCK_SIZE, MAX_BLOCKS, MAX_UPLOAD_SIZE, MODIFIED_META,
+        SINGLE_UPLOAD_LIMIT, SparseWriter, StorageCredential, TEXT_CHECKSUM_META, TempFile,
+        TextHasher, blob_url, block_id, check_unchanged, compress, compression, decompress,
+        delete_blobs, get_page_ranges, get_remote_blob, hash_text, is_encrypted, list_blobs,
+        local_path_in, ranges_to_download,
+    },
+    sync::{
+        Changed, MAX_REPLANS, PlanSummary, SkipReason, SyncAction, SyncType, check_writable,
+        choose, confirm, confirm_deletions, dry_run, execute_all, explain_modified,
+        explain_ordering, is_drifted, modified_time, report_canary, report_progress, split_stale,
+        take_canary,
+    },
+    synced::{Changes, SyncHistory, SyncedState, SyncedUpdate, explain_changes},
+    trash::Trash,
+    versions::{
+        VERSION_KEY, VersionStore, VersionUpdate, compare_versions, explain_versions,
+        time_fingerprint,
+    },
+    watch::{Watched, wait_for_changes},
 };
 
-const MODIFIED_META: &str = "modified";
-
 impl Command for SyncFileOptions {
-    async fn execute(self, global_options: &GlobalOptions) -> anyhow::Result<()> {
+    async fn execute(mut self, global_options: &GlobalOptions) -> anyhow::Result<Status> {
+        if !self.watch.watch {
+            return self
+                .sync_once(global_options)
+                .await
+                .map(|(status, _)| status);
+        }
+
+        // Nobody is around to confirm each run
+        self.sync.sync_mode.get_or_insert(SyncMode::Push);
+        self.sync.no_confirm = true;
+        if let Some(port) = self.watch.metrics_port {
+            metrics::serve(self.watch.metrics_addr, port).await?;
+        }
+        let mut watched = Vec::new();
+        loop {
+            let result = self.clone().sync_once(global_options).await;
+            metrics::record_sync(result.is_ok());
+            match result {
+                Ok((_, paths)) => watched = paths,
+                // Give up if it never worked
+                Err(error) if watched.is_empty() => return Err(error),
+                Err(error) => error!("{error:#}"),
+            }
+            info!("Watching {} file(s) for changes", watched.len());
+            wait_for_changes(&Watched::Files(&watched), self.watch.debounce).await?;
+        }
+    }
+}
+
+impl SyncFileOptions {
+    /// Finds the files to synchronize and synchronizes them once.
+    ///
+    /// Returns how it finished and the local paths that were synchronized.
+    async fn sync_once(
+        self,
+        global_options: &GlobalOptions,
+    ) -> anyhow::Result<(Status, Vec<PathBuf>)> {
+        let config = Config::load(global_options)?;
+
         // Load dotenv file
         let dotenv = if global_options.no_env_file {
             None
         } else {
-            DotenvFile::from_path_exists(&global_options.env_file)?
+            DotenvFile::from_path_exists(&global_options.env_file, global_options.strict_parse)?
         };
+        let credential = self.azure_storage.credential(dotenv.as_ref())?;
 
-        // De-dupe the input paths to better support shell-level globbing
-        let paths: HashSet<_> = self
-            .paths
-            .into_iter()
-            .map(|path| {
-                match path.canonicalize() {
-                    Ok(path) => Ok(path),
-                    // File doesn't exist yet, so can't be canonicalized
-                    Err(error) if error.kind() == ErrorKind::NotFound => Ok(path),
-                    // Other type of I/O error
-                    Err(error) => Err(error),
-                }
-            })
-            .collect::<Result<_, _>>()?;
-
-        // Convert to an ordered list so that we can track associated blob names
-        let paths = Vec::from_iter(paths);
+        // Find the files to sync and the containers they're stored in, along
+        // with any blob names chosen for them explicitly
+        let blob_name = self.blob_name.as_deref();
+        let files = if self.paths.is_empty() && self.remote_prefix.is_none() {
+            if config.files.is_empty() {
+                bail!(
+                    "No files to synchronize (pass some paths or declare files in the config file)"
+                );
+            }
 
-        // Ensure all blob names are unique
-        let (blob_names, duplicate_names) = paths.iter().try_fold(
-            (
-                Vec::with_capacity(paths.len()),
-                HashSet::with_capacity(paths.len()),
-            ),
-            |(mut blob_names, mut duplicates), path| -> anyhow::Result<_> {
-                // Get path parts
-                let mut name = path
-                    .file_name()
-                    .context("Expected path to file")
-                    .and_then(|name| name.to_str().context("File name must be valid Unicode"));
-                let mut stem = path
-                    .file_stem()
-                    .context("Expected path to file")
-                    .and_then(|stem| stem.to_str().context("File stem must be valid Unicode"));
-                let mut ext = path
-                    .extension()
-                    .map(|ext| ext.to_str().context("File extension must be valid Unicode"))
-                    .transpose();
-
-                /// Tries to copy the `Ok` variant out of a result.
-                ///
-                /// This replaces the result with `Ok(value)`.
-                macro_rules! copy_try {
-                    ($result:ident) => {{
-                        let value = $result?;
-                        $result = Ok(value);
-                        value
-                    }};
-                }
+            config
+                .files
+                .iter()
+                .map(|file| {
+                    let (endpoint, name) =
+                        self.azure_storage
+                            .resolve_file(file, &config, dotenv.as_ref())?;
+                    let blob_name = file.blob_name.as_deref().or(blob_name);
+                    Ok((file.path.clone(), Container { endpoint, name }, blob_name))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?
+        } else {
+            let (endpoint, name) = self.azure_storage.resolve(&config, dotenv.as_ref())?;
+            let container = Container { endpoint, name };
+            self.paths
+                .iter()
+                .map(|path| (path.clone(), container.clone(), blob_name))
+                .collect()
+        };
 
-                // Format blob name
-                let mut blob_name = String::with_capacity(path.as_os_str().len());
-                let mut placeholder = false;
-                for part in self.blob_name.split('#') {
-                    if placeholder {
-                        let inserted = match part {
-                            "name" => Cow::Borrowed(copy_try!(name)),
-                            "stem" => Cow::Borrowed(copy_try!(stem)),
-                            "suffix" => Cow::Owned(
-                                copy_try!(ext)
-                                    .map(|ext| format!(".{ext}"))
-                                    .unwrap_or_default(),
-                            ),
-                            "ext" => Cow::Borrowed(copy_try!(ext).unwrap_or_default()),
-                            other => bail!("Invalid placeholder: {other:?}"),
-                        };
-                        blob_name.push_str(&inserted);
-                    } else {
-                        blob_name.push_str(part);
+        // De-dupe the input paths to better support shell-level globbing
+        let current_dir = current_dir().context("Failed to get the current directory")?;
+        let variables = vars().collect();
+        let mut seen = HashSet::with_capacity(files.len());
+        let mut contexts = Vec::with_capacity(files.len());
+        let mut duplicate_names = HashSet::new();
+        for (path, container, blob_name) in files {
+            let local_path = match path.canonicalize() {
+                Ok(path) => path,
+                // File doesn't exist yet, so can't be canonicalized
+                Err(error) if error.kind() == ErrorKind::NotFound => path,
+                // Other type of I/O error
+                Err(error) => return Err(error.into()),
+            };
+            // Blob names can only be formatted from Unicode file names
+            let name_path = match local_path.file_name() {
+                Some(name) if name.to_str().is_none() => {
+                    if !self.lossy_names {
+                        warn!(
+                            "Skipping {} (file name isn't valid Unicode, pass --lossy-names to replace invalid characters)",
+                            local_path.display()
+                        );
+                        continue;
                     }
-                    placeholder = !placeholder;
+                    Cow::Owned(local_path.with_file_name(&*name.to_string_lossy()))
                 }
+                _ => Cow::Borrowed(local_path.as_path()),
+            };
 
-                // Make sure the right number of #s are found
-                if !placeholder {
-                    bail!("Blob name is malformed (invalid number of #s)");
-                }
+            let blob_name = resolve_blob_name(
+                &config,
+                &current_dir,
+                &local_path,
+                &name_path,
+                blob_name,
+                &variables,
+            )?;
+            if !seen.insert((local_path.clone(), container.clone())) {
+                continue;
+            }
 
-                // Check if it's a duplicate
-                if blob_names.contains(&blob_name) {
-                    // Duplicate name
-                    duplicates.insert(blob_name);
-                } else {
-                    // Unique name
-                    blob_names.push(blob_name);
-                }
+            // Ensure all blob names are unique
+            if contexts.iter().any(|context: &Context| {
+                context.container == container && context.blob_name == blob_name
+            }) {
+                duplicate_names.insert(blob_name);
+                continue;
+            }
 
-                Ok((blob_names, duplicates))
-            },
-        )?;
+            contexts.push(Context {
+                container,
+                blob_name,
+                local_path,
+            });
+        }
+
+        // Add blobs that only need to be named remotely
+        if let Some(prefix) = &self.remote_prefix {
+            let (endpoint, name) = self.azure_storage.resolve(&config, dotenv.as_ref())?;
+            config.policy.check_storage(&endpoint, &name)?;
+            let container = Container { endpoint, name };
+            for context in find_remote_contexts(&credential, &container, prefix).await? {
+                if !contexts.iter().any(|existing| {
+                    existing.container == context.container
+                        && existing.blob_name == context.blob_name
+                }) {
+                    contexts.push(context);
+                }
+            }
+        }
 
         // Check if any names are invalid
-        if blob_names.iter().any(String::is_empty) {
+        if contexts.iter().any(|context| context.blob_name.is_empty()) {
             bail!("Empty blob names are not allowed");
         }
 
@@ -145,54 +236,273 @@ impl Command for SyncFileOptions {
             bail!("Duplicate blob names: {duplicate_names}");
         }
 
-        // Convert each input path to an action
-        let credential =
-            DefaultAzureCredential::new().context("Failed to get default Azure credential")?;
-        let endpoint = self
-            .azure_storage
-            .storage_account_url
-            .resolve(dotenv.as_ref())?;
-        let container_name = self.azure_storage.container_name.resolve(dotenv.as_ref())?;
-        let actions: FuturesUnordered<_> = paths
-            .into_iter()
-            .zip(blob_names)
-            .map(|(path, blob_name)| {
-                get_file_action(
-                    path,
-                    blob_name,
-                    credential.clone(),
-                    &endpoint,
-                    &container_name,
-                    self.sync.sync_mode,
+        let paths = contexts
+            .iter()
+            .map(|context| context.local_path.clone())
+            .collect();
+        let encryption = self
+            .encrypt_key
+            .as_ref()
+            .map(|key| EncryptionKey::load(key, dotenv.as_ref()))
+            .transpose()?;
+        let status = self
+            .sync_contexts(
+                contexts,
+                &current_dir,
+                credential,
+                encryption,
+                &config,
+                global_options,
+            )
+            .await?;
+
+        Ok((status, paths))
+    }
+}
+
+/// Chooses the name of the blob a local file is synchronized with.
+///
+/// Files can declare their own blob names, and otherwise the config file can
+/// choose them by pattern. Files outside of the current directory are matched
+/// by name. `name_path` is the path blob name patterns are formatted with.
+pub(super) fn resolve_blob_name(
+    config: &Config,
+    current_dir: &Path,
+    local_path: &Path,
+    name_path: &Path,
+    blob_name: Option<&str>,
+    variables: &HashMap<String, String>,
+) -> anyhow::Result<String> {
+    if let Some(annotated) = read_blob_annotation(local_path, variables)? {
+        return format_blob_name(&annotated, name_path);
+    }
+
+    let relative = relative_path(current_dir, local_path, true)
+        .or_else(|| Some(name_path.file_name()?.to_string_lossy().into_owned()))
+        .unwrap_or_default();
+    let blob_name = blob_name
+        .or_else(|| config.blob_name_for(&relative))
+        .unwrap_or("#name#");
+    format_blob_name(blob_name, name_path)
+}
+
+/// Finds the blobs in a container whose names start with a prefix, and the
+/// local files they're synchronized with.
+async fn find_remote_contexts(
+    credential: &StorageCredential,
+    container: &Container,
+    prefix: &str,
+) -> anyhow::Result<Vec<Context>> {
+    let client = credential.container_client(&container.endpoint, container.name.clone())?;
+    let blobs = list_blobs(
+        &client,
+        Some(prefix).filter(|prefix| !prefix.is_empty()),
+        false,
+    )
+    .await
+    .with_context(|| format!("Failed to list blobs in {}", container.name))?;
+
+    let current_dir = current_dir().context("Failed to get the current directory")?;
+    let mut contexts = Vec::with_capacity(blobs.len());
+    for blob in blobs {
+        let Some(relative) = blob.name.strip_prefix(prefix) else {
+            continue;
+        };
+        let relative = relative.trim_start_matches('/');
+        let Some(local_path) = local_path_in(&current_dir, relative) else {
+            warn!("Skipping {} (no local path for it)", blob.name);
+            continue;
+        };
+
+        contexts.push(Context {
+            container: container.clone(),
+            local_path,
+            blob_name: blob.name,
+        });
+    }
+    debug!(count = contexts.len(), prefix, "Found remote blobs");
+
+    Ok(contexts)
+}
+
+impl SyncFileOptions {
+    /// Synchronizes files with the blobs they're stored in.
+    ///
+    /// When staging pulled files, their paths relative to `root` are kept.
+    /// Pushed files are encrypted with `encryption` if it's set, and pulled
+    /// ones decrypted.
+    pub(super) async fn sync_contexts(
+        self,
+        mut contexts: Vec<Context>,
+        root: &Path,
+        credential: StorageCredential,
+        encryption: Option<EncryptionKey>,
+        config: &Config,
+        global_options: &GlobalOptions,
+    ) -> anyhow::Result<Status> {
+        // Only synchronize files whose conflicts are being resolved
+        if !self.decisions.is_empty() {
+            let mut decided = Vec::with_capacity(self.decisions.len());
+            for context in contexts {
+                let client = credential.blob_client(
+                    &context.container.endpoint,
+                    context.container.name.clone(),
+                    context.blob_name.clone(),
+                )?;
+                if self.decisions.contains(&blob_url(&client)) {
+                    decided.push(context);
+                }
+            }
+            contexts = decided;
+        }
+
+        // Make sure every container is allowed
+        let containers: BTreeSet<_> = contexts.iter().map(|context| &context.container).collect();
+        for container in &containers {
+            config
+                .policy
+                .check_storage(&container.endpoint, &container.name)?;
+        }
+
+        // Check network rules once rather than failing every action with them
+        if contexts.len() > 1 {
+            for container in &containers {
+                let client =
+                    credential.container_client(&container.endpoint, container.name.clone())?;
+                firewall::preflight_storage(&client, &container.endpoint).await?;
+            }
+        }
+
+        if self.preserve_acl {
+            check_supported()?;
+        }
+
+        // Convert each file to an action
+        if self.inventory.is_some() && containers.len() > 1 {
+            bail!("An inventory report can only be used with files in one container");
+        }
+        let inventory = self
+            .inventory
+            .as_deref()
+            .map(Inventory::from_path)
+            .transpose()?;
+
+        // Skip everything if nothing changed since the last identical run
+        let mut last_run = if self.idempotent {
+            let inputs = self.plan_inputs(&contexts, config);
+            let state = StateCache::load(Path::new(MANIFEST_FILE))?;
+            if let Some(last) = state.get(&inputs)
+                && last.local == fingerprint_local(&contexts)?
+                && last.remote
+                    == get_remote_etags(
+                        &contexts,
+                        &credential,
+                        self.sync.plan_concurrency(MAX_CONCURRENT_PLANS),
+                    )
+                    .await?
+            {
+                info!("Nothing changed since the last identical run");
+                return Ok(Status::Success);
+            }
+            Some((state, inputs))
+        } else {
+            None
+        };
+        let sync_mode = self.sync.sync_mode(config, SyncCommand::File);
+        if self.delete {
+            if sync_mode.can_push() == sync_mode.can_pull() {
+                bail!("--delete needs a sync mode that only pushes or only pulls");
+            }
+            if self.stage_dir.is_some() {
+                bail!("--delete can't be used with --stage-dir");
+            }
+        }
+        if !(1..=MAX_BLOCK_SIZE).contains(&self.block_size) {
+            bail!(
+                "--block-size must be between 1 B and {}",
+                Size(MAX_BLOCK_SIZE)
+            );
+        }
+        let transfers = Transfers {
+            temp_dir: self.temp_dir.clone(),
+            resume: self.resume,
+            blocks: BlockUpload {
+                size: self.block_size,
+                concurrency: self.block_concurrency.get(),
+            },
+            encryption: encryption.map(Arc::new),
+            compression: self.compress,
+        };
+        // There's nobody to ask when only checking
+        let on_conflict = match self.on_conflict {
+            ConflictStrategy::Prompt if self.sync.check_only => ConflictStrategy::Newest,
+            strategy => strategy,
+        };
+        let planner = Planner {
+            credential: Arc::new(credential),
+            sync_mode,
+            compare: CompareOptions {
+                time_tolerance: self.sync.time_tolerance(),
+                checksum: self.checksum,
+                normalize_text: self.normalize_text,
+            },
+            changes: ChangeOptions {
+                delete: self.delete,
+                read_only: config.read_only,
+                preserve_acl: self.preserve_acl,
+            },
+            trash: (self.delete && !self.hard_delete)
+                .then(Trash::new)
+                .transpose()?
+                .map(Arc::new),
+            on_conflict,
+            history: Arc::new(SyncHistory::load(Path::new(MANIFEST_FILE))?),
+            inventory: inventory.as_ref(),
+            // Encrypted files can hold secrets safely
+            secret_scan: (!self.allow_secrets && transfers.encryption.is_none())
+                .then_some(config.secret_scan),
+            versions: config.version_store()?,
+            concurrency: self.sync.plan_concurrency(MAX_CONCURRENT_PLANS),
+            explain: self.sync.explain,
+            reported: Mutex::default(),
+            decisions: &self.decisions,
+        };
+
+        // Print where files are synchronized to
+        info!("Using:");
+        for container in &containers {
+            info!("  Endpoint: {}", container.endpoint);
+            info!("  Container: {}", container.name);
+        }
+        let staging = self.stage_dir.as_deref().map(|stage_dir| (stage_dir, root));
+        let properties = (self.sidecar, global_options.verbose > 0);
+        let mut actions = planner.plan(contexts.clone()).await?;
+        stage_pulls(&mut actions, staging)?;
+        configure_transfers(&mut actions, &transfers);
+        output_properties(&mut actions, properties);
+        print_actions(&actions);
+        planner.save_conflicts(&self.conflict_report)?;
+        let summary = PlanSummary::new(&actions);
+        summary.report(self.sync.json)?;
+        self.sync.record_plan(&summary);
+        let in_sync: Vec<_> = actions
+            .iter()
+            .filter(|action| {
+                matches!(
+                    action,
+                    SyncType::Skip {
+                        reason: SkipReason::Unchanged,
+                        ..
+                    }
                 )
             })
+            .map(|action| action.context().clone())
             .collect();
-        let mut actions: Vec<_> = actions.try_collect().await?;
-        actions.sort();
 
-        // Print actions to the user
-        info!("Using:");
-        info!("  Endpoint: {endpoint}");
-        info!("  Container: {container_name}");
-        info!("Actions:");
-        for action in &actions {
-            match action {
-                SyncType::Push(inner) => info!(
-                    "<- PUSH: {} <- {}",
-                    inner.context.blob_name,
-                    simplify_path(&inner.context.local_path).display(),
-                ),
-                SyncType::Pull(inner) => info!(
-                    "-> PULL: {} -> {}",
-                    inner.context.blob_name,
-                    simplify_path(&inner.context.local_path).display(),
-                ),
-                SyncType::Skip { reason, data } => info!(
-                    "   SKIP ({reason}): {} -- {}",
-                    data.blob_name,
-                    simplify_path(&data.local_path).display(),
-                ),
-            }
+        // Make sure each action would succeed
+        if self.sync.dry_run {
+            dry_run(&actions, &self.sync).await?;
+            return Ok(Status::Success);
         }
 
         // If we're only checking, make no changes
@@ -200,203 +510,2281 @@ impl Command for SyncFileOptions {
             .iter()
             .all(|action| matches!(action, SyncType::Skip { .. }));
         if self.sync.check_only || unchanged {
-            exit(i32::from(!unchanged));
+            if unchanged && let Some((state, inputs)) = &mut last_run {
+                record_marker(state, inputs, &contexts, &planner).await?;
+            }
+            if !self.sync.check_only {
+                record_synced(&in_sync, &planner).await?;
+            }
+            if self.sync.check_only && summary.has_diverged() {
+                return Ok(Status::Diverged);
+            }
+            let failed = self.sync.check_only && summary.has_failed_skips(&config.fail_on_skip);
+            if is_drifted(&actions, self.sync.max_drift) | failed {
+                return Ok(Status::Drifted);
+            }
+            return Ok(Status::Success);
         }
 
-        // Ask for confirmation
+        // Ask for confirmation, always confirming deletions separately
+        self.sync.confirm(&summary)?;
         if !self.sync.no_confirm {
-            confirm()?;
+            confirm_deletions_in(&actions)?;
         }
 
-        // Execute the action
-        let actions: FuturesUnordered<_> = actions.into_iter().map(SyncAction::execute).collect();
-        actions.try_collect::<()>().await?;
+        // Plan files again if they changed while waiting for confirmation
+        for replans in 0.. {
+            let (fresh, stale) = split_stale(actions, &self.sync).await?;
+            actions = fresh;
+            if stale.is_empty() {
+                break;
+            }
+            if replans == MAX_REPLANS {
+                bail!("Files kept changing after planning, try again later");
+            }
 
-        Ok(())
+            warn!(
+                "{} file(s) changed since planning, planning them again",
+                stale.len()
+            );
+            let contexts = stale.into_iter().map(FileAction::into_context);
+            let mut replanned = planner.plan(contexts).await?;
+            stage_pulls(&mut replanned, staging)?;
+            configure_transfers(&mut replanned, &transfers);
+            output_properties(&mut replanned, properties);
+            print_actions(&replanned);
+            planner.save_conflicts(&self.conflict_report)?;
+            if !self.sync.no_confirm {
+                confirm()?;
+                confirm_deletions_in(&replanned)?;
+            }
+            actions.extend(replanned);
+        }
+
+        // Only make the first few changes if they're being tried out
+        let (mut actions, held_back) = take_canary(actions, self.sync.canary);
+
+        // Track how the transfers are going if requested, drawing progress
+        // bars unless log messages are structured
+        let bars = global_options.log_format == LogFormat::Text;
+        let progress = self.progress.then(|| track_progress(&mut actions, bars));
+        let reporter = progress.as_ref().map(Progress::spawn_reporter);
+
+        // Execute the actions, which record what each file looks like once
+        // it's transferred
+        let audit = AuditLog::new(&config.audit, planner.credential.azure.clone())?;
+        // Blobs are deleted together in batches once everything else is done
+        let mut deletions = Vec::new();
+        let actions = actions
+            .into_iter()
+            .filter_map(|action| match action {
+                SyncType::DeleteRemote(inner) => {
+                    deletions.push(inner);
+                    None
+                }
+                action => Some(action),
+            })
+            .collect();
+        let mut result = execute_all(actions, &audit, &self.sync).await;
+        if result.is_ok() {
+            result = delete_remote(deletions, &audit, &self.sync).await;
+        }
+        if let Some(reporter) = reporter {
+            reporter.stop();
+        }
+
+        // Record the versions and states of files that were synchronized, even
+        // if others failed
+        if let Some(versions) = &planner.versions {
+            versions.save()?;
+        }
+        planner.history.save()?;
+        if let Some(trash) = &planner.trash
+            && trash.dir().exists()
+        {
+            info!("Deleted files were moved to {}", trash.dir().display());
+        }
+
+        // Remember that this run succeeded, unless changes were held back
+        if result.is_ok() {
+            report_canary(held_back);
+            record_synced(&in_sync, &planner).await?;
+        }
+        if result.is_ok()
+            && held_back == 0
+            && let Some((state, inputs)) = &mut last_run
+        {
+            record_marker(state, inputs, &contexts, &planner).await?;
+        }
+
+        result.map(|()| Status::Success)
+    }
+
+    /// Hashes the inputs of this run that affect what's planned, to recognize
+    /// identical runs.
+    fn plan_inputs(&self, contexts: &[Context], config: &Config) -> String {
+        let inputs = format!(
+            "{contexts:?}\n{:?}\n{}\n{}\n{}\n{}\n{:?}\n{}\n{}\n{:?}\n{:?}",
+            self.sync.sync_mode(config, SyncCommand::File),
+            self.sync.time_tolerance,
+            self.checksum,
+            self.normalize_text,
+            self.delete,
+            self.on_conflict,
+            self.allow_secrets,
+            config.read_only,
+            self.inventory,
+            self.stage_dir,
+        );
+        hash_value(&inputs)
     }
 }
 
-/// Removes verbatim prefixes from Windows paths to make them more readable.
-///
-/// For example, strips the `\\?\` from `\\?\C:\foo.txt`.
-#[cfg(windows)]
-fn simplify_path(old_path: &Path) -> Cow<'_, Path> {
-    use std::path::{Component, Prefix};
+/// Asks the user to confirm the deletions in a plan, if there are any.
+fn confirm_deletions_in(actions: &[FileAction]) -> anyhow::Result<()> {
+    let summary = PlanSummary::new(actions);
+    let deletions = summary.delete_local + summary.delete_remote;
+    if deletions > 0 {
+        confirm_deletions(deletions)?;
+    }
 
-    // Check if the first component is a verbatim disk path (like `\\?\C:`)
-    if let Some(Component::Prefix(prefix)) = old_path.components().next()
-        && let Prefix::VerbatimDisk(disk) = prefix.kind()
-    {
-        // Replace it with a simple disk prefix
-        let mut new_path = PathBuf::with_capacity(old_path.as_os_str().len());
-        new_path.push(format!(r"{}:", disk as char));
+    Ok(())
+}
 
-        // Push the rest of the path
-        new_path.extend(old_path.components().skip(1));
+/// Fingerprints the local files being synchronized.
+fn fingerprint_local(contexts: &[Context]) -> anyhow::Result<String> {
+    fingerprint_files(contexts.iter().map(|context| context.local_path.as_path()))
+}
 
-        new_path.into()
-    } else {
-        old_path.into()
+/// Gets the `ETag` of each remote blob being synchronized, by its URL.
+async fn get_remote_etags(
+    contexts: &[Context],
+    credential: &StorageCredential,
+    concurrency: usize,
+) -> anyhow::Result<BTreeMap<String, Option<String>>> {
+    stream::iter(contexts)
+        .map(|context| async move {
+            let client = credential.blob_client(
+                &context.container.endpoint,
+                context.container.name.clone(),
+                context.blob_name.clone(),
+            )?;
+            let etag = get_remote_blob(&client)
+                .await?
+                .and_then(|remote| remote.etag);
+            anyhow::Ok((blob_url(&client), etag))
+        })
+        .buffer_unordered(concurrency)
+        .try_collect()
+        .await
+}
+
+/// Records the state of the synchronized files after a successful run.
+async fn record_marker(
+    state: &mut StateCache,
+    inputs: &str,
+    contexts: &[Context],
+    planner: &Planner<'_>,
+) -> anyhow::Result<()> {
+    let marker = PlanMarker {
+        local: fingerprint_local(contexts)?,
+        remote: get_remote_etags(contexts, &planner.credential, planner.concurrency).await?,
+    };
+    state.save(inputs.to_string(), marker)
+}
+
+/// Records what files that were already in sync look like now, so later runs
+/// can tell which copies changed.
+///
+/// Files are only hashed again if they or their blobs changed since they were
+/// last recorded. Transferred files are recorded as they're transferred
+/// instead.
+async fn record_synced(contexts: &[Context], planner: &Planner<'_>) -> anyhow::Result<()> {
+    let states: Vec<_> = stream::iter(contexts)
+        .map(|context| async move {
+            let client = planner.credential.blob_client(
+                &context.container.endpoint,
+                context.container.name.clone(),
+                context.blob_name.clone(),
+            )?;
+            let etag = get_remote_blob(&client)
+                .await?
+                .and_then(|remote| remote.etag);
+            let modified = metadata(&context.local_path)?.modified()?.into();
+            let resource = blob_url(&client);
+            let hash = match planner.history.get(&resource) {
+                Some(state) if state.etag == etag && !state.modified_since(modified)? => state.hash,
+                _ => Some(hash_file(&context.local_path, false).await?.0),
+            };
+            let state = SyncedState::now(etag, hash, Some(modified))?;
+            anyhow::Ok((resource, state))
+        })
+        .buffer_unordered(planner.concurrency)
+        .try_collect()
+        .await?;
+    for (resource, state) in states {
+        planner.history.record(resource, state);
     }
+
+    planner.history.save()
 }
 
-/// Does nothing on non-Windows systems.
-#[cfg(not(windows))]
-fn simplify_path(old_path: &Path) -> Cow<'_, Path> {
-    old_path.into()
+/// Maximum number of files to plan at once.
+const MAX_CONCURRENT_PLANS: usize = 32;
+
+/// How many bytes to download between recording the progress of resumable
+/// downloads.
+const RESUME_INTERVAL: u64 = 8 * 1024 * 1024;
+
+/// An action to take on a file.
+type FileAction = SyncType<PushFile, PullFile, Context, DeleteFile, DeleteBlob>;
+
+impl FileAction {
+    /// Gets the file this action is for.
+    fn context(&self) -> &Context {
+        match self {
+            SyncType::Push(inner) => &inner.context,
+            SyncType::Pull(inner) => &inner.context,
+            SyncType::DeleteLocal(inner) => &inner.context,
+            SyncType::DeleteRemote(inner) => &inner.context,
+            SyncType::Skip { data, .. } => data,
+        }
+    }
+
+    /// Takes the file this action is for.
+    fn into_context(self) -> Context {
+        match self {
+            SyncType::Push(inner) => inner.context,
+            SyncType::Pull(inner) => inner.context,
+            SyncType::DeleteLocal(inner) => inner.context,
+            SyncType::DeleteRemote(inner) => inner.context,
+            SyncType::Skip { data, .. } => data,
+        }
+    }
 }
 
-async fn get_file_action(
-    local_path: PathBuf,
-    blob_name: String,
-    credential: Arc<DefaultAzureCredential>,
-    endpoint: &Url,
-    container_name: &str,
-    sync_mode: SyncMode,
-) -> anyhow::Result<SyncType<PushFile, PullFile, Context>> {
-    // Open the local file
-    let file = match File::open(&local_path) {
-        Ok(file) => Some(file),
-        Err(error) => {
-            if error.kind() == ErrorKind::NotFound {
-                None
-            } else {
-                bail!(error);
+/// Prints the actions that will be taken.
+///
+/// If files are synchronized with more than one container, actions are
+/// grouped by container.
+fn print_actions(actions: &[FileAction]) {
+    info!("Actions:");
+    let contexts: Vec<_> = actions.iter().map(FileAction::context).collect();
+    let grouped = contexts
+        .windows(2)
+        .any(|pair| pair[0].container != pair[1].container);
+    let mut upload = 0;
+    let mut download = 0;
+    for (index, action) in actions.iter().enumerate() {
+        let container = &contexts[index].container;
+        if grouped && (index == 0 || contexts[index - 1].container != *container) {
+            info!("{}{}:", container.endpoint, container.name);
+        }
+
+        match action {
+            SyncType::Push(inner) => {
+                upload += inner.local_size;
+                info!(
+                    "<- PUSH: {} <- {} ({}, {})",
+                    inner.context.blob_name,
+                    simplify_path(&inner.context.local_path).display(),
+                    inner.remote_modified.map_or_else(
+                        || "new".to_string(),
+                        |remote| newer_side(inner.local_modified, remote)
+                    ),
+                    format_sizes(Some(inner.local_size), inner.remote_size),
+                );
+            }
+            SyncType::Pull(inner) => {
+                download += inner.remote_size.unwrap_or_default();
+                info!(
+                    "-> PULL: {} -> {} ({}, {})",
+                    inner.context.blob_name,
+                    simplify_path(&inner.destination).display(),
+                    inner.local_modified.map_or_else(
+                        || "new".to_string(),
+                        |local| newer_side(local, inner.remote_modified)
+                    ),
+                    format_sizes(inner.local_size, inner.remote_size),
+                );
             }
+            SyncType::DeleteLocal(inner) => info!(
+                "-> DELETE: {} (deleted remotely, {})",
+                simplify_path(&inner.context.local_path).display(),
+                format_sizes(Some(inner.local_size), None),
+            ),
+            SyncType::DeleteRemote(inner) => info!(
+                "<- DELETE: {} (deleted locally, {})",
+                inner.context.blob_name,
+                format_sizes(None, inner.remote_size),
+            ),
+            SyncType::Skip { reason, data } => info!(
+                "   SKIP ({reason}): {} -- {}",
+                data.blob_name,
+                simplify_path(&data.local_path).display(),
+            ),
         }
+    }
+
+    if upload > 0 || download > 0 {
+        info!(
+            "Will upload {} and download {}",
+            Size(upload),
+            Size(download)
+        );
+    }
+}
+
+/// Redirects pulled files into a staging directory, keeping their paths
+/// relative to the root directory.
+fn stage_pulls(actions: &mut [FileAction], stage: Option<(&Path, &Path)>) -> anyhow::Result<()> {
+    let Some((stage_dir, root)) = stage else {
+        return Ok(());
     };
 
-    // Get the local modified time
-    let local_modified = file
-        .as_ref()
-        .map(|file| file.metadata()?.modified())
-        .transpose()?
-        .map(OffsetDateTime::from);
-
-    // Open the remote blob
-    let client = BlobClient::new(
-        endpoint.as_str(),
-        container_name.to_string(),
-        blob_name.clone(),
-        credential,
-        None,
-    )?;
-    let (remote_blob, remote_modified) = match client.download(None).await {
-        Ok(blob) => {
-            // Get when the remote blob was last modified
-            let remote_modified = blob
-                .metadata()?
-                .get(MODIFIED_META)
-                .map(|time| OffsetDateTime::parse(time, &Rfc3339))
-                .transpose()?;
-            let remote_modified = match remote_modified {
-                Some(time) => time,
-                None => blob
-                    .last_modified()?
-                    .context("unable to determine when blob was modified")?,
+    for action in actions {
+        if let SyncType::Pull(inner) = action {
+            let local_path = &inner.context.local_path;
+            let relative = if local_path.is_relative() {
+                // Files that don't exist yet may not be canonicalized
+                Some(local_path.as_path())
+            } else {
+                local_path.strip_prefix(root).ok()
             };
+            let relative = relative
+                .filter(|relative| {
+                    !relative
+                        .components()
+                        .any(|component| component == Component::ParentDir)
+                })
+                .with_context(|| {
+                    format!(
+                        "Can't stage {} (it's outside of {})",
+                        simplify_path(local_path).display(),
+                        simplify_path(root).display()
+                    )
+                })?;
+            inner.destination = stage_dir.join(relative);
 
-            (Some(blob), Some(remote_modified))
+            // The local file isn't replaced, so it isn't in sync
+            inner.synced = None;
         }
-        Err(error) => {
-            // Only allow NotFound - fail otherwise
-            if error.http_status() != Some(StatusCode::NotFound) {
-                bail!(error);
-            }
+    }
 
-            (None, None)
-        }
-    };
+    Ok(())
+}
 
-    let context = Context {
-        local_path: local_path.clone(),
-        blob_name,
-    };
-    Ok(SyncType::from_modified(
-        sync_mode,
-        local_modified,
-        remote_modified,
-        remote_blob,
-        |local_modified, remote_blob| PushFile {
-            context: context.clone(),
-            client,
-            local_modified,
-            remote_etag: remote_blob.and_then(|blob| blob.etag().ok().flatten()),
-        },
-        |remote_modified, remote_blob| PullFile {
-            context: context.clone(),
-            remote_blob: remote_blob
-                .expect("remote blob should be Some")
-                .into_raw_body(),
-            remote_modified,
-        },
-        |_| context.clone(),
-    ))
+/// Sets how pushed files are uploaded and pulled files are downloaded.
+fn configure_transfers(actions: &mut [FileAction], transfers: &Transfers) {
+    for action in actions {
+        match action {
+            SyncType::Push(inner) => {
+                inner.blocks = transfers.blocks;
+                inner.encryption.clone_from(&transfers.encryption);
+                inner.compression = transfers.compression;
+            }
+            SyncType::Pull(inner) => {
+                inner.temp_dir.clone_from(&transfers.temp_dir);
+                inner.resume = transfers.resume;
+                inner.encryption.clone_from(&transfers.encryption);
+            }
+            _ => {}
+        }
+    }
 }
 
-#[derive(Clone, Debug)]
-struct Context {
-    local_path: PathBuf,
-    blob_name: String,
+/// What was transferred when a file was pushed or pulled.
+struct Transferred {
+    /// The blob's `ETag` afterwards, if known.
+    etag: Option<String>,
+
+    /// A hash of the file's contents as they were transferred, if known.
+    hash: Option<String>,
 }
 
-sortable_by_key!(Context, str, |context| &context.blob_name);
+/// How files are transferred.
+struct Transfers {
+    /// Where to download pulled files before moving them into place, or
+    /// `None` to download them next to their destinations.
+    temp_dir: Option<PathBuf>,
 
-struct PullFile {
-    context: Context,
-    remote_blob: ResponseBody,
-    remote_modified: OffsetDateTime,
+    /// Whether to keep interrupted downloads and continue earlier ones.
+    resume: bool,
+
+    /// How to upload files in blocks.
+    blocks: BlockUpload,
+
+    /// The key to encrypt pushed files and decrypt pulled ones with, if any.
+    encryption: Option<Arc<EncryptionKey>>,
+
+    /// How to compress pushed files, if at all.
+    compression: Option<Compression>,
 }
 
-sortable_by_key!(PullFile, Context, |action| &action.context);
+/// How files are uploaded in blocks.
+#[derive(Clone, Copy, Debug)]
+struct BlockUpload {
+    /// The size of each block.
+    size: u64,
 
-impl SyncAction for PullFile {
-    async fn execute(mut self) -> anyhow::Result<()> {
-        // Save the file to disk
-        let mut file = File::create(self.context.local_path)?;
-        while let Some(chunk) = self.remote_blob.try_next().await? {
-            file.write_all(&chunk)?;
+    /// How many blocks of a file to upload at once.
+    concurrency: usize,
+}
+
+impl Default for BlockUpload {
+    fn default() -> Self {
+        Self {
+            size: BLOCK_SIZE as u64,
+            concurrency: 1,
         }
-        file.set_modified(self.remote_modified.into())?;
+    }
+}
 
-        Ok(())
+/// Where to output the properties of a pulled blob.
+struct PropertiesOutput {
+    /// Whether to write them to a sidecar file.
+    sidecar: bool,
+
+    /// Whether to print them.
+    print: bool,
+}
+
+/// Outputs the properties of pulled blobs to a sidecar file, printing them,
+/// or both, as set by `(sidecar, print)`.
+fn output_properties(actions: &mut [FileAction], (sidecar, print): (bool, bool)) {
+    if !sidecar && !print {
+        return;
+    }
+
+    for action in actions {
+        if let SyncType::Pull(inner) = action {
+            inner.properties = Some(Box::new(PropertiesOutput { sidecar, print }));
+        }
     }
 }
 
-struct PushFile {
-    context: Context,
-    client: BlobClient,
-    local_modified: OffsetDateTime,
-    remote_etag: Option<String>,
+/// Starts tracking the progress of the files that will be transferred.
+///
+/// If `bars` is set, progress bars are drawn when stderr is a terminal.
+fn track_progress(actions: &mut [FileAction], bars: bool) -> Arc<Progress> {
+    let total = actions
+        .iter()
+        .map(|action| match action {
+            SyncType::Push(inner) => inner.local_size,
+            SyncType::Pull(inner) => inner.remote_size.unwrap_or_default(),
+            SyncType::DeleteLocal(_) | SyncType::DeleteRemote(_) | SyncType::Skip { .. } => 0,
+        })
+        .sum();
+    let progress = Progress::new(total, bars);
+    for action in actions {
+        match action {
+            SyncType::Push(inner) => inner.progress = Some(progress.clone()),
+            SyncType::Pull(inner) => inner.progress = Some(progress.clone()),
+            SyncType::DeleteLocal(_) | SyncType::DeleteRemote(_) | SyncType::Skip { .. } => {}
+        }
+    }
+
+    progress
 }
 
-sortable_by_key!(PushFile, Context, |action| &action.context);
+/// Formats the sizes of a local file and remote blob, leaving out whichever
+/// is unknown.
+fn format_sizes(local: Option<u64>, remote: Option<u64>) -> String {
+    match (local, remote) {
+        (Some(local), Some(remote)) => format!("local {}, remote {}", Size(local), Size(remote)),
+        (Some(local), None) => format!("local {}", Size(local)),
+        (None, Some(remote)) => format!("remote {}", Size(remote)),
+        (None, None) => "size unknown".to_string(),
+    }
+}
 
-impl SyncAction for PushFile {
-    async fn execute(self) -> anyhow::Result<()> {
-        let local_file = AsyncFile::open(self.context.local_path).await?;
-        let content_length = local_file.metadata().await?.len();
-        let stream = FileStreamBuilder::new(local_file).build().await?;
-        let metadata = [(
-            MODIFIED_META.to_string(),
-            self.local_modified.format(&Rfc3339)?,
-        )]
-        .into_iter()
-        .collect();
+/// Scans files that are about to be pushed for secrets.
+///
+/// Fails if any are found, unless only warning about them.
+fn check_secrets(actions: &[FileAction], secret_scan: SecretScan) -> anyhow::Result<()> {
+    let mut flagged = 0;
+    for action in actions {
+        let SyncType::Push(push) = action else {
+            continue;
+        };
 
-        self.client
-            .upload(
-                stream.into(),
-                true,
-                content_length,
-                Some(BlockBlobClientUploadOptions {
-                    if_match: self.remote_etag,
-                    metadata: Some(metadata),
-                    ..Default::default()
-                }),
+        let found = scan_file(&push.context.local_path).with_context(|| {
+            format!(
+                "Failed to scan {} for secrets",
+                simplify_path(&push.context.local_path).display()
             )
-            .await?;
+        })?;
+        if !found.is_empty() {
+            let found = Vec::from_iter(found).join(", ");
+            warn!(
+                "Possible secrets in {}: {found}",
+                simplify_path(&push.context.local_path).display(),
+            );
+            flagged += 1;
+        }
+    }
+
+    if flagged > 0 && secret_scan == SecretScan::Block {
+        bail!("{flagged} file(s) may contain secrets (use --allow-secrets to push anyway)");
+    }
+
+    Ok(())
+}
+
+/// Formats the name of the blob a file is synchronized with from a pattern.
+fn format_blob_name(pattern: &str, path: &Path) -> anyhow::Result<String> {
+    // Get path parts
+    let mut name = path
+        .file_name()
+        .context("Expected path to file")
+        .and_then(|name| name.to_str().context("File name must be valid Unicode"));
+    let mut stem = path
+        .file_stem()
+        .context("Expected path to file")
+        .and_then(|stem| stem.to_str().context("File stem must be valid Unicode"));
+    let mut ext = path
+        .extension()
+        .map(|ext| ext.to_str().context("File extension must be valid Unicode"))
+        .transpose();
+
+    /// Tries to copy the `Ok` variant out of a result.
+    ///
+    /// This replaces the result with `Ok(value)`.
+    macro_rules! copy_try {
+        ($result:ident) => {{
+            let value = $result?;
+            $result = Ok(value);
+            value
+        }};
+    }
+
+    // Format blob name
+    let mut blob_name = String::with_capacity(path.as_os_str().len());
+    let mut placeholder = false;
+    for part in pattern.split('#') {
+        if placeholder {
+            let inserted = match part {
+                "name" => Cow::Borrowed(copy_try!(name)),
+                "stem" => Cow::Borrowed(copy_try!(stem)),
+                "suffix" => Cow::Owned(
+                    copy_try!(ext)
+                        .map(|ext| format!(".{ext}"))
+                        .unwrap_or_default(),
+                ),
+                "ext" => Cow::Borrowed(copy_try!(ext).unwrap_or_default()),
+                other => bail!("Invalid placeholder: {other:?}"),
+            };
+            blob_name.push_str(&inserted);
+        } else {
+            blob_name.push_str(part);
+        }
+        placeholder = !placeholder;
+    }
+
+    // Make sure the right number of #s are found
+    if !placeholder {
+        bail!("Blob name is malformed (invalid number of #s)");
+    }
+
+    Ok(blob_name)
+}
+
+/// Removes verbatim prefixes from Windows paths to make them more readable.
+///
+/// For example, strips the `\\?\` from `\\?\C:\foo.txt`.
+#[cfg(windows)]
+fn simplify_path(old_path: &Path) -> Cow<'_, Path> {
+    use std::path::{Component, Prefix};
+
+    // Check if the first component is a verbatim disk path (like `\\?\C:`)
+    if let Some(Component::Prefix(prefix)) = old_path.components().next()
+        && let Prefix::VerbatimDisk(disk) = prefix.kind()
+    {
+        // Replace it with a simple disk prefix
+        let mut new_path = PathBuf::with_capacity(old_path.as_os_str().len());
+        new_path.push(format!(r"{}:", disk as char));
+
+        // Push the rest of the path
+        new_path.extend(old_path.components().skip(1));
+
+        new_path.into()
+    } else {
+        old_path.into()
+    }
+}
+
+/// Does nothing on non-Windows systems.
+#[cfg(not(windows))]
+fn simplify_path(old_path: &Path) -> Cow<'_, Path> {
+    old_path.into()
+}
+
+/// How files are compared to tell whether they changed.
+struct CompareOptions {
+    /// How far apart modified times can be while still being considered the
+    /// same.
+    time_tolerance: Duration,
+    checksum: bool,
+
+    /// Whether to ignore line endings and trailing newlines when comparing
+    /// checksums.
+    normalize_text: bool,
+}
+
+/// What planned actions are allowed to change.
+struct ChangeOptions {
+    /// Whether to delete copies whose counterparts were deleted.
+    delete: bool,
+    read_only: bool,
+
+    /// Whether to preserve the owner and ACL of each file.
+    preserve_acl: bool,
+}
+
+/// Shared state for planning the actions to take on files.
+struct Planner<'a> {
+    credential: Arc<StorageCredential>,
+    sync_mode: SyncMode,
+    compare: CompareOptions,
+    changes: ChangeOptions,
+
+    /// Where to move deleted local files, or `None` to delete them outright.
+    trash: Option<Arc<Trash>>,
+    on_conflict: ConflictStrategy,
+
+    /// What files looked like when they were last synchronized.
+    history: Arc<SyncHistory>,
+    inventory: Option<&'a Inventory>,
+
+    /// How to handle secrets in pushed files, or `None` to allow them.
+    secret_scan: Option<SecretScan>,
+
+    /// Where versions are recorded, if version counters are used.
+    versions: Option<Arc<VersionStore>>,
+
+    /// How many files to plan at once.
+    concurrency: usize,
 
+    /// Whether to explain each decision.
+    explain: bool,
+
+    /// The conflicts skipped so far with `--on-conflict report`.
+    reported: Mutex<Vec<ReportedConflict>>,
+
+    /// How to resolve conflicts, when resolving reported ones.
+    decisions: &'a Decisions,
+}
+
+impl Planner<'_> {
+    /// Plans what to do with each file, asking the user to resolve any
+    /// conflicts.
+    async fn plan(
+        &self,
+        files: impl IntoIterator<Item = Context>,
+    ) -> anyhow::Result<Vec<FileAction>> {
+        let results: Vec<_> = stream::iter(files)
+            .map(|context| async {
+                let result = self.get_file_action(context.clone()).await;
+                (context, result)
+            })
+            .buffer_unordered(self.concurrency)
+            .collect()
+            .await;
+
+        // Report every file that couldn't be planned, not just the first
+        let total = results.len();
+        let mut plans = Vec::with_capacity(total);
+        let mut failures = Vec::new();
+        for (context, result) in results {
+            match result {
+                Ok(plan) => plans.push(plan),
+                Err(error) => failures.push((context, error)),
+            }
+        }
+        if !failures.is_empty() {
+            failures.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+            for (context, error) in &failures {
+                error!(
+                    "Failed to plan {} -- {}: {error:#}",
+                    context.blob_name,
+                    simplify_path(&context.local_path).display(),
+                );
+            }
+            bail!("Failed to plan {} of {total} file(s)", failures.len());
+        }
+
+        // Resolve conflicts one at a time
+        let mut actions = Vec::with_capacity(plans.len());
+        let mut explanations = Vec::with_capacity(plans.len());
+        let mut conflicts = Vec::new();
+        for plan in plans {
+            match plan {
+                // Only resolve conflicts when resolving them
+                PlannedFile::Action(action, _)
+                    if !self.decisions.is_empty() && !matches!(action, SyncType::Skip { .. }) =>
+                {
+                    let context = action.into_context();
+                    warn!(
+                        "{} is no longer in conflict, skipping it",
+                        context.blob_name
+                    );
+                    actions.push(SyncType::Skip {
+                        reason: SkipReason::Conflict,
+                        data: context,
+                    });
+                }
+                PlannedFile::Action(action, explanation) => {
+                    if self.explain {
+                        let explanation = if self.changes.read_only
+                            && matches!(action, SyncType::Push(_) | SyncType::DeleteRemote(_))
+                        {
+                            format!("{explanation}, but nothing is pushed in read-only mode")
+                        } else {
+                            explanation
+                        };
+                        explanations.push((action.context().clone(), explanation));
+                    }
+                    actions.push(action);
+                }
+                PlannedFile::Conflict(conflict) => conflicts.push(conflict),
+            }
+        }
+
+        // Explain the decisions that were made automatically
+        if self.explain {
+            explanations.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+            info!("Explanations:");
+            for (context, explanation) in explanations {
+                info!(
+                    "  {} -- {}: {explanation}",
+                    context.blob_name,
+                    simplify_path(&context.local_path).display(),
+                );
+            }
+        }
+        conflicts.sort_by(|a, b| a.context.cmp(&b.context));
+        let mut failed = Vec::new();
+        for conflict in conflicts {
+            match self.on_conflict {
+                ConflictStrategy::Newest => unreachable!("conflicts aren't planned with `newest`"),
+                ConflictStrategy::PreferLocal => {
+                    actions.push(conflict.prefer(Resolution::KeepLocal, self.sync_mode));
+                }
+                ConflictStrategy::PreferRemote => {
+                    actions.push(conflict.prefer(Resolution::KeepRemote, self.sync_mode));
+                }
+                ConflictStrategy::Prompt => actions.push(conflict.resolve(self.sync_mode).await?),
+                ConflictStrategy::Fail => failed.push(conflict.context.blob_name),
+                ConflictStrategy::Report => {
+                    // Resolve conflicts that were decided on, and report the rest
+                    let reported = conflict.describe(self.sync_mode).await?;
+                    if self.decisions.is_empty() {
+                        let mut all_reported = self.reported.lock().expect("poisoned");
+                        all_reported.retain(|conflict| conflict.remote != reported.remote);
+                        all_reported.push(reported);
+                        actions.push(conflict.into_action(Resolution::Skip));
+                    } else {
+                        let resolution = self.decisions.resolve(&reported);
+                        actions.push(conflict.into_action(resolution));
+                    }
+                }
+            }
+        }
+        if !failed.is_empty() {
+            bail!(
+                "Files changed both locally and remotely (--on-conflict fail): {}",
+                failed.join(", ")
+            );
+        }
+
+        if self.changes.read_only {
+            actions = actions
+                .into_iter()
+                .map(|action| match action {
+                    SyncType::DeleteRemote(inner) => SyncType::Skip {
+                        reason: SkipReason::ReadOnly,
+                        data: inner.context,
+                    },
+                    action => action.read_only(|push| push.context),
+                })
+                .collect();
+        }
+        actions.sort();
+
+        // Make sure no secrets are pushed by accident
+        if let Some(secret_scan) = self.secret_scan {
+            check_secrets(&actions, secret_scan)?;
+        }
+
+        Ok(actions)
+    }
+
+    /// Writes the conflicts reported so far to the conflict report, if there
+    /// are any.
+    fn save_conflicts(&self, path: &Path) -> anyhow::Result<()> {
+        let reported = self.reported.lock().expect("poisoned").clone();
+        if reported.is_empty() {
+            return Ok(());
+        }
+
+        let count = reported.len();
+        ConflictReport::new(reported).save(path)?;
+        warn!("Reported {count} conflict(s) in {}", path.display());
         Ok(())
     }
+
+    async fn get_file_action(&self, context: Context) -> anyhow::Result<PlannedFile> {
+        // Open the local file
+        let file = match File::open(&context.local_path) {
+            Ok(file) => Some(file),
+            Err(error) => {
+                if error.kind() == ErrorKind::NotFound {
+                    None
+                } else {
+                    bail!(error);
+                }
+            }
+        };
+
+        // Get the local modified time and size
+        let local_metadata = file.as_ref().map(File::metadata).transpose()?;
+        let local_modified = local_metadata
+            .as_ref()
+            .map(Metadata::modified)
+            .transpose()?
+            .map(OffsetDateTime::from);
+        let local_size = local_metadata.as_ref().map(Metadata::len);
+
+        // Get the state of the remote blob
+        let client = self.credential.blob_client(
+            &context.container.endpoint,
+            context.container.name.clone(),
+            context.blob_name.clone(),
+        )?;
+        let remote = match self.inventory {
+            Some(inventory) => inventory
+                .get(&context.container.name, &context.blob_name)
+                .cloned(),
+            None => get_remote_blob(&client).await?,
+        };
+
+        // Compare version counters if they're used
+        let resource = blob_url(&client);
+        let remote_version = remote.as_ref().and_then(|remote| remote.version);
+        let version_order = match (&self.versions, local_modified) {
+            (Some(versions), Some(local_modified)) => compare_versions(
+                versions.get(&resource).as_ref(),
+                &time_fingerprint(local_modified)?,
+                remote_version,
+            ),
+            _ => None,
+        };
+        let synced = SyncedUpdate {
+            history: self.history.clone(),
+            resource: resource.clone(),
+        };
+        let push_version = self.versions.as_ref().map(|store| VersionUpdate {
+            store: store.clone(),
+            resource: resource.clone(),
+            version: remote_version.map_or(1, |version| version + 1),
+        });
+        let pull_version = self
+            .versions
+            .as_ref()
+            .zip(remote_version)
+            .map(|(store, version)| VersionUpdate {
+                store: store.clone(),
+                resource,
+                version,
+            });
+
+        // Mirror deletions instead of copying the file back
+        if self.changes.delete {
+            match (local_modified, &remote) {
+                (None, Some(remote)) if !self.sync_mode.can_pull() => {
+                    return Ok(PlannedFile::Action(
+                        SyncType::DeleteRemote(DeleteBlob {
+                            context,
+                            client,
+                            credential: self.credential.clone(),
+                            remote_modified: remote.modified,
+                            remote_size: remote.size,
+                            remote_etag: remote.etag.clone(),
+                            remote_checksum: remote.checksum.clone(),
+                        }),
+                        "only the remote copy exists, so it's deleted (--delete)".to_string(),
+                    ));
+                }
+                (Some(local_modified), None) if !self.sync_mode.can_push() => {
+                    return Ok(PlannedFile::Action(
+                        SyncType::DeleteLocal(DeleteFile {
+                            context,
+                            client,
+                            local_modified,
+                            local_size: local_size.unwrap_or_default(),
+                            trash: self.trash.clone(),
+                        }),
+                        "only the local copy exists, so it's deleted (--delete)".to_string(),
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        // Compare contents if the remote blob has a checksum to compare against
+        let remote_checksum = remote.as_ref().and_then(|remote| remote.checksum.as_ref());
+        let (local_checksum, local_text_checksum) =
+            if self.compare.checksum && file.is_some() && remote_checksum.is_some() {
+                let (checksum, text_checksum) =
+                    hash_file(&context.local_path, self.compare.normalize_text).await?;
+                (Some(checksum), text_checksum)
+            } else {
+                (None, None)
+            };
+        if local_checksum.is_some() && local_checksum.as_ref() == remote_checksum {
+            return Ok(PlannedFile::Action(
+                SyncType::Skip {
+                    reason: SkipReason::Unchanged,
+                    data: context,
+                },
+                "local file matches the remote blob's checksum, so it's considered unchanged"
+                    .to_string(),
+            ));
+        }
+        let remote_text_checksum = remote
+            .as_ref()
+            .and_then(|remote| remote.text_checksum.as_ref());
+        if local_text_checksum.is_some() && local_text_checksum.as_ref() == remote_text_checksum {
+            return Ok(PlannedFile::Action(
+                SyncType::Skip {
+                    reason: SkipReason::Unchanged,
+                    data: context,
+                },
+                "local file matches the remote blob's checksum apart from line endings and trailing newlines, so it's considered unchanged"
+                    .to_string(),
+            ));
+        }
+
+        // Otherwise, compare both copies with the file last synchronized,
+        // only hashing the local file if it was modified since
+        let changes = match (self.history.get(&context.url()), local_modified, &remote) {
+            (Some(state), Some(local_modified), Some(remote)) => {
+                let local = if state.modified_since(local_modified)? {
+                    let hash = match &local_checksum {
+                        Some(checksum) => checksum.clone(),
+                        None => hash_file(&context.local_path, false).await?.0,
+                    };
+                    state.hash.as_ref() != Some(&hash)
+                } else {
+                    false
+                };
+                Some(Changes {
+                    local,
+                    remote: remote.etag.is_none() || remote.etag != state.etag,
+                })
+            }
+            _ => None,
+        };
+        let order = match version_order {
+            Some(ordering) => Some((ordering, explain_versions(ordering))),
+            None => changes
+                .and_then(Changes::ordering)
+                .map(|ordering| (ordering, explain_changes(ordering))),
+        };
+
+        // Leave files that changed on both sides for the user to decide on
+        if self.on_conflict != ConflictStrategy::Newest
+            && order.is_none()
+            && let (Some(local_modified), Some(remote)) = (local_modified, &remote)
+            && changes.map_or_else(
+                || {
+                    local_checksum.is_some()
+                        || (local_modified - remote.modified).abs() >= self.compare.time_tolerance
+                },
+                Changes::both,
+            )
+        {
+            return Ok(PlannedFile::Conflict(Conflict {
+                context,
+                client,
+                credential: self.credential.clone(),
+                local_modified,
+                local_size: local_size.unwrap_or_default(),
+                local_checksum,
+                remote_modified: remote.modified,
+                remote_etag: remote.etag.clone(),
+                remote_checksum: remote.checksum.clone(),
+                remote_size: remote.size,
+                remote_compression: remote.compression.clone(),
+                remote_blob_type: remote.blob_type,
+                checksum: self.compare.checksum,
+                normalize_text: self.compare.normalize_text,
+                local_text_checksum,
+                push_version,
+                pull_version,
+                synced,
+                preserve_acl: self.changes.preserve_acl,
+            }));
+        }
+
+        let remote_checksum = remote_checksum.cloned();
+        let remote_modified = remote.as_ref().map(|remote| remote.modified);
+        let remote_size = remote.as_ref().and_then(|remote| remote.size);
+        let remote_compression = remote
+            .as_ref()
+            .and_then(|remote| remote.compression.clone());
+        let remote_blob_type = remote.as_ref().and_then(|remote| remote.blob_type);
+        let remote_etag = remote.and_then(|remote| remote.etag);
+        let seed = (client, remote_etag, remote_checksum);
+        let push = |local_modified, (client, remote_etag, remote_checksum)| PushFile {
+            context: context.clone(),
+            client,
+            local_modified,
+            local_size: local_size.unwrap_or_default(),
+            remote_modified,
+            remote_size,
+            remote_etag,
+            remote_checksum,
+            checksum: self.compare.checksum,
+            local_checksum,
+            normalize_text: self.compare.normalize_text,
+            local_text_checksum: local_text_checksum.clone(),
+            version: push_version,
+            synced: Some(synced.clone()),
+            preserve_acl: self.changes.preserve_acl,
+            progress: None,
+            blocks: BlockUpload::default(),
+            encryption: None,
+            compression: None,
+        };
+        let pull = |remote_modified, (client, remote_etag, remote_checksum)| PullFile {
+            context: context.clone(),
+            client,
+            credential: self.credential.clone(),
+            destination: context.local_path.clone(),
+            temp_dir: None,
+            resume: false,
+            encryption: None,
+            properties: None,
+            local_modified,
+            local_size,
+            remote_modified,
+            remote_size,
+            remote_etag,
+            remote_checksum,
+            remote_compression: remote_compression.clone(),
+            remote_blob_type,
+            version: pull_version,
+            synced: Some(synced.clone()),
+            preserve_acl: self.changes.preserve_acl,
+            progress: None,
+        };
+        let skip = |_| context.clone();
+
+        // Prefer version counters and recorded states to modified times when
+        // they're conclusive
+        let (action, explanation) = match (order, local_modified, remote_modified) {
+            (Some((ordering, why)), Some(local_modified), Some(remote_modified)) => (
+                SyncType::from_ordering(
+                    self.sync_mode,
+                    ordering,
+                    local_modified,
+                    remote_modified,
+                    seed,
+                    push,
+                    pull,
+                    skip,
+                ),
+                explain_ordering(self.sync_mode, ordering, why),
+            ),
+            _ => (
+                SyncType::from_modified(
+                    self.sync_mode,
+                    local_modified,
+                    remote_modified,
+                    self.compare.time_tolerance,
+                    seed,
+                    push,
+                    pull,
+                    skip,
+                ),
+                explain_modified(
+                    self.sync_mode,
+                    local_modified,
+                    remote_modified,
+                    self.compare.time_tolerance,
+                ),
+            ),
+        };
+
+        Ok(PlannedFile::Action(action, explanation))
+    }
+}
+
+/// The result of planning what to do with a file.
+enum PlannedFile {
+    /// The action to take, and an explanation of why.
+    Action(FileAction, String),
+
+    /// The file changed on both sides and the user needs to decide what to do.
+    Conflict(Conflict),
+}
+
+/// Largest files that are compared line-by-line when resolving conflicts.
+const MAX_DIFF_SIZE: u64 = 1024 * 1024;
+
+/// A file that changed both locally and remotely.
+struct Conflict {
+    context: Context,
+    client: BlobClient,
+    credential: Arc<StorageCredential>,
+    local_modified: OffsetDateTime,
+    local_size: u64,
+    local_checksum: Option<String>,
+    remote_modified: OffsetDateTime,
+    remote_etag: Option<String>,
+    remote_checksum: Option<String>,
+    remote_size: Option<u64>,
+    remote_compression: Option<String>,
+    remote_blob_type: Option<BlobType>,
+    checksum: bool,
+    normalize_text: bool,
+    local_text_checksum: Option<String>,
+    push_version: Option<VersionUpdate>,
+    pull_version: Option<VersionUpdate>,
+    synced: SyncedUpdate,
+    preserve_acl: bool,
+}
+
+impl Conflict {
+    /// Shows the user how the local file and remote blob differ and asks them
+    /// which one to keep.
+    async fn resolve(self, sync_mode: SyncMode) -> anyhow::Result<FileAction> {
+        println!(
+            "CONFLICT: {} -- {}",
+            self.context.blob_name,
+            simplify_path(&self.context.local_path).display(),
+        );
+        self.show_differences().await?;
+
+        // Only offer choices allowed by the sync mode
+        let mut choices = Vec::with_capacity(3);
+        if sync_mode.can_push() {
+            choices.push(("l", "keep local"));
+        }
+        if sync_mode.can_pull() {
+            choices.push(("r", "keep remote"));
+        }
+        choices.push(("s", "skip"));
+
+        let resolution = match choices[choose(&choices)?].0 {
+            "l" => Resolution::KeepLocal,
+            "r" => Resolution::KeepRemote,
+            _ => Resolution::Skip,
+        };
+
+        Ok(self.into_action(resolution))
+    }
+
+    /// Resolves the conflict by keeping one copy, or skips it if the sync mode
+    /// doesn't allow that.
+    fn prefer(self, resolution: Resolution, sync_mode: SyncMode) -> FileAction {
+        let allowed = match resolution {
+            Resolution::KeepLocal => sync_mode.can_push(),
+            Resolution::KeepRemote => sync_mode.can_pull(),
+            Resolution::Skip => true,
+        };
+        self.into_action(if allowed {
+            resolution
+        } else {
+            Resolution::Skip
+        })
+    }
+
+    /// Converts the conflict into the action that resolves it.
+    fn into_action(self, resolution: Resolution) -> FileAction {
+        match resolution {
+            Resolution::KeepLocal => SyncType::Push(PushFile {
+                context: self.context,
+                client: self.client,
+                local_modified: self.local_modified,
+                local_size: self.local_size,
+                remote_modified: Some(self.remote_modified),
+                remote_size: self.remote_size,
+                remote_etag: self.remote_etag,
+                remote_checksum: self.remote_checksum,
+                checksum: self.checksum,
+                local_checksum: self.local_checksum,
+                normalize_text: self.normalize_text,
+                local_text_checksum: self.local_text_checksum,
+                version: self.push_version,
+                synced: Some(self.synced),
+                preserve_acl: self.preserve_acl,
+                progress: None,
+                blocks: BlockUpload::default(),
+                encryption: None,
+                compression: None,
+            }),
+            Resolution::KeepRemote => SyncType::Pull(PullFile {
+                destination: self.context.local_path.clone(),
+                temp_dir: None,
+                resume: false,
+                encryption: None,
+                properties: None,
+                context: self.context,
+                client: self.client,
+                credential: self.credential,
+                local_modified: Some(self.local_modified),
+                local_size: Some(self.local_size),
+                remote_modified: self.remote_modified,
+                remote_size: self.remote_size,
+                remote_etag: self.remote_etag,
+                remote_checksum: self.remote_checksum,
+                remote_compression: self.remote_compression,
+                remote_blob_type: self.remote_blob_type,
+                version: self.pull_version,
+                synced: Some(self.synced),
+                preserve_acl: self.preserve_acl,
+                progress: None,
+            }),
+            Resolution::Skip => SyncType::Skip {
+                reason: SkipReason::Conflict,
+                data: self.context,
+            },
+        }
+    }
+
+    /// Describes the conflict for the conflict report.
+    async fn describe(&self, sync_mode: SyncMode) -> anyhow::Result<ReportedConflict> {
+        let local_hash = match &self.local_checksum {
+            Some(checksum) => checksum.clone(),
+            None => hash_file(&self.context.local_path, false).await?.0,
+        };
+        let local_side = ConflictSide {
+            path: Some(self.context.local_path.display().to_string()),
+            size: Some(self.local_size),
+            ..ConflictSide::new(Some(self.local_modified), Some(local_hash))?
+        };
+        let remote_side = ConflictSide {
+            size: self.remote_size,
+            etag: self.remote_etag.clone(),
+            ..ConflictSide::new(Some(self.remote_modified), self.remote_checksum.clone())?
+        };
+        ReportedConflict::new(
+            self.context.blob_name.clone(),
+            blob_url(&self.client),
+            local_side,
+            remote_side,
+            sync_mode.can_push(),
+            sync_mode.can_pull(),
+        )
+    }
+
+    /// Prints a diff of the local file and remote blob if they're both small
+    /// text files, or a comparison of their metadata otherwise.
+    async fn show_differences(&self) -> anyhow::Result<()> {
+        let local_size = self.local_size;
+        if local_size <= MAX_DIFF_SIZE && self.remote_size.is_some_and(|size| size <= MAX_DIFF_SIZE)
+        {
+            let local = tokio::fs::read(&self.context.local_path).await?;
+            let remote = self
+                .client
+                .download(Some(BlobClientDownloadOptions {
+                    if_match: self.remote_etag.clone(),
+                    ..Default::default()
+                }))
+                .await?
+                .into_raw_body()
+                .collect()
+                .await?;
+            if let (Some(local), Some(remote)) = (as_text(&local), as_text(&remote)) {
+                let diff = TextDiff::from_lines(remote, local);
+                print!("{}", diff.unified_diff().header("remote", "local"));
+                return Ok(());
+            }
+        }
+
+        let remote_size = self
+            .remote_size
+            .map_or_else(|| "unknown size".to_string(), |size| Size(size).to_string());
+        println!(
+            "  local:  {}, modified {}",
+            Size(local_size),
+            self.local_modified.format(&Rfc3339)?,
+        );
+        println!(
+            "  remote: {remote_size}, modified {}",
+            self.remote_modified.format(&Rfc3339)?,
+        );
+
+        Ok(())
+    }
+}
+
+/// Gets the contents of a file as text, if it appears to be text.
+fn as_text(contents: &[u8]) -> Option<&str> {
+    std::str::from_utf8(contents)
+        .ok()
+        .filter(|text| !text.contains('\0'))
+}
+
+/// Computes the checksum of a local file.
+///
+/// If `normalize_text` is set, the checksum ignoring line endings and
+/// trailing newlines is computed too, if the file is text.
+pub(super) async fn hash_file(
+    path: &Path,
+    normalize_text: bool,
+) -> anyhow::Result<(String, Option<String>)> {
+    let mut file = BufReader::new(AsyncFile::open(path).await?);
+    let mut hasher = Sha256::new();
+    let mut text_hasher = normalize_text.then(TextHasher::default);
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        if let Some(text_hasher) = &mut text_hasher {
+            text_hasher.update(&buffer[..read]);
+        }
+    }
+
+    Ok((
+        format!("{:x}", hasher.finalize()),
+        text_hasher.and_then(TextHasher::finalize),
+    ))
+}
+
+/// A container that files are synchronized with.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub(super) struct Container {
+    pub endpoint: Url,
+    pub name: String,
+}
+
+/// A file and where it's synchronized to.
+///
+/// Files are ordered by their container and then their blob name.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub(super) struct Context {
+    pub container: Container,
+    pub blob_name: String,
+    pub local_path: PathBuf,
+}
+
+impl Context {
+    /// Gets the URL of the blob, like [`blob_url`] does for its client.
+    fn url(&self) -> String {
+        format!(
+            "{}/{}/{}",
+            self.container.endpoint.as_str().trim_end_matches('/'),
+            self.container.name,
+            self.blob_name
+        )
+    }
+}
+
+struct PullFile {
+    context: Context,
+    client: BlobClient,
+
+    /// How to authenticate requests the client can't make.
+    credential: Arc<StorageCredential>,
+
+    /// Where to save the file, which is the local file unless it's staged.
+    destination: PathBuf,
+
+    /// Where to download the file before moving it to its destination, or
+    /// `None` to download it next to its destination.
+    temp_dir: Option<PathBuf>,
+
+    /// Whether to keep the download if it's interrupted, and continue an
+    /// earlier one that was.
+    resume: bool,
+
+    /// The key to decrypt the blob with if it's encrypted.
+    encryption: Option<Arc<EncryptionKey>>,
+
+    /// Where to output the blob's properties, if anywhere.
+    properties: Option<Box<PropertiesOutput>>,
+
+    /// When the local file was last modified, or `None` if it doesn't exist.
+    local_modified: Option<OffsetDateTime>,
+
+    /// The size of the local file, or `None` if it doesn't exist.
+    local_size: Option<u64>,
+    remote_modified: OffsetDateTime,
+
+    /// The size of the remote blob, if known.
+    remote_size: Option<u64>,
+    remote_etag: Option<String>,
+    remote_checksum: Option<String>,
+
+    /// How the blob was compressed when it was pushed, if it was.
+    remote_compression: Option<String>,
+    remote_blob_type: Option<BlobType>,
+
+    /// The version to record once pulled, if version counters are used.
+    version: Option<VersionUpdate>,
+
+    /// Where to record what the file looks like once pulled, if anywhere.
+    synced: Option<SyncedUpdate>,
+
+    /// Whether to restore the owner and ACL stored with the blob.
+    preserve_acl: bool,
+
+    /// Where to report progress, if it's tracked.
+    progress: Option<Arc<Progress>>,
+}
+
+sortable_by_key!(PullFile, Context, |action| &action.context);
+
+impl PullFile {
+    /// Fails if the local file changed since planning.
+    fn check_local(&self) -> anyhow::Result<()> {
+        if modified_time(&self.context.local_path)? != self.local_modified {
+            bail!(Changed(format!(
+                "{} changed since planning",
+                simplify_path(&self.context.local_path).display()
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl SyncAction for PullFile {
+    async fn execute(self, audit: &AuditLog) -> anyhow::Result<()> {
+        self.check_local()?;
+
+        // Download to a temporary file so the destination is never left
+        // partially written. When resuming, the temporary file is kept if the
+        // download is interrupted, and the next one continues from there
+        if let Some(parent) = self.destination.parent() {
+            create_dir_all(parent)?;
+        }
+        let (temp_file, file, offset) = match self.remote_etag.as_deref() {
+            // Encrypted and compressed blobs are decrypted and decompressed all
+            // at once, so they can't be resumed
+            Some(etag)
+                if self.resume
+                    && self.encryption.is_none()
+                    && self.remote_compression.is_none() =>
+            {
+                TempFile::resumable(self.temp_dir.as_deref(), &self.destination, etag)?
+            }
+            _ => {
+                let (temp_file, file) =
+                    TempFile::create(self.temp_dir.as_deref(), &self.destination)?;
+                (temp_file, file, 0)
+            }
+        };
+
+        // A download that finished but was never moved into place can't be
+        // continued from the end of the blob, so start it over
+        let offset = if offset > 0 && self.remote_size.is_none_or(|size| offset >= size) {
+            file.set_len(0)?;
+            temp_file.record(0)?;
+            0
+        } else {
+            offset
+        };
+
+        // Only the pages of page blobs that were written are downloaded, since
+        // the rest reads as zeros
+        let mut pages = match self.remote_size {
+            Some(size)
+                if self.remote_blob_type == Some(BlobType::PageBlob)
+                    && self.encryption.is_none()
+                    && self.remote_compression.is_none() =>
+            {
+                let written =
+                    get_page_ranges(&self.credential, &self.client, self.remote_etag.as_deref())
+                        .await?;
+                Some((ranges_to_download(&written, offset, size).into_iter(), size))
+            }
+            _ => None,
+        };
+        let first_range = pages.as_mut().and_then(|(ranges, _)| ranges.next());
+        let range_header = |range: &Range<u64>| format!("bytes={}-{}", range.start, range.end - 1);
+
+        // Download the blob, making sure it hasn't changed since planning
+        let response = self
+            .client
+            .download(Some(BlobClientDownloadOptions {
+                if_match: self.remote_etag,
+                range: match &first_range {
+                    Some(range) => Some(range_header(range)),
+                    None => (offset > 0).then(|| format!("bytes={offset}-")),
+                },
+                ..Default::default()
+            }))
+            .await?;
+        let properties = match &self.properties {
+            Some(output) => Some((
+                BlobProperties::from_download(&self.credential, &self.client, &response).await?,
+                output,
+            )),
+            None => None,
+        };
+        let mut metadata = response.metadata()?;
+        let etag = response.etag()?;
+        let encryption = match &self.encryption {
+            Some(key) if is_encrypted(&metadata) => Some(key),
+            None if is_encrypted(&metadata) => bail!(
+                "{} is encrypted, pass --encrypt-key to decrypt it",
+                self.context.blob_name
+            ),
+            // Anyone who can write to the container could replace an
+            // encrypted blob with one that isn't
+            Some(_) => bail!(
+                "{} isn't encrypted, but --encrypt-key was passed",
+                self.context.blob_name
+            ),
+            None => None,
+        };
+
+        // The HTTP client decompresses gzip-encoded blobs as they're
+        // downloaded, so only blobs that weren't decompressed need it here
+        let compression = match compression(&metadata) {
+            Some(compression) if encryption.is_some() || response.content_encoding()?.is_some() => {
+                Some(compression.to_string())
+            }
+            _ => None,
+        };
+        let acl = if self.preserve_acl {
+            metadata.remove(ACL_META)
+        } else {
+            None
+        };
+        let mut remote_blob = response.into_raw_body();
+
+        // Save the file to disk
+        let item = self.progress.as_ref().map(|progress| {
+            let item = progress.start(
+                self.context.blob_name.clone(),
+                self.remote_size.unwrap_or_default(),
+            );
+            item.advance(offset);
+            item
+        });
+
+        // The hash covers what was downloaded before too, if resuming
+        let mut hasher = Sha256::new();
+        if offset > 0 {
+            std::io::copy(&mut std::io::Read::take(&file, offset), &mut hasher)?;
+        }
+
+        // Runs of zeros, like the unwritten pages of page blobs, are left as
+        // holes so the file is sparse where the filesystem supports it
+        let mut writer = SparseWriter::resume(file, offset);
+        if encryption.is_some() || compression.is_some() {
+            // Encrypted and compressed blobs can only be decrypted and
+            // decompressed all at once
+            let mut contents = Vec::new();
+            while let Some(chunk) = remote_blob.try_next().await? {
+                contents.extend_from_slice(&chunk);
+                report_progress();
+                if let Some(item) = &item {
+                    item.advance(chunk.len() as u64);
+                }
+            }
+            if let Some(key) = encryption {
+                contents = key
+                    .decrypt(&self.context.blob_name, &metadata, contents)
+                    .with_context(|| format!("Can't decrypt {}", self.context.blob_name))?;
+            }
+            if let Some(compression) = &compression {
+                contents = decompress(compression, &contents)
+                    .with_context(|| format!("Can't decompress {}", self.context.blob_name))?;
+            }
+            hasher.update(&contents);
+            writer.write(&contents)?;
+        } else {
+            if let Some(range) = &first_range {
+                skip_hole(&mut writer, &mut hasher, item.as_ref(), range.start);
+            }
+            let mut unrecorded = 0;
+            loop {
+                while let Some(chunk) = remote_blob.try_next().await? {
+                    hasher.update(&chunk);
+                    writer.write(&chunk)?;
+                    report_progress();
+                    if let Some(item) = &item {
+                        item.advance(chunk.len() as u64);
+                    }
+
+                    // Record progress every so often so it can be resumed
+                    unrecorded += chunk.len() as u64;
+                    if self.resume && unrecorded >= RESUME_INTERVAL {
+                        temp_file.record(writer.sync()?)?;
+                        unrecorded = 0;
+                    }
+                }
+
+                // Continue with the next pages that were written
+                let Some(range) = pages.as_mut().and_then(|(ranges, _)| ranges.next()) else {
+                    break;
+                };
+                skip_hole(&mut writer, &mut hasher, item.as_ref(), range.start);
+                remote_blob = self
+                    .client
+                    .download(Some(BlobClientDownloadOptions {
+                        if_match: etag.clone(),
+                        range: Some(range_header(&range)),
+                        ..Default::default()
+                    }))
+                    .await?
+                    .into_raw_body();
+            }
+            if let Some((_, size)) = pages {
+                skip_hole(&mut writer, &mut hasher, item.as_ref(), size);
+            }
+        }
+        let file = writer.finish()?;
+        if let Some(item) = item {
+            item.finish();
+        }
+        drop(file);
+        temp_file.persist(&self.destination)?;
+        let file = File::options().write(true).open(&self.destination)?;
+        file.set_modified(self.remote_modified.into())?;
+        if let Some((properties, output)) = properties {
+            if output.print {
+                properties.print(&self.context.blob_name);
+            }
+            if output.sidecar {
+                properties.write_sidecar(&self.destination)?;
+            }
+        }
+        let local_modified = file.metadata()?.modified()?.into();
+        if let Some(version) = self.version {
+            version.record(time_fingerprint(local_modified)?);
+        }
+        if let Some(synced) = self.synced {
+            let hash = format!("{:x}", hasher.finalize());
+            synced.record(etag, Some(hash), Some(local_modified))?;
+        }
+        drop(file);
+        if let Some(acl) = acl {
+            write_acl(&self.destination, &acl)?;
+        }
+
+        audit
+            .record(AuditEntry {
+                action: "pull",
+                resource: blob_url(&self.client),
+                before: None,
+                after: self.remote_checksum,
+                bytes: self.remote_size,
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn dry_run(&self) -> anyhow::Result<()> {
+        check_unchanged(&self.client, true, self.remote_etag.as_deref())
+            .await
+            .and_then(|()| check_writable(&self.destination))
+            .with_context(|| format!("Can't pull {}", self.context.blob_name))
+    }
+
+    async fn check_fresh(&self) -> anyhow::Result<()> {
+        self.check_local()?;
+        check_unchanged(&self.client, true, self.remote_etag.as_deref())
+            .await
+            .with_context(|| self.context.blob_name.clone())
+    }
+
+    fn changed_at(&self) -> Option<OffsetDateTime> {
+        Some(self.remote_modified)
+    }
+}
+
+struct PushFile {
+    context: Context,
+    client: BlobClient,
+    local_modified: OffsetDateTime,
+    local_size: u64,
+
+    /// When the remote blob was last modified, or `None` if it doesn't exist.
+    remote_modified: Option<OffsetDateTime>,
+
+    /// The size of the remote blob, if it exists and its size is known.
+    remote_size: Option<u64>,
+
+    /// The `ETag` of the remote blob, or `None` if it doesn't exist.
+    remote_etag: Option<String>,
+    remote_checksum: Option<String>,
+    checksum: bool,
+    local_checksum: Option<String>,
+
+    /// Whether to record the checksum ignoring line endings and trailing
+    /// newlines.
+    normalize_text: bool,
+
+    /// The checksum of the local file ignoring line endings and trailing
+    /// newlines, if it's text and was computed when planning.
+    local_text_checksum: Option<String>,
+
+    /// The version to record once pushed, if version counters are used.
+    version: Option<VersionUpdate>,
+
+    /// Where to record what the file looks like once pushed, if anywhere.
+    synced: Option<SyncedUpdate>,
+
+    /// Whether to store the file's owner and ACL with the blob.
+    preserve_acl: bool,
+
+    /// Where to report progress, if it's tracked.
+    progress: Option<Arc<Progress>>,
+
+    /// How to upload the file if it's uploaded in blocks.
+    blocks: BlockUpload,
+
+    /// The key to encrypt the file with, if any.
+    encryption: Option<Arc<EncryptionKey>>,
+
+    /// How to compress the file, if at all.
+    compression: Option<Compression>,
+}
+
+sortable_by_key!(PushFile, Context, |action| &action.context);
+
+impl PushFile {
+    /// Fails if the local file changed since planning.
+    fn check_local(&self) -> anyhow::Result<()> {
+        if modified_time(&self.context.local_path)? != Some(self.local_modified) {
+            bail!(Changed(format!(
+                "{} changed since planning",
+                simplify_path(&self.context.local_path).display()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Gets the metadata to store with the blob.
+    fn metadata(
+        &self,
+        checksum: Option<&str>,
+        text_checksum: Option<&str>,
+    ) -> anyhow::Result<HashMap<String, String>> {
+        let mut metadata = HashMap::from([(
+            MODIFIED_META.to_string(),
+            self.local_modified.format(&Rfc3339)?,
+        )]);
+        if let Some(checksum) = checksum {
+            metadata.insert(CHECKSUM_META.to_string(), checksum.to_string());
+        }
+        if let Some(text_checksum) = text_checksum {
+            metadata.insert(TEXT_CHECKSUM_META.to_string(), text_checksum.to_string());
+        }
+        if let Some(version) = &self.version {
+            metadata.insert(VERSION_KEY.to_string(), version.version.to_string());
+        }
+        if self.preserve_acl {
+            metadata.insert(ACL_META.to_string(), read_acl(&self.context.local_path)?);
+        }
+
+        Ok(metadata)
+    }
+
+    /// Uploads the file in a single request.
+    async fn upload(&self) -> anyhow::Result<Transferred> {
+        let local_file = AsyncFile::open(&self.context.local_path).await?;
+        let content_length = local_file.metadata().await?.len();
+        let stream = FileStreamBuilder::new(local_file).build().await?;
+        let metadata = self.metadata(
+            self.local_checksum.as_deref(),
+            self.local_text_checksum.as_deref(),
+        )?;
+
+        let response = self
+            .client
+            .upload(
+                stream.into(),
+                true,
+                content_length,
+                Some(BlockBlobClientUploadOptions {
+                    if_none_match: self.remote_etag.is_none().then(|| "*".to_string()),
+                    if_match: self.remote_etag.clone(),
+                    metadata: Some(metadata),
+                    ..Default::default()
+                }),
+            )
+            .await?;
+
+        Ok(Transferred {
+            etag: response.etag()?,
+            hash: self.local_checksum.clone(),
+        })
+    }
+
+    /// Checks whether the file is uploaded in blocks rather than in a single
+    /// request.
+    ///
+    /// Large files are uploaded in blocks so they can be uploaded in parallel
+    /// and resumed, as are files whose checksum is computed or recorded, or
+    /// whose progress is tracked while uploading.
+    ///
+    /// Encrypted and compressed files are never uploaded in blocks, since
+    /// they're encrypted and compressed all at once.
+    fn uses_blocks(&self) -> bool {
+        self.encryption.is_none()
+            && self.compression.is_none()
+            && ((self.checksum || self.synced.is_some()) && self.local_checksum.is_none()
+                || (self.normalize_text && self.local_text_checksum.is_none())
+                || self.progress.is_some()
+                || self.local_size > SINGLE_UPLOAD_LIMIT)
+    }
+
+    /// Compresses and encrypts the file as configured and uploads it in a
+    /// single request.
+    ///
+    /// The checksum stored with the blob is of the file's contents before
+    /// they're compressed, so it can be compared with the local file.
+    /// Encrypted files have no checksum, since it would reveal whether they
+    /// hold a guessed value.
+    async fn upload_in_memory(&self) -> anyhow::Result<Transferred> {
+        let mut contents = tokio::fs::read(&self.context.local_path).await?;
+        let hash = hash_value(&contents);
+        let item = self
+            .progress
+            .as_ref()
+            .map(|progress| progress.start(self.context.blob_name.clone(), self.local_size));
+        let (checksum, text_checksum) = if self.encryption.is_some() {
+            (None, None)
+        } else {
+            let checksum = match &self.local_checksum {
+                Some(checksum) => Some(checksum.clone()),
+                None => self.checksum.then(|| hash_value(&contents)),
+            };
+            let text_checksum = if self.normalize_text {
+                hash_text(&contents)
+            } else {
+                None
+            };
+            (checksum, text_checksum)
+        };
+        let mut metadata = self.metadata(checksum.as_deref(), text_checksum.as_deref())?;
+
+        // Compress before encrypting, since encrypted contents don't compress
+        let mut content_encoding = None;
+        if let Some(compression) = self.compression {
+            contents = compress(compression, &contents)?;
+            metadata.insert(COMPRESSION_META.to_string(), compression.name().to_string());
+            content_encoding = Some(compression.name().to_string());
+        }
+        if let Some(key) = &self.encryption {
+            let (encrypted, encryption) = key.encrypt(&self.context.blob_name, contents)?;
+            contents = encrypted;
+            metadata.extend(encryption);
+
+            // HTTP clients would fail to decompress what's stored
+            content_encoding = None;
+        }
+        let content_length = contents.len() as u64;
+
+        let response = self
+            .client
+            .upload(
+                contents.try_into()?,
+                true,
+                content_length,
+                Some(BlockBlobClientUploadOptions {
+                    if_none_match: self.remote_etag.is_none().then(|| "*".to_string()),
+                    if_match: self.remote_etag.clone(),
+                    metadata: Some(metadata),
+                    blob_content_encoding: content_encoding,
+                    ..Default::default()
+                }),
+            )
+            .await?;
+        if let Some(item) = item {
+            item.advance(self.local_size);
+            item.finish();
+        }
+
+        Ok(Transferred {
+            etag: response.etag()?,
+            hash: Some(hash),
+        })
+    }
+
+    /// Uploads the file as a list of blocks, computing its checksum from the
+    /// same buffers that are being uploaded.
+    ///
+    /// Blocks left uncommitted by a previous failed upload are reused if their
+    /// contents match, so only the remaining blocks need to be staged. Several
+    /// blocks are staged at once, but they're read and hashed in order.
+    async fn upload_blocks(&self) -> anyhow::Result<Transferred> {
+        let local_file = AsyncFile::open(&self.context.local_path).await?;
+        let client = self.client.block_blob_client();
+
+        // Get blocks staged by a previous upload
+        let staged: HashSet<_> = match client
+            .get_block_list(BlockListType::Uncommitted, None)
+            .await
+        {
+            Ok(response) => response
+                .into_body()
+                .await?
+                .uncommitted_blocks
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|block| block.name)
+                .collect(),
+            Err(error) if error.http_status() == Some(StatusCode::NotFound) => HashSet::new(),
+            Err(error) => bail!(error),
+        };
+
+        let item = self
+            .progress
+            .as_ref()
+            .map(|progress| progress.start(self.context.blob_name.clone(), self.local_size));
+        let block_size = self.blocks.size;
+        let mut hasher = Sha256::new();
+        let mut text_hasher = self.normalize_text.then(TextHasher::default);
+        let block_ids: Vec<_> =
+            stream::try_unfold((local_file, 0), |(mut file, index)| async move {
+                // Read the next block
+                let mut buffer = Vec::new();
+                (&mut file)
+                    .take(block_size)
+                    .read_to_end(&mut buffer)
+                    .await?;
+                if buffer.is_empty() {
+                    return anyhow::Ok(None);
+                }
+                Ok(Some(((index, buffer), (file, index + 1))))
+            })
+            .inspect_ok(|(_, buffer)| {
+                hasher.update(buffer);
+                if let Some(text_hasher) = &mut text_hasher {
+                    text_hasher.update(buffer);
+                }
+            })
+            .map_ok(|(index, buffer)| {
+                let (client, staged, item) = (&client, &staged, &item);
+                async move {
+                    // Stage it (unless it was already staged)
+                    let block_id = block_id(index, &buffer);
+                    let size = buffer.len() as u64;
+                    if staged.contains(&block_id) {
+                        debug!(
+                            blob_name = self.context.blob_name,
+                            block = index,
+                            "reusing staged block"
+                        );
+                    } else {
+                        client
+                            .stage_block(&block_id, size, buffer.try_into()?, None)
+                            .await?;
+                    }
+                    report_progress();
+                    if let Some(item) = item {
+                        item.advance(size);
+                    }
+                    anyhow::Ok(block_id)
+                }
+            })
+            .try_buffered(self.blocks.concurrency)
+            .try_collect()
+            .await?;
+        if let Some(item) = item {
+            item.finish();
+        }
+
+        // Commit the staged blocks
+        let checksum = format!("{:x}", hasher.finalize());
+        let text_checksum = text_hasher.and_then(TextHasher::finalize);
+        let metadata = self.metadata(Some(&checksum), text_checksum.as_deref())?;
+        let blocks = BlockLookupList {
+            latest: Some(block_ids),
+            ..Default::default()
+        };
+        let response = client
+            .commit_block_list(
+                blocks.try_into()?,
+                Some(BlockBlobClientCommitBlockListOptions {
+                    if_none_match: self.remote_etag.is_none().then(|| "*".to_string()),
+                    if_match: self.remote_etag.clone(),
+                    metadata: Some(metadata),
+                    ..Default::default()
+                }),
+            )
+            .await?;
+
+        Ok(Transferred {
+            etag: response.etag()?,
+            hash: Some(checksum),
+        })
+    }
+}
+
+impl SyncAction for PushFile {
+    async fn execute(self, audit: &AuditLog) -> anyhow::Result<()> {
+        self.check_local()?;
+        let transferred = if self.encryption.is_some() || self.compression.is_some() {
+            self.upload_in_memory().await?
+        } else if self.uses_blocks() {
+            self.upload_blocks().await?
+        } else {
+            self.upload().await?
+        };
+        if let Some(version) = self.version {
+            version.record(time_fingerprint(self.local_modified)?);
+        }
+        if let Some(synced) = self.synced {
+            synced.record(
+                transferred.etag,
+                transferred.hash.clone(),
+                Some(self.local_modified),
+            )?;
+        }
+
+        // Encrypted files' hashes would reveal whether they hold a guessed
+        // value
+        audit
+            .record(AuditEntry {
+                action: "push",
+                resource: blob_url(&self.client),
+                before: self.remote_checksum,
+                after: transferred.hash.filter(|_| self.encryption.is_none()),
+                bytes: Some(self.local_size),
+            })
+            .await
+    }
+
+    async fn dry_run(&self) -> anyhow::Result<()> {
+        let result = async {
+            // Make sure the file can be read and isn't too big
+            let local_file = AsyncFile::open(&self.context.local_path).await?;
+            let size = local_file.metadata().await?.len();
+            let max_size = if self.uses_blocks() {
+                MAX_BLOCKS * self.blocks.size
+            } else {
+                MAX_UPLOAD_SIZE
+            };
+            if size > max_size {
+                bail!(
+                    "File is too large ({}, limit {})",
+                    Size(size),
+                    Size(max_size)
+                );
+            }
+
+            check_unchanged(
+                &self.client,
+                self.remote_etag.is_some(),
+                self.remote_etag.as_deref(),
+            )
+            .await
+        };
+
+        result
+            .await
+            .with_context(|| format!("Can't push {}", self.context.blob_name))
+    }
+
+    async fn check_fresh(&self) -> anyhow::Result<()> {
+        self.check_local()?;
+        check_unchanged(
+            &self.client,
+            self.remote_etag.is_some(),
+            self.remote_etag.as_deref(),
+        )
+        .await
+        .with_context(|| self.context.blob_name.clone())
+    }
+
+    fn changed_at(&self) -> Option<OffsetDateTime> {
+        Some(self.local_modified)
+    }
+}
+
+/// A local file to delete because its blob was deleted.
+struct DeleteFile {
+    context: Context,
+    client: BlobClient,
+    local_modified: OffsetDateTime,
+    local_size: u64,
+
+    /// Where to move the file, or `None` to delete it outright.
+    trash: Option<Arc<Trash>>,
+}
+
+sortable_by_key!(DeleteFile, Context, |action| &action.context);
+
+impl DeleteFile {
+    /// Fails if the local file changed since planning.
+    fn check_local(&self) -> anyhow::Result<()> {
+        if modified_time(&self.context.local_path)? != Some(self.local_modified) {
+            bail!(Changed(format!(
+                "{} changed since planning",
+                simplify_path(&self.context.local_path).display()
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl SyncAction for DeleteFile {
+    async fn execute(self, audit: &AuditLog) -> anyhow::Result<()> {
+        self.check_local()?;
+        let result = match &self.trash {
+            Some(trash) => trash.discard(&self.context.local_path),
+            None => remove_file(&self.context.local_path).map_err(Into::into),
+        };
+        result.with_context(|| {
+            format!(
+                "Failed to delete {}",
+                simplify_path(&self.context.local_path).display()
+            )
+        })?;
+
+        audit
+            .record(AuditEntry {
+                action: "delete-local",
+                resource: blob_url(&self.client),
+                before: None,
+                after: None,
+                bytes: None,
+            })
+            .await
+    }
+
+    async fn dry_run(&self) -> anyhow::Result<()> {
+        check_writable(&self.context.local_path).with_context(|| {
+            format!(
+                "Can't delete {}",
+                simplify_path(&self.context.local_path).display()
+            )
+        })
+    }
+
+    async fn check_fresh(&self) -> anyhow::Result<()> {
+        self.check_local()?;
+        check_unchanged(&self.client, false, None)
+            .await
+            .with_context(|| self.context.blob_name.clone())
+    }
+}
+
+/// Leaves a hole in a file being downloaded up to `position`, hashing it as
+/// the zeros it reads back as.
+fn skip_hole(
+    writer: &mut SparseWriter,
+    hasher: &mut Sha256,
+    item: Option<&ItemProgress>,
+    position: u64,
+) {
+    let mut skipped = writer.skip_to(position);
+    if let Some(item) = item {
+        item.advance(skipped);
+    }
+    let zeros = [0; 4096];
+    while skipped > 0 {
+        let len = usize::try_from(skipped).map_or(zeros.len(), |len| len.min(zeros.len()));
+        hasher.update(&zeros[..len]);
+        skipped -= len as u64;
+    }
+}
+
+/// A blob to delete because its local file was deleted.
+struct DeleteBlob {
+    context: Context,
+    client: BlobClient,
+
+    /// How to authenticate deleting the blob in a batch.
+    credential: Arc<StorageCredential>,
+    remote_modified: OffsetDateTime,
+
+    /// The size of the remote blob, if known.
+    remote_size: Option<u64>,
+    remote_etag: Option<String>,
+    remote_checksum: Option<String>,
+}
+
+sortable_by_key!(DeleteBlob, Context, |action| &action.context);
+
+impl DeleteBlob {
+    /// Fails if the local file was created since planning.
+    fn check_local(&self) -> anyhow::Result<()> {
+        if modified_time(&self.context.local_path)?.is_some() {
+            bail!(Changed(format!(
+                "{} was created since planning",
+                simplify_path(&self.context.local_path).display()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Gets how to delete the blob, failing if the local file was created
+    /// since planning.
+    fn into_deletion(self) -> anyhow::Result<BlobDeletion> {
+        self.check_local()?;
+
+        // Delete the blob only if it hasn't changed since planning
+        Ok(BlobDeletion {
+            audit: AuditEntry {
+                action: "delete-remote",
+                resource: blob_url(&self.client),
+                before: self.remote_checksum,
+                after: None,
+                bytes: None,
+            },
+            client: self.client,
+            etag: self.remote_etag,
+        })
+    }
+}
+
+/// Deletes the blobs whose local files were deleted together, in batches.
+async fn delete_remote(
+    actions: Vec<DeleteBlob>,
+    audit: &AuditLog,
+    options: &SyncOptions,
+) -> anyhow::Result<()> {
+    let Some(credential) = actions.first().map(|action| action.credential.clone()) else {
+        return Ok(());
+    };
+    let deletions: Vec<_> = actions
+        .into_iter()
+        .map(DeleteBlob::into_deletion)
+        .collect::<anyhow::Result<_>>()?;
+    let concurrency = options
+        .max_concurrency
+        .map_or(deletions.len(), NonZeroUsize::get);
+    delete_blobs(&credential, deletions, concurrency, audit).await
+}
+
+impl SyncAction for DeleteBlob {
+    async fn execute(self, audit: &AuditLog) -> anyhow::Result<()> {
+        let credential = self.credential.clone();
+        delete_blobs(&credential, [self.into_deletion()?], 1, audit).await
+    }
+
+    async fn dry_run(&self) -> anyhow::Result<()> {
+        check_unchanged(&self.client, true, self.remote_etag.as_deref())
+            .await
+            .with_context(|| format!("Can't delete {}", self.context.blob_name))
+    }
+
+    async fn check_fresh(&self) -> anyhow::Result<()> {
+        self.check_local()?;
+        check_unchanged(&self.client, true, self.remote_etag.as_deref())
+            .await
+            .with_context(|| self.context.blob_name.clone())
+    }
+
+    fn changed_at(&self) -> Option<OffsetDateTime> {
+        Some(self.remote_modified)
+    }
 }
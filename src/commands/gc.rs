@@ -0,0 +1,248 @@
+use std::num::NonZeroUsize;
+
+use azure_storage_blob::{
+    BlobClient, BlobContainerClient,
+    models::{BlobType, BlockListType},
+};
+use futures::{StreamExt, TryStreamExt, future::ok};
+use time::OffsetDateTime;
+use tracing::info;
+
+use crate::{
+    audit::{AuditEntry, AuditLog},
+    cli::{GcOptions, GlobalOptions},
+    commands::{Command, Status},
+    config::Config,
+    dotenv::DotenvFile,
+    format::Size,
+    storage::{BlobDeletion, BlobEntry, blob_url, delete_blobs, is_block_id, list_blobs},
+    sync::{SkipReason, confirm},
+};
+
+/// Maximum number of requests to make at once, unless --max-concurrency is
+/// passed.
+const MAX_CONCURRENT_REQUESTS: usize = 16;
+
+impl Command for GcOptions {
+    async fn execute(self, global_options: &GlobalOptions) -> anyhow::Result<Status> {
+        let config = Config::load(global_options)?;
+
+        // Load dotenv file
+        let dotenv = if global_options.no_env_file {
+            None
+        } else {
+            DotenvFile::from_path_exists(&global_options.env_file, global_options.strict_parse)?
+        };
+
+        // Create client
+        let credential = self.azure_storage.credential(dotenv.as_ref())?;
+        let (endpoint, container_name) = self.azure_storage.resolve(&config, dotenv.as_ref())?;
+        config.policy.check_storage(&endpoint, &container_name)?;
+        let client = credential.container_client(&endpoint, container_name.clone())?;
+        info!("Using:");
+        info!("  Endpoint: {endpoint}");
+        info!("  Container: {container_name}");
+
+        // Find blobs with blocks left behind by failed uploads
+        let cutoff = OffsetDateTime::now_utc() - self.min_age;
+        let blobs = list_blobs(&client, None, true).await?;
+        let concurrency = self
+            .max_concurrency
+            .map_or(MAX_CONCURRENT_REQUESTS, NonZeroUsize::get);
+        let mut actions: Vec<_> = futures::stream::iter(blobs)
+            .map(|blob| get_gc_action(&client, blob, cutoff))
+            .buffer_unordered(concurrency)
+            .try_filter_map(ok)
+            .try_collect()
+            .await?;
+        if config.read_only {
+            actions = actions.into_iter().map(GcAction::read_only).collect();
+        }
+        actions.sort();
+
+        // Print actions to the user
+        info!("Actions:");
+        for action in &actions {
+            match action {
+                GcAction::Delete(inner) => {
+                    info!("   DELETE: {} ({})", inner.blob_name, Size(inner.size));
+                }
+                GcAction::Skip { reason, data } => {
+                    info!(
+                        "   SKIP ({reason}): {} ({})",
+                        data.blob_name,
+                        Size(data.size)
+                    );
+                }
+            }
+        }
+
+        let reclaimable: u64 = actions
+            .iter()
+            .filter_map(|action| match action {
+                GcAction::Delete(inner) => Some(inner.size),
+                GcAction::Skip { .. } => None,
+            })
+            .sum();
+        info!("Reclaimable: {}", Size(reclaimable));
+
+        // If we're only checking, make no changes
+        let unchanged = actions
+            .iter()
+            .all(|action| matches!(action, GcAction::Skip { .. }));
+        if self.check_only || unchanged {
+            if !unchanged {
+                return Ok(Status::Drifted);
+            }
+            return Ok(Status::Success);
+        }
+
+        // Ask for confirmation
+        if !self.no_confirm {
+            confirm()?;
+        }
+
+        // Execute the actions
+        let audit = AuditLog::new(&config.audit, credential.azure.clone())?;
+        delete_blobs(
+            &credential,
+            actions.into_iter().filter_map(|action| match action {
+                GcAction::Delete(inner) => Some(BlobDeletion {
+                    audit: AuditEntry {
+                        action: "delete",
+                        resource: blob_url(&inner.client),
+                        before: None,
+                        after: None,
+                        bytes: None,
+                    },
+                    client: inner.client,
+                    etag: inner.etag,
+                }),
+                GcAction::Skip { .. } => None,
+            }),
+            concurrency,
+            &audit,
+        )
+        .await?;
+        info!("Reclaimed {}", Size(reclaimable));
+
+        Ok(Status::Success)
+    }
+}
+
+/// Inspects a blob's uncommitted blocks to determine if it can be cleaned up.
+async fn get_gc_action(
+    client: &BlobContainerClient,
+    blob: BlobEntry,
+    cutoff: OffsetDateTime,
+) -> anyhow::Result<Option<GcAction>> {
+    // Only block blobs have blocks, and asking other blobs for them fails
+    if blob
+        .blob_type
+        .as_ref()
+        .is_some_and(|blob_type| *blob_type != BlobType::BlockBlob)
+    {
+        return Ok(None);
+    }
+
+    let blob_client = client.blob_client(blob.name.clone());
+    let block_list = blob_client
+        .block_blob_client()
+        .get_block_list(BlockListType::All, None)
+        .await?
+        .into_body()
+        .await?;
+
+    // Only consider blocks staged by azsync
+    let size: i64 = block_list
+        .uncommitted_blocks
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|block| block.name.as_deref().is_some_and(is_block_id))
+        .filter_map(|block| block.size)
+        .sum();
+    if size == 0 {
+        return Ok(None);
+    }
+
+    let data = StaleBlocks {
+        blob_name: blob.name,
+        size: size.try_into().unwrap_or_default(),
+    };
+
+    // Leave recent uploads alone since they may still be in progress
+    if blob.last_modified.is_some_and(|modified| modified > cutoff) {
+        return Ok(Some(GcAction::Skip {
+            reason: SkipReason::TooRecent,
+            data,
+        }));
+    }
+
+    // Committed blobs can't be deleted without losing their content
+    if block_list
+        .committed_blocks
+        .is_some_and(|blocks| !blocks.is_empty())
+    {
+        return Ok(Some(GcAction::Skip {
+            reason: SkipReason::WillExpire,
+            data,
+        }));
+    }
+
+    Ok(Some(GcAction::Delete(Box::new(DeleteBlob {
+        blob_name: data.blob_name,
+        size: data.size,
+        client: blob_client,
+        etag: blob.etag,
+    }))))
+}
+
+/// An action to take on a blob with stale blocks.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum GcAction {
+    /// Delete the blob.
+    Delete(Box<DeleteBlob>),
+
+    /// Leave the blob as-is.
+    Skip {
+        /// The reason for skipping.
+        reason: SkipReason,
+
+        /// The stale blocks being skipped.
+        data: StaleBlocks,
+    },
+}
+
+impl GcAction {
+    /// Skip this action if it would delete anything.
+    fn read_only(self) -> Self {
+        match self {
+            Self::Delete(inner) => Self::Skip {
+                reason: SkipReason::ReadOnly,
+                data: StaleBlocks {
+                    blob_name: inner.blob_name,
+                    size: inner.size,
+                },
+            },
+            other @ Self::Skip { .. } => other,
+        }
+    }
+}
+
+/// Uncommitted blocks staged for a blob.
+struct StaleBlocks {
+    blob_name: String,
+    size: u64,
+}
+
+sortable_by_key!(StaleBlocks, str, |blocks| &blocks.blob_name);
+
+/// Deletes a blob that was never committed.
+struct DeleteBlob {
+    blob_name: String,
+    size: u64,
+    client: BlobClient,
+    etag: Option<String>,
+}
+
+sortable_by_key!(DeleteBlob, str, |action| &action.blob_name);
@@ -0,0 +1,176 @@
+use std::{
+    env::{current_dir, vars},
+    fs::metadata,
+    io::ErrorKind,
+    path::Path,
+};
+
+use anyhow::Context;
+use time::OffsetDateTime;
+use tracing::{info, warn};
+
+use crate::{
+    audit::{hash_secret, load_hash_key},
+    cli::{GlobalOptions, InventoryFormat, InventoryOptions},
+    commands::{
+        Command, Status,
+        dotenv::secret_resource,
+        file::{hash_file, resolve_blob_name},
+    },
+    config::Config,
+    dotenv::DotenvFile,
+    format::Size,
+    manifest::MANIFEST_FILE,
+    storage::blob_url,
+    synced::SyncHistory,
+    tracked::{TrackedKind, TrackedReport, TrackedResource, timestamp},
+};
+
+impl Command for InventoryOptions {
+    async fn execute(self, global_options: &GlobalOptions) -> anyhow::Result<Status> {
+        load_hash_key()?;
+        let config = Config::load(global_options)?;
+
+        // Load dotenv file
+        let dotenv = if global_options.no_env_file {
+            None
+        } else {
+            DotenvFile::from_path_exists(&global_options.env_file, global_options.strict_parse)?
+        };
+
+        let mut report = TrackedReport::new();
+        let history = SyncHistory::load(Path::new(MANIFEST_FILE))?;
+
+        // List the files declared in the config file
+        if !config.files.is_empty() {
+            let credential = self.azure_storage.credential(dotenv.as_ref())?;
+            let current_dir = current_dir().context("Failed to get the current directory")?;
+            let variables = vars().collect();
+            for file in &config.files {
+                let (endpoint, container_name) =
+                    self.azure_storage
+                        .resolve_file(file, &config, dotenv.as_ref())?;
+                config.policy.check_storage(&endpoint, &container_name)?;
+                let local_path = match file.path.canonicalize() {
+                    Ok(path) => path,
+                    Err(error) if error.kind() == ErrorKind::NotFound => file.path.clone(),
+                    Err(error) => return Err(error.into()),
+                };
+                let blob_name = resolve_blob_name(
+                    &config,
+                    &current_dir,
+                    &local_path,
+                    &local_path,
+                    file.blob_name.as_deref(),
+                    &variables,
+                )?;
+                let client = credential.blob_client(&endpoint, container_name, blob_name)?;
+                let resource = blob_url(&client);
+                let (hash, size) = match metadata(&local_path) {
+                    Ok(metadata) => (
+                        Some(hash_file(&local_path, false).await?.0),
+                        Some(metadata.len()),
+                    ),
+                    Err(error) if error.kind() == ErrorKind::NotFound => (None, None),
+                    Err(error) => return Err(error.into()),
+                };
+                report.add(TrackedResource {
+                    kind: TrackedKind::File,
+                    local: file.path.display().to_string(),
+                    last_synced: last_synced(&history, &resource)?,
+                    remote: resource,
+                    hash,
+                    size,
+                });
+            }
+        }
+
+        // List the variables in the dotenv file
+        if let Some(dotenv) = dotenv
+            .as_ref()
+            .filter(|dotenv| !dotenv.parameters.is_empty())
+        {
+            match self.key_vault.resolve(&config, Some(dotenv)) {
+                Ok(key_vault_url) => {
+                    config.policy.check_key_vault(&key_vault_url)?;
+
+                    // Variables that aren't synchronized with this Key Vault are left out
+                    let mut names: Vec<_> = dotenv
+                        .parameters
+                        .keys()
+                        .filter(|&name| {
+                            dotenv.directives.get(name).is_none_or(|directives| {
+                                !directives.never_synced() && directives.vault.is_none()
+                            })
+                        })
+                        .map(String::as_str)
+                        .collect();
+                    names.sort_unstable();
+                    for name in names {
+                        let value = &dotenv.parameters[name];
+                        let resource = secret_resource(&key_vault_url, name);
+                        report.add(TrackedResource {
+                            kind: TrackedKind::Variable,
+                            local: name.to_string(),
+                            last_synced: last_synced(&history, &resource)?,
+                            remote: resource,
+                            hash: Some(hash_secret(value)),
+                            size: Some(value.len() as u64),
+                        });
+                    }
+                }
+                Err(error) => warn!("Not listing variables (no Key Vault): {error:#}"),
+            }
+        }
+
+        if self.output == InventoryFormat::Json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            return Ok(Status::Success);
+        }
+
+        if report.resources.is_empty() {
+            info!("Nothing is tracked");
+            return Ok(Status::Success);
+        }
+
+        // Print a table of the resources
+        let width = report
+            .resources
+            .iter()
+            .map(|resource| resource.local.len())
+            .chain(["LOCAL".len()])
+            .max()
+            .unwrap_or_default();
+        println!(
+            "{:<8}  {:<width$}  {:<20}  {:>10}  REMOTE",
+            "KIND", "LOCAL", "LAST SYNCED", "SIZE"
+        );
+        for resource in &report.resources {
+            let last_synced = resource
+                .last_synced
+                .map(timestamp)
+                .transpose()?
+                .unwrap_or_else(|| "never".to_string());
+            let size = resource
+                .size
+                .map_or_else(|| "missing".to_string(), |size| Size(size).to_string());
+            println!(
+                "{:<8}  {:<width$}  {last_synced:<20}  {size:>10}  {}",
+                resource.kind.name(),
+                resource.local,
+                resource.remote,
+            );
+        }
+
+        Ok(Status::Success)
+    }
+}
+
+/// Gets when a resource was last synchronized according to its recorded
+/// state, if it ever was.
+fn last_synced(history: &SyncHistory, resource: &str) -> anyhow::Result<Option<OffsetDateTime>> {
+    history
+        .get(resource)
+        .map(|state| state.synced_at())
+        .transpose()
+}
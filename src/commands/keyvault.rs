@@ -0,0 +1,540 @@
+use std::{
+    collections::HashSet,
+    fs::{OpenOptions, create_dir_all, read},
+    io::{ErrorKind, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use anyhow::{Context, bail};
+use azure_identity::DefaultAzureCredential;
+use azure_security_keyvault_secrets::{SecretClient, models::SetSecretParameters};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use futures::{StreamExt, TryStreamExt, stream};
+use time::{Duration, OffsetDateTime};
+use tracing::{info, warn};
+use url::Url;
+
+use crate::{
+    audit::{AuditEntry, AuditLog, hash_secret, load_hash_key},
+    cli::{GlobalOptions, SecretFile, SyncMode, SyncSecretsOptions},
+    commands::{Command, Status, dotenv::check_secret_unchanged},
+    config::{Config, SyncCommand},
+    dotenv::{DotenvFile, MAX_SECRET_VALUE_SIZE},
+    sync::{
+        Changed, MAX_REPLANS, PlanSummary, SkipReason, SyncAction, SyncType, check_writable,
+        confirm, dry_run, execute_all, explain_modified, is_drifted, modified_time, report_canary,
+        split_stale, take_canary,
+    },
+};
+
+/// Content type of secrets holding base64-encoded PKCS #12 archives, like
+/// certificates imported into Key Vault.
+const PKCS12_CONTENT_TYPE: &str = "application/x-pkcs12";
+
+/// Extensions of files that are stored base64-encoded as PKCS #12 archives.
+const PKCS12_EXTENSIONS: [&str; 2] = ["pfx", "p12"];
+
+/// Maximum number of secrets to plan at once.
+const MAX_CONCURRENT_PLANS: usize = 16;
+
+/// An action to take on a secret.
+type SecretAction = SyncType<PushSecret, PullSecret, SecretFile>;
+
+/// A secret, its file's contents, and the secret loaded from Key Vault.
+type SecretSeed = (SecretFile, Option<Vec<u8>>, Option<RemoteSecret>);
+
+impl SecretAction {
+    /// Gets the secret this action is for.
+    fn secret(&self) -> &SecretFile {
+        match self {
+            SyncType::Push(inner) => &inner.secret,
+            SyncType::Pull(inner) => &inner.secret,
+            SyncType::DeleteLocal(never) | SyncType::DeleteRemote(never) => match *never {},
+            SyncType::Skip { data, .. } => data,
+        }
+    }
+
+    /// Takes the secret this action is for.
+    fn into_secret(self) -> SecretFile {
+        match self {
+            SyncType::Push(inner) => inner.secret,
+            SyncType::Pull(inner) => inner.secret,
+            SyncType::DeleteLocal(never) | SyncType::DeleteRemote(never) => match never {},
+            SyncType::Skip { data, .. } => data,
+        }
+    }
+}
+
+impl Command for SyncSecretsOptions {
+    async fn execute(self, global_options: &GlobalOptions) -> anyhow::Result<Status> {
+        load_hash_key()?;
+        let config = Config::load(global_options)?;
+
+        // Load dotenv file
+        let dotenv = if global_options.no_env_file {
+            None
+        } else {
+            DotenvFile::from_path_exists(&global_options.env_file, global_options.strict_parse)?
+        };
+
+        // Each secret and file can only be synchronized once
+        let mut names = HashSet::with_capacity(self.secrets.len());
+        let mut paths = HashSet::with_capacity(self.secrets.len());
+        for secret in &self.secrets {
+            if !names.insert(&secret.name) {
+                bail!("Secret {} is listed more than once", secret.name);
+            }
+            if !paths.insert(&secret.path) {
+                bail!("{} is listed more than once", secret.path.display());
+            }
+        }
+
+        // Create client
+        let credential =
+            DefaultAzureCredential::new().context("Failed to get default Azure credential")?;
+        let key_vault_url = self.key_vault.resolve(&config, dotenv.as_ref())?;
+        config.policy.check_key_vault(&key_vault_url)?;
+        info!("Using:");
+        info!("  Key Vault: {key_vault_url}");
+        let planner = Planner {
+            client: Arc::new(
+                SecretClient::new(key_vault_url.as_str(), credential.clone(), None)
+                    .context("Failed to create Key Vault secrets client")?,
+            ),
+            key_vault_url,
+            sync_mode: self.sync.sync_mode(&config, SyncCommand::Keyvault),
+            time_tolerance: self.sync.time_tolerance(),
+            read_only: config.read_only,
+            concurrency: self.sync.plan_concurrency(MAX_CONCURRENT_PLANS),
+            explain: self.sync.explain,
+        };
+
+        let mut actions = planner.plan(self.secrets.clone()).await?;
+        print_actions(&actions);
+        let summary = PlanSummary::new(&actions);
+        summary.report(self.sync.json)?;
+        self.sync.record_plan(&summary);
+
+        // Make sure each action would succeed
+        if self.sync.dry_run {
+            dry_run(&actions, &self.sync).await?;
+            return Ok(Status::Success);
+        }
+
+        // If we're only checking, make no changes
+        let unchanged = actions
+            .iter()
+            .all(|action| matches!(action, SyncType::Skip { .. }));
+        if self.sync.check_only || unchanged {
+            if self.sync.check_only && summary.has_diverged() {
+                return Ok(Status::Diverged);
+            }
+            let failed = self.sync.check_only && summary.has_failed_skips(&config.fail_on_skip);
+            if is_drifted(&actions, self.sync.max_drift) | failed {
+                return Ok(Status::Drifted);
+            }
+            return Ok(Status::Success);
+        }
+
+        // Ask for confirmation
+        self.sync.confirm(&summary)?;
+
+        // Plan secrets again if they changed while waiting for confirmation
+        for replans in 0.. {
+            let (fresh, stale) = split_stale(actions, &self.sync).await?;
+            actions = fresh;
+            if stale.is_empty() {
+                break;
+            }
+            if replans == MAX_REPLANS {
+                bail!("Secrets kept changing after planning, try again later");
+            }
+
+            warn!(
+                "{} secret(s) changed since planning, planning them again",
+                stale.len()
+            );
+            let replanned = planner
+                .plan(stale.into_iter().map(SecretAction::into_secret))
+                .await?;
+            print_actions(&replanned);
+            if !self.sync.no_confirm {
+                confirm()?;
+            }
+            actions.extend(replanned);
+        }
+
+        // Execute the actions
+        let (actions, held_back) = take_canary(actions, self.sync.canary);
+        let audit = AuditLog::new(&config.audit, credential)?;
+        execute_all(actions, &audit, &self.sync).await?;
+        report_canary(held_back);
+
+        Ok(Status::Success)
+    }
+}
+
+/// Prints the actions that will be taken.
+fn print_actions(actions: &[SecretAction]) {
+    info!("Actions:");
+    for action in actions {
+        let SecretFile { name, path } = action.secret();
+        match action {
+            SyncType::Push(_) => info!("<- PUSH: {name} <- {}", path.display()),
+            SyncType::Pull(_) => info!("-> PULL: {name} -> {}", path.display()),
+            SyncType::DeleteLocal(never) | SyncType::DeleteRemote(never) => match *never {},
+            SyncType::Skip { reason, .. } => {
+                info!("   SKIP ({reason}): {name} -- {}", path.display());
+            }
+        }
+    }
+}
+
+/// Shared state for planning the actions to take on secrets.
+struct Planner {
+    client: Arc<SecretClient>,
+    key_vault_url: Url,
+    sync_mode: SyncMode,
+
+    /// How far apart modified times can be while still being considered the
+    /// same.
+    time_tolerance: Duration,
+    read_only: bool,
+
+    /// How many secrets to plan at once.
+    concurrency: usize,
+
+    /// Whether to explain each decision.
+    explain: bool,
+}
+
+impl Planner {
+    /// Plans what to do with each secret.
+    async fn plan(
+        &self,
+        secrets: impl IntoIterator<Item = SecretFile>,
+    ) -> anyhow::Result<Vec<SecretAction>> {
+        let mut planned: Vec<_> = stream::iter(secrets)
+            .map(|secret| self.plan_secret(secret))
+            .buffer_unordered(self.concurrency)
+            .try_collect()
+            .await?;
+        planned.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+
+        // Explain the decisions that were made
+        if self.explain {
+            info!("Explanations:");
+            for (action, explanation) in &planned {
+                let SecretFile { name, path } = action.secret();
+                if self.read_only && matches!(action, SyncType::Push(_)) {
+                    info!(
+                        "  {name} -- {}: {explanation}, but nothing is pushed in read-only mode",
+                        path.display()
+                    );
+                } else {
+                    info!("  {name} -- {}: {explanation}", path.display());
+                }
+            }
+        }
+
+        Ok(planned
+            .into_iter()
+            .map(|(action, _)| {
+                if self.read_only {
+                    action.read_only(|push| push.secret)
+                } else {
+                    action
+                }
+            })
+            .collect())
+    }
+
+    /// Plans what to do with a secret, and explains why.
+    async fn plan_secret(&self, secret: SecretFile) -> anyhow::Result<(SecretAction, String)> {
+        let remote = if self.sync_mode == SyncMode::PushAlways {
+            None
+        } else {
+            get_remote_secret(&self.client, &secret.name)
+                .await
+                .with_context(|| format!("Failed to load {} from Key Vault", secret.name))?
+        };
+        let local = match read(&secret.path) {
+            Ok(value) => Some(value),
+            Err(error) if error.kind() == ErrorKind::NotFound => None,
+            Err(error) => {
+                return Err(error).with_context(|| format!("Can't read {}", secret.path.display()));
+            }
+        };
+        let local_modified = modified_time(&secret.path)?.filter(|_| local.is_some());
+
+        if let (Some(local), Some(remote)) = (&local, &remote)
+            && *local == remote.value
+        {
+            return Ok((
+                SyncType::Skip {
+                    reason: SkipReason::Unchanged,
+                    data: secret,
+                },
+                "the file matches the secret's value".to_string(),
+            ));
+        }
+
+        let resource = format!(
+            "{}/secrets/{}",
+            self.key_vault_url.as_str().trim_end_matches('/'),
+            secret.name
+        );
+        let remote_updated = remote.as_ref().and_then(|remote| remote.updated);
+        let pkcs12 = is_pkcs12_file(&secret.path)
+            || remote
+                .as_ref()
+                .is_some_and(|remote| remote.content_type.as_deref() == Some(PKCS12_CONTENT_TYPE));
+        let seed = (secret, local, remote);
+        let push = |local_modified, (secret, local, remote): SecretSeed| PushSecret {
+            secret,
+            value: local.expect("local value should be Some"),
+            old_value: remote.map(|remote| remote.value),
+            pkcs12,
+            resource: resource.clone(),
+            local_modified,
+            remote_updated,
+            client: self.client.clone(),
+        };
+        let pull = |remote_modified, (secret, local, remote): SecretSeed| PullSecret {
+            secret,
+            value: remote.expect("remote value should be Some").value,
+            old_value: local,
+            resource: resource.clone(),
+            local_modified,
+            remote_modified,
+            remote_updated,
+            client: self.client.clone(),
+        };
+        let skip = |(secret, ..): SecretSeed| secret;
+
+        Ok((
+            SyncType::from_modified(
+                self.sync_mode,
+                local_modified,
+                remote_updated,
+                self.time_tolerance,
+                seed,
+                push,
+                pull,
+                skip,
+            ),
+            explain_modified(
+                self.sync_mode,
+                local_modified,
+                remote_updated,
+                self.time_tolerance,
+            ),
+        ))
+    }
+}
+
+/// A secret loaded from Key Vault.
+struct RemoteSecret {
+    /// The secret's value, decoded if it's stored base64-encoded.
+    value: Vec<u8>,
+    content_type: Option<String>,
+
+    /// When the secret was last updated.
+    updated: Option<OffsetDateTime>,
+}
+
+/// Gets a secret from Key Vault, if it exists.
+async fn get_remote_secret(
+    client: &SecretClient,
+    name: &str,
+) -> anyhow::Result<Option<RemoteSecret>> {
+    let secret = match client.get_secret(name, "", None).await {
+        Ok(response) => response.into_body().await?,
+        Err(error) if error.http_status() == Some(404.into()) => return Ok(None),
+        Err(error) => return Err(error.into()),
+    };
+
+    let value = secret.value.unwrap_or_default();
+    let value = if secret.content_type.as_deref() == Some(PKCS12_CONTENT_TYPE) {
+        BASE64_STANDARD
+            .decode(value.trim())
+            .context("Invalid base64 in PKCS #12 secret")?
+    } else {
+        value.into_bytes()
+    };
+
+    Ok(Some(RemoteSecret {
+        value,
+        content_type: secret.content_type,
+        updated: secret
+            .attributes
+            .and_then(|attributes| attributes.updated.or(attributes.created)),
+    }))
+}
+
+/// Checks whether a file is stored in Key Vault as a PKCS #12 archive.
+fn is_pkcs12_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            PKCS12_EXTENSIONS
+                .iter()
+                .any(|pkcs12| extension.eq_ignore_ascii_case(pkcs12))
+        })
+}
+
+/// Fails if a local file changed since it was planned against.
+fn check_file_unchanged(path: &Path, planned: Option<OffsetDateTime>) -> anyhow::Result<()> {
+    if modified_time(path)? != planned {
+        bail!(Changed(format!(
+            "{} changed since planning",
+            path.display()
+        )));
+    }
+
+    Ok(())
+}
+
+struct PullSecret {
+    secret: SecretFile,
+    value: Vec<u8>,
+    old_value: Option<Vec<u8>>,
+    resource: String,
+
+    /// When the local file was last modified, or `None` if it doesn't exist.
+    local_modified: Option<OffsetDateTime>,
+    remote_modified: OffsetDateTime,
+
+    /// When the secret was last updated when planning.
+    remote_updated: Option<OffsetDateTime>,
+    client: Arc<SecretClient>,
+}
+
+sortable_by_key!(PullSecret, SecretFile, |action| &action.secret);
+
+impl SyncAction for PullSecret {
+    async fn execute(self, audit: &AuditLog) -> anyhow::Result<()> {
+        let path = &self.secret.path;
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            create_dir_all(parent)?;
+        }
+        // Only the owner should be able to read new secret files
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options
+            .open(path)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        file.write_all(&self.value)?;
+        file.set_modified(self.remote_modified.into())?;
+
+        audit
+            .record(AuditEntry {
+                action: "pull",
+                resource: self.resource,
+                before: self.old_value.as_deref().map(hash_secret),
+                after: Some(hash_secret(&self.value)),
+                bytes: Some(self.value.len() as u64),
+            })
+            .await
+    }
+
+    async fn dry_run(&self) -> anyhow::Result<()> {
+        check_writable(&self.secret.path)
+    }
+
+    async fn check_fresh(&self) -> anyhow::Result<()> {
+        check_file_unchanged(&self.secret.path, self.local_modified)?;
+        check_secret_unchanged(&self.client, &self.secret.name, self.remote_updated).await
+    }
+
+    fn changed_at(&self) -> Option<OffsetDateTime> {
+        Some(self.remote_modified)
+    }
+}
+
+struct PushSecret {
+    secret: SecretFile,
+    value: Vec<u8>,
+    old_value: Option<Vec<u8>>,
+
+    /// Whether to store the value base64-encoded as a PKCS #12 archive.
+    pkcs12: bool,
+    resource: String,
+    local_modified: OffsetDateTime,
+
+    /// When the secret was last updated when planning, if it existed.
+    remote_updated: Option<OffsetDateTime>,
+    client: Arc<SecretClient>,
+}
+
+sortable_by_key!(PushSecret, SecretFile, |action| &action.secret);
+
+impl PushSecret {
+    /// Encodes the file's contents as a secret value.
+    fn encode(&self) -> anyhow::Result<String> {
+        if self.pkcs12 {
+            return Ok(BASE64_STANDARD.encode(&self.value));
+        }
+
+        let value = String::from_utf8(self.value.clone()).with_context(|| {
+            format!(
+                "{} isn't valid UTF-8 (only .pfx and .p12 files are base64-encoded)",
+                self.secret.path.display()
+            )
+        })?;
+        Ok(value)
+    }
+}
+
+impl SyncAction for PushSecret {
+    async fn execute(self, audit: &AuditLog) -> anyhow::Result<()> {
+        let params = SetSecretParameters {
+            content_type: self.pkcs12.then(|| PKCS12_CONTENT_TYPE.to_string()),
+            value: Some(self.encode()?),
+            ..Default::default()
+        };
+        self.client
+            .set_secret(&self.secret.name, params.try_into()?, None)
+            .await?;
+
+        audit
+            .record(AuditEntry {
+                action: "push",
+                resource: self.resource,
+                before: self.old_value.as_deref().map(hash_secret),
+                after: Some(hash_secret(&self.value)),
+                bytes: Some(self.value.len() as u64),
+            })
+            .await
+    }
+
+    async fn dry_run(&self) -> anyhow::Result<()> {
+        let value = self
+            .encode()
+            .with_context(|| format!("Can't push {}", self.secret.name))?;
+        if value.len() as u64 > MAX_SECRET_VALUE_SIZE {
+            bail!(
+                "Can't push {}: value is too large ({} bytes, limit {MAX_SECRET_VALUE_SIZE})",
+                self.secret.name,
+                value.len(),
+            );
+        }
+
+        Ok(())
+    }
+
+    async fn check_fresh(&self) -> anyhow::Result<()> {
+        check_file_unchanged(&self.secret.path, Some(self.local_modified))?;
+        check_secret_unchanged(&self.client, &self.secret.name, self.remote_updated).await
+    }
+
+    fn changed_at(&self) -> Option<OffsetDateTime> {
+        Some(self.local_modified)
+    }
+}
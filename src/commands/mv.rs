@@ -0,0 +1,204 @@
+use anyhow::{Context as _, bail};
+use azure_core::http::headers::CONTENT_TYPE;
+use azure_storage_blob::{
+    BlobClient,
+    models::{
+        BlobClientDeleteOptions, BlobClientDownloadOptions, BlobClientDownloadResultHeaders,
+        BlobClientGetPropertiesResultHeaders, BlockBlobClientCommitBlockListOptions,
+        BlockLookupList,
+    },
+};
+use futures::TryStreamExt;
+use tracing::info;
+use typespec_client_core::http::StatusCode;
+
+use crate::{
+    audit::{AuditEntry, AuditLog},
+    cli::{GlobalOptions, MoveOptions},
+    commands::{Command, Status},
+    config::Config,
+    dotenv::DotenvFile,
+    storage::{
+        BLOCK_SIZE, COMPRESSION_META, blob_url, block_id, get_blob_tags, is_encrypted, tags_string,
+    },
+    sync::confirm,
+};
+
+impl Command for MoveOptions {
+    async fn execute(self, global_options: &GlobalOptions) -> anyhow::Result<Status> {
+        let config = Config::load(global_options)?;
+
+        // Load dotenv file
+        let dotenv = if global_options.no_env_file {
+            None
+        } else {
+            DotenvFile::from_path_exists(&global_options.env_file, global_options.strict_parse)?
+        };
+
+        if config.read_only {
+            bail!("Blobs can't be renamed in read-only mode");
+        }
+        if self.source == self.destination {
+            bail!("Source and destination are the same blob");
+        }
+
+        // Create clients
+        let credential = self.azure_storage.credential(dotenv.as_ref())?;
+        let (endpoint, container_name) = self.azure_storage.resolve(&config, dotenv.as_ref())?;
+        config.policy.check_storage(&endpoint, &container_name)?;
+        let client = credential.container_client(&endpoint, container_name.clone())?;
+        let source = client.blob_client(self.source.clone());
+        let destination = client.blob_client(self.destination.clone());
+        info!("Using:");
+        info!("  Endpoint: {endpoint}");
+        info!("  Container: {container_name}");
+
+        // Make sure the source exists and the destination doesn't
+        let properties = match source.get_properties(None).await {
+            Ok(properties) => properties,
+            Err(error) if error.http_status() == Some(StatusCode::NotFound) => {
+                bail!("Blob not found: {}", self.source);
+            }
+            Err(error) => bail!(error),
+        };
+        if is_encrypted(&properties.metadata()?) {
+            bail!(
+                "{} is encrypted for its name, so it can't be renamed. Pull it and push it under the new name instead",
+                self.source
+            );
+        }
+        match destination.get_properties(None).await {
+            Ok(_) => bail!("Blob already exists: {}", self.destination),
+            Err(error) if error.http_status() == Some(StatusCode::NotFound) => {}
+            Err(error) => bail!(error),
+        }
+
+        // Print action to the user
+        info!("Actions:");
+        info!("   MOVE: {} -> {}", self.source, self.destination);
+
+        // Ask for confirmation
+        if !self.no_confirm {
+            confirm()?;
+        }
+
+        // Copy the blob with its properties and tags, then delete the original
+        let etag = properties.etag()?;
+        let tags = if properties.tag_count()?.unwrap_or_default() > 0 {
+            let tags = get_blob_tags(&credential, &source)
+                .await
+                .context("Failed to get blob tags")?;
+            Some(tags_string(&tags))
+        } else {
+            None
+        };
+        copy_blob(
+            &source,
+            &destination,
+            etag.clone(),
+            BlockBlobClientCommitBlockListOptions {
+                if_none_match: Some("*".to_string()),
+                metadata: Some(properties.metadata()?),
+                blob_cache_control: properties.cache_control()?,
+                blob_content_disposition: properties.content_disposition()?,
+                blob_content_encoding: properties.content_encoding()?,
+                blob_content_language: properties.content_language()?,
+                blob_content_md5: properties.content_md5()?,
+                blob_content_type: properties.headers().get_optional_string(&CONTENT_TYPE),
+                blob_tags_string: tags,
+                ..Default::default()
+            },
+        )
+        .await
+        .with_context(|| format!("Failed to copy {} to {}", self.source, self.destination))?;
+        source
+            .delete(Some(BlobClientDeleteOptions {
+                if_match: etag,
+                ..Default::default()
+            }))
+            .await
+            .with_context(|| {
+                format!(
+                    "Copied to {}, but failed to delete {}",
+                    self.destination, self.source
+                )
+            })?;
+
+        AuditLog::new(&config.audit, credential.azure)?
+            .record(AuditEntry {
+                action: "move",
+                resource: format!("{} -> {}", blob_url(&source), blob_url(&destination)),
+                before: None,
+                after: None,
+                bytes: None,
+            })
+            .await?;
+
+        Ok(Status::Success)
+    }
+}
+
+/// Copies a blob's contents to another blob through this client.
+///
+/// The storage SDK doesn't support server-side copies yet, so the contents are
+/// streamed into blocks staged on the destination blob instead. Only one block
+/// is held in memory at a time.
+///
+/// The HTTP client decompresses gzip-encoded blobs as they're downloaded, so
+/// those are stored uncompressed, without their `Content-Encoding`,
+/// compression metadata, or the MD5 hash of their compressed contents.
+async fn copy_blob(
+    source: &BlobClient,
+    destination: &BlobClient,
+    etag: Option<String>,
+    mut options: BlockBlobClientCommitBlockListOptions<'_>,
+) -> anyhow::Result<()> {
+    let client = destination.block_blob_client();
+    let response = source
+        .download(Some(BlobClientDownloadOptions {
+            if_match: etag,
+            ..Default::default()
+        }))
+        .await?;
+    if options.blob_content_encoding.is_some() && response.content_encoding()?.is_none() {
+        options.blob_content_encoding = None;
+        options.blob_content_md5 = None;
+        if let Some(metadata) = &mut options.metadata {
+            metadata.remove(COMPRESSION_META);
+        }
+    }
+    let mut body = response.into_raw_body();
+
+    let mut block_ids = Vec::new();
+    let mut buffer = Vec::with_capacity(BLOCK_SIZE);
+    let mut done = false;
+    while !done {
+        // Fill the next block
+        match body.try_next().await? {
+            Some(chunk) => buffer.extend_from_slice(&chunk),
+            None => done = true,
+        }
+
+        // Stage full blocks, and whatever is left at the end
+        while buffer.len() >= BLOCK_SIZE || (done && !buffer.is_empty()) {
+            let rest = buffer.split_off(buffer.len().min(BLOCK_SIZE));
+            let block = std::mem::replace(&mut buffer, rest);
+            let block_id = block_id(block_ids.len(), &block);
+            client
+                .stage_block(&block_id, block.len() as u64, block.try_into()?, None)
+                .await?;
+            block_ids.push(block_id);
+        }
+    }
+
+    // Commit the staged blocks
+    let blocks = BlockLookupList {
+        latest: Some(block_ids),
+        ..Default::default()
+    };
+    client
+        .commit_block_list(blocks.try_into()?, Some(options))
+        .await?;
+
+    Ok(())
+}
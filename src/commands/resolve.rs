@@ -0,0 +1,70 @@
+use anyhow::{Context, bail};
+use clap::{CommandFactory, FromArgMatches, parser::ValueSource};
+use tracing::{info, warn};
+
+use crate::{
+    cli::{Cli, CliCommand, ConflictStrategy, GlobalOptions, ResolveOptions},
+    commands::{Command, Status},
+    conflicts::{ConflictReport, Decisions},
+};
+
+impl Command for ResolveOptions {
+    async fn execute(self, global_options: &GlobalOptions) -> anyhow::Result<Status> {
+        let report = ConflictReport::load(&self.from)?;
+        let decisions = Decisions::decide(&report, &self.accept_local, &self.accept_remote)?;
+        if decisions.is_empty() {
+            info!("No conflicts were decided on, so there's nothing to resolve");
+            return Ok(Status::Success);
+        }
+
+        // Run the command that reported the conflicts again, making changes
+        // only to resolve them. The global options passed now apply instead
+        // of the ones it was run with.
+        let matches = Cli::command()
+            .try_get_matches_from(&report.command)
+            .context("Invalid command in conflict report")?;
+        let Cli { subcommand, .. } =
+            Cli::from_arg_matches(&matches).context("Invalid command in conflict report")?;
+        let ignored: Vec<_> = Cli::command()
+            .get_arguments()
+            .filter(|arg| arg.is_global_set())
+            .filter(|arg| {
+                matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine)
+            })
+            .filter_map(|arg| arg.get_long())
+            .map(|long| format!("--{long}"))
+            .collect();
+        if !ignored.is_empty() {
+            warn!(
+                "The conflicts were reported with {}, which aren't used again; pass them to `azsync resolve` if they're needed",
+                ignored.join(", ")
+            );
+        }
+        info!("Resolving {} conflict(s)", decisions.len());
+        match subcommand {
+            CliCommand::Dir(mut options) => {
+                options.on_conflict = ConflictStrategy::Report;
+                options.sync.check_only = false;
+                options.idempotent = false;
+                options.decisions = decisions;
+                options.execute(global_options).await
+            }
+            CliCommand::Dotenv(mut options) => {
+                options.on_conflict = ConflictStrategy::Report;
+                options.sync.check_only = false;
+                options.watch.watch = false;
+                options.decisions = decisions;
+                options.execute(global_options).await
+            }
+            CliCommand::File(mut options) => {
+                options.on_conflict = ConflictStrategy::Report;
+                options.sync.check_only = false;
+                options.idempotent = false;
+                options.watch.watch = false;
+                options.decisions = decisions;
+                options.execute(global_options).await
+            }
+            _ => bail!("Conflicts can only be resolved for `azsync dir`, `dotenv`, or `file`"),
+        }
+    }
+}
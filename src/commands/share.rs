@@ -0,0 +1,77 @@
+use anyhow::{Context as _, bail};
+use azure_identity::DefaultAzureCredential;
+use azure_storage_blob::BlobClient;
+use time::{Duration, OffsetDateTime};
+use tracing::info;
+
+use crate::{
+    cli::{GlobalOptions, ShareOptions},
+    commands::{Command, Status},
+    config::Config,
+    dotenv::DotenvFile,
+    storage::{blob_read_url, get_remote_blob, get_user_delegation_key},
+};
+
+/// Maximum time a user delegation key can be valid for.
+const MAX_EXPIRY: Duration = Duration::days(7);
+
+impl Command for ShareOptions {
+    async fn execute(self, global_options: &GlobalOptions) -> anyhow::Result<Status> {
+        let config = Config::load(global_options)?;
+
+        // Load dotenv file
+        let dotenv = if global_options.no_env_file {
+            None
+        } else {
+            DotenvFile::from_path_exists(&global_options.env_file, global_options.strict_parse)?
+        };
+
+        if self.expiry > MAX_EXPIRY {
+            bail!("Links can be valid for at most 7 days");
+        }
+        if self.azure_storage.connection_string.is_some()
+            || !self.azure_storage.sas_tokens.is_empty()
+        {
+            bail!(
+                "Links are signed with a user delegation key, which needs an Azure credential instead of a connection string or SAS token"
+            );
+        }
+
+        // Make sure the blob exists
+        let credential =
+            DefaultAzureCredential::new().context("Failed to get default Azure credential")?;
+        let (endpoint, container_name) = self.azure_storage.resolve(&config, dotenv.as_ref())?;
+        config.policy.check_storage(&endpoint, &container_name)?;
+        let client = BlobClient::new(
+            endpoint.as_str(),
+            container_name.clone(),
+            self.blob_name.clone(),
+            credential.clone(),
+            None,
+        )?;
+        info!("Using:");
+        info!("  Endpoint: {endpoint}");
+        info!("  Container: {container_name}");
+        if get_remote_blob(&client).await?.is_none() {
+            bail!("Blob not found: {}", self.blob_name);
+        }
+
+        // Sign a URL for it, allowing for some clock skew
+        let now = OffsetDateTime::now_utc();
+        let start = now - Duration::minutes(5);
+        let expiry = now + self.expiry;
+        let key = get_user_delegation_key(credential, &endpoint, start, expiry).await?;
+        let url = blob_read_url(
+            &key,
+            &endpoint,
+            &container_name,
+            &self.blob_name,
+            start,
+            expiry,
+        )?;
+        info!("Link expires at {expiry}");
+        println!("{url}");
+
+        Ok(Status::Success)
+    }
+}
@@ -0,0 +1,101 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    env::vars,
+    fs::{read_to_string, write},
+    io::{Write, stdout},
+};
+
+use anyhow::{Context, bail};
+use azure_identity::DefaultAzureCredential;
+use azure_security_keyvault_secrets::SecretClient;
+use tracing::info;
+
+use crate::{
+    cli::{ExecTemplateOptions, GlobalOptions, SyncMode},
+    commands::{
+        Command, Status,
+        dotenv::{MAX_CONCURRENT_REQUESTS, get_remote_vars},
+    },
+    config::Config,
+    dotenv::{DotenvFile, expand},
+};
+
+impl Command for ExecTemplateOptions {
+    async fn execute(self, global_options: &GlobalOptions) -> anyhow::Result<Status> {
+        let config = Config::load(global_options)?;
+        let template = read_to_string(&self.template)
+            .with_context(|| format!("Failed to read {}", self.template.display()))?;
+
+        // Values from the dotenv file override environment variables
+        let dotenv =
+            DotenvFile::from_path_exists(&global_options.env_file, global_options.strict_parse)?;
+        let mut parameters: HashMap<_, _> = vars().collect();
+        if let Some(dotenv) = &dotenv {
+            parameters.extend(dotenv.parameters.clone());
+        }
+
+        // Pull the values of the variables used by the template
+        if self.pull {
+            let (mut used, mut missing) = (BTreeSet::new(), BTreeSet::new());
+            let mut expanded = expand(template.chars(), &parameters);
+            expanded.on_expand(|name| {
+                used.insert(name.to_string());
+            });
+            expanded.on_missing(|name| {
+                missing.insert(name.to_string());
+            });
+            expanded.for_each(drop);
+            used.append(&mut missing);
+
+            let credential =
+                DefaultAzureCredential::new().context("Failed to get default Azure credential")?;
+            let key_vault_url = self.key_vault.resolve(
+                &config,
+                dotenv.as_ref().filter(|_| !global_options.no_env_file),
+            )?;
+            config.policy.check_key_vault(&key_vault_url)?;
+            info!("Using:");
+            info!("  Key Vault: {key_vault_url}");
+            let client = SecretClient::new(key_vault_url.as_str(), credential, None)
+                .context("Failed to create Key Vault secrets client")?;
+            let remote_vars = get_remote_vars(
+                &client,
+                SyncMode::PullAlways,
+                used.iter().map(String::as_str),
+                MAX_CONCURRENT_REQUESTS,
+            )
+            .await?;
+            parameters.extend(
+                remote_vars
+                    .values
+                    .into_iter()
+                    .map(|(name, remote)| (name, remote.value)),
+            );
+        }
+
+        // Render the template
+        let mut missing = BTreeSet::new();
+        let mut rendered = expand(template.chars(), &parameters);
+        rendered.on_missing(|name| {
+            missing.insert(name.to_string());
+        });
+        let rendered: String = rendered.collect();
+        if !missing.is_empty() && !self.allow_missing {
+            let missing = Vec::from_iter(missing).join(", ");
+            bail!("Variables used by the template are not defined: {missing}");
+        }
+
+        // Write the output
+        if let Some(output) = &self.output {
+            write(output, rendered)
+                .with_context(|| format!("Failed to write {}", output.display()))?;
+            info!("Rendered {}", output.display());
+        } else {
+            let mut stdout = stdout().lock();
+            stdout.write_all(rendered.as_bytes())?;
+            stdout.flush()?;
+        }
+
+        Ok(Status::Success)
+    }
+}
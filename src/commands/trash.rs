@@ -0,0 +1,59 @@
+use time::OffsetDateTime;
+use tracing::info;
+
+use crate::{
+    cli::{EmptyTrashOptions, GlobalOptions, TrashCommand, TrashOptions},
+    commands::{Command, Status},
+    format::Size,
+    sync::confirm,
+    trash::list_trash,
+};
+
+impl Command for TrashOptions {
+    async fn execute(self, global_options: &GlobalOptions) -> anyhow::Result<Status> {
+        match self.command {
+            TrashCommand::Empty(command) => command.execute(global_options).await,
+        }
+    }
+}
+
+impl Command for EmptyTrashOptions {
+    async fn execute(self, _global_options: &GlobalOptions) -> anyhow::Result<Status> {
+        // Find the runs old enough to delete. Runs whose time can't be told
+        // are only deleted when emptying everything
+        let now = OffsetDateTime::now_utc();
+        let runs: Vec<_> = list_trash()?
+            .into_iter()
+            .filter(|run| match self.older_than {
+                Some(min_age) => run.time.is_some_and(|time| now - time >= min_age),
+                None => true,
+            })
+            .collect();
+        if runs.is_empty() {
+            info!("Nothing to delete");
+            return Ok(Status::Success);
+        }
+
+        let files: u64 = runs.iter().map(|run| run.files).sum();
+        let bytes: u64 = runs.iter().map(|run| run.bytes).sum();
+        info!("Plan:");
+        for run in &runs {
+            info!(
+                "  {}: {} file(s), {}",
+                run.path.display(),
+                run.files,
+                Size(run.bytes)
+            );
+        }
+        info!("Deleting {files} file(s), {} in total", Size(bytes));
+
+        if !self.no_confirm {
+            confirm()?;
+        }
+        for run in &runs {
+            run.remove()?;
+        }
+
+        Ok(Status::Success)
+    }
+}
@@ -0,0 +1,75 @@
+use tracing::info;
+
+use crate::{
+    cli::{GlobalOptions, UsageOptions},
+    commands::{Command, Status},
+    config::Config,
+    dotenv::DotenvFile,
+    format::Size,
+    storage::list_blobs,
+    usage::UsageReport,
+};
+
+impl Command for UsageOptions {
+    async fn execute(self, global_options: &GlobalOptions) -> anyhow::Result<Status> {
+        let config = Config::load(global_options)?;
+
+        // Load dotenv file
+        let dotenv = if global_options.no_env_file {
+            None
+        } else {
+            DotenvFile::from_path_exists(&global_options.env_file, global_options.strict_parse)?
+        };
+
+        // Create client
+        let credential = self.azure_storage.credential(dotenv.as_ref())?;
+        let (endpoint, container_name) = self.azure_storage.resolve(&config, dotenv.as_ref())?;
+        config.policy.check_storage(&endpoint, &container_name)?;
+        let client = credential.container_client(&endpoint, container_name.clone())?;
+        info!("Using:");
+        info!("  Endpoint: {endpoint}");
+        info!("  Container: {container_name}");
+        if let Some(prefix) = &self.prefix {
+            info!("  Prefix: {prefix}");
+        }
+
+        // Add up the size of each blob
+        let prefix = self.prefix.as_deref().unwrap_or_default();
+        let blobs = list_blobs(&client, self.prefix.as_deref(), false).await?;
+        let mut report = UsageReport::new(self.group_by);
+        for blob in &blobs {
+            let name = blob.name.strip_prefix(prefix).unwrap_or(&blob.name);
+            report.add(name, blob.size.unwrap_or_default());
+        }
+
+        if self.json {
+            println!("{}", serde_json::to_string(&report)?);
+            return Ok(Status::Success);
+        }
+
+        // Print a table of the groups, largest first
+        let groups = report.largest();
+        let width = groups
+            .iter()
+            .map(|(name, _)| name.len())
+            .chain(["GROUP".len(), "TOTAL".len()])
+            .max()
+            .unwrap_or_default();
+        println!("{:<width$}  {:>8}  {:>10}", "GROUP", "BLOBS", "SIZE");
+        for (name, totals) in groups {
+            println!(
+                "{name:<width$}  {:>8}  {:>10}",
+                totals.count,
+                Size(totals.bytes).to_string()
+            );
+        }
+        println!(
+            "{:<width$}  {:>8}  {:>10}",
+            "TOTAL",
+            report.total.count,
+            Size(report.total.bytes).to_string()
+        );
+
+        Ok(Status::Success)
+    }
+}
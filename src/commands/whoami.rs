@@ -0,0 +1,90 @@
+use anyhow::bail;
+use url::Url;
+
+use crate::{
+    cli::{GlobalOptions, WhoamiOptions},
+    commands::{Command, Status},
+    config::Config,
+    dotenv::DotenvFile,
+    identity::{Claims, MANAGEMENT_SCOPE, ResourceManager, find_credential},
+};
+
+impl Command for WhoamiOptions {
+    async fn execute(self, global_options: &GlobalOptions) -> anyhow::Result<Status> {
+        let config = Config::load(global_options)?;
+
+        // Load dotenv file
+        let dotenv = if global_options.no_env_file {
+            None
+        } else {
+            DotenvFile::from_path_exists(&global_options.env_file, global_options.strict_parse)?
+        };
+
+        // Find the credential that would be used
+        let (credential_name, token) = find_credential(MANAGEMENT_SCOPE).await?;
+        let claims = Claims::from_token(token.token.secret())?;
+        println!("Credential: {credential_name}");
+        println!("User: {}", claims.user_name().unwrap_or("none"));
+        println!("Object ID: {}", claims.oid.as_deref().unwrap_or("unknown"));
+        println!("App ID: {}", claims.app_id().unwrap_or("unknown"));
+        println!("Tenant: {}", claims.tid.as_deref().unwrap_or("unknown"));
+        println!("Audience: {}", claims.aud.as_deref().unwrap_or("unknown"));
+        println!("Scopes: {}", claims.scopes().join(" "));
+
+        // List roles on the configured resources, if any
+        let Some(oid) = &claims.oid else {
+            return Ok(Status::Success);
+        };
+        let manager = ResourceManager::new(&token);
+        let resources = [
+            (
+                "Storage account",
+                "Microsoft.Storage/storageAccounts",
+                self.azure_storage
+                    .resolve(&config, dotenv.as_ref())
+                    .map(|(endpoint, _)| endpoint),
+            ),
+            (
+                "Key Vault",
+                "Microsoft.KeyVault/vaults",
+                self.key_vault.resolve(&config, dotenv.as_ref()),
+            ),
+        ];
+        for (label, resource_type, url) in resources {
+            let Ok(url) = url else {
+                println!("{label}: not configured");
+                continue;
+            };
+
+            match roles(&manager, resource_type, &url, oid).await {
+                Ok(roles) => println!("{label} ({url}): {roles}"),
+                Err(error) => println!("{label} ({url}): unknown roles ({error:#})"),
+            }
+        }
+
+        Ok(Status::Success)
+    }
+}
+
+/// Lists the roles assigned to a principal on the resource at a URL.
+async fn roles(
+    manager: &ResourceManager,
+    resource_type: &str,
+    url: &Url,
+    principal_id: &str,
+) -> anyhow::Result<String> {
+    // Resources are named after the first part of their host name
+    let Some(name) = url.host_str().and_then(|host| host.split('.').next()) else {
+        bail!("no host name");
+    };
+    let Some(resource_id) = manager.find_resource(resource_type, name).await? else {
+        return Ok("resource not found".to_string());
+    };
+
+    let roles = manager.role_names(&resource_id, principal_id).await?;
+    if roles.is_empty() {
+        return Ok("no roles".to_string());
+    }
+
+    Ok(Vec::from_iter(roles).join(", "))
+}
@@ -0,0 +1,691 @@
+use std::{
+    collections::HashMap,
+    env::vars,
+    fs::read_to_string,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{Context, bail};
+use serde::Deserialize;
+use tracing::{info, warn};
+use url::Url;
+
+use crate::{
+    audit::AuditConfig,
+    cli::{GlobalOptions, MaybeEnv, SyncMode},
+    dotenv::interpolate,
+    glob::Pattern,
+    manifest::MANIFEST_FILE,
+    secrets::SecretScan,
+    sync::SkipReason,
+    versions::VersionStore,
+};
+
+/// Options loaded from an azsync config file.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// The storage account's endpoint.
+    pub storage_account_url: Option<MaybeEnv<Url>>,
+
+    /// The name of the container in the storage account.
+    pub container_name: Option<MaybeEnv<String>>,
+
+    /// The URL to the Key Vault instance.
+    pub key_vault_url: Option<MaybeEnv<Url>>,
+
+    /// The URL to a replica of the Key Vault instance to pull from when it's
+    /// unavailable.
+    pub key_vault_replica_url: Option<MaybeEnv<Url>>,
+
+    /// How to synchronize values.
+    pub sync_mode: Option<SyncMode>,
+
+    /// How to synchronize values with particular commands, overriding
+    /// `sync_mode`.
+    pub sync_modes: CommandSyncModes,
+
+    /// The name of the remote blob for `azsync file`, or a pattern for it.
+    pub blob_name: Option<String>,
+
+    /// Blob name patterns for files matching certain patterns, like all
+    /// `*.sql` files.
+    ///
+    /// These take precedence over `blob_name`, but not over blob names passed
+    /// on the command line or set for individual files.
+    pub blob_names: Vec<BlobNameRule>,
+
+    /// Never modify anything in Azure.
+    pub read_only: bool,
+
+    /// Which resources azsync is allowed to synchronize with.
+    pub policy: Policy,
+
+    /// What to do when a file that's about to be pushed contains secrets.
+    pub secret_scan: SecretScan,
+
+    /// Decide which way to synchronize using version counters instead of
+    /// modified times where possible.
+    pub version_counters: bool,
+
+    /// Reasons for skipping resources that fail `--check-only`.
+    pub fail_on_skip: Vec<SkipReason>,
+
+    /// Where to record executed actions.
+    pub audit: AuditConfig,
+
+    /// Files to synchronize when no paths are passed to `azsync file`.
+    pub files: Vec<FileEntry>,
+
+    /// Named sets of options that can be selected with `--profile`.
+    pub profiles: HashMap<String, Profile>,
+}
+
+impl Config {
+    /// Loads the config file selected by the global options, if it exists.
+    ///
+    /// The selected profile is applied on top of the config file, and options
+    /// set on the command line are applied on top of that.
+    pub fn load(global_options: &GlobalOptions) -> anyhow::Result<Self> {
+        let mut config = Self::from_path_exists(&global_options.config)?.unwrap_or_default();
+        if let Some(name) = &global_options.profile {
+            config.apply_profile(name)?;
+        }
+        config.read_only |= global_options.read_only;
+        if global_options.override_policy {
+            warn!("Ignoring policy from config file");
+            config.policy = Policy::default();
+        }
+
+        Ok(config)
+    }
+
+    /// Gets the blob name (or pattern) for a file, given its path relative to
+    /// the current directory.
+    ///
+    /// The first rule in `blob_names` that matches the file is used, falling
+    /// back to `blob_name`.
+    pub fn blob_name_for(&self, path: &str) -> Option<&str> {
+        self.blob_names
+            .iter()
+            .find(|rule| rule.pattern.matches(path))
+            .map(|rule| rule.blob_name.as_str())
+            .or(self.blob_name.as_deref())
+    }
+
+    /// Gets the default sync mode for a command.
+    ///
+    /// The mode set for the command takes precedence over `sync_mode`.
+    pub fn sync_mode_for(&self, command: SyncCommand) -> Option<SyncMode> {
+        self.sync_modes.get(command).or(self.sync_mode)
+    }
+
+    /// Loads the versions that resources were last synchronized at, if
+    /// version counters are enabled.
+    pub fn version_store(&self) -> anyhow::Result<Option<Arc<VersionStore>>> {
+        if !self.version_counters {
+            return Ok(None);
+        }
+
+        VersionStore::load(Path::new(MANIFEST_FILE))
+            .map(Arc::new)
+            .map(Some)
+    }
+
+    /// Loads a config file from the given path (if it exists).
+    pub fn from_path_exists(path: &Path) -> anyhow::Result<Option<Self>> {
+        let source = match read_to_string(path) {
+            Ok(source) => source,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(error) => bail!(error),
+        };
+
+        Self::parse(&source, &vars().collect())
+            .with_context(|| format!("Failed to parse config file {}", path.display()))
+            .map(Some)
+    }
+
+    /// Parses a config file, expanding references to variables in its values.
+    fn parse(source: &str, variables: &HashMap<String, String>) -> anyhow::Result<Self> {
+        let mut value: toml::Value = toml::from_str(source)?;
+        interpolate_value(&mut value, variables);
+        Ok(value.try_into()?)
+    }
+
+    /// Overrides options with the ones set in a profile and the profiles it
+    /// inherits from.
+    fn apply_profile(&mut self, name: &str) -> anyhow::Result<()> {
+        // Find the profile and its bases, from the profile to the first base
+        let mut names = vec![name.to_string()];
+        let mut profiles = Vec::new();
+        loop {
+            let current = names.last().expect("names should not be empty");
+            let profile = self
+                .profiles
+                .remove(current)
+                .with_context(|| format!("Profile not found: {current}"))?;
+            let base = profile.inherits.clone();
+            profiles.push(profile);
+
+            let Some(base) = base else {
+                break;
+            };
+            if names.contains(&base) {
+                bail!("Profile {base} inherits from itself");
+            }
+            names.push(base);
+        }
+        info!("Using profile: {}", names.join(" <- "));
+
+        // Apply bases first so that each profile overrides the ones it inherits from
+        for profile in profiles.into_iter().rev() {
+            self.apply(profile);
+        }
+
+        Ok(())
+    }
+
+    /// Overrides options with the ones set in a single profile.
+    fn apply(&mut self, profile: Profile) {
+        if profile.storage_account_url.is_some() {
+            self.storage_account_url = profile.storage_account_url;
+        }
+        if profile.container_name.is_some() {
+            self.container_name = profile.container_name;
+        }
+        if profile.key_vault_url.is_some() {
+            self.key_vault_url = profile.key_vault_url;
+        }
+        if profile.key_vault_replica_url.is_some() {
+            self.key_vault_replica_url = profile.key_vault_replica_url;
+        }
+        if profile.sync_mode.is_some() {
+            self.sync_mode = profile.sync_mode;
+        }
+        self.sync_modes.apply(profile.sync_modes);
+        if profile.blob_name.is_some() {
+            self.blob_name = profile.blob_name;
+        }
+        if let Some(blob_names) = profile.blob_names {
+            self.blob_names = blob_names;
+        }
+        if let Some(read_only) = profile.read_only {
+            self.read_only = read_only;
+        }
+        if let Some(policy) = profile.policy {
+            self.policy = policy;
+        }
+        if let Some(secret_scan) = profile.secret_scan {
+            self.secret_scan = secret_scan;
+        }
+        if let Some(version_counters) = profile.version_counters {
+            self.version_counters = version_counters;
+        }
+        if let Some(fail_on_skip) = profile.fail_on_skip {
+            self.fail_on_skip = fail_on_skip;
+        }
+    }
+}
+
+/// Expands references to variables like `${ENVIRONMENT}` in every string in a
+/// config value.
+fn interpolate_value(value: &mut toml::Value, variables: &HashMap<String, String>) {
+    match value {
+        toml::Value::String(string) if string.contains('$') => {
+            *string = interpolate(string, variables);
+        }
+        toml::Value::Array(values) => {
+            for value in values {
+                interpolate_value(value, variables);
+            }
+        }
+        toml::Value::Table(table) => {
+            for (_, value) in table.iter_mut() {
+                interpolate_value(value, variables);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// A file to synchronize, declared in the config file.
+///
+/// Each file can be stored in a different storage account or container.
+/// Options that aren't set for the file are resolved the same way as for
+/// paths passed on the command line.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct FileEntry {
+    /// The path to the local file, relative to the current directory.
+    pub path: PathBuf,
+
+    /// The name of the remote blob, or a pattern for it.
+    pub blob_name: Option<String>,
+
+    /// The storage account's endpoint.
+    pub storage_account_url: Option<MaybeEnv<Url>>,
+
+    /// The name of the container in the storage account.
+    pub container_name: Option<MaybeEnv<String>>,
+}
+
+/// A blob name pattern for the files matching a pattern, declared in the
+/// config file.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct BlobNameRule {
+    /// The files this applies to, like `*.sql`.
+    pub pattern: Pattern,
+
+    /// The name of the remote blob, or a pattern for it.
+    pub blob_name: String,
+}
+
+/// Commands that can have their own default sync mode.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SyncCommand {
+    /// `azsync dotenv`.
+    Dotenv,
+
+    /// `azsync file` and `azsync dir`.
+    File,
+
+    /// `azsync keyvault`.
+    Keyvault,
+}
+
+/// The default sync modes of individual commands, declared in the config
+/// file.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CommandSyncModes {
+    /// The sync mode for `azsync dotenv`.
+    pub dotenv: Option<SyncMode>,
+
+    /// The sync mode for `azsync file` and `azsync dir`.
+    pub file: Option<SyncMode>,
+
+    /// The sync mode for `azsync keyvault`.
+    pub keyvault: Option<SyncMode>,
+}
+
+impl CommandSyncModes {
+    /// Gets the sync mode set for a command, if any.
+    pub fn get(self, command: SyncCommand) -> Option<SyncMode> {
+        match command {
+            SyncCommand::Dotenv => self.dotenv,
+            SyncCommand::File => self.file,
+            SyncCommand::Keyvault => self.keyvault,
+        }
+    }
+
+    /// Overrides the sync modes that are set in a profile, leaving the others
+    /// as they are.
+    fn apply(&mut self, profile: Self) {
+        self.dotenv = profile.dotenv.or(self.dotenv);
+        self.file = profile.file.or(self.file);
+        self.keyvault = profile.keyvault.or(self.keyvault);
+    }
+}
+
+/// A named set of options in the config file.
+///
+/// Options that aren't set in the profile are left as they are at the top
+/// level of the config file.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Profile {
+    /// Another profile to inherit options from.
+    ///
+    /// Options set in this profile override the ones in the inherited profile.
+    pub inherits: Option<String>,
+
+    /// The storage account's endpoint.
+    pub storage_account_url: Option<MaybeEnv<Url>>,
+
+    /// The name of the container in the storage account.
+    pub container_name: Option<MaybeEnv<String>>,
+
+    /// The URL to the Key Vault instance.
+    pub key_vault_url: Option<MaybeEnv<Url>>,
+
+    /// The URL to a replica of the Key Vault instance to pull from when it's
+    /// unavailable.
+    pub key_vault_replica_url: Option<MaybeEnv<Url>>,
+
+    /// How to synchronize values.
+    pub sync_mode: Option<SyncMode>,
+
+    /// How to synchronize values with particular commands.
+    ///
+    /// Only the commands set here are overridden.
+    pub sync_modes: CommandSyncModes,
+
+    /// The name of the remote blob for `azsync file`, or a pattern for it.
+    pub blob_name: Option<String>,
+
+    /// Blob name patterns for files matching certain patterns.
+    pub blob_names: Option<Vec<BlobNameRule>>,
+
+    /// Never modify anything in Azure.
+    pub read_only: Option<bool>,
+
+    /// Which resources azsync is allowed to synchronize with.
+    pub policy: Option<Policy>,
+
+    /// What to do when a file that's about to be pushed contains secrets.
+    pub secret_scan: Option<SecretScan>,
+
+    /// Decide which way to synchronize using version counters instead of
+    /// modified times where possible.
+    pub version_counters: Option<bool>,
+
+    /// Reasons for skipping resources that fail `--check-only`.
+    pub fail_on_skip: Option<Vec<SkipReason>>,
+}
+
+/// Which resources azsync is allowed to synchronize with.
+///
+/// Resources must match the allow rules (if any are given) and must not match
+/// any deny rules.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Policy {
+    /// Resources that are allowed.
+    pub allow: Option<PolicyRules>,
+
+    /// Resources that are denied.
+    pub deny: Option<PolicyRules>,
+}
+
+impl Policy {
+    /// Fails if the storage account or container is not permitted.
+    pub fn check_storage(&self, endpoint: &Url, container_name: &str) -> anyhow::Result<()> {
+        let allowed = |rules: &PolicyRules| {
+            rules
+                .storage_accounts
+                .as_ref()
+                .is_none_or(|accounts| accounts.iter().any(|url| same_host(url, endpoint)))
+                && rules
+                    .containers
+                    .as_ref()
+                    .is_none_or(|containers| containers.iter().any(|name| name == container_name))
+        };
+        let denied = |rules: &PolicyRules| {
+            rules
+                .storage_accounts
+                .iter()
+                .flatten()
+                .any(|url| same_host(url, endpoint))
+                || rules
+                    .containers
+                    .iter()
+                    .flatten()
+                    .any(|name| name == container_name)
+        };
+
+        if !self.allow.as_ref().is_none_or(allowed) || self.deny.as_ref().is_some_and(denied) {
+            bail!(
+                "Container {container_name} in {endpoint} is not permitted by policy \
+                (use --override-policy to ignore)"
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Fails if the Key Vault is not permitted.
+    pub fn check_key_vault(&self, key_vault_url: &Url) -> anyhow::Result<()> {
+        let matches = |rules: &PolicyRules| {
+            rules
+                .key_vaults
+                .as_ref()
+                .map(|vaults| vaults.iter().any(|url| same_host(url, key_vault_url)))
+        };
+
+        if self
+            .allow
+            .as_ref()
+            .and_then(matches)
+            .is_some_and(|allowed| !allowed)
+            || self.deny.as_ref().and_then(matches).unwrap_or_default()
+        {
+            bail!(
+                "Key Vault {key_vault_url} is not permitted by policy \
+                (use --override-policy to ignore)"
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Resources matched by a policy.
+///
+/// Omitted lists don't match or restrict anything.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct PolicyRules {
+    /// Storage account endpoints.
+    pub storage_accounts: Option<Vec<Url>>,
+
+    /// Container names.
+    pub containers: Option<Vec<String>>,
+
+    /// Key Vault URLs.
+    pub key_vaults: Option<Vec<Url>>,
+}
+
+/// Checks if two URLs refer to the same host.
+fn same_host(a: &Url, b: &Url) -> bool {
+    a.host_str()
+        .zip(b.host_str())
+        .is_some_and(|(a, b)| a.eq_ignore_ascii_case(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    const POLICY: &str = r#"
+        [policy.allow]
+        storage-accounts = ["https://dev.blob.core.windows.net/"]
+        key-vaults = ["https://dev-kv.vault.azure.net/"]
+
+        [policy.deny]
+        containers = ["prod"]
+    "#;
+
+    fn policy() -> Policy {
+        toml::from_str::<Config>(POLICY).unwrap().policy
+    }
+
+    #[test_case("https://dev.blob.core.windows.net/", "configs" => true; "allowed")]
+    #[test_case("https://DEV.blob.core.windows.net", "configs" => true; "allowed case-insensitive")]
+    #[test_case("https://other.blob.core.windows.net/", "configs" => false; "not allowed")]
+    #[test_case("https://dev.blob.core.windows.net/", "prod" => false; "denied")]
+    fn storage(endpoint: &str, container_name: &str) -> bool {
+        policy()
+            .check_storage(&Url::parse(endpoint).unwrap(), container_name)
+            .is_ok()
+    }
+
+    #[test_case("https://dev-kv.vault.azure.net/" => true; "allowed")]
+    #[test_case("https://prod-kv.vault.azure.net/" => false; "not allowed")]
+    fn key_vault(url: &str) -> bool {
+        policy().check_key_vault(&Url::parse(url).unwrap()).is_ok()
+    }
+
+    #[test]
+    fn empty_policy_allows_everything() {
+        let policy = Policy::default();
+        let url = Url::parse("https://any.blob.core.windows.net/").unwrap();
+        assert!(policy.check_storage(&url, "any").is_ok());
+        assert!(policy.check_key_vault(&url).is_ok());
+    }
+
+    const PROFILES: &str = r#"
+        container-name = "dev"
+        sync-mode = "sync"
+
+        [profiles.prod]
+        container-name = "env:PROD_CONTAINER"
+        sync-mode = "pull"
+        blob-name = "prod/#name#"
+        read-only = true
+        fail-on-skip = ["not-found", "conflict"]
+    "#;
+
+    #[test]
+    fn profile_overrides_options() {
+        let mut config: Config = toml::from_str(PROFILES).unwrap();
+        config.apply_profile("prod").unwrap();
+
+        assert_eq!(
+            config.container_name,
+            Some(MaybeEnv::EnvVar("PROD_CONTAINER".to_string()))
+        );
+        assert_eq!(config.sync_mode, Some(SyncMode::Pull));
+        assert_eq!(config.blob_name.as_deref(), Some("prod/#name#"));
+        assert!(config.read_only);
+        assert_eq!(
+            config.fail_on_skip,
+            [SkipReason::NotFound, SkipReason::Conflict]
+        );
+    }
+
+    const INHERITED_PROFILES: &str = r#"
+        [profiles.base]
+        container-name = "shared"
+        sync-mode = "pull"
+
+        [profiles.staging]
+        inherits = "base"
+        key-vault-url = "https://${ENVIRONMENT}-kv.vault.azure.net/"
+
+        [profiles.loop]
+        inherits = "loop2"
+
+        [profiles.loop2]
+        inherits = "loop"
+    "#;
+
+    #[test]
+    fn profile_inherits_options() {
+        let variables = [("ENVIRONMENT".to_string(), "staging".to_string())].into();
+        let mut config = Config::parse(INHERITED_PROFILES, &variables).unwrap();
+        config.apply_profile("staging").unwrap();
+
+        assert_eq!(
+            config.container_name,
+            Some(MaybeEnv::Value("shared".to_string()))
+        );
+        assert_eq!(config.sync_mode, Some(SyncMode::Pull));
+        assert_eq!(
+            config.key_vault_url,
+            Some(MaybeEnv::Value(
+                Url::parse("https://staging-kv.vault.azure.net/").unwrap()
+            ))
+        );
+    }
+
+    #[test_case(None, SyncCommand::Dotenv, Some(SyncMode::Pull); "command")]
+    #[test_case(None, SyncCommand::File, Some(SyncMode::Sync); "fallback")]
+    #[test_case(Some("prod"), SyncCommand::Dotenv, Some(SyncMode::Pull); "kept from top level")]
+    #[test_case(Some("prod"), SyncCommand::File, Some(SyncMode::Push); "from profile")]
+    #[test_case(Some("prod"), SyncCommand::Keyvault, Some(SyncMode::PullAlways); "profile fallback")]
+    fn command_sync_modes(profile: Option<&str>, command: SyncCommand, expected: Option<SyncMode>) {
+        let mut config: Config = toml::from_str(
+            r#"
+            sync-mode = "sync"
+
+            [sync-modes]
+            dotenv = "pull"
+
+            [profiles.prod]
+            sync-mode = "pull-always"
+            sync-modes.file = "push"
+            "#,
+        )
+        .unwrap();
+        if let Some(profile) = profile {
+            config.apply_profile(profile).unwrap();
+        }
+
+        assert_eq!(config.sync_mode_for(command), expected);
+    }
+
+    #[test]
+    fn profile_inheritance_cycle() {
+        let mut config = Config::parse(INHERITED_PROFILES, &HashMap::new()).unwrap();
+        assert!(config.apply_profile("loop").is_err());
+    }
+
+    #[test]
+    fn missing_profile() {
+        let mut config: Config = toml::from_str(PROFILES).unwrap();
+        assert!(config.apply_profile("staging").is_err());
+    }
+
+    #[test]
+    fn files() {
+        let config: Config = toml::from_str(
+            r##"
+            [[files]]
+            path = "app.json"
+
+            [[files]]
+            path = "certs/ca.pem"
+            blob-name = "#stem#.crt"
+            storage-account-url = "https://shared.blob.core.windows.net/"
+            container-name = "certs"
+            "##,
+        )
+        .unwrap();
+
+        assert_eq!(config.files.len(), 2);
+        assert_eq!(config.files[0].path, Path::new("app.json"));
+        assert!(config.files[0].storage_account_url.is_none());
+        assert_eq!(config.files[1].blob_name.as_deref(), Some("#stem#.crt"));
+        assert_eq!(
+            config.files[1].container_name,
+            Some(MaybeEnv::Value("certs".to_string()))
+        );
+    }
+
+    #[test_case("schema/users.sql", Some("db/#name#"); "first rule")]
+    #[test_case("app.json", Some("configs/#stem#/#name#"); "second rule")]
+    #[test_case("certs/app.json", Some("certs/#name#"); "path rule")]
+    #[test_case("README.md", Some("misc/#name#"); "default")]
+    fn blob_name_rules(path: &str, expected: Option<&str>) {
+        let config: Config = toml::from_str(
+            r#"
+            blob-name = "misc/#name#"
+
+            [[blob-names]]
+            pattern = "*.sql"
+            blob-name = "db/#name#"
+
+            [[blob-names]]
+            pattern = "certs/*"
+            blob-name = "certs/#name#"
+
+            [[blob-names]]
+            pattern = "*.json"
+            blob-name = "configs/#stem#/#name#"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.blob_name_for(path), expected);
+    }
+
+    #[test]
+    fn unknown_fields() {
+        assert!(toml::from_str::<Config>("[policy.allow]\nvaults = []").is_err());
+    }
+}
@@ -0,0 +1,375 @@
+use std::{
+    collections::BTreeMap,
+    env::args,
+    fs::{read_to_string, write},
+    path::Path,
+};
+
+use anyhow::{Context, bail};
+use serde::{Deserialize, Serialize};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use tracing::warn;
+
+use crate::glob::Pattern;
+
+/// Where conflicts are reported by default.
+pub const CONFLICTS_FILE: &str = "azsync-conflicts.json";
+
+/// Conflicts that were left unresolved by a run, and the command that found
+/// them.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ConflictReport {
+    /// The arguments of the command that found the conflicts, so they can be
+    /// resolved by running it again.
+    pub command: Vec<String>,
+
+    /// The conflicts that were found.
+    pub conflicts: Vec<ReportedConflict>,
+}
+
+impl ConflictReport {
+    /// Creates a report of conflicts found by the current command.
+    pub fn new(mut conflicts: Vec<ReportedConflict>) -> Self {
+        conflicts.sort_unstable_by(|a, b| a.remote.cmp(&b.remote));
+        Self {
+            command: args().collect(),
+            conflicts,
+        }
+    }
+
+    /// Loads a report from a file.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let source = read_to_string(path)
+            .with_context(|| format!("Failed to read conflict report {}", path.display()))?;
+        serde_json::from_str(&source)
+            .with_context(|| format!("Failed to parse conflict report {}", path.display()))
+    }
+
+    /// Writes the report to a file, replacing any earlier report.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        write(path, json).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// A value that changed both locally and remotely.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ReportedConflict {
+    /// The name of the blob or variable.
+    pub name: String,
+
+    /// The URL of the remote copy.
+    pub remote: String,
+
+    /// The local copy.
+    pub local_side: ConflictSide,
+
+    /// The remote copy.
+    pub remote_side: ConflictSide,
+
+    /// The resolutions allowed by the sync mode and directives.
+    pub allowed: Vec<Resolution>,
+
+    /// Which copy to keep, based on which was modified most recently.
+    pub suggested: Resolution,
+
+    /// Which copy to keep, once decided.
+    ///
+    /// Conflicts are reported without one. Set it to record a decision for
+    /// `azsync resolve`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<Resolution>,
+}
+
+impl ReportedConflict {
+    /// Creates a reported conflict, suggesting how to resolve it.
+    pub fn new(
+        name: String,
+        remote: String,
+        local_side: ConflictSide,
+        remote_side: ConflictSide,
+        can_push: bool,
+        can_pull: bool,
+    ) -> anyhow::Result<Self> {
+        let mut allowed = Vec::with_capacity(3);
+        if can_push {
+            allowed.push(Resolution::KeepLocal);
+        }
+        if can_pull {
+            allowed.push(Resolution::KeepRemote);
+        }
+        allowed.push(Resolution::Skip);
+        let suggested = suggest(
+            local_side.modified()?,
+            remote_side.modified()?,
+            can_push,
+            can_pull,
+        );
+
+        Ok(Self {
+            name,
+            remote,
+            local_side,
+            remote_side,
+            allowed,
+            suggested,
+            resolution: None,
+        })
+    }
+
+    /// Checks whether neither copy changed since the other conflict was
+    /// reported.
+    fn is_same_as(&self, other: &Self) -> bool {
+        self.local_side.hash == other.local_side.hash
+            && self.remote_side.hash == other.remote_side.hash
+            && self.remote_side.etag == other.remote_side.etag
+    }
+}
+
+/// Decisions on how to resolve reported conflicts, by the URL of each remote
+/// copy.
+///
+/// Conflicts that were decided to be skipped aren't included.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Decisions(BTreeMap<String, ReportedConflict>);
+
+impl Decisions {
+    /// Decides how to resolve each conflict in a report.
+    ///
+    /// Conflicts whose names match a pattern are resolved that way, and the
+    /// rest are resolved the way recorded in the report, if any.
+    pub fn decide(
+        report: &ConflictReport,
+        accept_local: &[Pattern],
+        accept_remote: &[Pattern],
+    ) -> anyhow::Result<Self> {
+        let mut decisions = BTreeMap::new();
+        for conflict in &report.conflicts {
+            let name = &conflict.name;
+            let local = accept_local.iter().any(|pattern| pattern.matches(name));
+            let remote = accept_remote.iter().any(|pattern| pattern.matches(name));
+            let resolution = match (local, remote) {
+                (true, true) => bail!("{name} matches both --accept-local and --accept-remote"),
+                (true, false) => Resolution::KeepLocal,
+                (false, true) => Resolution::KeepRemote,
+                (false, false) => match conflict.resolution {
+                    Some(resolution) => resolution,
+                    None => continue,
+                },
+            };
+            if !conflict.allowed.contains(&resolution) {
+                bail!(
+                    "Can't {} for {name}: the sync mode doesn't allow it",
+                    resolution.as_str()
+                );
+            }
+            if resolution != Resolution::Skip {
+                let conflict = ReportedConflict {
+                    resolution: Some(resolution),
+                    ..conflict.clone()
+                };
+                decisions.insert(conflict.remote.clone(), conflict);
+            }
+        }
+
+        Ok(Self(decisions))
+    }
+
+    /// Checks whether there are no decisions.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Gets how many conflicts were decided.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Checks whether the conflict with a remote copy was decided.
+    pub fn contains(&self, remote: &str) -> bool {
+        self.0.contains_key(remote)
+    }
+
+    /// Gets how to resolve a conflict as it is now.
+    ///
+    /// If either copy changed since the conflict was reported, it's skipped
+    /// rather than resolved using outdated information.
+    pub fn resolve(&self, current: &ReportedConflict) -> Resolution {
+        let Some(decided) = self.0.get(&current.remote) else {
+            return Resolution::Skip;
+        };
+        if !decided.is_same_as(current) {
+            warn!(
+                "{} changed since its conflict was reported, skipping it",
+                current.name
+            );
+            return Resolution::Skip;
+        }
+
+        decided.resolution.unwrap_or(Resolution::Skip)
+    }
+}
+
+/// The state of one copy of a value in conflict.
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ConflictSide {
+    /// Where the copy is stored, if it's a local file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+
+    /// When the copy was last modified, in RFC 3339 format.
+    pub modified: Option<String>,
+
+    /// A SHA-256 hash of the copy's contents, if known.
+    pub hash: Option<String>,
+
+    /// The copy's size in bytes, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+
+    /// The `ETag` of the remote copy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+}
+
+impl ConflictSide {
+    /// Creates a side with the given modified time and hash.
+    pub fn new(modified: Option<OffsetDateTime>, hash: Option<String>) -> anyhow::Result<Self> {
+        Ok(Self {
+            modified: modified.map(|time| time.format(&Rfc3339)).transpose()?,
+            hash,
+            ..Default::default()
+        })
+    }
+
+    /// Gets when the copy was last modified.
+    pub fn modified(&self) -> anyhow::Result<Option<OffsetDateTime>> {
+        self.modified
+            .as_deref()
+            .map(|time| OffsetDateTime::parse(time, &Rfc3339))
+            .transpose()
+            .context("Invalid modified time in conflict report")
+    }
+}
+
+/// How to resolve a conflict.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Resolution {
+    /// Push the local copy.
+    KeepLocal,
+
+    /// Pull the remote copy.
+    KeepRemote,
+
+    /// Leave both copies alone.
+    Skip,
+}
+
+impl Resolution {
+    /// Describes the resolution in a few words.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::KeepLocal => "keep local",
+            Self::KeepRemote => "keep remote",
+            Self::Skip => "skip",
+        }
+    }
+}
+
+/// Suggests keeping whichever copy was modified most recently, if the sync
+/// mode allows it.
+fn suggest(
+    local: Option<OffsetDateTime>,
+    remote: Option<OffsetDateTime>,
+    can_push: bool,
+    can_pull: bool,
+) -> Resolution {
+    match (local, remote) {
+        (Some(local), Some(remote)) if local > remote && can_push => Resolution::KeepLocal,
+        (Some(local), Some(remote)) if local < remote && can_pull => Resolution::KeepRemote,
+        _ => Resolution::Skip,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+    use time::macros::datetime;
+
+    use super::*;
+
+    const OLD: OffsetDateTime = datetime!(2024-01-01 00:00 UTC);
+    const NEW: OffsetDateTime = datetime!(2025-01-01 00:00 UTC);
+
+    /// Creates a report of conflicts with the given names and recorded
+    /// resolutions, which can only be pushed.
+    fn report(conflicts: &[(&str, Option<Resolution>)]) -> ConflictReport {
+        let conflicts = conflicts
+            .iter()
+            .map(|&(name, resolution)| ReportedConflict {
+                resolution,
+                ..ReportedConflict::new(
+                    name.to_string(),
+                    format!("https://example/{name}"),
+                    ConflictSide::default(),
+                    ConflictSide::default(),
+                    true,
+                    false,
+                )
+                .unwrap()
+            })
+            .collect();
+        ConflictReport {
+            command: Vec::new(),
+            conflicts,
+        }
+    }
+
+    #[test_case(&[("a", None)], &[], &[] => Ok(vec![]); "undecided")]
+    #[test_case(&[("a", Some(Resolution::KeepLocal))], &[], &[] => Ok(vec!["a"]); "recorded")]
+    #[test_case(&[("a", Some(Resolution::Skip))], &[], &[] => Ok(vec![]); "recorded skip")]
+    #[test_case(&[("a.json", None), ("b.txt", None)], &["*.json"], &[] => Ok(vec!["a.json"]); "accept local")]
+    #[test_case(&[("a", None)], &["a"], &["*"] => Err(()); "both patterns")]
+    #[test_case(&[("a", None)], &[], &["a"] => Err(()); "not allowed")]
+    fn decide(
+        conflicts: &[(&str, Option<Resolution>)],
+        accept_local: &[&str],
+        accept_remote: &[&str],
+    ) -> Result<Vec<&'static str>, ()> {
+        let parse = |patterns: &[&str]| -> Vec<Pattern> {
+            patterns
+                .iter()
+                .map(|pattern| pattern.parse().unwrap())
+                .collect()
+        };
+        let decisions = Decisions::decide(
+            &report(conflicts),
+            &parse(accept_local),
+            &parse(accept_remote),
+        )
+        .map_err(drop)?;
+        Ok(["a", "a.json", "b.txt"]
+            .into_iter()
+            .filter(|name| decisions.contains(&format!("https://example/{name}")))
+            .collect())
+    }
+
+    #[test_case(Some(NEW), Some(OLD), true, true => Resolution::KeepLocal; "local newer")]
+    #[test_case(Some(OLD), Some(NEW), true, true => Resolution::KeepRemote; "remote newer")]
+    #[test_case(Some(NEW), Some(NEW), true, true => Resolution::Skip; "same time")]
+    #[test_case(Some(NEW), Some(OLD), false, true => Resolution::Skip; "can't push")]
+    #[test_case(Some(OLD), Some(NEW), true, false => Resolution::Skip; "can't pull")]
+    #[test_case(None, Some(NEW), true, true => Resolution::Skip; "unknown time")]
+    fn suggestion(
+        local: Option<OffsetDateTime>,
+        remote: Option<OffsetDateTime>,
+        can_push: bool,
+        can_pull: bool,
+    ) -> Resolution {
+        suggest(local, remote, can_push, can_pull)
+    }
+}
@@ -1,6 +1,21 @@
+mod directive;
+mod encoding;
 mod expand;
+mod export;
 mod file;
+mod lint;
+mod outputs;
 mod parse;
+mod section;
+mod trailer;
 mod unescape;
 
+pub use directive::*;
+pub use encoding::Encoding;
+pub use expand::{expand, interpolate, interpolate_defined};
+pub use export::render_export;
 pub use file::*;
+pub use lint::*;
+pub use outputs::*;
+pub use parse::ParseIssue;
+pub use trailer::Trailer;
@@ -0,0 +1,167 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Context, bail};
+
+/// Per-variable behavior set by `# azsync: ...` comments in a dotenv file.
+///
+/// Directives on their own line apply to the next variable defined in the
+/// file. Directives at the end of a variable definition apply to that
+/// variable. Multiple directives can be separated by commas.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "Each flag is an independent switch"
+)]
+pub struct Directives {
+    /// Never synchronize the variable.
+    pub skip: bool,
+
+    /// Only list the variable in the template for developers to fill in, and
+    /// never synchronize it.
+    pub template_only: bool,
+
+    /// Only push the variable, never pull it.
+    pub push_only: bool,
+
+    /// Only pull the variable, never push it.
+    pub pull_only: bool,
+
+    /// The content type to store the variable with.
+    pub content_type: Option<String>,
+
+    /// Tags to store the variable with, in addition to its existing ones.
+    pub tags: BTreeMap<String, String>,
+
+    /// The name of the Key Vault to store the variable in, as passed to
+    /// `--vault`.
+    pub vault: Option<String>,
+}
+
+impl Directives {
+    /// Parses the body of a directive comment (after `azsync:`) into this set
+    /// of directives.
+    pub(super) fn parse(&mut self, body: &str) -> anyhow::Result<()> {
+        for directive in body.split(',').map(str::trim) {
+            match directive.split_once('=') {
+                None if directive == "skip" => self.skip = true,
+                None if directive == "template-only" => self.template_only = true,
+                None if directive == "push-only" => self.push_only = true,
+                None if directive == "pull-only" => self.pull_only = true,
+                Some((key, value)) if key.trim() == "content-type" => {
+                    self.content_type = Some(value.trim().to_string());
+                }
+                Some((key, value)) if key.trim() == "vault" && !value.trim().is_empty() => {
+                    self.vault = Some(value.trim().to_string());
+                }
+                Some((key, value)) if key.trim() == "tag" => {
+                    let (tag, value) = value
+                        .split_once('=')
+                        .filter(|(tag, _)| !tag.trim().is_empty())
+                        .with_context(|| format!("Expected tag=KEY=VALUE: {directive:?}"))?;
+                    self.tags
+                        .insert(tag.trim().to_string(), value.trim().to_string());
+                }
+                _ => bail!("Unknown azsync directive: {directive:?}"),
+            }
+        }
+
+        if self.push_only && self.pull_only {
+            bail!("A variable can't be both push-only and pull-only");
+        }
+
+        Ok(())
+    }
+
+    /// Adds the directives from another set to this one.
+    pub(super) fn merge(&mut self, other: Self) {
+        self.skip |= other.skip;
+        self.template_only |= other.template_only;
+        self.push_only |= other.push_only;
+        self.pull_only |= other.pull_only;
+        if other.content_type.is_some() {
+            self.content_type = other.content_type;
+        }
+        if other.vault.is_some() {
+            self.vault = other.vault;
+        }
+        self.tags.extend(other.tags);
+    }
+
+    /// Checks if the variable is never synchronized.
+    pub fn never_synced(&self) -> bool {
+        self.skip || self.template_only
+    }
+
+    /// Checks if no directives are set.
+    pub(super) fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("skip" => Directives { skip: true, ..Default::default() }; "skip")]
+    #[test_case("template-only" => Directives { template_only: true, ..Default::default() }; "template only")]
+    #[test_case("push-only" => Directives { push_only: true, ..Default::default() }; "push only")]
+    #[test_case(
+        "pull-only, content-type = application/json"
+        => Directives {
+            pull_only: true,
+            content_type: Some("application/json".to_string()),
+            ..Default::default()
+        };
+        "multiple"
+    )]
+    #[test_case("vault=shared" => Directives { vault: Some("shared".to_string()), ..Default::default() }; "vault")]
+    #[test_case(
+        "tag=project=foo, tag = owner = ops"
+        => Directives {
+            tags: BTreeMap::from([
+                ("owner".to_string(), "ops".to_string()),
+                ("project".to_string(), "foo".to_string()),
+            ]),
+            ..Default::default()
+        };
+        "tags"
+    )]
+    fn parse(body: &str) -> Directives {
+        let mut directives = Directives::default();
+        directives.parse(body).unwrap();
+        directives
+    }
+
+    #[test_case("sikp"; "unknown")]
+    #[test_case("push-only, pull-only"; "conflicting")]
+    #[test_case("vault="; "empty vault")]
+    #[test_case("tag=project"; "tag without value")]
+    #[test_case("tag==foo"; "empty tag")]
+    fn invalid(body: &str) {
+        assert!(Directives::default().parse(body).is_err());
+    }
+
+    #[test]
+    fn merge() {
+        let mut directives = Directives {
+            skip: true,
+            content_type: Some("text/plain".to_string()),
+            ..Default::default()
+        };
+        directives.merge(Directives {
+            content_type: Some("application/json".to_string()),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            directives,
+            Directives {
+                skip: true,
+                content_type: Some("application/json".to_string()),
+                ..Default::default()
+            }
+        );
+    }
+}
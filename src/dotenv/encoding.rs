@@ -0,0 +1,109 @@
+use anyhow::{Context, ensure};
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16_LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16_BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+/// The encoding of a dotenv file, detected from its byte order mark.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Encoding {
+    /// UTF-8 without a byte order mark.
+    #[default]
+    Utf8,
+
+    /// UTF-8 with a byte order mark.
+    Utf8Bom,
+
+    /// Little-endian UTF-16 with a byte order mark.
+    Utf16Le,
+
+    /// Big-endian UTF-16 with a byte order mark.
+    Utf16Be,
+}
+
+impl Encoding {
+    /// Detects the encoding of the given bytes and decodes them.
+    ///
+    /// Files without a byte order mark are assumed to be UTF-8.
+    pub fn decode(bytes: &[u8]) -> anyhow::Result<(Self, String)> {
+        if let Some(bytes) = bytes.strip_prefix(UTF8_BOM) {
+            let source = String::from_utf8(bytes.to_vec()).context("Invalid UTF-8")?;
+            Ok((Self::Utf8Bom, source))
+        } else if let Some(bytes) = bytes.strip_prefix(UTF16_LE_BOM) {
+            Ok((Self::Utf16Le, decode_utf16(bytes, u16::from_le_bytes)?))
+        } else if let Some(bytes) = bytes.strip_prefix(UTF16_BE_BOM) {
+            Ok((Self::Utf16Be, decode_utf16(bytes, u16::from_be_bytes)?))
+        } else {
+            let source = String::from_utf8(bytes.to_vec()).context("Invalid UTF-8")?;
+            Ok((Self::Utf8, source))
+        }
+    }
+
+    /// Encodes a string, including the byte order mark if there is one.
+    pub fn encode(self, source: &str) -> Vec<u8> {
+        match self {
+            Self::Utf8 => source.as_bytes().to_vec(),
+            Self::Utf8Bom => [UTF8_BOM, source.as_bytes()].concat(),
+            Self::Utf16Le => UTF16_LE_BOM
+                .iter()
+                .copied()
+                .chain(source.encode_utf16().flat_map(u16::to_le_bytes))
+                .collect(),
+            Self::Utf16Be => UTF16_BE_BOM
+                .iter()
+                .copied()
+                .chain(source.encode_utf16().flat_map(u16::to_be_bytes))
+                .collect(),
+        }
+    }
+}
+
+/// Decodes UTF-16 with the given byte order.
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> anyhow::Result<String> {
+    ensure!(
+        bytes.len().is_multiple_of(2),
+        "Invalid UTF-16 (odd number of bytes)"
+    );
+    let units = bytes
+        .chunks_exact(2)
+        .map(|pair| from_bytes([pair[0], pair[1]]));
+    char::decode_utf16(units)
+        .collect::<Result<_, _>>()
+        .context("Invalid UTF-16")
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(Encoding::Utf8; "utf-8")]
+    #[test_case(Encoding::Utf8Bom; "utf-8 with bom")]
+    #[test_case(Encoding::Utf16Le; "utf-16 le")]
+    #[test_case(Encoding::Utf16Be; "utf-16 be")]
+    fn round_trip(encoding: Encoding) {
+        let source = "A=1\r\nB='ünïcödé 🦀'\r\n";
+
+        let (decoded_encoding, decoded) = Encoding::decode(&encoding.encode(source)).unwrap();
+
+        assert_eq!(decoded_encoding, encoding);
+        assert_eq!(decoded, source);
+    }
+
+    #[test]
+    fn detects_utf16() {
+        let (encoding, source) =
+            Encoding::decode(&[0xFF, 0xFE, b'A', 0, b'=', 0, b'1', 0]).unwrap();
+
+        assert_eq!(encoding, Encoding::Utf16Le);
+        assert_eq!(source, "A=1");
+    }
+
+    #[test_case(&[0xFF, 0xFE, b'A']; "odd length")]
+    #[test_case(&[0xFF, 0xFE, 0x00, 0xD8]; "unpaired surrogate")]
+    #[test_case(&[0xC3]; "invalid utf-8")]
+    fn invalid(bytes: &[u8]) {
+        assert!(Encoding::decode(bytes).is_err());
+    }
+}
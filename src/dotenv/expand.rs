@@ -1,8 +1,12 @@
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{BTreeSet, HashMap, VecDeque},
     iter::Peekable,
 };
 
+use anyhow::bail;
+
+use crate::dotenv::unescape::unescape;
+
 /// Performs bash-style parameter expansion on a string.
 pub fn expand<Chars>(
     chars: Chars,
@@ -16,9 +20,36 @@ where
         parameters,
         state: State::default(),
         on_expand: None,
+        on_missing: None,
     }
 }
 
+/// Expands parameters in a value and removes escapes, the same way as values
+/// in a dotenv file.
+pub fn interpolate(value: &str, parameters: &HashMap<String, String>) -> String {
+    unescape(expand(value.chars(), parameters)).collect()
+}
+
+/// Interpolates a value like [`interpolate`], but fails if it references any
+/// parameters that aren't defined.
+pub fn interpolate_defined(
+    value: &str,
+    parameters: &HashMap<String, String>,
+) -> anyhow::Result<String> {
+    let mut missing = BTreeSet::new();
+    let mut expanded = expand(value.chars(), parameters);
+    expanded.on_missing(|name| {
+        missing.insert(name.to_string());
+    });
+    let interpolated = unescape(expanded).collect();
+    if !missing.is_empty() {
+        let missing = Vec::from_iter(missing).join(", ");
+        bail!("Variables used by {value:?} are not defined: {missing}");
+    }
+
+    Ok(interpolated)
+}
+
 /// Bash-style parameter expansion.
 pub struct Expand<'i, Chars>
 where
@@ -28,6 +59,7 @@ where
     parameters: &'i HashMap<String, String>,
     state: State,
     on_expand: Option<Box<dyn for<'s> FnMut(&'s str) + 'i>>,
+    on_missing: Option<Box<dyn for<'s> FnMut(&'s str) + 'i>>,
 }
 
 impl<'i, Chars> Expand<'i, Chars>
@@ -38,6 +70,12 @@ where
     pub fn on_expand(&mut self, f: impl for<'s> FnMut(&'s str) + 'i) {
         self.on_expand = Some(Box::new(f));
     }
+
+    /// Call the provided function whenever a name that isn't defined is
+    /// expanded.
+    pub fn on_missing(&mut self, f: impl for<'s> FnMut(&'s str) + 'i) {
+        self.on_missing = Some(Box::new(f));
+    }
 }
 
 impl<Chars> Iterator for Expand<'_, Chars>
@@ -132,6 +170,9 @@ where
 
                         // Parameter not defined (or valid) - skip it
                         // Note: we can't return errors for invalid parameter names like bash can
+                        if !*invalid && let Some(on_missing) = &mut self.on_missing {
+                            on_missing(name);
+                        }
                         self.state = State::NotExpanding;
                         continue;
                     }
@@ -176,6 +217,9 @@ where
                     }
 
                     // Parameter not defined - skip it
+                    if let Some(on_missing) = &mut self.on_missing {
+                        on_missing(name);
+                    }
                     self.state = State::NotExpanding;
                     continue;
                 }
@@ -264,4 +308,25 @@ mod tests {
 
         expand(s.chars(), &parameters).collect()
     }
+
+    #[test]
+    fn missing() {
+        let parameters = [("abc".to_string(), "a".to_string())].into();
+        let mut missing = Vec::new();
+        let mut expanded = expand("$abc ${def} $ghi ${a-b}".chars(), &parameters);
+        expanded.on_missing(|name| missing.push(name.to_string()));
+
+        assert_eq!(expanded.collect::<String>(), "a   ");
+        assert_eq!(missing, ["def", "ghi"]);
+    }
+
+    #[test]
+    fn interpolate_missing() {
+        let parameters = [("abc".to_string(), "a".to_string())].into();
+        assert_eq!(
+            interpolate_defined("$abc/${abc}", &parameters).unwrap(),
+            "a/a"
+        );
+        assert!(interpolate_defined("$abc/${def}", &parameters).is_err());
+    }
 }
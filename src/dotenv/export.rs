@@ -0,0 +1,152 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+
+use crate::cli::ExportFormat;
+
+/// Renders variables in a format other tools can read.
+///
+/// `secret_name` is the name of the Kubernetes Secret, and is only used by
+/// that format.
+pub fn render_export(
+    vars: &BTreeMap<String, String>,
+    format: ExportFormat,
+    secret_name: &str,
+) -> anyhow::Result<String> {
+    let mut output = String::new();
+    match format {
+        ExportFormat::Json => {
+            output = serde_json::to_string_pretty(vars)?;
+            output.push('\n');
+        }
+        ExportFormat::Yaml => {
+            if vars.is_empty() {
+                output.push_str("{}\n");
+            }
+            for (name, value) in vars {
+                writeln!(output, "{}: {}", yaml_string(name)?, yaml_string(value)?)?;
+            }
+        }
+        ExportFormat::K8sSecret => {
+            output.push_str("apiVersion: v1\n");
+            output.push_str("kind: Secret\n");
+            output.push_str("metadata:\n");
+            writeln!(output, "  name: {}", yaml_string(secret_name)?)?;
+            output.push_str("type: Opaque\n");
+            if vars.is_empty() {
+                output.push_str("data: {}\n");
+            } else {
+                output.push_str("data:\n");
+            }
+            for (name, value) in vars {
+                writeln!(
+                    output,
+                    "  {}: {}",
+                    yaml_string(name)?,
+                    BASE64_STANDARD.encode(value)
+                )?;
+            }
+        }
+        ExportFormat::Bash => {
+            for (name, value) in vars {
+                writeln!(output, "export {name}='{}'", value.replace('\'', r"'\''"))?;
+            }
+        }
+        ExportFormat::Powershell => {
+            for (name, value) in vars {
+                writeln!(output, "$env:{name} = '{}'", powershell_string(value))?;
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+/// Escapes a string for a single-quoted PowerShell string.
+///
+/// PowerShell ends single-quoted strings at typographic single quotes too, so
+/// each kind of quote is doubled.
+fn powershell_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}') {
+            escaped.push(c);
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Quotes a string for YAML.
+///
+/// JSON strings are valid double-quoted YAML scalars, so they're used to
+/// avoid YAML's rules for plain scalars, like `yes` being a boolean.
+fn yaml_string(s: &str) -> anyhow::Result<String> {
+    Ok(serde_json::to_string(s)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use test_case::test_case;
+
+    use super::*;
+
+    fn vars() -> BTreeMap<String, String> {
+        BTreeMap::from([
+            ("DB_URL".to_string(), "postgres://db".to_string()),
+            ("GREETING".to_string(), "it's \"here\"\nyes".to_string()),
+        ])
+    }
+
+    #[test_case(
+        ExportFormat::Json,
+        "{\n  \"DB_URL\": \"postgres://db\",\n  \"GREETING\": \"it's \\\"here\\\"\\nyes\"\n}\n";
+        "json"
+    )]
+    #[test_case(
+        ExportFormat::Yaml,
+        "\"DB_URL\": \"postgres://db\"\n\"GREETING\": \"it's \\\"here\\\"\\nyes\"\n";
+        "yaml"
+    )]
+    #[test_case(
+        ExportFormat::K8sSecret,
+        concat!(
+            "apiVersion: v1\nkind: Secret\nmetadata:\n  name: \"app\"\ntype: Opaque\ndata:\n",
+            "  \"DB_URL\": cG9zdGdyZXM6Ly9kYg==\n",
+            "  \"GREETING\": aXQncyAiaGVyZSIKeWVz\n",
+        );
+        "k8s secret"
+    )]
+    #[test_case(
+        ExportFormat::Bash,
+        "export DB_URL='postgres://db'\nexport GREETING='it'\\''s \"here\"\nyes'\n";
+        "bash"
+    )]
+    #[test_case(
+        ExportFormat::Powershell,
+        "$env:DB_URL = 'postgres://db'\n$env:GREETING = 'it''s \"here\"\nyes'\n";
+        "powershell"
+    )]
+    fn render(format: ExportFormat, expected: &str) {
+        assert_eq!(render_export(&vars(), format, "app").unwrap(), expected);
+    }
+
+    #[test_case("it's", "it''s"; "apostrophe")]
+    #[test_case("\u{2018}a\u{2019}", "\u{2018}\u{2018}a\u{2019}\u{2019}"; "curly quotes")]
+    #[test_case("\u{201A}a\u{201B}", "\u{201A}\u{201A}a\u{201B}\u{201B}"; "low and reversed quotes")]
+    #[test_case("\"a\"", "\"a\""; "double quotes")]
+    fn powershell_quotes(value: &str, expected: &str) {
+        assert_eq!(powershell_string(value), expected);
+    }
+
+    #[test_case(ExportFormat::Json, "{}\n"; "json")]
+    #[test_case(ExportFormat::Yaml, "{}\n"; "yaml")]
+    #[test_case(ExportFormat::Bash, ""; "bash")]
+    fn empty(format: ExportFormat, expected: &str) {
+        assert_eq!(
+            render_export(&BTreeMap::new(), format, "app").unwrap(),
+            expected
+        );
+    }
+}
@@ -9,7 +9,11 @@ use std::{
     path::Path,
 };
 
+use anyhow::{Context, bail};
 use time::OffsetDateTime;
+use tracing::warn;
+
+use crate::dotenv::{Directives, Encoding, ParseIssue, parse::line_number};
 
 /// A loaded dotenv file.
 #[derive(Clone, Debug, Default)]
@@ -23,19 +27,72 @@ pub struct DotenvFile {
     /// The source locations for the values of variables defined in this file.
     pub(super) value_spans: HashMap<String, Range<usize>>,
 
+    /// How the values of variables defined in this file are quoted.
+    pub(super) value_quotes: HashMap<String, Quote>,
+
+    /// The source locations of each definition of the variables in this file.
+    pub(super) definition_spans: HashMap<String, Vec<Range<usize>>>,
+
     /// Parameters that are expanded after being defined.
     ///
     /// These parameters cannot be replaced in-place because doing so would
     /// affect other parameters defined later in the file.
     pub(super) referenced: HashSet<String>,
 
+    /// Problems found while parsing this file.
+    pub issues: Vec<ParseIssue>,
+
+    /// The source location of the trailer recording when this file was last
+    /// synchronized, if it has one.
+    pub(super) trailer_span: Option<Range<usize>>,
+
+    /// Names expanded in the values of variables defined in this file that
+    /// weren't defined before them.
+    pub(super) undefined_references: HashMap<String, Vec<String>>,
+
+    /// Directives set on variables defined in this file.
+    pub directives: HashMap<String, Directives>,
+
     /// The last modified date, if available.
     pub last_modified: Option<OffsetDateTime>,
+
+    /// The encoding the file was read with, and should be written back in.
+    pub encoding: Encoding,
 }
 
 impl DotenvFile {
     /// Load this dotenv file from the given file path (if it exists)
-    pub fn from_path_exists(path: &Path) -> anyhow::Result<Option<Self>> {
+    ///
+    /// Issues found while parsing the file are logged as warnings, or fail
+    /// loading it if `strict` is set.
+    pub fn from_path_exists(path: &Path, strict: bool) -> anyhow::Result<Option<Self>> {
+        let dotenv = Self::read_path_exists(path)?;
+        if let Some(dotenv) = &dotenv {
+            dotenv.report_issues(&path.display().to_string(), strict)?;
+        }
+
+        Ok(dotenv)
+    }
+
+    /// Load a dotenv file from its raw contents, like when it's downloaded.
+    ///
+    /// Issues found while parsing the file are logged as warnings, or fail
+    /// loading it if `strict` is set.
+    pub fn from_bytes(bytes: &[u8], name: &str, strict: bool) -> anyhow::Result<Self> {
+        let (encoding, source) =
+            Encoding::decode(bytes).with_context(|| format!("Failed to read {name}"))?;
+        let dotenv = Self {
+            encoding,
+            ..Self::parse(source)?
+        };
+        dotenv.report_issues(name, strict)?;
+
+        Ok(dotenv)
+    }
+
+    /// Load this dotenv file from the given file path (if it exists), without
+    /// reporting issues found while parsing it.
+    pub fn read_path_exists(path: &Path) -> anyhow::Result<Option<Self>> {
         // Open file
         let file = File::open(path);
         if let Err(error) = &file
@@ -47,8 +104,10 @@ impl DotenvFile {
 
         // Read the file
         let mut file = file?;
-        let mut source = String::new();
-        file.read_to_string(&mut source)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        let (encoding, source) = Encoding::decode(&bytes)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
 
         // Parse it
         let dotenv = Self::parse(source)?;
@@ -56,37 +115,148 @@ impl DotenvFile {
         // Attach last modified time if available
         Ok(Some(Self {
             last_modified: Some(file.metadata()?.modified()?.into()),
+            encoding,
             ..dotenv
         }))
     }
 
+    /// Gets the line number of the last definition of a variable in this file.
+    pub fn line_of(&self, name: &str) -> Option<usize> {
+        let span = self.definition_spans.get(name)?.last()?;
+        Some(line_number(&self.source, span.start))
+    }
+
+    /// Logs the issues found while parsing this file, or fails if `strict` is
+    /// set and there are any.
+    fn report_issues(&self, name: &str, strict: bool) -> anyhow::Result<()> {
+        if strict && !self.issues.is_empty() {
+            let issues: Vec<_> = self.issues.iter().map(ToString::to_string).collect();
+            bail!(
+                "Failed to parse {name} (--strict-parse):\n  {}",
+                issues.join("\n  ")
+            );
+        }
+
+        for issue in &self.issues {
+            warn!("{name}: {issue}");
+        }
+
+        Ok(())
+    }
+
     /// Replaces the parameter values in this file, returning the modified
     /// contents.
     ///
     /// New parameters are appended to the end of the file. Existing parameters
     /// are replaced in-place. Any parameters not provided to this function that
     /// exist in the file will be left as-is.
-    pub fn replace(&self, replacements: HashMap<String, String>) -> String {
+    ///
+    /// Replaced values keep the quotes they had where possible, and the rest of
+    /// their line is left as-is. Parameters that have to be defined again at
+    /// the end of the file keep the `export` keyword, spacing, and inline
+    /// comment of their last definition.
+    pub fn replace(&self, replacements: HashMap<String, String>) -> anyhow::Result<String> {
+        self.replace_with_comments(replacements, &HashMap::new(), None)
+    }
+
+    /// Replaces the parameter values in this file like [`Self::replace`],
+    /// writing a comment above each parameter that's new to the file.
+    ///
+    /// Comments are given without the leading `#`.
+    ///
+    /// If a template is given, parameters that are new to the file are added
+    /// to the section they're in in the template, after the last parameter
+    /// under the same comment header in this file. Sections this file doesn't
+    /// have yet are added to the end of it, header included.
+    pub fn replace_with_comments(
+        &self,
+        replacements: HashMap<String, String>,
+        comments: &HashMap<String, String>,
+        template: Option<&DotenvFile>,
+    ) -> anyhow::Result<String> {
+        // Find the section each new parameter belongs in, and where it ends
+        let template_sections = template.map(DotenvFile::sections).unwrap_or_default();
+        let placements: HashMap<&str, (usize, &str)> = template_sections
+            .iter()
+            .flat_map(|section| {
+                section
+                    .names
+                    .iter()
+                    .map(|name| (name.as_str(), section.header.as_str()))
+            })
+            .enumerate()
+            .map(|(order, (name, header))| (name, (order, header)))
+            .collect();
+        let sections = self.sections();
+        let section_ends: HashMap<&str, usize> = sections
+            .iter()
+            .rev()
+            .map(|section| (section.header.as_str(), section.end))
+            .collect();
+
         // Split up replacements and additions
         let mut replaced = Vec::with_capacity(replacements.len());
         let mut added = Vec::with_capacity(replacements.len());
+        let mut inserted = Vec::new();
+        let mut new_sections = Vec::new();
         for (name, new_value) in replacements {
-            if !self.referenced.contains(&name)
-                && let Some(span) = self.value_spans.get(&name)
-            {
-                // Replace the value in-place
-                replaced.push((span.clone(), new_value));
-            } else {
-                // Add the value to the end of the file
-                added.push((name, new_value));
+            let quote = self.value_quotes.get(&name).copied().unwrap_or_default();
+            match self.value_spans.get(&name) {
+                Some(span) if !self.referenced.contains(&name) => {
+                    // Replace the value in-place
+                    replaced.push((span.clone(), escape(&new_value, quote).into_owned()));
+                }
+                Some(span) => {
+                    // Define it again at the end of the file like it was defined last
+                    let definition = self.definition_spans[&name]
+                        .last()
+                        .map_or(span.start, |definition| definition.start);
+                    let prefix = self.source[definition..span.start].trim_start();
+                    let comment = self.inline_comment(span.end);
+                    added.push(format!("{prefix}{}{comment}", escape(&new_value, quote)));
+                }
+                None => {
+                    let mut lines = String::new();
+                    if let Some(comment) = comments.get(&name) {
+                        writeln!(lines, "# {comment}")?;
+                    }
+                    writeln!(lines, "{name}={}", escape(&new_value, Quote::None))?;
+
+                    match placements.get(name.as_str()) {
+                        // Add the value to the end of its section
+                        Some(&(order, header)) => match section_ends.get(header) {
+                            Some(&end) => inserted.push((end, order, lines)),
+                            None => new_sections.push((order, header, lines)),
+                        },
+                        // Add the value to the end of the file
+                        None => added.push(lines.trim_end().to_string()),
+                    }
+                }
+            }
+        }
+
+        // Insert values at the end of their sections, in template order
+        inserted.sort_by_key(|&(end, order, _)| (end, order));
+        let mut insertions: Vec<(usize, String)> = Vec::new();
+        for (end, _, lines) in inserted {
+            match insertions.last_mut() {
+                Some((last_end, text)) if *last_end == end => text.push_str(&lines),
+                _ => {
+                    let newline = if self.source[..end].ends_with('\n') {
+                        ""
+                    } else {
+                        "\n"
+                    };
+                    insertions.push((end, format!("{newline}{lines}")));
+                }
             }
         }
+        replaced.extend(insertions.into_iter().map(|(end, text)| (end..end, text)));
 
         // Replace values in reverse order to avoid shifting later indexes
         replaced.sort_by_key(|(span, _)| Reverse(span.end));
         let mut content = self.source.clone();
-        for (span, value) in replaced {
-            let escaped = escape(&value);
+        for (span, escaped) in replaced {
             content.replace_range(span, &escaped);
         }
 
@@ -97,32 +267,118 @@ impl DotenvFile {
                 content.push('\n');
             }
 
-            for (name, value) in added {
-                let value = escape(&value);
-                let _ = writeln!(content, "{name}={value}");
+            for line in added {
+                writeln!(content, "{line}")?;
             }
         }
 
+        // Add sections this file doesn't have yet, in template order
+        new_sections.sort_by_key(|&(order, _, _)| order);
+        let mut last_header = None;
+        for (_, header, lines) in new_sections {
+            if last_header != Some(header) {
+                if content.chars().last().is_some_and(|c| c != '\n') {
+                    content.push('\n');
+                }
+                if !content.is_empty() {
+                    content.push('\n');
+                }
+                writeln!(content, "{header}")?;
+                last_header = Some(header);
+            }
+            content.push_str(&lines);
+        }
+
+        Ok(content)
+    }
+
+    /// Gets the comment after a value ending at `end`, with the whitespace
+    /// before it, or an empty string if there's nothing else on its line.
+    fn inline_comment(&self, end: usize) -> &str {
+        let rest = &self.source[end..];
+        let rest = rest[..rest.find('\n').unwrap_or(rest.len())].trim_end_matches('\r');
+        if rest.trim_start().starts_with('#') {
+            rest.trim_end()
+        } else {
+            ""
+        }
+    }
+
+    /// Removes every definition of the given parameters from this file,
+    /// returning the modified contents.
+    ///
+    /// The whole line each definition is on is removed.
+    pub fn remove(&self, names: &HashSet<String>) -> String {
+        // Find the lines to remove
+        let mut lines: Vec<_> = names
+            .iter()
+            .filter_map(|name| self.definition_spans.get(name))
+            .flatten()
+            .map(|span| {
+                let start = self.source[..span.start]
+                    .rfind('\n')
+                    .map_or(0, |index| index + 1);
+                let end = self.source[span.end..]
+                    .find('\n')
+                    .map_or(self.source.len(), |index| span.end + index + 1);
+                start..end
+            })
+            .collect();
+
+        // Remove them in reverse order to avoid shifting later indexes
+        lines.sort_by_key(|line| Reverse(line.start));
+        lines.dedup();
+        let mut content = self.source.clone();
+        for line in lines {
+            content.replace_range(line, "");
+        }
+
         content
     }
 }
 
-/// Escapes a value so that it's valid in a dotenv file.
-fn escape(value: &str) -> Cow<'_, str> {
+/// How a value is quoted in a dotenv file.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub(super) enum Quote {
+    /// The value isn't quoted.
+    #[default]
+    None,
+
+    /// The value is in single quotes.
+    Single,
+
+    /// The value is in double quotes.
+    Double,
+}
+
+/// Escapes a value so that it's valid in a dotenv file, using the given quotes
+/// where possible.
+fn escape(value: &str, quote: Quote) -> Cow<'_, str> {
     const ESCAPED: &[char] = &['\\', '$', '"', '\''];
-    if value.contains(ESCAPED) || value != value.trim() {
-        let value = ESCAPED.iter().fold(value.to_owned(), |value, &c| {
-            value.replace(c, &format!("\\{c}"))
-        });
-        format!("\"{value}\"").into()
-    } else {
-        value.into()
+    match quote {
+        // Single-quoted values can't contain single quotes, even escaped
+        Quote::Single if !value.contains('\'') => format!("'{value}'").into(),
+        // Multi-line values need quotes, which keep the newlines as they are
+        Quote::None
+            if !value.contains(ESCAPED)
+                && !value.contains(['\n', '\r'])
+                && value == value.trim() =>
+        {
+            value.into()
+        }
+        _ => {
+            let value = ESCAPED.iter().fold(value.to_owned(), |value, &c| {
+                value.replace(c, &format!("\\{c}"))
+            });
+            format!("\"{value}\"").into()
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use pretty_assertions::assert_eq;
+    use test_case::test_case;
 
     use super::*;
 
@@ -140,11 +396,21 @@ mod tests {
             .map(|(k, v)| (k.to_string(), v.to_string()))
             .collect();
 
-        let replaced = dotenv.replace(replacements);
+        let replaced = dotenv.replace(replacements).unwrap();
 
         assert_eq!(SIMPLE_REPLACED, replaced);
     }
 
+    #[test]
+    fn remove_simple() {
+        let dotenv = DotenvFile::parse(SIMPLE).unwrap();
+        let names = ["A", "C", "D"].into_iter().map(String::from).collect();
+
+        let removed = dotenv.remove(&names);
+
+        assert_eq!("B='four five six'\n", removed.replace("\r\n", "\n"));
+    }
+
     #[test]
     fn replace_expansion() {
         let dotenv = DotenvFile::parse(EXPANSION).unwrap();
@@ -158,11 +424,133 @@ mod tests {
         .map(|(k, v)| (k.to_string(), v.to_string()))
         .collect();
 
-        let replaced = dotenv.replace(replacements);
+        let replaced = dotenv.replace(replacements).unwrap();
 
         assert_eq!(EXPANSION_REPLACED, replaced);
     }
 
+    #[test]
+    fn replace_export() {
+        let dotenv =
+            DotenvFile::parse("A = 1\nexport   B  =   \"b\"\nC=\"${B}\"\nD='d'\n").unwrap();
+        let replacements = [("A", "2"), ("B", "b b"), ("D", "it's")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        let replaced = dotenv.replace(replacements).unwrap();
+
+        assert_eq!(
+            "A = 2\nexport   B  =   \"b\"\nC=\"${B}\"\nD=\"it\\'s\"\nexport   B  =   \"b b\"\n",
+            replaced
+        );
+    }
+
+    #[test]
+    fn replace_keeps_comments() {
+        let dotenv = DotenvFile::parse("A=a   # first\nB=\"$A\" # uses A\n").unwrap();
+        let replacements = [("A", "new"), ("B", "b")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        let replaced = dotenv.replace(replacements).unwrap();
+
+        assert_eq!(
+            "A=a   # first\nB=\"b\" # uses A\nA=new   # first\n",
+            replaced
+        );
+    }
+
+    #[test]
+    fn replace_with_comments() {
+        let dotenv = DotenvFile::parse("A=a # kept\n").unwrap();
+        let replacements = [("A", "aa"), ("B", "bb")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let comments = [("A", "from A"), ("B", "from B")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        let replaced = dotenv
+            .replace_with_comments(replacements, &comments, None)
+            .unwrap();
+
+        assert_eq!("A=aa # kept\n# from B\nB=bb\n", replaced);
+    }
+
+    #[test]
+    fn replace_into_sections() {
+        let template = DotenvFile::parse(
+            "TOP=\n\n# Database\nDATABASE_URL=\nDATABASE_POOL=\n\n# Cache\nCACHE_TTL=\nCACHE_KEY=\n",
+        )
+        .unwrap();
+        let dotenv = DotenvFile::parse("# Database\nDATABASE_URL=db\n\n# Other\nOTHER=1").unwrap();
+        let replacements = [
+            ("DATABASE_POOL", "5"),
+            ("CACHE_KEY", "key"),
+            ("CACHE_TTL", "60"),
+            ("UNLISTED", "u"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let comments = [("DATABASE_POOL", "from pool")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        let replaced = dotenv
+            .replace_with_comments(replacements, &comments, Some(&template))
+            .unwrap();
+
+        assert_eq!(
+            "# Database\nDATABASE_URL=db\n# from pool\nDATABASE_POOL=5\n\n# Other\nOTHER=1\nUNLISTED=u\n\n# Cache\nCACHE_TTL=60\nCACHE_KEY=key\n",
+            replaced
+        );
+    }
+
+    #[test]
+    fn replace_multiline() {
+        let dotenv = DotenvFile::parse("A=a\nB='b'\nC=c\n").unwrap();
+        let replacements = [
+            ("A", "-----BEGIN KEY-----\nabc\n-----END KEY-----"),
+            ("B", "one\ntwo"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        let replaced = dotenv.replace(replacements).unwrap();
+
+        assert_eq!(
+            "A=\"-----BEGIN KEY-----\nabc\n-----END KEY-----\"\nB='one\ntwo'\nC=c\n",
+            replaced
+        );
+        let reparsed = DotenvFile::parse(replaced).unwrap();
+        assert_eq!(
+            reparsed.parameters["A"],
+            "-----BEGIN KEY-----\nabc\n-----END KEY-----"
+        );
+        assert_eq!(reparsed.parameters["B"], "one\ntwo");
+        assert_eq!(reparsed.parameters["C"], "c");
+    }
+
+    #[test_case("value", Quote::None, "value"; "plain")]
+    #[test_case("a b", Quote::Single, "'a b'"; "single")]
+    #[test_case("$a", Quote::Single, "'$a'"; "single with dollar")]
+    #[test_case("it's", Quote::Single, r#""it\'s""#; "single with quote")]
+    #[test_case("value", Quote::Double, r#""value""#; "double")]
+    #[test_case(" padded", Quote::None, r#"" padded""#; "padded")]
+    #[test_case("a\nb", Quote::None, "\"a\nb\""; "multi-line")]
+    #[test_case("a\nb", Quote::Single, "'a\nb'"; "single multi-line")]
+    #[test_case("a\\\nb", Quote::None, "\"a\\\\\nb\""; "multi-line with backslash")]
+    fn escapes(value: &str, quote: Quote, expected: &str) {
+        assert_eq!(expected, escape(value, quote));
+    }
+
     #[test]
     fn replace_empty() {
         let dotenv = DotenvFile::default();
@@ -172,7 +560,7 @@ mod tests {
             .collect();
         let expected: HashSet<_> = ["A=aaa", "B=bbb", "C=ccc"].into_iter().collect();
 
-        let replaced = dotenv.replace(replacements);
+        let replaced = dotenv.replace(replacements).unwrap();
 
         let lines: HashSet<_> = replaced.lines().collect();
         assert_eq!(lines, expected);
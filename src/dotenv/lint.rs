@@ -0,0 +1,337 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter},
+};
+
+use crate::{
+    dotenv::{DotenvFile, ParseIssue},
+    format::Size,
+};
+
+/// The longest name a Key Vault secret can have.
+pub const MAX_SECRET_NAME_LEN: usize = 127;
+
+/// The largest value a Key Vault secret can have, in bytes.
+pub const MAX_SECRET_VALUE_SIZE: u64 = 25 * 1024;
+
+/// An issue found while linting dotenv files.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Lint {
+    /// The file the issue is in.
+    pub file: LintFile,
+
+    /// The line the issue is on, if it's on a specific line.
+    pub line: Option<usize>,
+
+    /// The issue.
+    pub issue: LintIssue,
+}
+
+/// A file that's linted.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum LintFile {
+    /// The dotenv file.
+    Dotenv,
+
+    /// The dotenv template file.
+    Template,
+}
+
+/// A kind of issue found while linting dotenv files.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum LintIssue {
+    /// The file couldn't be parsed cleanly.
+    Parse(ParseIssue),
+
+    /// A variable is defined locally but isn't in the template.
+    NotInTemplate {
+        /// The name of the variable.
+        name: String,
+    },
+
+    /// A variable is in the template but isn't defined locally.
+    NotDefinedLocally {
+        /// The name of the variable.
+        name: String,
+    },
+
+    /// A variable's name is too long for a Key Vault secret.
+    SecretNameTooLong {
+        /// The name of the variable.
+        name: String,
+    },
+
+    /// Two variables are stored in the same Key Vault secret, since secret
+    /// names aren't case-sensitive.
+    SecretNameCollision {
+        /// The name of the variable.
+        name: String,
+
+        /// The name of the variable it collides with.
+        other: String,
+    },
+
+    /// A variable's value expands a name that isn't defined before it.
+    UndefinedReference {
+        /// The name of the variable.
+        name: String,
+
+        /// The name that isn't defined.
+        reference: String,
+    },
+
+    /// A variable's value is too large to store in Key Vault.
+    ValueTooLarge {
+        /// The name of the variable.
+        name: String,
+
+        /// The size of the value in bytes.
+        size: u64,
+
+        /// The largest allowed size in bytes.
+        max: u64,
+    },
+}
+
+impl Display for LintIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Parse(issue) => write!(f, "{issue}"),
+            Self::NotInTemplate { name } => {
+                write!(
+                    f,
+                    "{name} isn't in the template, so it's never synchronized"
+                )
+            }
+            Self::NotDefinedLocally { name } => {
+                write!(f, "{name} is in the template but isn't defined locally")
+            }
+            Self::SecretNameTooLong { name } => write!(
+                f,
+                "{name} is too long for a Key Vault secret name ({} characters, at most {MAX_SECRET_NAME_LEN})",
+                name.len()
+            ),
+            Self::SecretNameCollision { name, other } => write!(
+                f,
+                "{name} and {other} are stored in the same Key Vault secret ({})",
+                secret_name(name)
+            ),
+            Self::UndefinedReference { name, reference } => write!(
+                f,
+                "{name} references {reference}, which isn't defined before it"
+            ),
+            Self::ValueTooLarge { name, size, max } => write!(
+                f,
+                "{name} is {}, larger than the maximum of {}",
+                Size(*size),
+                Size(*max)
+            ),
+        }
+    }
+}
+
+/// Finds issues in a dotenv file and its template.
+///
+/// Values are only checked in the dotenv file, since values in the template
+/// are never used. Issues are sorted by file and line.
+pub fn lint(
+    dotenv: Option<&DotenvFile>,
+    template: Option<&DotenvFile>,
+    max_value_size: u64,
+) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    for (file, dotenv) in [(LintFile::Dotenv, dotenv), (LintFile::Template, template)] {
+        let Some(dotenv) = dotenv else {
+            continue;
+        };
+
+        // Parse issues
+        lints.extend(dotenv.issues.iter().map(|issue| Lint {
+            file,
+            line: Some(issue.line()),
+            issue: LintIssue::Parse(issue.clone()),
+        }));
+
+        // Secret names
+        let mut secrets: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for name in dotenv.parameters.keys() {
+            secrets.entry(secret_name(name)).or_default().push(name);
+        }
+        for names in secrets.values_mut() {
+            // Report every definition after the first
+            names.sort_by_key(|name| dotenv.line_of(name));
+            for name in &names[1..] {
+                lints.push(Lint {
+                    file,
+                    line: dotenv.line_of(name),
+                    issue: LintIssue::SecretNameCollision {
+                        name: (*name).clone(),
+                        other: names[0].clone(),
+                    },
+                });
+            }
+        }
+        lints.extend(
+            dotenv
+                .parameters
+                .keys()
+                .filter(|name| name.len() > MAX_SECRET_NAME_LEN)
+                .map(|name| Lint {
+                    file,
+                    line: dotenv.line_of(name),
+                    issue: LintIssue::SecretNameTooLong { name: name.clone() },
+                }),
+        );
+    }
+
+    if let Some(dotenv) = dotenv {
+        // Values
+        for (name, references) in &dotenv.undefined_references {
+            lints.extend(references.iter().map(|reference| Lint {
+                file: LintFile::Dotenv,
+                line: dotenv.line_of(name),
+                issue: LintIssue::UndefinedReference {
+                    name: name.clone(),
+                    reference: reference.clone(),
+                },
+            }));
+        }
+        for (name, value) in &dotenv.parameters {
+            let size = value.len() as u64;
+            if size > max_value_size {
+                lints.push(Lint {
+                    file: LintFile::Dotenv,
+                    line: dotenv.line_of(name),
+                    issue: LintIssue::ValueTooLarge {
+                        name: name.clone(),
+                        size,
+                        max: max_value_size,
+                    },
+                });
+            }
+        }
+    }
+
+    if let (Some(dotenv), Some(template)) = (dotenv, template) {
+        // Differences from the template
+        lints.extend(
+            dotenv
+                .parameters
+                .keys()
+                .filter(|name| !template.parameters.contains_key(*name))
+                .map(|name| Lint {
+                    file: LintFile::Dotenv,
+                    line: dotenv.line_of(name),
+                    issue: LintIssue::NotInTemplate { name: name.clone() },
+                }),
+        );
+        lints.extend(
+            template
+                .parameters
+                .keys()
+                .filter(|name| !dotenv.parameters.contains_key(*name))
+                .map(|name| Lint {
+                    file: LintFile::Template,
+                    line: template.line_of(name),
+                    issue: LintIssue::NotDefinedLocally { name: name.clone() },
+                }),
+        );
+    }
+
+    lints.sort_by_cached_key(|lint| (lint.file, lint.line, lint.issue.to_string()));
+    lints
+}
+
+/// Gets the name of the Key Vault secret a variable is stored in, ignoring
+/// case.
+fn secret_name(name: &str) -> String {
+    name.replace('_', "-").to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issues(dotenv: &str, template: Option<&str>) -> Vec<(LintFile, Option<usize>, String)> {
+        let dotenv = DotenvFile::parse(dotenv).unwrap();
+        let template = template.map(|template| DotenvFile::parse(template).unwrap());
+        lint(Some(&dotenv), template.as_ref(), 8)
+            .into_iter()
+            .map(|lint| (lint.file, lint.line, lint.issue.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn clean() {
+        assert_eq!(issues("A=1\nB=$A\n", Some("A=\nB=\n")), []);
+    }
+
+    #[test]
+    fn template_differences() {
+        assert_eq!(
+            issues("A=1\nB=2\n", Some("A=\nC=\n")),
+            [
+                (
+                    LintFile::Dotenv,
+                    Some(2),
+                    "B isn't in the template, so it's never synchronized".to_string()
+                ),
+                (
+                    LintFile::Template,
+                    Some(2),
+                    "C is in the template but isn't defined locally".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn secret_names() {
+        let long = "A".repeat(MAX_SECRET_NAME_LEN + 1);
+        let lints = issues(&format!("db_url=1\nDB_URL=2\n{long}=3\n"), None);
+        assert_eq!(
+            lints,
+            [
+                (
+                    LintFile::Dotenv,
+                    Some(2),
+                    "DB_URL and db_url are stored in the same Key Vault secret (db-url)"
+                        .to_string()
+                ),
+                (
+                    LintFile::Dotenv,
+                    Some(3),
+                    format!(
+                        "{long} is too long for a Key Vault secret name (128 characters, at most 127)"
+                    )
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn values() {
+        assert_eq!(
+            issues("A=${MISSING}\nB=123456789\n", None),
+            [
+                (
+                    LintFile::Dotenv,
+                    Some(1),
+                    "A references MISSING, which isn't defined before it".to_string()
+                ),
+                (
+                    LintFile::Dotenv,
+                    Some(2),
+                    "B is 9 B, larger than the maximum of 8 B".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn template_values_are_ignored() {
+        let dotenv = DotenvFile::parse("A=1\n").unwrap();
+        let template = DotenvFile::parse("A=${MISSING}123456789\n").unwrap();
+        assert_eq!(lint(Some(&dotenv), Some(&template), 8), []);
+    }
+}
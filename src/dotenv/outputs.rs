@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Context, bail};
+use serde_json::Value;
+
+/// Reads deployment outputs from JSON, returning the value of each output by
+/// name.
+///
+/// Accepts a whole ARM deployment (with `properties.outputs`), just its
+/// outputs (like from `az deployment group show --query properties.outputs`),
+/// and `terraform output -json`. Outputs may either be objects with a `value`
+/// field or plain values. Values that aren't strings are converted to JSON.
+pub fn parse_outputs(json: &str) -> anyhow::Result<BTreeMap<String, String>> {
+    let mut root: Value = serde_json::from_str(json).context("Invalid JSON")?;
+
+    // Find the outputs
+    if let Some(outputs) = root.pointer_mut("/properties/outputs") {
+        root = outputs.take();
+    } else if let Some(outputs) = root.get_mut("outputs") {
+        root = outputs.take();
+    }
+    let Value::Object(outputs) = root else {
+        bail!("Expected an object of deployment outputs");
+    };
+
+    // Get their values
+    let outputs = outputs
+        .into_iter()
+        .map(|(name, output)| {
+            let value = match output {
+                Value::Object(mut output) if output.contains_key("value") => {
+                    output.remove("value").expect("output should have a value")
+                }
+                value => value,
+            };
+            let value = match value {
+                Value::String(value) => value,
+                Value::Null => String::new(),
+                value => value.to_string(),
+            };
+            (name, value)
+        })
+        .collect();
+
+    Ok(outputs)
+}
+
+/// Converts an output name like `storageAccountUrl` or `storage-account-url`
+/// into a variable name like `STORAGE_ACCOUNT_URL`.
+pub fn variable_name(output_name: &str) -> String {
+    let mut name = String::with_capacity(output_name.len() + 4);
+    let mut previous: Option<char> = None;
+    for c in output_name.chars() {
+        if c.is_uppercase()
+            && previous.is_some_and(|previous| previous.is_lowercase() || previous.is_ascii_digit())
+        {
+            name.push('_');
+        }
+
+        if c.is_ascii_alphanumeric() {
+            name.push(c.to_ascii_uppercase());
+        } else {
+            name.push('_');
+        }
+        previous = Some(c);
+    }
+
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(r#"{"properties":{"outputs":{"url":{"type":"String","value":"https://a"}}}}"#; "deployment")]
+    #[test_case(r#"{"url":{"type":"String","value":"https://a"}}"#; "arm outputs")]
+    #[test_case(r#"{"url":{"sensitive":false,"type":"string","value":"https://a"}}"#; "terraform")]
+    #[test_case(r#"{"url":"https://a"}"#; "plain")]
+    fn formats(json: &str) {
+        let outputs = parse_outputs(json).unwrap();
+        assert_eq!(
+            outputs,
+            [("url".to_string(), "https://a".to_string())].into()
+        );
+    }
+
+    #[test]
+    fn non_string_values() {
+        let outputs = parse_outputs(
+            r#"{"port":{"value":8080},"enabled":{"value":true},"tags":{"value":{"a":"b"}}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            outputs,
+            [
+                ("enabled".to_string(), "true".to_string()),
+                ("port".to_string(), "8080".to_string()),
+                ("tags".to_string(), r#"{"a":"b"}"#.to_string()),
+            ]
+            .into()
+        );
+    }
+
+    #[test]
+    fn not_an_object() {
+        assert!(parse_outputs("[]").is_err());
+    }
+
+    #[test_case("storageAccountUrl" => "STORAGE_ACCOUNT_URL"; "camel case")]
+    #[test_case("StorageAccountURL" => "STORAGE_ACCOUNT_URL"; "pascal case")]
+    #[test_case("storage-account-url" => "STORAGE_ACCOUNT_URL"; "kebab case")]
+    #[test_case("storage_account_url" => "STORAGE_ACCOUNT_URL"; "snake case")]
+    #[test_case("sql2Connection" => "SQL2_CONNECTION"; "digits")]
+    fn names(output_name: &str) -> String {
+        variable_name(output_name)
+    }
+}
@@ -1,5 +1,7 @@
 use std::{
     collections::{HashMap, HashSet},
+    fmt::{Display, Formatter},
+    mem::take,
     ops::Range,
     str::FromStr,
 };
@@ -8,7 +10,10 @@ use anyhow::{Context, bail, ensure};
 use pest::{Parser, Span, iterators::Pair};
 use pest_derive::Parser;
 
-use crate::dotenv::{DotenvFile, expand::expand, unescape::unescape};
+use crate::dotenv::{
+    Directives, DotenvFile, Encoding, Quote, expand::expand, trailer::find_trailer,
+    unescape::unescape,
+};
 
 #[derive(Parser)]
 #[grammar = "grammars/dotenv.pest"]
@@ -22,17 +27,74 @@ impl DotenvFile {
         let pairs = DotenvParser::parse(Rule::dotenv, &source)?;
         let mut parameters = HashMap::new();
         let mut value_spans = HashMap::new();
+        let mut value_quotes = HashMap::new();
+        let mut definition_spans: HashMap<String, Vec<_>> = HashMap::new();
         let mut referenced = HashSet::new(); // names that are expanded later in the file
+        let mut undefined_references: HashMap<String, Vec<String>> = HashMap::new();
+        let mut directives: HashMap<String, Directives> = HashMap::new();
+        let mut pending = Directives::default(); // directives for the next variable
+        let mut last_name = None;
+        let mut issues = Vec::new();
         for pair in pairs {
             match pair.as_rule() {
                 Rule::var_definition => {
                     // Parse a variable definition
-                    let (name, value) = var_definition(pair, &parameters, &mut referenced)?;
+                    let span = pair.as_span();
+                    let mut undefined = Vec::new();
+                    let (name, value, quote) =
+                        var_definition(pair, &parameters, &mut referenced, &mut undefined)?;
+                    definition_spans
+                        .entry(name.clone())
+                        .or_default()
+                        .push(span.start()..span.end());
+
+                    // Apply directives from the lines before it
+                    if !pending.is_empty() {
+                        directives
+                            .entry(name.clone())
+                            .or_default()
+                            .merge(take(&mut pending));
+                    }
 
                     // Overwrite previous definition if needed
                     referenced.remove(&name); // New definition (even if self-referencing)
                     parameters.insert(name.clone(), value.value);
-                    value_spans.insert(name, value.span);
+                    value_spans.insert(name.clone(), value.span);
+                    value_quotes.insert(name.clone(), quote);
+                    if !undefined.is_empty() {
+                        undefined_references
+                            .entry(name.clone())
+                            .or_default()
+                            .extend(undefined);
+                    }
+                    last_name = Some(name);
+                }
+                Rule::malformed_line => {
+                    // Ignore the line so the rest of the file can still be used
+                    issues.push(ParseIssue::Malformed {
+                        line: pair.line_col().0,
+                    });
+                    last_name = None;
+                }
+                Rule::directive => {
+                    // Directives after a definition apply to it, otherwise to
+                    // the next definition
+                    let start = pair.as_span().start();
+                    let line_start = source[..start].rfind('\n').map_or(0, |index| index + 1);
+                    let target = match &last_name {
+                        Some(name) if !source[line_start..start].trim().is_empty() => {
+                            directives.entry(name.clone()).or_default()
+                        }
+                        _ => &mut pending,
+                    };
+
+                    let body = pair
+                        .into_inner()
+                        .next()
+                        .context("Missing directive body (this is a bug)")?;
+                    target.parse(body.as_str()).with_context(|| {
+                        format!("Invalid directive on line {}", body.line_col().0)
+                    })?;
                 }
                 Rule::EOI => {
                     // Done
@@ -41,12 +103,34 @@ impl DotenvFile {
             }
         }
 
+        // Find variables that are defined more than once
+        issues.extend(
+            definition_spans
+                .iter()
+                .filter(|(_, spans)| spans.len() > 1)
+                .map(|(name, spans)| ParseIssue::Duplicate {
+                    name: name.clone(),
+                    lines: spans
+                        .iter()
+                        .map(|span| line_number(&source, span.start))
+                        .collect(),
+                }),
+        );
+        issues.sort_by_key(ParseIssue::line);
+
         Ok(DotenvFile {
+            trailer_span: find_trailer(&source),
             source,
+            issues,
             parameters,
             value_spans,
+            value_quotes,
+            definition_spans,
             referenced,
+            undefined_references,
+            directives,
             last_modified: None,
+            encoding: Encoding::default(),
         })
     }
 }
@@ -63,9 +147,11 @@ fn var_definition(
     pair: Pair<'_, Rule>,
     parameters: &HashMap<String, String>,
     referenced: &mut HashSet<String>,
-) -> anyhow::Result<(String, Spanned<String>)> {
+    undefined: &mut Vec<String>,
+) -> anyhow::Result<(String, Spanned<String>, Quote)> {
     let mut name = None;
     let mut value = None;
+    let mut quote = Quote::None;
     for pair in pair.into_inner() {
         match pair.as_rule() {
             Rule::var_name => {
@@ -84,6 +170,7 @@ fn var_definition(
                 processed.on_expand(|name| {
                     referenced.insert(name.to_string());
                 });
+                processed.on_missing(|name| undefined.push(name.to_string()));
                 let processed = unescape(processed);
                 value = Some(Spanned::new(processed.collect(), pair.as_span()));
             }
@@ -94,6 +181,7 @@ fn var_definition(
                 );
                 let processed = unquote(pair.as_str(), '\'')
                     .context("Single-quoted value missing one or more quotes (this is a bug)")?;
+                quote = Quote::Single;
                 value = Some(Spanned::new(processed.to_owned(), pair.as_span()));
             }
             Rule::var_value_dq => {
@@ -103,10 +191,12 @@ fn var_definition(
                 );
                 let processed = unquote(pair.as_str(), '"')
                     .context("Double-quoted value missing one or more quotes (this is a bug)")?;
+                quote = Quote::Double;
                 let mut processed = expand(processed.chars(), parameters);
                 processed.on_expand(|name| {
                     referenced.insert(name.to_string());
                 });
+                processed.on_missing(|name| undefined.push(name.to_string()));
                 let processed = unescape(processed);
                 value = Some(Spanned::new(processed.collect(), pair.as_span()));
             }
@@ -116,7 +206,59 @@ fn var_definition(
 
     let name = name.context("Missing variable name (this is a bug)")?;
     let value = value.context("Missing variable value (this is a bug)")?;
-    Ok((name, value))
+    Ok((name, value, quote))
+}
+
+/// A problem in a dotenv file that doesn't prevent it from being loaded.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ParseIssue {
+    /// A variable is defined more than once. The last definition is used.
+    Duplicate {
+        /// The name of the variable.
+        name: String,
+
+        /// The line numbers of each definition.
+        lines: Vec<usize>,
+    },
+
+    /// A line isn't a definition, comment, or directive. It's ignored.
+    Malformed {
+        /// The line number.
+        line: usize,
+    },
+}
+
+impl ParseIssue {
+    /// The first line the issue is on.
+    pub fn line(&self) -> usize {
+        match self {
+            Self::Duplicate { lines, .. } => lines.first().copied().unwrap_or_default(),
+            Self::Malformed { line } => *line,
+        }
+    }
+}
+
+impl Display for ParseIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Duplicate { name, lines } => {
+                let lines: Vec<_> = lines.iter().map(ToString::to_string).collect();
+                write!(
+                    f,
+                    "{name} is defined more than once (on lines {}), so the last definition is used",
+                    lines.join(", ")
+                )
+            }
+            Self::Malformed { line } => {
+                write!(f, "line {line} is not a valid definition, so it's ignored")
+            }
+        }
+    }
+}
+
+/// Gets the 1-based line number of an index into the source.
+pub(super) fn line_number(source: &str, index: usize) -> usize {
+    source[..index].matches('\n').count() + 1
 }
 
 /// Removes a leading and trailing quote character from the string.
@@ -156,6 +298,8 @@ mod tests {
     const EXPORT: &str = include_str!("tests/export.env");
     const EXPANSION: &str = include_str!("tests/expansion.env");
     const COMMENTS: &str = include_str!("tests/comments.env");
+    const DIRECTIVES: &str = include_str!("tests/directives.env");
+    const MULTILINE: &str = include_str!("tests/multiline.env");
 
     const SIMPLE_VALUES: &[(&str, &str)] =
         &[("A", "123"), ("B", "four five six"), ("C", "seven 8 nine")];
@@ -163,6 +307,15 @@ mod tests {
         &[("A", "123"), ("B", "four five six"), ("C", "seven 8 nine")];
     const COMMENTS_VALUES: &[(&str, &str)] =
         &[("A", "123#456"), ("B", "123#456"), ("C", "123#456")];
+    const MULTILINE_VALUES: &[(&str, &str)] = &[
+        (
+            "CERT",
+            "-----BEGIN CERTIFICATE-----\nMIIB\nabc=\n-----END CERTIFICATE-----",
+        ),
+        ("KEY", "line one\nline \"two\""),
+        ("PATH_END", "C:\\dir\\\\"),
+        ("AFTER", "done"),
+    ];
     const EXPANSION_VALUES: &[(&str, &str)] = &[
         ("A", "456"),
         ("B", "123 456"),
@@ -196,6 +349,7 @@ mod tests {
     #[test_case(EXPORT, EXPORT_VALUES; "export")]
     #[test_case(COMMENTS, COMMENTS_VALUES; "comments")]
     #[test_case(EXPANSION, EXPANSION_VALUES; "expansion")]
+    #[test_case(MULTILINE, MULTILINE_VALUES; "multi-line")]
     fn values(s: &str, expected: &[(&str, &str)]) {
         let mut dotenv = DotenvFile::parse(s).unwrap();
 
@@ -240,4 +394,78 @@ mod tests {
         assert!(dotenv.parameters.is_empty());
         assert!(dotenv.value_spans.is_empty());
     }
+
+    #[test]
+    fn directives() {
+        let dotenv = DotenvFile::parse(DIRECTIVES).unwrap();
+
+        assert_eq!(
+            dotenv.directives.get("A"),
+            Some(&Directives {
+                skip: true,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            dotenv.directives.get("B"),
+            Some(&Directives {
+                push_only: true,
+                ..Default::default()
+            })
+        );
+        assert_eq!(dotenv.directives.get("C"), None);
+        assert_eq!(
+            dotenv.directives.get("D"),
+            Some(&Directives {
+                pull_only: true,
+                content_type: Some("application/json".to_string()),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            dotenv.directives.get("E"),
+            Some(&Directives {
+                pull_only: true,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn duplicates() {
+        let dotenv = DotenvFile::parse("A=1\nB=2\n\nexport A=3\n").unwrap();
+
+        assert_eq!(dotenv.parameters.get("A").map(String::as_str), Some("3"));
+        assert_eq!(
+            dotenv.issues,
+            [ParseIssue::Duplicate {
+                name: "A".to_string(),
+                lines: vec![1, 4],
+            }]
+        );
+    }
+
+    #[test_case("A=1\nnot a definition\nB=2\n", 2; "plain text")]
+    #[test_case("A=1\nB='quoted' trailing\n", 2; "text after quotes")]
+    #[test_case("A=1\n=2", 2; "missing name")]
+    fn malformed(s: &str, line: usize) {
+        let dotenv = DotenvFile::parse(s).unwrap();
+
+        assert_eq!(dotenv.parameters.get("A").map(String::as_str), Some("1"));
+        assert_eq!(dotenv.issues, [ParseIssue::Malformed { line }]);
+    }
+
+    #[test_case("A=1\n   \nB=2"; "whitespace line")]
+    #[test_case("A=1\n  # comment\n"; "indented comment")]
+    #[test_case("A=1\n# comment"; "comment without newline")]
+    #[test_case("A=1 # comment"; "trailing comment without newline")]
+    fn no_issues(s: &str) {
+        let dotenv = DotenvFile::parse(s).unwrap();
+        assert_eq!(dotenv.issues, []);
+    }
+
+    #[test]
+    fn invalid_directive() {
+        assert!(DotenvFile::parse("# azsync: sikp\nA=1\n").is_err());
+    }
 }
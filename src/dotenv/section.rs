@@ -0,0 +1,156 @@
+use std::ops::Range;
+
+use crate::dotenv::DotenvFile;
+
+/// A group of variables under a comment header, like `# Database`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(super) struct Section {
+    /// The comment line the section starts with, trimmed.
+    pub header: String,
+
+    /// The variables defined in the section, in order.
+    pub names: Vec<String>,
+
+    /// Where the last line of the section ends, which is where variables
+    /// added to it go.
+    pub end: usize,
+}
+
+impl DotenvFile {
+    /// Splits the variables in this file into sections.
+    ///
+    /// A section starts with a comment that comes after a blank line or at the
+    /// start of the file, and lasts until the next one. Directives aren't
+    /// headers. Variables before the first header aren't in any section.
+    pub(super) fn sections(&self) -> Vec<Section> {
+        // Definitions in the order they appear, to tell them apart from lines
+        // that look like comments in multi-line values
+        let mut definitions: Vec<(&Range<usize>, &str)> = self
+            .definition_spans
+            .iter()
+            .flat_map(|(name, spans)| spans.iter().map(move |span| (span, name.as_str())))
+            .collect();
+        definitions.sort_by_key(|(span, _)| span.start);
+        let mut definitions = definitions.into_iter().peekable();
+
+        let mut sections = Vec::new();
+        let mut current: Option<Section> = None;
+        let mut after_blank = true;
+        let mut in_header = false;
+        let mut definition_end = 0;
+        let mut offset = 0;
+        for line in self.source.split_inclusive('\n') {
+            let start = offset;
+            offset += line.len();
+
+            // Later lines of a multi-line definition
+            if start < definition_end {
+                if let Some(section) = &mut current {
+                    section.end = offset;
+                }
+                continue;
+            }
+
+            // Definitions
+            if let Some((span, name)) = definitions.next_if(|(span, _)| span.start < offset) {
+                definition_end = span.end;
+                if let Some(section) = &mut current {
+                    section.names.push(name.to_string());
+                    section.end = offset;
+                }
+                after_blank = false;
+                in_header = false;
+                continue;
+            }
+
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                after_blank = true;
+                in_header = false;
+            } else if is_directive(trimmed) {
+                // Directives belong to the next variable, not the section
+            } else if trimmed.starts_with('#') {
+                if after_blank {
+                    sections.extend(current.take());
+                    current = Some(Section {
+                        header: trimmed.to_string(),
+                        names: Vec::new(),
+                        end: offset,
+                    });
+                    in_header = true;
+                } else if in_header && let Some(section) = &mut current {
+                    section.end = offset;
+                }
+                after_blank = false;
+            } else {
+                after_blank = false;
+                in_header = false;
+            }
+        }
+        sections.extend(current);
+
+        sections
+    }
+}
+
+/// Checks whether a trimmed line is a directive, like `# azsync: skip`.
+fn is_directive(line: &str) -> bool {
+    line.strip_prefix('#')
+        .is_some_and(|comment| comment.trim_start().starts_with("azsync:"))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn sections() {
+        let source = "\
+TOP=1
+
+# Database
+# Connection settings
+DATABASE_URL=postgres://
+DATABASE_POOL=5
+
+# Cache
+# azsync: skip
+CACHE_KEY=\"line one
+# not a header
+
+line three\"
+CACHE_TTL=60
+
+# Empty section
+";
+        let dotenv = DotenvFile::parse(source).unwrap();
+
+        let sections: Vec<_> = dotenv
+            .sections()
+            .into_iter()
+            .map(|section| {
+                let last_line = source[..section.end].lines().last().unwrap();
+                (section.header, section.names, last_line)
+            })
+            .collect();
+
+        assert_eq!(
+            sections,
+            [
+                (
+                    "# Database".to_string(),
+                    vec!["DATABASE_URL".to_string(), "DATABASE_POOL".to_string()],
+                    "DATABASE_POOL=5",
+                ),
+                (
+                    "# Cache".to_string(),
+                    vec!["CACHE_KEY".to_string(), "CACHE_TTL".to_string()],
+                    "CACHE_TTL=60",
+                ),
+                ("# Empty section".to_string(), Vec::new(), "# Empty section"),
+            ]
+        );
+    }
+}
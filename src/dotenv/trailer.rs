@@ -0,0 +1,140 @@
+use std::ops::Range;
+
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+use crate::dotenv::DotenvFile;
+
+/// The first line of the trailer.
+const TRAILER_START: &str = "# --- azsync ---";
+
+/// The last line of the trailer.
+const TRAILER_END: &str = "# --- end azsync ---";
+
+/// A comment block at the end of a dotenv file recording when it was last
+/// synchronized, and with what.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Trailer {
+    /// When the file was synchronized.
+    pub synchronized: OffsetDateTime,
+
+    /// The profile selected from the config file, if any.
+    pub profile: Option<String>,
+
+    /// The stores the file was synchronized with, like
+    /// `Key Vault: https://my-vault.vault.azure.net/`.
+    pub stores: Vec<String>,
+}
+
+impl Trailer {
+    /// Formats the trailer as comment lines, each ending with `newline`.
+    fn render(&self, newline: &str) -> anyhow::Result<String> {
+        let synchronized = self.synchronized.replace_nanosecond(0)?.format(&Rfc3339)?;
+        let mut lines = vec![
+            TRAILER_START.to_string(),
+            format!("# Last synchronized: {synchronized}"),
+        ];
+        if let Some(profile) = &self.profile {
+            lines.push(format!("# Profile: {profile}"));
+        }
+        lines.extend(self.stores.iter().map(|store| format!("# {store}")));
+        lines.push(TRAILER_END.to_string());
+
+        Ok(lines.into_iter().map(|line| line + newline).collect())
+    }
+}
+
+impl DotenvFile {
+    /// Writes a trailer to the end of this file, returning the modified
+    /// contents.
+    ///
+    /// An existing trailer is replaced. If variables were added after it, it's
+    /// moved back to the end.
+    pub fn set_trailer(&self, trailer: &Trailer) -> anyhow::Result<String> {
+        let newline = if self.source.contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        };
+
+        let mut content = self.source.clone();
+        if let Some(span) = &self.trailer_span {
+            content.replace_range(span.clone(), "");
+        }
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push_str(newline);
+        }
+        content.push_str(&trailer.render(newline)?);
+
+        Ok(content)
+    }
+}
+
+/// Finds the source location of the trailer in a dotenv file, including the
+/// end of its last line.
+pub(super) fn find_trailer(source: &str) -> Option<Range<usize>> {
+    let mut start = None;
+    let mut offset = 0;
+    for line in source.split_inclusive('\n') {
+        let end = offset + line.len();
+        match line.trim_end() {
+            TRAILER_START => start = start.or(Some(offset)),
+            TRAILER_END => {
+                if let Some(start) = start {
+                    return Some(start..end);
+                }
+            }
+            _ => {}
+        }
+        offset = end;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use test_case::test_case;
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn trailer() -> Trailer {
+        Trailer {
+            synchronized: datetime!(2025-06-01 12:30:45.5 UTC),
+            profile: Some("prod".to_string()),
+            stores: vec!["Key Vault: https://my-vault.vault.azure.net/".to_string()],
+        }
+    }
+
+    const TRAILER: &str = "# --- azsync ---\n\
+        # Last synchronized: 2025-06-01T12:30:45Z\n\
+        # Profile: prod\n\
+        # Key Vault: https://my-vault.vault.azure.net/\n\
+        # --- end azsync ---\n";
+
+    #[test_case("", TRAILER; "empty")]
+    #[test_case("A=1", &format!("A=1\n{TRAILER}"); "no trailing newline")]
+    #[test_case(
+        "A=1\n# --- azsync ---\n# Last synchronized: 2020-01-01T00:00:00Z\n# --- end azsync ---\n",
+        &format!("A=1\n{TRAILER}");
+        "replaced"
+    )]
+    #[test_case(
+        "A=1\n# --- azsync ---\n# --- end azsync ---\nB=2\n",
+        &format!("A=1\nB=2\n{TRAILER}");
+        "moved to the end"
+    )]
+    #[test_case("A=1\n# --- azsync ---\nB=2\n", &format!("A=1\n# --- azsync ---\nB=2\n{TRAILER}"); "unterminated")]
+    fn set_trailer(source: &str, expected: &str) {
+        let dotenv = DotenvFile::parse(source).unwrap();
+        assert_eq!(expected, dotenv.set_trailer(&trailer()).unwrap());
+    }
+
+    #[test]
+    fn keeps_line_endings() {
+        let dotenv = DotenvFile::parse("A=1\r\n").unwrap();
+        let updated = dotenv.set_trailer(&trailer()).unwrap();
+        assert_eq!(format!("A=1\r\n{}", TRAILER.replace('\n', "\r\n")), updated);
+    }
+}
@@ -0,0 +1,202 @@
+use std::time::Duration;
+
+use anyhow::bail;
+use azure_storage_blob::BlobContainerClient;
+use serde::Deserialize;
+use tokio::time::timeout;
+use tracing::debug;
+use typespec_client_core::{
+    error::HttpError,
+    http::{Method, Request, StatusCode, new_http_client},
+};
+use url::Url;
+
+/// The error code storage accounts respond with when their network rules
+/// reject a request.
+const STORAGE_DENIED: &str = "AuthorizationFailure";
+
+/// A service that responds with the caller's public IP address.
+const ADDRESS_LOOKUP_URL: &str = "https://api.ipify.org";
+
+/// How long to wait for the caller's public IP address.
+const ADDRESS_LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Explains why a request was rejected if it was blocked by a Key Vault's or
+/// storage account's network rules, which otherwise show up as a bare 403
+/// error.
+pub fn explain(error: &anyhow::Error) -> Option<String> {
+    let http_error = find_forbidden(error)?;
+    if http_error.error_code() == Some(STORAGE_DENIED) {
+        return Some(explain_storage(None, None));
+    }
+
+    explain_key_vault(http_error.body())
+}
+
+/// Checks that a storage account's network rules allow requests to a
+/// container before starting a run that sends many of them.
+///
+/// Other errors are left for the run itself to report.
+pub async fn preflight_storage(client: &BlobContainerClient, endpoint: &Url) -> anyhow::Result<()> {
+    let Err(error) = client.get_properties(None).await else {
+        return Ok(());
+    };
+    let error = anyhow::Error::from(error);
+    if find_forbidden(&error).and_then(HttpError::error_code) != Some(STORAGE_DENIED) {
+        debug!("Ignoring failed preflight request: {error:#}");
+        return Ok(());
+    }
+
+    let account = endpoint.host_str().and_then(|host| host.split('.').next());
+    let address = public_address().await;
+    bail!("{}", explain_storage(account, address.as_deref()))
+}
+
+/// Finds a 403 response in an error's chain.
+fn find_forbidden(error: &anyhow::Error) -> Option<&HttpError> {
+    error
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<azure_core::Error>())
+        .find_map(HttpError::try_from)
+        .filter(|http_error| http_error.status() == StatusCode::Forbidden)
+}
+
+/// Looks up the public IP address requests are sent from, if possible.
+async fn public_address() -> Option<String> {
+    let url = Url::parse(ADDRESS_LOOKUP_URL).ok()?;
+    let request = Request::new(url, Method::Get);
+    let lookup = async {
+        let response = new_http_client().execute_request(&request).await.ok()?;
+        if !response.status().is_success() {
+            return None;
+        }
+        let body = response.into_body().collect().await.ok()?;
+        let address = String::from_utf8(body.to_vec()).ok()?;
+        Some(address.trim().to_string()).filter(|address| !address.is_empty())
+    };
+    timeout(ADDRESS_LOOKUP_TIMEOUT, lookup).await.ok().flatten()
+}
+
+/// Explains a storage account rejecting a request because of its firewall,
+/// virtual network, or private endpoint settings.
+fn explain_storage(account: Option<&str>, address: Option<&str>) -> String {
+    let account = account.map_or_else(
+        || "The storage account".to_string(),
+        |account| format!("Storage account {account}"),
+    );
+    let address = address
+        .map(|address| format!(" from {address}"))
+        .unwrap_or_default();
+    format!(
+        "{account} rejected the request{address} because its network rules don't allow your \
+         network. Requests are only accepted from the IP ranges and virtual network subnets \
+         allowed in the account's networking settings, or through a private endpoint if public \
+         network access is disabled. Ask for your address or subnet to be allowed, or run azsync \
+         from a network that's allowed, like over a VPN."
+    )
+}
+
+/// Explains a Key Vault error response if it was caused by the vault's
+/// firewall or private endpoint settings.
+fn explain_key_vault(body: &[u8]) -> Option<String> {
+    let ErrorResponse { error } = serde_json::from_slice(body).ok()?;
+    let message = error.message.unwrap_or_default();
+    let field = |name: &str| {
+        message
+            .lines()
+            .find_map(|line| line.trim().strip_prefix(name)?.strip_prefix(": "))
+            .map(str::to_string)
+    };
+    let name = field("Vault").and_then(|vault| Some(vault.split(';').next()?.to_string()));
+    let vault = name.as_ref().map_or_else(
+        || "The Key Vault".to_string(),
+        |name| format!("Key Vault {name}"),
+    );
+    let private_link = name
+        .map(|name| {
+            format!(
+                " If the vault has a private endpoint, {name}.vault.azure.net needs to resolve to \
+                 {name}.privatelink.vaultcore.azure.net (or the equivalent in your cloud) from \
+                 your network."
+            )
+        })
+        .unwrap_or_default();
+
+    match error.innererror.and_then(|inner| inner.code).as_deref() {
+        Some("ForbiddenByFirewall") => {
+            let address = field("Client address")
+                .map(|address| format!(" from {address}"))
+                .unwrap_or_default();
+            Some(format!(
+                "{vault} rejected the request{address} because its firewall doesn't allow your \
+                 network. Ask for your address to be allowed in the vault's networking settings, \
+                 or run azsync from a network that's allowed, like over a VPN.{private_link}"
+            ))
+        }
+        Some("ForbiddenByConnection") => Some(format!(
+            "{vault} has public network access disabled, so it only accepts requests through a \
+             private endpoint. Run azsync from a network connected to one.{private_link}"
+        )),
+        _ => None,
+    }
+}
+
+/// An error response from Key Vault.
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: ErrorDetails,
+}
+
+/// The details of an error from Key Vault.
+#[derive(Deserialize)]
+struct ErrorDetails {
+    message: Option<String>,
+    innererror: Option<InnerError>,
+}
+
+/// The more specific cause of an error from Key Vault.
+#[derive(Deserialize)]
+struct InnerError {
+    code: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(
+        r#"{"error":{"code":"Forbidden","message":"Client address is not authorized and caller is not a trusted service.\r\nClient address: 203.0.113.5\r\nCaller: appid=1\r\nVault: my-vault;location=westus2","innererror":{"code":"ForbiddenByFirewall"}}}"#
+        => Some("Key Vault my-vault rejected the request from 203.0.113.5 because its firewall doesn't allow your network. Ask for your address to be allowed in the vault's networking settings, or run azsync from a network that's allowed, like over a VPN. If the vault has a private endpoint, my-vault.vault.azure.net needs to resolve to my-vault.privatelink.vaultcore.azure.net (or the equivalent in your cloud) from your network.".to_string());
+        "firewall"
+    )]
+    #[test_case(
+        r#"{"error":{"code":"Forbidden","message":"Public network access is disabled and request is not from a trusted service nor via an approved private link.","innererror":{"code":"ForbiddenByConnection"}}}"#
+        => Some("The Key Vault has public network access disabled, so it only accepts requests through a private endpoint. Run azsync from a network connected to one.".to_string());
+        "private endpoint only"
+    )]
+    #[test_case(
+        r#"{"error":{"code":"Forbidden","message":"The user does not have secrets get permission.","innererror":{"code":"AccessDenied"}}}"#
+        => None;
+        "not network"
+    )]
+    #[test_case("not json" => None; "invalid body")]
+    fn key_vault(body: &str) -> Option<String> {
+        explain_key_vault(body.as_bytes())
+    }
+
+    #[test_case(
+        Some("myaccount"), Some("203.0.113.5")
+        => "Storage account myaccount rejected the request from 203.0.113.5 because its network rules don't allow your network. Requests are only accepted from the IP ranges and virtual network subnets allowed in the account's networking settings, or through a private endpoint if public network access is disabled. Ask for your address or subnet to be allowed, or run azsync from a network that's allowed, like over a VPN.";
+        "known account and address"
+    )]
+    #[test_case(
+        None, None
+        => "The storage account rejected the request because its network rules don't allow your network. Requests are only accepted from the IP ranges and virtual network subnets allowed in the account's networking settings, or through a private endpoint if public network access is disabled. Ask for your address or subnet to be allowed, or run azsync from a network that's allowed, like over a VPN.";
+        "unknown account and address"
+    )]
+    fn storage(account: Option<&str>, address: Option<&str>) -> String {
+        explain_storage(account, address)
+    }
+}
@@ -0,0 +1,144 @@
+use std::fmt::{Display, Formatter, Write};
+
+use similar::{ChangeTag, TextDiff};
+use time::{Duration, OffsetDateTime};
+
+/// A number of bytes, displayed in human-readable units.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub struct Size(pub u64);
+
+impl Display for Size {
+    #[expect(clippy::cast_precision_loss, reason = "Only used for display")]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB"];
+
+        if self.0 < 1024 {
+            return write!(f, "{} B", self.0);
+        }
+
+        let mut value = self.0 as f64 / 1024.0;
+        let mut unit = UNITS[0];
+        for &next in &UNITS[1..] {
+            if value < 1024.0 {
+                break;
+            }
+            value /= 1024.0;
+            unit = next;
+        }
+
+        write!(f, "{value:.1} {unit}")
+    }
+}
+
+/// A length of time, displayed in its two largest units (like `3h 12m`).
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub struct Elapsed(pub Duration);
+
+impl Display for Elapsed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        const UNITS: &[(&str, i64)] = &[("d", 86_400), ("h", 3_600), ("m", 60), ("s", 1)];
+
+        let mut seconds = self.0.whole_seconds().unsigned_abs();
+        let mut parts = Vec::with_capacity(2);
+        for &(unit, size) in UNITS {
+            let size = size.unsigned_abs();
+            let count = seconds / size;
+            seconds %= size;
+            if count > 0 || !parts.is_empty() {
+                parts.push(format!("{count}{unit}"));
+            }
+            if parts.len() == 2 {
+                break;
+            }
+        }
+
+        if parts.is_empty() {
+            return write!(f, "0s");
+        }
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+/// Describes which of two copies was modified more recently, and by how much
+/// (like `local newer by 3h 12m`).
+pub fn newer_side(local: OffsetDateTime, remote: OffsetDateTime) -> String {
+    let difference = local - remote;
+    if difference.whole_seconds() == 0 {
+        "same time".to_string()
+    } else if difference.is_positive() {
+        format!("local newer by {}", Elapsed(difference))
+    } else {
+        format!("remote newer by {}", Elapsed(difference))
+    }
+}
+
+/// What each word of a value is replaced with when values are hidden.
+const MASK: &str = "***";
+
+/// Formats a word-level diff from one value to another, like
+/// `git diff --word-diff`.
+///
+/// Removed words are shown as `[-word-]` and added words as `{+word+}`. Unless
+/// `show_values` is set, each word is masked so that only the shape of the
+/// change is shown.
+pub fn word_diff(old: &str, new: &str, show_values: bool) -> String {
+    let mut output = String::new();
+    for change in TextDiff::from_words(old, new).iter_all_changes() {
+        let word = change.value();
+        let word = if show_values || word.trim().is_empty() {
+            word
+        } else {
+            MASK
+        };
+        let _ = match change.tag() {
+            ChangeTag::Equal => write!(output, "{word}"),
+            ChangeTag::Delete => write!(output, "[-{word}-]"),
+            ChangeTag::Insert => write!(output, "{{+{word}+}}"),
+        };
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(0 => "0 B"; "zero")]
+    #[test_case(1023 => "1023 B"; "bytes")]
+    #[test_case(1024 => "1.0 KiB"; "kibibytes")]
+    #[test_case(1536 => "1.5 KiB"; "fractional")]
+    #[test_case(5 * 1024 * 1024 * 1024 => "5.0 GiB"; "gibibytes")]
+    #[test_case(u64::MAX => "16777216.0 TiB"; "max")]
+    fn size(bytes: u64) -> String {
+        Size(bytes).to_string()
+    }
+
+    #[test_case(Duration::ZERO => "0s"; "zero")]
+    #[test_case(Duration::seconds(45) => "45s"; "seconds")]
+    #[test_case(Duration::seconds(3 * 3600 + 12 * 60 + 5) => "3h 12m"; "hours and minutes")]
+    #[test_case(Duration::hours(2) => "2h 0m"; "whole hours")]
+    #[test_case(Duration::days(3) + Duration::hours(4) => "3d 4h"; "days")]
+    #[test_case(Duration::minutes(-5) => "5m 0s"; "negative")]
+    fn elapsed(duration: Duration) -> String {
+        Elapsed(duration).to_string()
+    }
+
+    #[test_case(0 => "same time"; "same")]
+    #[test_case(90 => "local newer by 1m 30s"; "local newer")]
+    #[test_case(-7200 => "remote newer by 2h 0m"; "remote newer")]
+    fn newer(seconds: i64) -> String {
+        let remote = OffsetDateTime::UNIX_EPOCH;
+        newer_side(remote + Duration::seconds(seconds), remote)
+    }
+
+    #[test_case("a b c", "a x c", true => "a [-b-]{+x+} c"; "changed word")]
+    #[test_case("a b", "a b c", true => "a b{+ +}{+c+}"; "added word")]
+    #[test_case("a b c", "a x c", false => "*** [-***-]{+***+} ***"; "masked")]
+    #[test_case("same", "same", false => "***"; "unchanged")]
+    fn words(old: &str, new: &str, show_values: bool) -> String {
+        word_diff(old, new, show_values)
+    }
+}
@@ -0,0 +1,120 @@
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use anyhow::ensure;
+use serde::{Deserialize, Deserializer, de};
+
+/// A pattern matching relative paths, like `*.log` or `build/**`.
+///
+/// `*` matches anything except `/`, `**` matches anything including `/`, and
+/// `?` matches any single character except `/`. Patterns without a `/` match
+/// the name of any file or directory in the path, so `node_modules` excludes
+/// everything inside of a `node_modules` directory. Patterns with a `/` match
+/// the whole path.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Pattern(String);
+
+impl Pattern {
+    /// Checks whether a relative path, using `/` as its separator, matches this
+    /// pattern.
+    pub fn matches(&self, path: &str) -> bool {
+        let pattern: Vec<_> = self.0.chars().collect();
+        if pattern.contains(&'/') {
+            let pattern = pattern.strip_prefix(&['/']).unwrap_or(&pattern);
+            let path: Vec<_> = path.chars().collect();
+            glob_match(pattern, &path)
+        } else {
+            path.split('/').any(|component| {
+                let component: Vec<_> = component.chars().collect();
+                glob_match(&pattern, &component)
+            })
+        }
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ensure!(!s.is_empty(), "Patterns can't be empty");
+
+        // Directories match everything inside of them
+        let pattern = match s.strip_suffix('/') {
+            Some(directory) => format!("{directory}/**"),
+            None => s.to_string(),
+        };
+        Ok(Self(pattern))
+    }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Matches text against a glob pattern.
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', '/', rest @ ..] => {
+            // `**/` matches any number of directories, including none
+            glob_match(rest, text)
+                || (0..text.len())
+                    .filter(|&index| text[index] == '/')
+                    .any(|index| glob_match(rest, &text[index + 1..]))
+        }
+        ['*', '*', rest @ ..] => (0..=text.len()).any(|index| glob_match(rest, &text[index..])),
+        ['*', rest @ ..] => {
+            let end = text.iter().position(|&c| c == '/').unwrap_or(text.len());
+            (0..=end).any(|index| glob_match(rest, &text[index..]))
+        }
+        ['?', rest @ ..] => {
+            matches!(text.first(), Some(&c) if c != '/') && glob_match(rest, &text[1..])
+        }
+        [c, rest @ ..] => text.first() == Some(c) && glob_match(rest, &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("*.log", "app.log" => true; "extension")]
+    #[test_case("*.log", "logs/app.log" => true; "extension in directory")]
+    #[test_case("*.log", "app.log.txt" => false; "different extension")]
+    #[test_case("node_modules", "web/node_modules/a/b.js" => true; "directory name")]
+    #[test_case("data?.csv", "data1.csv" => true; "single character")]
+    #[test_case("data?.csv", "data10.csv" => false; "too many characters")]
+    #[test_case("build/*", "build/a.txt" => true; "files in directory")]
+    #[test_case("build/*", "build/a/b.txt" => false; "star doesn't cross directories")]
+    #[test_case("build/**", "build/a/b.txt" => true; "double star crosses directories")]
+    #[test_case("build/", "build/a/b.txt" => true; "trailing slash")]
+    #[test_case("/build/*", "build/a.txt" => true; "leading slash")]
+    #[test_case("**/*.tmp", "a.tmp" => true; "double star matches no directories")]
+    #[test_case("**/*.tmp", "a/b/c.tmp" => true; "double star matches directories")]
+    #[test_case("src/*.rs", "other/src/main.rs" => false; "anchored")]
+    fn matches(pattern: &str, path: &str) -> bool {
+        pattern.parse::<Pattern>().unwrap().matches(path)
+    }
+
+    #[test]
+    fn empty() {
+        assert!("".parse::<Pattern>().is_err());
+    }
+}
@@ -0,0 +1,272 @@
+use std::{collections::BTreeSet, sync::Arc};
+
+use anyhow::{Context, bail};
+use azure_core::credentials::{AccessToken, TokenCredential};
+use azure_identity::{AzureCliCredential, AzureDeveloperCliCredential};
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use serde::{Deserialize, de::DeserializeOwned};
+use typespec_client_core::http::{HttpClient, Method, Request, new_http_client};
+use url::Url;
+
+/// Scope for requests to Azure Resource Manager.
+pub const MANAGEMENT_SCOPE: &str = "https://management.azure.com/.default";
+
+/// Finds the first credential that can get a token, trying them in the same
+/// order as `DefaultAzureCredential`.
+///
+/// Returns the name of the credential and the token it got for `scope`.
+pub async fn find_credential(scope: &str) -> anyhow::Result<(&'static str, AccessToken)> {
+    let mut errors = Vec::new();
+    let credentials: [(&'static str, anyhow::Result<Arc<dyn TokenCredential>>); 2] = [
+        (
+            "Azure CLI",
+            AzureCliCredential::new(None)
+                .map(|credential| credential as _)
+                .map_err(Into::into),
+        ),
+        (
+            "Azure Developer CLI",
+            AzureDeveloperCliCredential::new(None)
+                .map(|credential| credential as _)
+                .map_err(Into::into),
+        ),
+    ];
+    for (name, credential) in credentials {
+        match credential {
+            Ok(credential) => match credential.get_token(&[scope], None).await {
+                Ok(token) => return Ok((name, token)),
+                Err(error) => errors.push(format!("{name}: {error}")),
+            },
+            Err(error) => errors.push(format!("{name}: {error}")),
+        }
+    }
+
+    bail!("No credential could get a token:\n{}", errors.join("\n"));
+}
+
+/// Claims about the identity an access token was issued to.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Claims {
+    /// The object ID of the user or service principal.
+    pub oid: Option<String>,
+
+    /// The tenant ID.
+    pub tid: Option<String>,
+
+    /// The application ID (v1 tokens).
+    pub appid: Option<String>,
+
+    /// The application ID (v2 tokens).
+    pub azp: Option<String>,
+
+    /// The user's sign-in name.
+    pub upn: Option<String>,
+
+    /// The user's name, if `upn` isn't set.
+    pub unique_name: Option<String>,
+
+    /// The resource the token is for.
+    pub aud: Option<String>,
+
+    /// Delegated scopes, separated by spaces.
+    pub scp: Option<String>,
+
+    /// Application roles.
+    pub roles: Option<Vec<String>>,
+}
+
+impl Claims {
+    /// Reads the claims from a JWT access token without validating it.
+    pub fn from_token(token: &str) -> anyhow::Result<Self> {
+        let payload = token.split('.').nth(1).context("Malformed access token")?;
+        let payload = BASE64_URL_SAFE_NO_PAD
+            .decode(payload.trim_end_matches('='))
+            .context("Malformed access token")?;
+        serde_json::from_slice(&payload).context("Malformed access token claims")
+    }
+
+    /// Gets the application ID the token was issued to.
+    pub fn app_id(&self) -> Option<&str> {
+        self.appid.as_deref().or(self.azp.as_deref())
+    }
+
+    /// Gets the sign-in name of the user the token was issued to.
+    pub fn user_name(&self) -> Option<&str> {
+        self.upn.as_deref().or(self.unique_name.as_deref())
+    }
+
+    /// Gets the scopes and roles granted by the token.
+    pub fn scopes(&self) -> Vec<&str> {
+        self.scp
+            .iter()
+            .flat_map(|scp| scp.split_whitespace())
+            .chain(self.roles.iter().flatten().map(String::as_str))
+            .collect()
+    }
+}
+
+/// A minimal Azure Resource Manager client.
+pub struct ResourceManager {
+    client: Arc<dyn HttpClient>,
+    token: String,
+}
+
+impl ResourceManager {
+    /// Creates a client authenticated with a token for [`MANAGEMENT_SCOPE`].
+    pub fn new(token: &AccessToken) -> Self {
+        Self {
+            client: new_http_client(),
+            token: token.token.secret().to_string(),
+        }
+    }
+
+    /// Finds the ID of a resource by its type and name in any subscription
+    /// the identity can see.
+    pub async fn find_resource(
+        &self,
+        resource_type: &str,
+        name: &str,
+    ) -> anyhow::Result<Option<String>> {
+        let subscriptions: List<Subscription> =
+            self.get("/subscriptions", "2022-12-01", None).await?;
+        for subscription in subscriptions.value {
+            let filter = format!("resourceType eq '{resource_type}' and name eq '{name}'");
+            let resources: List<Resource> = self
+                .get(
+                    &format!("/subscriptions/{}/resources", subscription.subscription_id),
+                    "2021-04-01",
+                    Some(&filter),
+                )
+                .await?;
+            if let Some(resource) = resources.value.into_iter().next() {
+                return Ok(Some(resource.id));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Gets the names of the roles assigned to a principal that apply to a
+    /// resource, including ones inherited from groups and parent scopes.
+    pub async fn role_names(
+        &self,
+        resource_id: &str,
+        principal_id: &str,
+    ) -> anyhow::Result<BTreeSet<String>> {
+        let assignments: List<RoleAssignment> = self
+            .get(
+                &format!("{resource_id}/providers/Microsoft.Authorization/roleAssignments"),
+                "2022-04-01",
+                Some(&format!("assignedTo('{principal_id}')")),
+            )
+            .await?;
+        let definition_ids: BTreeSet<_> = assignments
+            .value
+            .into_iter()
+            .map(|assignment| assignment.properties.role_definition_id)
+            .collect();
+
+        let mut names = BTreeSet::new();
+        for definition_id in definition_ids {
+            let definition: RoleDefinition = self.get(&definition_id, "2022-04-01", None).await?;
+            names.insert(definition.properties.role_name);
+        }
+
+        Ok(names)
+    }
+
+    /// Sends a GET request for a resource path.
+    async fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        api_version: &str,
+        filter: Option<&str>,
+    ) -> anyhow::Result<T> {
+        let mut url = Url::parse("https://management.azure.com/")?.join(path)?;
+        url.query_pairs_mut()
+            .append_pair("api-version", api_version);
+        if let Some(filter) = filter {
+            url.query_pairs_mut().append_pair("$filter", filter);
+        }
+
+        let mut request = Request::new(url, Method::Get);
+        request.insert_header("authorization", format!("Bearer {}", self.token));
+        let response = self.client.execute_request(&request).await?;
+        let status = response.status();
+        let body = response.into_body().collect().await?;
+        if !status.is_success() {
+            bail!(
+                "Request to Azure Resource Manager failed ({status}): {}",
+                String::from_utf8_lossy(&body)
+            );
+        }
+
+        serde_json::from_slice(&body).context("Invalid response from Azure Resource Manager")
+    }
+}
+
+/// A list of resources returned by Azure Resource Manager.
+#[derive(Deserialize)]
+struct List<T> {
+    value: Vec<T>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Subscription {
+    subscription_id: String,
+}
+
+#[derive(Deserialize)]
+struct Resource {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct RoleAssignment {
+    properties: RoleAssignmentProperties,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RoleAssignmentProperties {
+    role_definition_id: String,
+}
+
+#[derive(Deserialize)]
+struct RoleDefinition {
+    properties: RoleDefinitionProperties,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RoleDefinitionProperties {
+    role_name: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn claims_from_token() {
+        let payload = BASE64_URL_SAFE_NO_PAD.encode(
+            r#"{"oid":"1234","tid":"5678","appid":"abcd","upn":"someone@example.com","scp":"user_impersonation openid"}"#,
+        );
+        let claims = Claims::from_token(&format!("header.{payload}.signature")).unwrap();
+
+        assert_eq!(claims.oid.as_deref(), Some("1234"));
+        assert_eq!(claims.tid.as_deref(), Some("5678"));
+        assert_eq!(claims.app_id(), Some("abcd"));
+        assert_eq!(claims.user_name(), Some("someone@example.com"));
+        assert_eq!(claims.scopes(), vec!["user_impersonation", "openid"]);
+    }
+
+    #[test]
+    fn malformed_token() {
+        assert!(Claims::from_token("not a token").is_err());
+    }
+}
@@ -0,0 +1,55 @@
+use std::{
+    fs::{File, TryLockError, create_dir_all},
+    path::Path,
+};
+
+use anyhow::{Context, bail};
+use tracing::info;
+
+/// Where the lock held while modifying local files is.
+pub const LOCK_FILE: &str = ".azsync/lock";
+
+/// An advisory lock on the local files azsync modifies in a directory, like the
+/// dotenv file and the state it records.
+///
+/// The lock is released when this is dropped, or when the process exits.
+#[derive(Debug)]
+pub struct RunLock {
+    _file: File,
+}
+
+impl RunLock {
+    /// Acquires the lock, waiting for other processes to release it if `wait`
+    /// is set, and failing immediately otherwise.
+    pub async fn acquire(wait: bool) -> anyhow::Result<Self> {
+        let path = Path::new(LOCK_FILE);
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let file = File::options()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(path)
+            .with_context(|| format!("Failed to open {LOCK_FILE}"))?;
+
+        let file = match file.try_lock() {
+            Ok(()) => file,
+            Err(TryLockError::WouldBlock) if wait => {
+                info!("Waiting for another azsync process in this directory to finish");
+                tokio::task::spawn_blocking(move || file.lock().map(|()| file))
+                    .await?
+                    .with_context(|| format!("Failed to lock {LOCK_FILE}"))?
+            }
+            Err(TryLockError::WouldBlock) => bail!(
+                "Another azsync process is running in this directory (pass --wait-for-lock to wait for it)"
+            ),
+            Err(TryLockError::Error(error)) => {
+                return Err(error).with_context(|| format!("Failed to lock {LOCK_FILE}"));
+            }
+        };
+
+        Ok(Self { _file: file })
+    }
+}
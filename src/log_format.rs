@@ -0,0 +1,255 @@
+use std::{
+    borrow::Cow,
+    fmt::{Debug, Write},
+    time::Instant,
+};
+
+use serde::{Serialize, Serializer, ser::SerializeMap};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use tracing::{
+    Event, Subscriber,
+    field::{Field, Visit},
+    span::{Attributes, Id},
+};
+use tracing_subscriber::{
+    Layer,
+    fmt::{FmtContext, FormatEvent, FormatFields, format::Writer},
+    layer::Context,
+    registry::LookupSpan,
+};
+
+/// The target of the event logged when each action finishes.
+///
+/// These events are for log aggregators, so text logs leave them out unless
+/// they're verbose.
+pub const ACTION_TARGET: &str = "azsync::action";
+
+/// Formats events as structured records that log aggregators can parse.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StructuredFormat {
+    /// One JSON object per line.
+    Json,
+
+    /// `key=value` pairs, one line per event.
+    Logfmt,
+}
+
+/// Formats events as structured records, tagged with the command that's
+/// running.
+#[derive(Clone, Copy, Debug)]
+pub struct StructuredLogs {
+    /// The format to write records in.
+    pub format: StructuredFormat,
+
+    /// The name of the command that's running, like `dotenv`.
+    pub command: &'static str,
+}
+
+impl<S, N> FormatEvent<S, N> for StructuredLogs
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> std::fmt::Result {
+        let mut fields = FieldVisitor::default();
+        event.record(&mut fields);
+
+        // Actions report how long they took, measured from when their span
+        // started
+        if event.metadata().target() == ACTION_TARGET
+            && let Some(started) = ctx.event_scope().and_then(|scope| {
+                scope
+                    .into_iter()
+                    .find_map(|span| span.extensions().get::<Started>().map(|started| started.0))
+            })
+        {
+            fields
+                .0
+                .push(("duration_ms", started.elapsed().as_millis().to_string()));
+        }
+
+        let record = LogRecord {
+            time: OffsetDateTime::now_utc()
+                .format(&Rfc3339)
+                .unwrap_or_default(),
+            level: event.metadata().level().as_str(),
+            target: event.metadata().target(),
+            command: self.command,
+            fields: fields.0,
+        };
+
+        match self.format {
+            StructuredFormat::Json => {
+                writeln!(writer, "{}", record.to_json().map_err(|_| std::fmt::Error)?)
+            }
+            StructuredFormat::Logfmt => writeln!(writer, "{}", record.to_logfmt()),
+        }
+    }
+}
+
+/// Records when each span started, so events logged in it can include how
+/// long it's been running.
+pub struct SpanTimings;
+
+/// When a span started.
+struct Started(Instant);
+
+impl<S> Layer<S> for SpanTimings
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, _attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Started(Instant::now()));
+        }
+    }
+}
+
+/// Collects the fields of an event, including its message.
+#[derive(Default)]
+struct FieldVisitor(Vec<(&'static str, String)>);
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.push((field.name(), value.to_string()));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0.push((field.name(), format!("{value:?}")));
+    }
+}
+
+/// A single log message and its fields.
+struct LogRecord<'a> {
+    time: String,
+    level: &'a str,
+    target: &'a str,
+    command: &'a str,
+    fields: Vec<(&'static str, String)>,
+}
+
+/// Serializes the record as an object with its fields in order, which a map
+/// wouldn't keep.
+impl Serialize for LogRecord<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(4 + self.fields.len()))?;
+        map.serialize_entry("timestamp", &self.time)?;
+        map.serialize_entry("level", self.level)?;
+        map.serialize_entry("target", self.target)?;
+        map.serialize_entry("command", self.command)?;
+        for (name, value) in &self.fields {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+impl LogRecord<'_> {
+    /// Formats the record as a JSON object.
+    fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+
+    /// Formats the record as `key=value` pairs.
+    fn to_logfmt(&self) -> String {
+        let mut line = format!(
+            "time={} level={} target={} command={}",
+            self.time,
+            self.level.to_lowercase(),
+            logfmt_value(self.target),
+            logfmt_value(self.command)
+        );
+        for (name, value) in &self.fields {
+            let name = if *name == "message" { "msg" } else { name };
+            let _ = write!(line, " {name}={}", logfmt_value(value));
+        }
+
+        line
+    }
+}
+
+/// Quotes a logfmt value if needed.
+fn logfmt_value(value: &str) -> Cow<'_, str> {
+    let needs_quotes = value.is_empty()
+        || value
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || matches!(c, '=' | '"' | '\\'));
+    if !needs_quotes {
+        return value.into();
+    }
+
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn record() -> LogRecord<'static> {
+        LogRecord {
+            time: "2024-01-02T03:04:05Z".to_string(),
+            level: "INFO",
+            target: "azsync::commands::file",
+            command: "file",
+            fields: vec![
+                ("message", "Pushed config.json".to_string()),
+                ("count", "2".to_string()),
+            ],
+        }
+    }
+
+    #[test]
+    fn json() {
+        assert_eq!(
+            record().to_json().unwrap(),
+            r#"{"timestamp":"2024-01-02T03:04:05Z","level":"INFO","target":"azsync::commands::file","command":"file","message":"Pushed config.json","count":"2"}"#
+        );
+    }
+
+    #[test]
+    fn json_escapes() {
+        let mut record = record();
+        record.fields = vec![("message", "say \"hi\"\n\u{1}".to_string())];
+        assert_eq!(
+            record.to_json().unwrap(),
+            r#"{"timestamp":"2024-01-02T03:04:05Z","level":"INFO","target":"azsync::commands::file","command":"file","message":"say \"hi\"\n\u0001"}"#
+        );
+    }
+
+    #[test]
+    fn logfmt() {
+        assert_eq!(
+            record().to_logfmt(),
+            r#"time=2024-01-02T03:04:05Z level=info target=azsync::commands::file command=file msg="Pushed config.json" count=2"#
+        );
+    }
+
+    #[test_case("plain", "plain"; "plain")]
+    #[test_case("", r#""""#; "empty")]
+    #[test_case("a b", r#""a b""#; "space")]
+    #[test_case("a=b", r#""a=b""#; "equals")]
+    #[test_case("say \"hi\"\n", r#""say \"hi\"\n""#; "escapes")]
+    fn logfmt_values(value: &str, expected: &str) {
+        assert_eq!(logfmt_value(value), expected);
+    }
+}
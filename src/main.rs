@@ -1,10 +1,38 @@
+mod acl;
+mod annotation;
 mod app;
+mod app_config;
+mod audit;
+mod batch;
 mod cli;
 mod commands;
+mod config;
+mod conflicts;
 mod dotenv;
+mod firewall;
+mod format;
+mod glob;
+mod identity;
+mod lock;
+mod log_format;
+mod manifest;
+mod metrics;
+mod progress;
+mod secrets;
+mod setup;
+mod state;
+mod storage;
 mod sync;
+mod synced;
+mod tracked;
+mod trash;
+mod usage;
+mod versions;
+mod watch;
+
+use std::process::ExitCode;
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    app::run().await
+async fn main() -> ExitCode {
+    Box::pin(app::run()).await
 }
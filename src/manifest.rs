@@ -0,0 +1,141 @@
+use std::{
+    collections::BTreeMap,
+    fs::{create_dir_all, read_to_string, remove_file, write},
+    io::ErrorKind,
+    path::Path,
+    sync::Mutex,
+};
+
+use anyhow::Context;
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
+
+/// Where the state azsync keeps about the current directory is recorded, like
+/// what each resource looked like when it was last synchronized.
+pub const MANIFEST_FILE: &str = ".azsync/manifest.json";
+
+/// Held while the manifest is updated, since jobs in `azsync batch` can run at
+/// the same time. Other processes are kept out by the
+/// [`RunLock`](crate::lock::RunLock).
+static MANIFEST: Mutex<()> = Mutex::new(());
+
+/// A part of the manifest, holding entries by the resource or inputs they're
+/// for.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Section {
+    /// What resources looked like when they were last synchronized.
+    Synced,
+
+    /// The version counters of resources when they were last synchronized.
+    Versions,
+
+    /// The state left by previous runs, to skip identical ones.
+    Runs,
+}
+
+impl Section {
+    /// Gets the key the section is stored under.
+    fn key(self) -> &'static str {
+        match self {
+            Self::Synced => "synced",
+            Self::Versions => "versions",
+            Self::Runs => "runs",
+        }
+    }
+
+    /// Gets the file the section was kept in before there was a manifest,
+    /// which is read until the section is first saved.
+    fn legacy_file(self) -> &'static str {
+        match self {
+            Self::Synced => ".azsync-synced.json",
+            Self::Versions => ".azsync-versions.json",
+            Self::Runs => ".azsync-state.json",
+        }
+    }
+}
+
+/// Loads the entries in a section of a manifest, if it has any.
+pub fn load_entries<T: DeserializeOwned>(
+    path: &Path,
+    section: Section,
+) -> anyhow::Result<BTreeMap<String, T>> {
+    let manifest: Option<BTreeMap<String, Value>> = read_json(path)?;
+    let value = match manifest.and_then(|mut manifest| manifest.remove(section.key())) {
+        Some(value) => value,
+        None => match read_json::<Value>(Path::new(section.legacy_file()))? {
+            Some(value) => value,
+            None => return Ok(BTreeMap::new()),
+        },
+    };
+
+    serde_json::from_value(value)
+        .with_context(|| format!("Failed to parse {} in {}", section.key(), path.display()))
+}
+
+/// Writes entries to a section of a manifest, keeping the other entries in
+/// it, including any written since it was loaded.
+pub fn save_entries<T: Serialize + DeserializeOwned>(
+    path: &Path,
+    section: Section,
+    entries: impl IntoIterator<Item = (String, T)>,
+) -> anyhow::Result<()> {
+    let _guard = MANIFEST
+        .lock()
+        .expect("manifest lock should not be poisoned");
+    let mut all = load_entries(path, section)?;
+    all.extend(entries);
+
+    let mut manifest: BTreeMap<String, Value> = read_json(path)?.unwrap_or_default();
+    manifest.insert(section.key().to_string(), serde_json::to_value(all)?);
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+    let json = serde_json::to_string_pretty(&manifest)?;
+    write(path, json).with_context(|| format!("Failed to write {}", path.display()))?;
+
+    // The manifest holds everything from the old file now
+    match remove_file(section.legacy_file()) {
+        Err(error) if error.kind() != ErrorKind::NotFound => {
+            Err(error).with_context(|| format!("Failed to remove {}", section.legacy_file()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Reads a JSON file, if it exists.
+fn read_json<T: DeserializeOwned>(path: &Path) -> anyhow::Result<Option<T>> {
+    match read_to_string(path) {
+        Ok(source) => serde_json::from_str(&source)
+            .map(Some)
+            .with_context(|| format!("Failed to parse {}", path.display())),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn sections_keep_other_entries() {
+        let path =
+            std::env::temp_dir().join(format!("azsync-manifest-{}.json", std::process::id()));
+        save_entries(&path, Section::Synced, [("a".to_string(), 1)]).unwrap();
+        save_entries(&path, Section::Synced, [("b".to_string(), 2)]).unwrap();
+        save_entries(&path, Section::Runs, [("c".to_string(), 3)]).unwrap();
+
+        let synced: BTreeMap<String, u32> = load_entries(&path, Section::Synced).unwrap();
+        let runs: BTreeMap<String, u32> = load_entries(&path, Section::Runs).unwrap();
+        let versions: BTreeMap<String, u32> = load_entries(&path, Section::Versions).unwrap();
+        let _ = remove_file(&path);
+        assert_eq!(
+            synced,
+            BTreeMap::from([("a".to_string(), 1), ("b".to_string(), 2)])
+        );
+        assert_eq!(runs, BTreeMap::from([("c".to_string(), 3)]));
+        assert_eq!(versions, BTreeMap::new());
+    }
+}
@@ -0,0 +1,174 @@
+use std::{net::IpAddr, sync::LazyLock, time::Duration};
+
+use anyhow::Context;
+use prometheus_client::{
+    encoding::{EncodeLabelSet, text::encode},
+    metrics::{counter::Counter, family::Family, gauge::Gauge},
+    registry::Registry,
+};
+use time::OffsetDateTime;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    time::timeout,
+};
+use tracing::{debug, info};
+
+/// How long to wait for a metrics request before giving up on it.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The metrics collected while azsync runs.
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// Counts of what azsync has done, exposed to Prometheus while watching.
+struct Metrics {
+    registry: Registry,
+    syncs: Counter,
+    errors: Counter,
+    actions: Family<ActionLabels, Counter>,
+    bytes: Counter,
+    last_success: Gauge,
+}
+
+/// Labels for the actions taken while synchronizing.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, EncodeLabelSet)]
+struct ActionLabels {
+    action: String,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let syncs = Counter::default();
+        let errors = Counter::default();
+        let actions = Family::default();
+        let bytes = Counter::default();
+        let last_success = Gauge::default();
+
+        let mut registry = Registry::with_prefix("azsync");
+        registry.register("syncs", "Synchronizations run", syncs.clone());
+        registry.register(
+            "sync_errors",
+            "Synchronizations that failed",
+            errors.clone(),
+        );
+        registry.register("actions", "Actions taken, by type", actions.clone());
+        registry.register("transferred_bytes", "Bytes transferred", bytes.clone());
+        registry.register(
+            "last_success_timestamp_seconds",
+            "When a synchronization last succeeded, as a Unix timestamp",
+            last_success.clone(),
+        );
+
+        Self {
+            registry,
+            syncs,
+            errors,
+            actions,
+            bytes,
+            last_success,
+        }
+    }
+}
+
+/// Counts an action that finished, and the bytes it transferred.
+pub fn record_action(action: &str, bytes: Option<u64>) {
+    METRICS
+        .actions
+        .get_or_create(&ActionLabels {
+            action: action.to_string(),
+        })
+        .inc();
+    if let Some(bytes) = bytes {
+        METRICS.bytes.inc_by(bytes);
+    }
+}
+
+/// Counts a synchronization that finished, and when it last succeeded.
+pub fn record_sync(succeeded: bool) {
+    METRICS.syncs.inc();
+    if succeeded {
+        METRICS
+            .last_success
+            .set(OffsetDateTime::now_utc().unix_timestamp());
+    } else {
+        METRICS.errors.inc();
+    }
+}
+
+/// Serves the metrics on an address and port in the background, in the text
+/// format Prometheus scrapes.
+///
+/// Every path gets the metrics, so `/metrics` works like any other exporter.
+pub async fn serve(address: IpAddr, port: u16) -> anyhow::Result<()> {
+    let listener = TcpListener::bind((address, port))
+        .await
+        .with_context(|| format!("Failed to listen for metrics requests on {address}:{port}"))?;
+    info!("Serving metrics on {address}:{port}");
+
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(async move {
+                        if let Err(error) = respond(stream).await {
+                            debug!("Failed to respond to metrics request: {error:#}");
+                        }
+                    });
+                }
+                Err(error) => debug!("Failed to accept metrics request: {error}"),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Responds to a request for the metrics.
+async fn respond(mut stream: TcpStream) -> anyhow::Result<()> {
+    // Only the end of the request headers matters, and clients that never
+    // send it aren't waited on forever
+    timeout(REQUEST_TIMEOUT, async {
+        let mut request = Vec::new();
+        let mut buffer = [0; 1024];
+        while !request.ends_with(b"\r\n\r\n") && request.len() < 16 * 1024 {
+            let read = stream.read(&mut buffer).await?;
+            if read == 0 {
+                break;
+            }
+            request.extend_from_slice(&buffer[..read]);
+        }
+        anyhow::Ok(())
+    })
+    .await
+    .context("Timed out reading metrics request")??;
+
+    let mut body = String::new();
+    encode(&mut body, &METRICS.registry)?;
+    let response = format!(
+        "HTTP/1.1 200 OK\r\n\
+         Content-Type: application/openmetrics-text; version=1.0.0; charset=utf-8\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n\
+         {body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_actions() {
+        record_action("push", Some(3));
+        record_sync(true);
+
+        let mut body = String::new();
+        encode(&mut body, &METRICS.registry).unwrap();
+        assert!(body.contains("azsync_actions_total{action=\"push\"}"));
+        assert!(body.contains("azsync_syncs_total"));
+        assert!(body.contains("azsync_last_success_timestamp_seconds"));
+    }
+}
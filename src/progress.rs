@@ -0,0 +1,381 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{self, IsTerminal, Write, stderr},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use tokio::{task::JoinHandle, time::interval};
+use tracing::info;
+
+use crate::format::{Elapsed, Size};
+
+/// How often progress is reported while transferring.
+pub const REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often progress bars are redrawn while transferring.
+const DRAW_INTERVAL: Duration = Duration::from_millis(200);
+
+/// The most transfers to draw a progress bar for at once.
+const MAX_BARS: usize = 8;
+
+/// The width of each progress bar, not counting its brackets.
+const BAR_WIDTH: usize = 24;
+
+/// How many lines of progress bars are currently drawn at the bottom of the
+/// terminal.
+///
+/// Log messages are written through this lock so they can clear the bars
+/// first, which are then redrawn below them.
+static DRAWN: Mutex<usize> = Mutex::new(0);
+
+/// Tracks how much of a set of transfers has completed.
+#[derive(Debug)]
+pub struct Progress {
+    started: Instant,
+    total: u64,
+    state: Mutex<State>,
+
+    /// Whether to draw progress bars instead of logging progress.
+    bars: bool,
+}
+
+/// The progress of all transfers.
+#[derive(Debug, Default)]
+struct State {
+    /// Bytes transferred so far, including by finished transfers.
+    transferred: u64,
+
+    /// Transfers that haven't finished yet, by name.
+    items: BTreeMap<String, Item>,
+}
+
+/// The progress of a single transfer.
+#[derive(Debug)]
+struct Item {
+    started: Instant,
+    size: u64,
+    transferred: u64,
+}
+
+impl Progress {
+    /// Starts tracking transfers of `total` bytes.
+    ///
+    /// If `bars` is set, progress bars are drawn on stderr when it's a
+    /// terminal. Otherwise, progress is logged periodically.
+    pub fn new(total: u64, bars: bool) -> Arc<Self> {
+        Arc::new(Self {
+            started: Instant::now(),
+            total,
+            state: Mutex::default(),
+            bars: bars && stderr().is_terminal(),
+        })
+    }
+
+    /// Starts tracking a transfer of `size` bytes.
+    pub fn start(self: &Arc<Self>, name: String, size: u64) -> ItemProgress {
+        self.lock().items.insert(
+            name.clone(),
+            Item {
+                started: Instant::now(),
+                size,
+                transferred: 0,
+            },
+        );
+
+        ItemProgress {
+            progress: self.clone(),
+            name,
+        }
+    }
+
+    /// Logs how far along each unfinished transfer is, and all of them
+    /// together.
+    pub fn report(&self) {
+        let state = self.lock();
+        for (name, item) in &state.items {
+            info!(
+                "  {name}: {}",
+                describe(item.transferred, item.size, item.started.elapsed())
+            );
+        }
+        info!(
+            "Progress: {}",
+            describe(state.transferred, self.total, self.started.elapsed())
+        );
+    }
+
+    /// Draws a progress bar for each unfinished transfer and one for all of
+    /// them together, replacing the ones drawn before.
+    pub fn draw(&self) {
+        let lines = {
+            let state = self.lock();
+            let mut lines: Vec<_> = state
+                .items
+                .iter()
+                .take(MAX_BARS)
+                .map(|(name, item)| {
+                    format!(
+                        "{} {name}: {}",
+                        bar(item.transferred, item.size),
+                        describe(item.transferred, item.size, item.started.elapsed())
+                    )
+                })
+                .collect();
+            if state.items.len() > MAX_BARS {
+                lines.push(format!("  ...and {} more", state.items.len() - MAX_BARS));
+            }
+            lines.push(format!(
+                "{} Total: {}",
+                bar(state.transferred, self.total),
+                describe(state.transferred, self.total, self.started.elapsed())
+            ));
+            lines
+        };
+
+        let mut drawn = lock_drawn();
+        let mut output = erase(*drawn);
+        // Cut lines off at the edge of the terminal rather than wrapping them,
+        // so they can be erased again by counting lines
+        output.push_str("\x1b[?7l");
+        for line in &lines {
+            let _ = writeln!(output, "{line}");
+        }
+        output.push_str("\x1b[?7h");
+        if stderr().write_all(output.as_bytes()).is_ok() {
+            *drawn = lines.len();
+        }
+    }
+
+    /// Reports progress periodically until the returned reporter is stopped.
+    pub fn spawn_reporter(self: &Arc<Self>) -> Reporter {
+        let progress = self.clone();
+        let task = tokio::spawn(async move {
+            if progress.bars {
+                let mut interval = interval(DRAW_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    progress.draw();
+                }
+            }
+
+            let mut interval = interval(REPORT_INTERVAL);
+            interval.tick().await; // the first tick completes immediately
+            loop {
+                interval.tick().await;
+                progress.report();
+            }
+        });
+
+        Reporter { task }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .expect("progress lock should not be poisoned")
+    }
+}
+
+/// Reports progress in the background.
+#[derive(Debug)]
+pub struct Reporter {
+    task: JoinHandle<()>,
+}
+
+impl Reporter {
+    /// Stops reporting progress, erasing any progress bars.
+    pub fn stop(self) {
+        self.task.abort();
+        let mut drawn = lock_drawn();
+        if stderr().write_all(erase(*drawn).as_bytes()).is_ok() {
+            *drawn = 0;
+        }
+    }
+}
+
+/// Writes log messages to stderr, erasing any progress bars first.
+///
+/// The bars are redrawn below the message the next time progress is drawn.
+pub struct LogWriter {
+    drawn: MutexGuard<'static, usize>,
+}
+
+impl LogWriter {
+    /// Starts writing a log message.
+    pub fn new() -> Self {
+        Self {
+            drawn: lock_drawn(),
+        }
+    }
+}
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if *self.drawn > 0 {
+            stderr().write_all(erase(*self.drawn).as_bytes())?;
+            *self.drawn = 0;
+        }
+        stderr().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        stderr().flush()
+    }
+}
+
+/// Locks the count of progress bar lines that are drawn.
+fn lock_drawn() -> MutexGuard<'static, usize> {
+    // The count is still accurate if another thread panicked while holding it
+    DRAWN
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Gets the escape codes to erase the last `lines` lines of the terminal,
+/// leaving the cursor where they started.
+fn erase(lines: usize) -> String {
+    if lines == 0 {
+        String::new()
+    } else {
+        format!("\x1b[{lines}F\x1b[J")
+    }
+}
+
+/// Tracks a single transfer.
+#[derive(Debug)]
+pub struct ItemProgress {
+    progress: Arc<Progress>,
+    name: String,
+}
+
+impl ItemProgress {
+    /// Records that more bytes were transferred.
+    pub fn advance(&self, bytes: u64) {
+        let mut state = self.progress.lock();
+        state.transferred += bytes;
+        if let Some(item) = state.items.get_mut(&self.name) {
+            item.transferred += bytes;
+        }
+    }
+
+    /// Stops tracking the transfer, logging how long it took.
+    pub fn finish(self) {
+        let item = self.progress.lock().items.remove(&self.name);
+        if let Some(item) = item {
+            let elapsed = item.started.elapsed();
+            info!(
+                "Transferred {}: {} in {} ({}/s)",
+                self.name,
+                Size(item.transferred),
+                Elapsed(elapsed.try_into().unwrap_or_default()),
+                Size(rate(item.transferred, elapsed)),
+            );
+        }
+    }
+}
+
+/// Computes the average transfer rate in bytes per second.
+#[expect(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss,
+    reason = "Only used for display"
+)]
+fn rate(transferred: u64, elapsed: Duration) -> u64 {
+    let seconds = elapsed.as_secs_f64();
+    if seconds <= 0.0 {
+        return 0;
+    }
+
+    (transferred as f64 / seconds) as u64
+}
+
+/// Estimates how long is left until `total` bytes are transferred, assuming
+/// the average rate so far stays the same.
+fn estimate_remaining(transferred: u64, total: u64, elapsed: Duration) -> Option<Duration> {
+    let rate = rate(transferred, elapsed);
+    if rate == 0 {
+        return None;
+    }
+
+    Some(Duration::from_secs(
+        total.saturating_sub(transferred).div_ceil(rate),
+    ))
+}
+
+/// Draws a bar showing how much of a transfer is done, like `[=====>    ]`.
+fn bar(transferred: u64, total: u64) -> String {
+    let filled = (u128::from(transferred) * BAR_WIDTH as u128)
+        .checked_div(u128::from(total))
+        .map_or(BAR_WIDTH, |filled| {
+            usize::try_from(filled).unwrap_or(BAR_WIDTH).min(BAR_WIDTH)
+        });
+    let head = if filled < BAR_WIDTH && filled > 0 {
+        ">"
+    } else {
+        ""
+    };
+    let filled = filled - head.len();
+    format!(
+        "[{}{head}{}]",
+        "=".repeat(filled),
+        " ".repeat(BAR_WIDTH - filled - head.len())
+    )
+}
+
+/// Describes how far along a transfer is, like
+/// `12.0 MiB of 40.0 MiB (30%), 3.1 MiB/s, about 9s left`.
+fn describe(transferred: u64, total: u64, elapsed: Duration) -> String {
+    let percent = (u128::from(transferred) * 100)
+        .checked_div(u128::from(total))
+        .unwrap_or(100);
+    let remaining = estimate_remaining(transferred, total, elapsed).map_or_else(
+        || "time left unknown".to_string(),
+        |remaining| {
+            format!(
+                "about {} left",
+                Elapsed(remaining.try_into().unwrap_or_default())
+            )
+        },
+    );
+    format!(
+        "{} of {} ({percent}%), {}/s, {remaining}",
+        Size(transferred),
+        Size(total),
+        Size(rate(transferred, elapsed)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(0, 100, 10 => None; "nothing transferred")]
+    #[test_case(50, 100, 10 => Some(Duration::from_secs(10)); "halfway")]
+    #[test_case(100, 100, 10 => Some(Duration::ZERO); "done")]
+    #[test_case(10, 100, 0 => None; "no time elapsed")]
+    fn remaining(transferred: u64, total: u64, elapsed: u64) -> Option<Duration> {
+        estimate_remaining(transferred, total, Duration::from_secs(elapsed))
+    }
+
+    #[test_case(0, 100, "[                        ]"; "nothing transferred")]
+    #[test_case(50, 100, "[===========>            ]"; "halfway")]
+    #[test_case(100, 100, "[========================]"; "done")]
+    #[test_case(0, 0, "[========================]"; "empty")]
+    #[test_case(200, 100, "[========================]"; "overshot")]
+    fn progress_bar(transferred: u64, total: u64, expected: &str) {
+        assert_eq!(bar(transferred, total), expected);
+    }
+
+    #[test]
+    fn description() {
+        assert_eq!(
+            describe(12 * 1024 * 1024, 40 * 1024 * 1024, Duration::from_secs(4)),
+            "12.0 MiB of 40.0 MiB (30%), 3.0 MiB/s, about 10s left"
+        );
+    }
+}
@@ -0,0 +1,247 @@
+use std::{
+    env::var_os,
+    fs::write,
+    io::{IsTerminal, stdin, stdout},
+};
+
+use anyhow::{Context, bail};
+use azure_identity::DefaultAzureCredential;
+use azure_security_keyvault_secrets::SecretClient;
+use futures::TryStreamExt;
+use toml::{Table, Value};
+use tracing::{info, warn};
+use url::Url;
+
+use crate::{
+    cli::GlobalOptions,
+    storage::StorageCredential,
+    sync::{ask_text, ask_yes_no},
+};
+
+/// Environment variables that Azure resources are read from when they aren't
+/// configured otherwise.
+const RESOURCE_VARS: [&str; 3] = [
+    "STORAGE_ACCOUNT_URL",
+    "STORAGE_ACCOUNT_CONTAINER",
+    "KEY_VAULT_URL",
+];
+
+/// The domain storage account names are expanded with.
+const STORAGE_DOMAIN: &str = "blob.core.windows.net";
+
+/// The domain Key Vault names are expanded with.
+const KEY_VAULT_DOMAIN: &str = "vault.azure.net";
+
+/// Whether nothing says which Azure resources to use yet, and there's someone
+/// at a terminal to ask.
+///
+/// This is the case when there's no config file, no dotenv file, and none of
+/// the environment variables resources are read from by default are set.
+pub fn is_first_run(global_options: &GlobalOptions) -> bool {
+    stdin().is_terminal()
+        && stdout().is_terminal()
+        && !global_options.config.exists()
+        && (global_options.no_env_file || !global_options.env_file.exists())
+        && RESOURCE_VARS.iter().all(|name| var_os(name).is_none())
+}
+
+/// The resources chosen while setting up azsync.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+struct Answers {
+    /// The storage account's endpoint and the container in it.
+    storage: Option<(Url, String)>,
+
+    /// The URL to the Key Vault instance.
+    key_vault_url: Option<Url>,
+}
+
+/// Asks which Azure resources to use, checks that they can be accessed, and
+/// writes them to the config file.
+///
+/// If a profile is selected, the resources are written to that profile
+/// instead of the top of the config file. Declining to set up azsync isn't an
+/// error; the command runs as usual and reports what's missing.
+pub async fn run(global_options: &GlobalOptions) -> anyhow::Result<()> {
+    info!("No config file, dotenv file, or environment variables say which Azure resources to use");
+    if !ask_yes_no("Set up azsync now")? {
+        return Ok(());
+    }
+
+    let mut answers = Answers::default();
+    while let Some(url) = ask_url("Storage account name or URL", STORAGE_DOMAIN)? {
+        let container_name = loop {
+            let name = ask_text("Container name")?;
+            if !name.is_empty() {
+                break name;
+            }
+        };
+        match check_storage(&url, &container_name).await {
+            Ok(()) => info!("Container {container_name} can be accessed"),
+            Err(error) => {
+                warn!("{error:#}");
+                if !ask_yes_no("Use it anyway")? {
+                    continue;
+                }
+            }
+        }
+        answers.storage = Some((url, container_name));
+        break;
+    }
+    while let Some(url) = ask_url("Key Vault name or URL", KEY_VAULT_DOMAIN)? {
+        match check_key_vault(&url).await {
+            Ok(()) => info!("Key Vault {url} can be accessed"),
+            Err(error) => {
+                warn!("{error:#}");
+                if !ask_yes_no("Use it anyway")? {
+                    continue;
+                }
+            }
+        }
+        answers.key_vault_url = Some(url);
+        break;
+    }
+    if answers == Answers::default() {
+        info!("Nothing to save");
+        return Ok(());
+    }
+
+    let config = render_config(&answers, global_options.profile.as_deref())?;
+    write(&global_options.config, config)
+        .with_context(|| format!("Failed to write {}", global_options.config.display()))?;
+    info!("Saved to {}", global_options.config.display());
+
+    Ok(())
+}
+
+/// Asks for the name or URL of a resource, returning `None` if it's skipped.
+///
+/// Asks again until the answer is empty or can be turned into a URL.
+fn ask_url(question: &str, domain: &str) -> anyhow::Result<Option<Url>> {
+    loop {
+        let input = ask_text(&format!("{question} (leave empty to skip)"))?;
+        if input.is_empty() {
+            return Ok(None);
+        }
+        match resource_url(&input, domain) {
+            Ok(url) => return Ok(Some(url)),
+            Err(error) => warn!("{error:#}"),
+        }
+    }
+}
+
+/// Gets a resource's URL from either its URL or its name.
+///
+/// Names are expanded to URLs in the given domain, so `myaccount` becomes
+/// `https://myaccount.blob.core.windows.net/` for storage accounts.
+fn resource_url(input: &str, domain: &str) -> anyhow::Result<Url> {
+    if input.contains("://") {
+        return Url::parse(input).with_context(|| format!("Invalid URL: {input}"));
+    }
+    if !input.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        bail!("Invalid name: {input}");
+    }
+
+    Ok(Url::parse(&format!("https://{input}.{domain}/"))?)
+}
+
+/// Checks that the blobs in a container can be listed.
+async fn check_storage(url: &Url, container_name: &str) -> anyhow::Result<()> {
+    let credential = StorageCredential::new(None, Vec::new())?;
+    let client = credential.container_client(url, container_name.to_string())?;
+    client
+        .list_blobs(None)?
+        .try_next()
+        .await
+        .with_context(|| format!("Can't list blobs in container {container_name}"))?;
+
+    Ok(())
+}
+
+/// Checks that the secrets in a Key Vault can be listed.
+async fn check_key_vault(url: &Url) -> anyhow::Result<()> {
+    let credential =
+        DefaultAzureCredential::new().context("Failed to get default Azure credential")?;
+    let client = SecretClient::new(url.as_str(), credential, None)
+        .context("Failed to create Key Vault secrets client")?;
+    client
+        .list_secret_properties(None)?
+        .try_next()
+        .await
+        .with_context(|| format!("Can't list secrets in Key Vault {url}"))?;
+
+    Ok(())
+}
+
+/// Writes the chosen resources as a config file, either at the top of the
+/// file or in a profile.
+fn render_config(answers: &Answers, profile: Option<&str>) -> anyhow::Result<String> {
+    let mut options = Table::new();
+    if let Some((url, container_name)) = &answers.storage {
+        options.insert("storage-account-url".into(), Value::String(url.to_string()));
+        options.insert(
+            "container-name".into(),
+            Value::String(container_name.clone()),
+        );
+    }
+    if let Some(url) = &answers.key_vault_url {
+        options.insert("key-vault-url".into(), Value::String(url.to_string()));
+    }
+
+    let config = match profile {
+        Some(name) => {
+            let profiles = Table::from_iter([(name.to_string(), Value::Table(options))]);
+            Table::from_iter([("profiles".to_string(), Value::Table(profiles))])
+        }
+        None => options,
+    };
+
+    Ok(toml::to_string(&config)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("myaccount", "https://myaccount.blob.core.windows.net/"; "name")]
+    #[test_case("https://myaccount.blob.core.windows.net/", "https://myaccount.blob.core.windows.net/"; "url")]
+    #[test_case("http://127.0.0.1:10000/devstoreaccount1", "http://127.0.0.1:10000/devstoreaccount1"; "emulator")]
+    fn urls(input: &str, expected: &str) {
+        let url = resource_url(input, STORAGE_DOMAIN).unwrap();
+        assert_eq!(url.as_str(), expected);
+    }
+
+    #[test_case("my account"; "space")]
+    #[test_case("myaccount.blob"; "dot")]
+    #[test_case("https://"; "invalid url")]
+    fn invalid_urls(input: &str) {
+        assert!(resource_url(input, STORAGE_DOMAIN).is_err());
+    }
+
+    #[test_case(None, "container-name = \"configs\"\nkey-vault-url = \"https://my-vault.vault.azure.net/\"\nstorage-account-url = \"https://myaccount.blob.core.windows.net/\"\n"; "top level")]
+    #[test_case(Some("dev"), "[profiles.dev]\ncontainer-name = \"configs\"\nkey-vault-url = \"https://my-vault.vault.azure.net/\"\nstorage-account-url = \"https://myaccount.blob.core.windows.net/\"\n"; "profile")]
+    fn config(profile: Option<&str>, expected: &str) {
+        let answers = Answers {
+            storage: Some((
+                Url::parse("https://myaccount.blob.core.windows.net/").unwrap(),
+                "configs".to_string(),
+            )),
+            key_vault_url: Some(Url::parse("https://my-vault.vault.azure.net/").unwrap()),
+        };
+        assert_eq!(render_config(&answers, profile).unwrap(), expected);
+    }
+
+    #[test]
+    fn config_without_storage() {
+        let answers = Answers {
+            storage: None,
+            key_vault_url: Some(Url::parse("https://my-vault.vault.azure.net/").unwrap()),
+        };
+        assert_eq!(
+            render_config(&answers, None).unwrap(),
+            "key-vault-url = \"https://my-vault.vault.azure.net/\"\n"
+        );
+    }
+}
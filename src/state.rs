@@ -0,0 +1,102 @@
+use std::{
+    collections::BTreeMap,
+    fs::metadata,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+use crate::{
+    audit::hash_value,
+    manifest::{Section, load_entries, save_entries},
+};
+
+/// The state of everything a run synchronized, as of when it finished.
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub struct PlanMarker {
+    /// A fingerprint of the local files.
+    pub local: String,
+
+    /// The `ETag` of each remote resource by its URL, or `None` if it didn't
+    /// exist.
+    pub remote: BTreeMap<String, Option<String>>,
+}
+
+/// The markers left by previous runs, by a hash of their inputs.
+#[derive(Debug)]
+pub struct StateCache {
+    path: PathBuf,
+    markers: BTreeMap<String, PlanMarker>,
+}
+
+impl StateCache {
+    /// Loads the recorded markers from a manifest, if it exists.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        Ok(Self {
+            path: path.to_owned(),
+            markers: load_entries(path, Section::Runs)?,
+        })
+    }
+
+    /// Gets the marker left by the last run with the given inputs.
+    pub fn get(&self, inputs: &str) -> Option<&PlanMarker> {
+        self.markers.get(inputs)
+    }
+
+    /// Records the marker left by a run with the given inputs, and writes it
+    /// to the manifest.
+    pub fn save(&mut self, inputs: String, marker: PlanMarker) -> anyhow::Result<()> {
+        self.markers.insert(inputs.clone(), marker.clone());
+        save_entries(&self.path, Section::Runs, [(inputs, marker)])
+    }
+}
+
+/// Fingerprints local files by their paths, sizes, and modified times.
+///
+/// Files that don't exist are included too, so creating them changes the
+/// fingerprint.
+pub fn fingerprint_files<'a>(paths: impl IntoIterator<Item = &'a Path>) -> anyhow::Result<String> {
+    let mut fingerprint = String::new();
+    for path in paths {
+        let line = match metadata(path) {
+            Ok(metadata) => {
+                let modified = OffsetDateTime::from(metadata.modified()?).format(&Rfc3339)?;
+                format!("{}\t{}\t{modified}\n", path.display(), metadata.len())
+            }
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                format!("{}\tmissing\n", path.display())
+            }
+            Err(error) => {
+                return Err(error).with_context(|| format!("Can't read {}", path.display()));
+            }
+        };
+        fingerprint.push_str(&line);
+    }
+
+    Ok(hash_value(&fingerprint))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fingerprint_is_stable() {
+        let paths = [Path::new("Cargo.toml"), Path::new("not-created.env")];
+        assert_eq!(
+            fingerprint_files(paths).unwrap(),
+            fingerprint_files(paths).unwrap()
+        );
+    }
+
+    #[test]
+    fn fingerprint_includes_missing_files() {
+        assert_ne!(
+            fingerprint_files([Path::new("Cargo.toml")]).unwrap(),
+            fingerprint_files([Path::new("Cargo.toml"), Path::new("not-created.env")]).unwrap()
+        );
+    }
+}
@@ -0,0 +1,361 @@
+mod batch;
+mod compression;
+mod connection_string;
+mod credential;
+mod encryption;
+mod inventory;
+mod pages;
+mod properties;
+mod sas;
+mod sas_token;
+mod shared_auth;
+mod sparse;
+mod temp;
+mod text;
+
+use std::{
+    fmt::Write,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{Context, anyhow, bail};
+use azure_storage_blob::{
+    BlobClient, BlobContainerClient,
+    models::{
+        BlobClientGetPropertiesResultHeaders, BlobContainerClientListBlobFlatSegmentOptions,
+        BlobType, ListBlobsIncludeItem,
+    },
+};
+use futures::TryStreamExt;
+use sha2::{Digest, Sha256};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use typespec_client_core::http::StatusCode;
+use url::Url;
+
+use crate::{
+    sync::Changed,
+    versions::{VERSION_KEY, parse_version},
+};
+
+pub use batch::*;
+pub use compression::*;
+pub use connection_string::*;
+pub use credential::*;
+pub use encryption::*;
+pub use inventory::*;
+pub use pages::*;
+pub use properties::*;
+pub use sas::*;
+pub use sas_token::*;
+pub use shared_auth::*;
+pub use sparse::*;
+pub use temp::*;
+pub use text::*;
+
+/// Metadata key for when the synchronized file was last modified.
+pub const MODIFIED_META: &str = "modified";
+
+/// Metadata key for the checksum of the synchronized file.
+pub const CHECKSUM_META: &str = "sha256";
+
+/// Metadata key for the checksum of the synchronized file ignoring line
+/// endings and trailing newlines, if it's text.
+pub const TEXT_CHECKSUM_META: &str = "text_sha256";
+
+/// Metadata key for the owner and access control list of the synchronized
+/// file, in SDDL form.
+pub const ACL_META: &str = "acl";
+
+/// Default size of each block staged when uploading in blocks.
+pub const BLOCK_SIZE: usize = 4 * 1024 * 1024;
+
+/// Maximum size of a single staged block.
+pub const MAX_BLOCK_SIZE: u64 = 4000 * 1024 * 1024;
+
+/// Files larger than this are uploaded in blocks rather than in a single
+/// request.
+pub const SINGLE_UPLOAD_LIMIT: u64 = 256 * 1024 * 1024;
+
+/// Maximum number of blocks a blob can be committed with.
+pub const MAX_BLOCKS: u64 = 50_000;
+
+/// Maximum size of a blob uploaded in a single request.
+pub const MAX_UPLOAD_SIZE: u64 = 5000 * 1024 * 1024;
+
+/// The state of a remote blob.
+#[derive(Clone, Debug)]
+pub struct RemoteBlob {
+    /// When the synchronized file was last modified.
+    ///
+    /// This is the time recorded by azsync when the file was pushed if
+    /// available, or when the blob was last modified otherwise.
+    pub modified: OffsetDateTime,
+
+    /// The blob's `ETag`.
+    pub etag: Option<String>,
+
+    /// The checksum of the blob's contents, if recorded.
+    pub checksum: Option<String>,
+
+    /// The checksum of the blob's contents ignoring line endings and trailing
+    /// newlines, if recorded.
+    pub text_checksum: Option<String>,
+
+    /// The size of the blob in bytes, if known.
+    ///
+    /// This is the size of the blob as stored, which may be compressed or
+    /// encrypted.
+    pub size: Option<u64>,
+
+    /// How the blob was compressed when it was pushed, if it was.
+    pub compression: Option<String>,
+
+    /// The type of the blob, if known.
+    pub blob_type: Option<BlobType>,
+
+    /// The version counter recorded by azsync when the file was pushed, if
+    /// version counters are used.
+    pub version: Option<u64>,
+}
+
+/// Gets the state of a remote blob, if it exists.
+pub async fn get_remote_blob(client: &BlobClient) -> anyhow::Result<Option<RemoteBlob>> {
+    let properties = match client.get_properties(None).await {
+        Ok(properties) => properties,
+        Err(error) => {
+            // Only allow NotFound - fail otherwise
+            if error.http_status() != Some(StatusCode::NotFound) {
+                bail!(error);
+            }
+
+            return Ok(None);
+        }
+    };
+
+    // Get when the remote blob was last modified
+    let metadata = properties.metadata()?;
+    let modified = metadata
+        .get(MODIFIED_META)
+        .map(|time| OffsetDateTime::parse(time, &Rfc3339))
+        .transpose()?;
+    let modified = match modified {
+        Some(time) => time,
+        None => properties
+            .last_modified()?
+            .context("unable to determine when blob was modified")?,
+    };
+
+    Ok(Some(RemoteBlob {
+        modified,
+        etag: properties.etag()?,
+        checksum: metadata.get(CHECKSUM_META).cloned(),
+        text_checksum: metadata.get(TEXT_CHECKSUM_META).cloned(),
+        size: properties.content_length()?,
+        compression: compression(&metadata).map(ToString::to_string),
+        blob_type: properties.blob_type()?,
+        version: parse_version(metadata.get(VERSION_KEY)),
+    }))
+}
+
+/// Fails if a remote blob changed since it was planned against.
+///
+/// `exists` is whether the blob existed when planning, and `etag` is its
+/// `ETag` at that time, if known.
+pub async fn check_unchanged(
+    client: &BlobClient,
+    exists: bool,
+    etag: Option<&str>,
+) -> anyhow::Result<()> {
+    match (get_remote_blob(client).await?, exists) {
+        (None, true) => bail!(Changed("Blob no longer exists".to_string())),
+        (Some(_), false) => bail!(Changed("Blob already exists".to_string())),
+        (Some(remote), true) if etag.is_some() && remote.etag.as_deref() != etag => {
+            bail!(Changed("Blob changed since planning".to_string()));
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Splits a blob's URL into its storage account endpoint, container name, and
+/// blob name.
+pub fn parse_blob_url(url: &Url) -> anyhow::Result<(String, String, String)> {
+    let mut segments = url.path_segments().context("Invalid blob URL")?;
+    let container_name = segments
+        .next()
+        .filter(|name| !name.is_empty())
+        .context("Blob URL is missing a container name")?;
+    let blob_name = segments.collect::<Vec<_>>().join("/");
+    if blob_name.is_empty() {
+        bail!("Blob URL is missing a blob name");
+    }
+
+    Ok((
+        url.origin().ascii_serialization(),
+        container_name.to_string(),
+        blob_name,
+    ))
+}
+
+/// Gets the URL of the blob a client refers to.
+pub fn blob_url(client: &BlobClient) -> String {
+    format!(
+        "{}/{}/{}",
+        client.endpoint().as_str().trim_end_matches('/'),
+        client.container_name(),
+        client.blob_name()
+    )
+}
+
+/// Gets the URL to send requests about a blob to that its client can't make.
+pub fn blob_request_url(client: &BlobClient) -> anyhow::Result<Url> {
+    let mut url = client.endpoint().clone();
+    url.set_query(None);
+    url.path_segments_mut()
+        .map_err(|()| anyhow!("Invalid storage endpoint {}", client.endpoint()))?
+        .pop_if_empty()
+        .push(client.container_name())
+        .extend(client.blob_name().split('/'));
+    Ok(url)
+}
+
+/// A blob found when listing a container.
+#[derive(Clone, Debug)]
+pub struct BlobEntry {
+    /// The name of the blob.
+    pub name: String,
+
+    /// When the blob was last modified according to the storage account.
+    pub last_modified: Option<OffsetDateTime>,
+
+    /// The blob's `ETag`.
+    pub etag: Option<String>,
+
+    /// The size of the blob in bytes, if known.
+    pub size: Option<u64>,
+
+    /// The type of the blob, like a block blob, if known.
+    pub blob_type: Option<BlobType>,
+}
+
+/// Lists all blobs in a container, following continuation markers.
+///
+/// If `uncommitted` is set, blobs that only have uncommitted blocks are
+/// included as well.
+pub async fn list_blobs(
+    client: &BlobContainerClient,
+    prefix: Option<&str>,
+    uncommitted: bool,
+) -> anyhow::Result<Vec<BlobEntry>> {
+    let include = uncommitted.then(|| vec![ListBlobsIncludeItem::UncommittedBlobs]);
+    let mut pages = client.list_blobs(Some(BlobContainerClientListBlobFlatSegmentOptions {
+        include,
+        prefix: prefix.map(ToString::to_string),
+        ..Default::default()
+    }))?;
+
+    let mut blobs = Vec::new();
+    while let Some(page) = pages.try_next().await? {
+        let page = page.into_body().await?;
+        for item in page.segment.blob_items {
+            let Some(name) = item.name.and_then(|name| name.content) else {
+                continue;
+            };
+            let properties = item.properties.unwrap_or_default();
+            blobs.push(BlobEntry {
+                name,
+                last_modified: properties.last_modified,
+                etag: properties.etag,
+                size: properties.content_length,
+                blob_type: properties.blob_type,
+            });
+        }
+    }
+
+    Ok(blobs)
+}
+
+/// Gets the local path of a blob inside a directory, from the blob's name
+/// relative to that directory.
+///
+/// Returns `None` if the name could point outside the directory, like when it
+/// has `.`, `..`, or empty components, or would be treated as an absolute
+/// path.
+pub fn local_path_in(root: &Path, relative: &str) -> Option<PathBuf> {
+    if relative
+        .split('/')
+        .any(|component| matches!(component, "" | "." | ".."))
+    {
+        return None;
+    }
+
+    let path = root.join(relative);
+    let inside = path.strip_prefix(root).is_ok_and(|inner| {
+        inner
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    });
+    inside.then_some(path)
+}
+
+/// Gets the ID of a block staged by azsync from its index and contents.
+///
+/// IDs include a hash of the block's contents so that blocks staged by a
+/// previous upload can be reused only if their contents haven't changed.
+pub fn block_id(index: usize, block: &[u8]) -> Vec<u8> {
+    let hash = Sha256::digest(block);
+    let mut id = format!("{index:08}-");
+    for byte in &hash[..16] {
+        let _ = write!(id, "{byte:02x}");
+    }
+
+    id.into_bytes()
+}
+
+/// Checks whether a block ID was generated by [`block_id`].
+pub fn is_block_id(id: &[u8]) -> bool {
+    id.len() == 41
+        && id[..8].iter().all(u8::is_ascii_digit)
+        && id[8] == b'-'
+        && id[9..]
+            .iter()
+            .all(|&c| c.is_ascii_digit() || (b'a'..=b'f').contains(&c))
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(0, b"" ; "empty")]
+    #[test_case(12, b"abc" ; "small")]
+    #[test_case(99_999_999, &[0; 1024] ; "large index")]
+    fn block_ids_are_recognized(index: usize, block: &[u8]) {
+        let id = block_id(index, block);
+        assert!(is_block_id(&id), "{:?}", String::from_utf8_lossy(&id));
+    }
+
+    #[test_case(b"" ; "empty")]
+    #[test_case(b"00000000" ; "index only")]
+    #[test_case(b"block-0" ; "other tool")]
+    #[test_case(b"00000000-0123456789ABCDEF0123456789ABCDEF" ; "uppercase")]
+    fn other_ids_are_not_recognized(id: &[u8]) {
+        assert!(!is_block_id(id));
+    }
+
+    #[test_case("app.json", Some("app.json"); "file")]
+    #[test_case("configs/app.json", Some("configs/app.json"); "nested")]
+    #[test_case("../../.ssh/authorized_keys", None; "parent")]
+    #[test_case("configs/../../x", None; "nested parent")]
+    #[test_case("/etc/x", None; "absolute")]
+    #[test_case("configs//x", None; "empty component")]
+    #[test_case("./x", None; "current directory")]
+    #[test_case("", None; "empty")]
+    fn local_paths(relative: &str, expected: Option<&str>) {
+        let root = Path::new("sync-root");
+        assert_eq!(
+            local_path_in(root, relative),
+            expected.map(|path| root.join(path))
+        );
+    }
+}
@@ -0,0 +1,402 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use anyhow::{Context as _, anyhow, bail};
+use azure_core::{
+    Uuid,
+    http::{Context, Method, Request, StatusCode, headers::CONTENT_TYPE},
+};
+use azure_storage_blob::BlobClient;
+use futures::{StreamExt, stream};
+use tracing::error;
+
+use crate::{
+    audit::{AuditEntry, AuditLog},
+    storage::{StorageCredential, blob_request_url, sas::SERVICE_VERSION},
+};
+
+/// Maximum number of sub-requests in one batch.
+pub const MAX_BATCH_SIZE: usize = 256;
+
+/// A blob to delete with [`delete_blobs`].
+pub struct BlobDeletion {
+    pub client: BlobClient,
+
+    /// Only delete the blob if it still has this `ETag`.
+    pub etag: Option<String>,
+
+    /// What to record in the audit log once the blob is deleted.
+    pub audit: AuditEntry,
+}
+
+/// Deletes a set of blobs with the Blob Batch API, reporting each blob that
+/// couldn't be deleted.
+///
+/// Blobs are deleted up to 256 per request, and at most `concurrency` requests
+/// are made at once. Unlike most operations, a failure to delete one blob
+/// doesn't prevent the others from being deleted.
+pub async fn delete_blobs(
+    credential: &StorageCredential,
+    blobs: impl IntoIterator<Item = BlobDeletion>,
+    concurrency: usize,
+    audit: &AuditLog,
+) -> anyhow::Result<()> {
+    // A batch can only change blobs in one container
+    let mut containers: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    for blob in blobs {
+        let container = (
+            blob.client.endpoint().to_string(),
+            blob.client.container_name().to_string(),
+        );
+        containers.entry(container).or_default().push(blob);
+    }
+    let mut batches = Vec::new();
+    for mut blobs in containers.into_values() {
+        while blobs.len() > MAX_BATCH_SIZE {
+            let rest = blobs.split_off(MAX_BATCH_SIZE);
+            batches.push(blobs);
+            blobs = rest;
+        }
+        batches.push(blobs);
+    }
+
+    let mut results = stream::iter(batches)
+        .map(|batch| async move {
+            let result = send_delete_batch(credential, &batch).await;
+            (batch, result)
+        })
+        .buffer_unordered(concurrency);
+
+    let mut total = 0;
+    let mut failed = 0;
+    while let Some((batch, result)) = results.next().await {
+        total += batch.len();
+        let mut responses = match result {
+            Ok(responses) => responses,
+            Err(error) => {
+                error!("Failed to delete {} blobs: {error:#}", batch.len());
+                failed += batch.len();
+                continue;
+            }
+        };
+
+        for (id, blob) in batch.into_iter().enumerate() {
+            match responses.remove(&id) {
+                Some(response) if response.status == StatusCode::Accepted => {
+                    audit.record(blob.audit).await?;
+                }
+                Some(response) => {
+                    error!("Failed to delete {}: {response}", blob.client.blob_name());
+                    failed += 1;
+                }
+                None => {
+                    error!(
+                        "Failed to delete {}: the batch had no response for it",
+                        blob.client.blob_name()
+                    );
+                    failed += 1;
+                }
+            }
+        }
+    }
+
+    if failed > 0 {
+        bail!("Failed to delete {failed} of {total} blobs");
+    }
+
+    Ok(())
+}
+
+/// Deletes blobs in one container with a batch request, returning the response
+/// to each deletion by its position.
+async fn send_delete_batch(
+    credential: &StorageCredential,
+    batch: &[BlobDeletion],
+) -> anyhow::Result<BTreeMap<usize, SubResponse>> {
+    let Some(first) = batch.first() else {
+        return Ok(BTreeMap::new());
+    };
+    let endpoint = first.client.endpoint();
+    let container_name = first.client.container_name();
+
+    // Each deletion is authenticated on its own
+    let mut deletions = Vec::with_capacity(batch.len());
+    for blob in batch {
+        let mut request = Request::new(blob_request_url(&blob.client)?, Method::Delete);
+        if let Some(etag) = &blob.etag {
+            request.insert_header("if-match", etag.clone());
+        }
+        credential
+            .authenticate_request(
+                endpoint,
+                container_name,
+                blob.client.blob_name(),
+                &mut request,
+            )
+            .await
+            .with_context(|| format!("Failed to authenticate {}", blob.client.blob_name()))?;
+        request.insert_header("content-length", "0");
+        deletions.push(request);
+    }
+
+    let mut url = endpoint.clone();
+    url.set_query(None);
+    url.path_segments_mut()
+        .map_err(|()| anyhow!("Invalid storage endpoint {endpoint}"))?
+        .pop_if_empty()
+        .push(container_name);
+    url.query_pairs_mut()
+        .append_pair("restype", "container")
+        .append_pair("comp", "batch");
+    let boundary = format!("batch_{}", Uuid::new_v4());
+    let mut request = Request::new(url, Method::Post);
+    request.insert_header("x-ms-version", SERVICE_VERSION);
+    request.insert_header(
+        "content-type",
+        format!("multipart/mixed; boundary={boundary}"),
+    );
+    request.set_body(batch_body(&boundary, &deletions));
+
+    let pipeline = credential.container_pipeline(endpoint, container_name);
+    let response = pipeline.send(&Context::new(), &mut request).await?;
+    let status = response.status();
+    let content_type = response
+        .headers()
+        .get_optional_string(&CONTENT_TYPE)
+        .unwrap_or_default();
+    let body = response.into_body().collect().await?;
+    let body = String::from_utf8_lossy(&body);
+    if status != StatusCode::Accepted {
+        bail!("Batch request failed ({status}): {body}");
+    }
+
+    parse_batch_response(&content_type, &body).context("Invalid batch response")
+}
+
+/// Builds the multipart body of a batch request from its sub-requests, which
+/// are identified by their positions.
+///
+/// See <https://learn.microsoft.com/rest/api/storageservices/blob-batch>.
+fn batch_body(boundary: &str, requests: &[Request]) -> String {
+    let mut body = String::new();
+    for (id, request) in requests.iter().enumerate() {
+        let _ = write!(
+            body,
+            "--{boundary}\r\n\
+             Content-Type: application/http\r\n\
+             Content-Transfer-Encoding: binary\r\n\
+             Content-ID: {id}\r\n\
+             \r\n\
+             {} {} HTTP/1.1\r\n",
+            request.method().as_ref(),
+            request.path_and_query(),
+        );
+        let mut headers: Vec<_> = request.headers().iter().collect();
+        headers.sort_unstable_by_key(|(name, _)| name.as_str());
+        for (name, value) in headers {
+            let _ = write!(body, "{}: {}\r\n", name.as_str(), value.as_str());
+        }
+        body.push_str("\r\n");
+    }
+    let _ = write!(body, "--{boundary}--\r\n");
+    body
+}
+
+/// The response to one sub-request in a batch.
+#[derive(Debug, PartialEq, Eq)]
+struct SubResponse {
+    status: StatusCode,
+
+    /// The storage error code, like `ConditionNotMet`.
+    error_code: Option<String>,
+}
+
+impl std::fmt::Display for SubResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.error_code {
+            Some(code) => write!(f, "{code} ({})", self.status),
+            None => write!(f, "{}", self.status),
+        }
+    }
+}
+
+/// Parses the multipart body of a batch response into the response to each
+/// sub-request, by its `Content-ID`.
+fn parse_batch_response(
+    content_type: &str,
+    body: &str,
+) -> anyhow::Result<BTreeMap<usize, SubResponse>> {
+    let boundary = content_type
+        .split(';')
+        .find_map(|parameter| parameter.trim().strip_prefix("boundary="))
+        .map(|boundary| boundary.trim_matches('"'))
+        .with_context(|| format!("No boundary in content type {content_type:?}"))?;
+
+    let mut responses = BTreeMap::new();
+    let delimiter = format!("--{boundary}");
+    for part in body.split(delimiter.as_str()).skip(1) {
+        if part.starts_with("--") {
+            break;
+        }
+
+        // The part's own headers, then the response's status line and headers
+        let mut lines = part.trim_start_matches(['\r', '\n']).lines();
+        let mut id = None;
+        for line in lines.by_ref().take_while(|line| !line.is_empty()) {
+            if let Some((name, value)) = line.split_once(':')
+                && name.trim().eq_ignore_ascii_case("content-id")
+            {
+                id = Some(
+                    value
+                        .trim()
+                        .parse::<usize>()
+                        .context("Invalid Content-ID")?,
+                );
+            }
+        }
+        let status_line = lines.next().context("Missing status line")?;
+        let status = status_line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|status| status.parse::<u16>().ok())
+            .with_context(|| format!("Invalid status line {status_line:?}"))?;
+        let error_code = lines
+            .take_while(|line| !line.is_empty())
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("x-ms-error-code"))
+            .map(|(_, value)| value.trim().to_string());
+        let response = SubResponse {
+            status: StatusCode::from(status),
+            error_code,
+        };
+
+        // A failed batch may answer with one response that isn't for any
+        // sub-request
+        let Some(id) = id else {
+            bail!("{response}");
+        };
+        responses.insert(id, response);
+    }
+
+    Ok(responses)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use url::Url;
+
+    use super::*;
+
+    #[test]
+    fn body_has_each_request() {
+        let mut requests = Vec::new();
+        for (name, etag) in [("a.txt", Some("\"1\"")), ("b c.txt", None)] {
+            let mut url = Url::parse("https://account.blob.core.windows.net/container").unwrap();
+            url.path_segments_mut().unwrap().push(name);
+            let mut request = Request::new(url, Method::Delete);
+            if let Some(etag) = etag {
+                request.insert_header("if-match", etag);
+            }
+            request.insert_header("content-length", "0");
+            requests.push(request);
+        }
+
+        assert_eq!(
+            batch_body("batch_1", &requests),
+            [
+                "--batch_1",
+                "Content-Type: application/http",
+                "Content-Transfer-Encoding: binary",
+                "Content-ID: 0",
+                "",
+                "DELETE /container/a.txt HTTP/1.1",
+                "content-length: 0",
+                "if-match: \"1\"",
+                "",
+                "--batch_1",
+                "Content-Type: application/http",
+                "Content-Transfer-Encoding: binary",
+                "Content-ID: 1",
+                "",
+                "DELETE /container/b%20c.txt HTTP/1.1",
+                "content-length: 0",
+                "",
+                "--batch_1--",
+                "",
+            ]
+            .join("\r\n")
+        );
+    }
+
+    #[test]
+    fn parses_each_response() {
+        let body = [
+            "--batchresponse_1",
+            "Content-Type: application/http",
+            "Content-ID: 0",
+            "",
+            "HTTP/1.1 202 Accepted",
+            "x-ms-delete-type-permanent: true",
+            "x-ms-version: 2022-11-02",
+            "",
+            "--batchresponse_1",
+            "Content-Type: application/http",
+            "Content-ID: 1",
+            "",
+            "HTTP/1.1 412 The condition specified using HTTP conditional header(s) is not met.",
+            "x-ms-error-code: ConditionNotMet",
+            "Content-Type: application/xml",
+            "",
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?><Error><Code>ConditionNotMet</Code></Error>",
+            "--batchresponse_1--",
+            "",
+        ]
+        .join("\r\n");
+
+        let responses =
+            parse_batch_response("multipart/mixed; boundary=batchresponse_1", &body).unwrap();
+        assert_eq!(
+            responses,
+            BTreeMap::from([
+                (
+                    0,
+                    SubResponse {
+                        status: StatusCode::Accepted,
+                        error_code: None,
+                    }
+                ),
+                (
+                    1,
+                    SubResponse {
+                        status: StatusCode::PreconditionFailed,
+                        error_code: Some("ConditionNotMet".to_string()),
+                    }
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn failed_batch_is_an_error() {
+        let body = [
+            "--batchresponse_1",
+            "Content-Type: application/http",
+            "",
+            "HTTP/1.1 400 One of the request inputs is not valid.",
+            "x-ms-error-code: InvalidInput",
+            "",
+            "--batchresponse_1--",
+            "",
+        ]
+        .join("\r\n");
+
+        let error =
+            parse_batch_response("multipart/mixed; boundary=batchresponse_1", &body).unwrap_err();
+        assert!(error.to_string().contains("InvalidInput"), "{error}");
+    }
+
+    #[test]
+    fn boundary_is_required() {
+        assert!(parse_batch_response("multipart/mixed", "").is_err());
+    }
+}
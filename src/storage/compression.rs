@@ -0,0 +1,80 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+};
+
+use anyhow::{Context, bail};
+use flate2::{read::GzDecoder, write::GzEncoder};
+
+use crate::cli::Compression;
+
+/// Metadata key for how a compressed blob was compressed.
+///
+/// This is recorded alongside the blob's `Content-Encoding`, since the HTTP
+/// client decompresses gzip-encoded responses and hides that header, but
+/// leaves zstd-encoded responses alone.
+pub const COMPRESSION_META: &str = "compression";
+
+/// Compresses the contents of a file.
+pub fn compress(compression: Compression, contents: &[u8]) -> anyhow::Result<Vec<u8>> {
+    match compression {
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(contents)?;
+            Ok(encoder.finish()?)
+        }
+        Compression::Zstd => Ok(zstd::encode_all(contents, 0)?),
+    }
+}
+
+/// Decompresses the contents of a blob compressed with [`compress`].
+pub fn decompress(compression: &str, contents: &[u8]) -> anyhow::Result<Vec<u8>> {
+    match compression {
+        "gzip" => {
+            let mut decompressed = Vec::new();
+            GzDecoder::new(contents)
+                .read_to_end(&mut decompressed)
+                .context("Invalid gzip data")?;
+            Ok(decompressed)
+        }
+        "zstd" => zstd::decode_all(contents).context("Invalid zstd data"),
+        other => bail!("Unsupported compression {other:?}"),
+    }
+}
+
+/// Gets how a blob was compressed from its metadata, if it was.
+pub fn compression(metadata: &HashMap<String, String>) -> Option<&str> {
+    metadata.get(COMPRESSION_META).map(String::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(Compression::Gzip; "gzip")]
+    #[test_case(Compression::Zstd; "zstd")]
+    fn round_trip(compression: Compression) {
+        let contents = b"some config\n".repeat(100);
+        let compressed = compress(compression, &contents).unwrap();
+        assert!(compressed.len() < contents.len());
+
+        let decompressed = decompress(compression.name(), &compressed).unwrap();
+        assert_eq!(decompressed, contents);
+    }
+
+    #[test_case(Compression::Gzip; "gzip")]
+    #[test_case(Compression::Zstd; "zstd")]
+    fn empty(compression: Compression) {
+        let compressed = compress(compression, b"").unwrap();
+        assert_eq!(decompress(compression.name(), &compressed).unwrap(), b"");
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(decompress("gzip", b"not gzip").is_err());
+        assert!(decompress("zstd", b"not zstd").is_err());
+        assert!(decompress("br", b"").is_err());
+    }
+}
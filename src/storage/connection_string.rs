@@ -0,0 +1,138 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use anyhow::{Context, bail};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use url::Url;
+
+use crate::storage::SharedAuth;
+
+/// Account used by the local storage emulator.
+const DEVELOPMENT_ACCOUNT: &str = "devstoreaccount1";
+
+/// Well-known key of the local storage emulator's account.
+const DEVELOPMENT_KEY: &str =
+    "Eby8vdM02xNOcqFlqUwJPLlmEtlCDXJ1OUzFT50uSRZ6IFsuFq2UVErCz4I6tq/K1SZFPTOtr/KBHBeksoGMGw==";
+
+/// Endpoint of the local storage emulator's blob service.
+const DEVELOPMENT_ENDPOINT: &str = "http://127.0.0.1:10000/devstoreaccount1/";
+
+/// A storage account connection string, like the ones shown under Security +
+/// networking -> Access keys in Azure Portal.
+///
+/// Requests are authenticated with the account key or SAS token it contains
+/// instead of an Azure credential.
+#[derive(Clone, Debug)]
+pub struct ConnectionString {
+    /// The blob service endpoint.
+    pub endpoint: Url,
+
+    /// How requests are authenticated.
+    pub auth: SharedAuth,
+}
+
+impl FromStr for ConnectionString {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut values = BTreeMap::new();
+        for pair in s.split(';').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .context("Connection string entries must be in the format Key=Value")?;
+            values.insert(key.to_ascii_lowercase(), value);
+        }
+
+        // Local storage emulator
+        if values
+            .get("usedevelopmentstorage")
+            .is_some_and(|value| value.eq_ignore_ascii_case("true"))
+        {
+            return Ok(Self {
+                endpoint: Url::parse(DEVELOPMENT_ENDPOINT)?,
+                auth: SharedAuth::AccountKey {
+                    account: DEVELOPMENT_ACCOUNT.to_string(),
+                    key: BASE64_STANDARD.decode(DEVELOPMENT_KEY)?,
+                },
+            });
+        }
+
+        let account = values.get("accountname").copied();
+        let mut endpoint = if let Some(endpoint) = values.get("blobendpoint") {
+            Url::parse(endpoint).context("Invalid BlobEndpoint in connection string")?
+        } else {
+            let account =
+                account.context("Connection string has no AccountName or BlobEndpoint")?;
+            let protocol = values.get("defaultendpointsprotocol").unwrap_or(&"https");
+            let suffix = values.get("endpointsuffix").unwrap_or(&"core.windows.net");
+            Url::parse(&format!("{protocol}://{account}.blob.{suffix}/"))
+                .context("Invalid endpoint in connection string")?
+        };
+        if !endpoint.path().ends_with('/') {
+            endpoint.set_path(&format!("{}/", endpoint.path()));
+        }
+
+        let auth = if let Some(key) = values.get("accountkey") {
+            let account =
+                account.context("Connection string has an AccountKey but no AccountName")?;
+            SharedAuth::AccountKey {
+                account: account.to_string(),
+                key: BASE64_STANDARD
+                    .decode(key)
+                    .context("Invalid AccountKey in connection string")?,
+            }
+        } else if let Some(sas) = values.get("sharedaccesssignature") {
+            SharedAuth::parse_sas(sas)
+                .context("Invalid SharedAccessSignature in connection string")?
+        } else {
+            bail!("Connection string has no AccountKey or SharedAccessSignature");
+        };
+
+        Ok(Self { endpoint, auth })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(
+        "DefaultEndpointsProtocol=https;AccountName=account;AccountKey=a2V5;EndpointSuffix=core.windows.net",
+        "https://account.blob.core.windows.net/";
+        "account key"
+    )]
+    #[test_case(
+        "BlobEndpoint=https://account.blob.core.windows.net;SharedAccessSignature=sv=2022-11-02&sig=abc",
+        "https://account.blob.core.windows.net/";
+        "sas"
+    )]
+    #[test_case(
+        "AccountName=account;AccountKey=a2V5;EndpointSuffix=core.chinacloudapi.cn",
+        "https://account.blob.core.chinacloudapi.cn/";
+        "sovereign cloud"
+    )]
+    #[test_case("UseDevelopmentStorage=true", DEVELOPMENT_ENDPOINT; "emulator")]
+    fn endpoints(connection_string: &str, expected: &str) {
+        let connection_string: ConnectionString = connection_string.parse().unwrap();
+        assert_eq!(connection_string.endpoint.as_str(), expected);
+    }
+
+    #[test_case("AccountName=account"; "no credentials")]
+    #[test_case("AccountKey=a2V5"; "no account")]
+    #[test_case("AccountName=account;AccountKey=not base64!"; "invalid key")]
+    #[test_case("BlobEndpoint=https://account.blob.core.windows.net;SharedAccessSignature=sv=1"; "unsigned sas")]
+    #[test_case("AccountName"; "missing value")]
+    fn invalid(connection_string: &str) {
+        assert!(connection_string.parse::<ConnectionString>().is_err());
+    }
+
+    #[test]
+    fn debug_hides_key() {
+        let connection_string: ConnectionString =
+            "AccountName=account;AccountKey=c2VjcmV0".parse().unwrap();
+        let debug = format!("{connection_string:?}");
+        assert!(!debug.contains("115, 101"), "{debug}");
+    }
+}
@@ -0,0 +1,220 @@
+use std::sync::Arc;
+
+use anyhow::Context as _;
+use async_trait::async_trait;
+use azure_core::{
+    credentials::TokenCredential,
+    http::{
+        ClientOptions, Context, Pipeline, RawResponse, Request, StatusCode, TransportOptions,
+        headers::Headers,
+        policies::{BearerTokenCredentialPolicy, Policy, PolicyResult},
+    },
+};
+use azure_identity::DefaultAzureCredential;
+use azure_storage_blob::{
+    BlobClient, BlobClientOptions, BlobContainerClient, BlobContainerClientOptions,
+};
+use url::Url;
+
+use crate::storage::{ConnectionString, PlaceholderCredential, SasToken, SharedAuth};
+
+/// How requests to Blob Storage are authenticated.
+///
+/// Requests to the storage account a connection string is for are
+/// authenticated with it, and requests to anything a SAS token is for use the
+/// narrowest token that applies. Everything else uses the default Azure
+/// credential.
+#[derive(Clone, Debug)]
+pub struct StorageCredential {
+    /// The default Azure credential.
+    pub azure: Arc<DefaultAzureCredential>,
+    connection_string: Option<ConnectionString>,
+    sas_tokens: Vec<SasToken>,
+}
+
+impl StorageCredential {
+    /// Creates a credential, authenticating with a connection string or SAS
+    /// tokens if any are given.
+    pub fn new(
+        connection_string: Option<ConnectionString>,
+        sas_tokens: Vec<SasToken>,
+    ) -> anyhow::Result<Self> {
+        let azure =
+            DefaultAzureCredential::new().context("Failed to get default Azure credential")?;
+        Ok(Self {
+            azure,
+            connection_string,
+            sas_tokens,
+        })
+    }
+
+    /// Creates a client for a blob.
+    pub fn blob_client(
+        &self,
+        endpoint: &Url,
+        container_name: String,
+        blob_name: String,
+    ) -> anyhow::Result<BlobClient> {
+        let mut options = BlobClientOptions::default();
+        let credential = self.authenticate(
+            endpoint,
+            &container_name,
+            Some(&blob_name),
+            &mut options.client_options,
+        );
+        Ok(BlobClient::new(
+            endpoint.as_str(),
+            container_name,
+            blob_name,
+            credential,
+            Some(options),
+        )?)
+    }
+
+    /// Creates a client for a container.
+    pub fn container_client(
+        &self,
+        endpoint: &Url,
+        container_name: String,
+    ) -> anyhow::Result<BlobContainerClient> {
+        let mut options = BlobContainerClientOptions::default();
+        let credential =
+            self.authenticate(endpoint, &container_name, None, &mut options.client_options);
+        Ok(BlobContainerClient::new(
+            endpoint.as_str(),
+            container_name,
+            credential,
+            Some(options),
+        )?)
+    }
+
+    /// Creates a pipeline for requests to a blob that its client can't make,
+    /// authenticated the same way.
+    pub fn blob_pipeline(&self, endpoint: &Url, container_name: &str, blob_name: &str) -> Pipeline {
+        self.pipeline(
+            endpoint,
+            container_name,
+            Some(blob_name),
+            ClientOptions::default(),
+        )
+    }
+
+    /// Creates a pipeline for requests to a container that its client can't
+    /// make, authenticated the same way.
+    pub fn container_pipeline(&self, endpoint: &Url, container_name: &str) -> Pipeline {
+        self.pipeline(endpoint, container_name, None, ClientOptions::default())
+    }
+
+    /// Authenticates a request to a blob without sending it, for requests that
+    /// are sent inside another one, like in a batch.
+    pub async fn authenticate_request(
+        &self,
+        endpoint: &Url,
+        container_name: &str,
+        blob_name: &str,
+        request: &mut Request,
+    ) -> anyhow::Result<()> {
+        let options = ClientOptions {
+            transport: Some(TransportOptions::new_custom_policy(Arc::new(Unsent))),
+            ..Default::default()
+        };
+        self.pipeline(endpoint, container_name, Some(blob_name), options)
+            .send(&Context::new(), request)
+            .await?;
+        Ok(())
+    }
+
+    /// Creates a pipeline for requests to a container or blob, authenticated
+    /// the same way as its client.
+    fn pipeline(
+        &self,
+        endpoint: &Url,
+        container_name: &str,
+        blob_name: Option<&str>,
+        mut options: ClientOptions,
+    ) -> Pipeline {
+        let credential = self.authenticate(endpoint, container_name, blob_name, &mut options);
+        let auth: Arc<dyn Policy> = Arc::new(BearerTokenCredentialPolicy::new(
+            credential,
+            ["https://storage.azure.com/.default"],
+        ));
+        Pipeline::new(
+            option_env!("CARGO_PKG_NAME"),
+            option_env!("CARGO_PKG_VERSION"),
+            options,
+            Vec::new(),
+            vec![auth],
+        )
+    }
+
+    /// Gets the credential to use for a container or blob, adding the policy
+    /// that authenticates with a connection string or SAS token if one
+    /// applies.
+    fn authenticate(
+        &self,
+        endpoint: &Url,
+        container_name: &str,
+        blob_name: Option<&str>,
+        options: &mut azure_core::http::ClientOptions,
+    ) -> Arc<dyn TokenCredential> {
+        match self.shared_auth(endpoint, container_name, blob_name) {
+            Some(auth) => {
+                options.per_try_policies.push(auth.policy());
+                Arc::new(PlaceholderCredential)
+            }
+            None => self.azure.clone(),
+        }
+    }
+
+    /// Finds how to authenticate with a container or blob without an Azure
+    /// credential, if possible.
+    fn shared_auth(
+        &self,
+        endpoint: &Url,
+        container_name: &str,
+        blob_name: Option<&str>,
+    ) -> Option<&SharedAuth> {
+        let sas_token = self
+            .sas_tokens
+            .iter()
+            .filter(|token| token.applies_to(endpoint, container_name, blob_name))
+            .max_by_key(|token| token.specificity());
+        if let Some(token) = sas_token {
+            return Some(&token.auth);
+        }
+
+        self.connection_string
+            .as_ref()
+            .filter(|connection_string| same_endpoint(&connection_string.endpoint, endpoint))
+            .map(|connection_string| &connection_string.auth)
+    }
+}
+
+/// A transport that doesn't send requests, so a pipeline only prepares them.
+#[derive(Debug)]
+struct Unsent;
+
+#[async_trait]
+impl Policy for Unsent {
+    async fn send(
+        &self,
+        _ctx: &Context,
+        _request: &mut Request,
+        _next: &[Arc<dyn Policy>],
+    ) -> PolicyResult {
+        Ok(RawResponse::from_bytes(
+            StatusCode::Ok,
+            Headers::new(),
+            Vec::new(),
+        ))
+    }
+}
+
+/// Checks whether two endpoints are for the same storage account, ignoring
+/// trailing slashes.
+pub(super) fn same_endpoint(a: &Url, b: &Url) -> bool {
+    a.scheme() == b.scheme()
+        && a.host_str() == b.host_str()
+        && a.port_or_known_default() == b.port_or_known_default()
+        && a.path().trim_end_matches('/') == b.path().trim_end_matches('/')
+}
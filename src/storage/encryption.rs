@@ -0,0 +1,241 @@
+use std::{collections::HashMap, env::var, fs::read_to_string, path::PathBuf};
+
+use anyhow::{Context, bail};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use ring::{
+    aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey},
+    rand::{SecureRandom, SystemRandom},
+};
+use sha2::{Digest, Sha256};
+
+use crate::{cli::MaybeEnv, dotenv::DotenvFile};
+
+/// Metadata key for the algorithm an encrypted blob was encrypted with.
+const ENCRYPTION_META: &str = "encryption";
+
+/// Metadata key for the nonce an encrypted blob was encrypted with.
+const NONCE_META: &str = "encryption_nonce";
+
+/// Metadata key for the ID of the key an encrypted blob was encrypted with.
+const KEY_ID_META: &str = "encryption_key_id";
+
+/// Metadata key for what an encrypted blob's contents are bound to, so they
+/// can't be moved to another blob and still be decrypted.
+const BINDING_META: &str = "encryption_binding";
+
+/// The binding of blobs whose contents are bound to their names.
+const NAME_BINDING: &str = "blob-name";
+
+/// The algorithm blobs are encrypted with, as recorded in their metadata.
+const ALGORITHM: &str = "AES-256-GCM";
+
+/// The length of an encryption key in bytes.
+const KEY_LEN: usize = 32;
+
+/// A key to encrypt pushed blobs and decrypt pulled ones with.
+#[derive(Debug)]
+pub struct EncryptionKey {
+    key: LessSafeKey,
+
+    /// Identifies the key without revealing it, so blobs encrypted with a
+    /// different key can be told apart.
+    id: String,
+}
+
+impl EncryptionKey {
+    /// Loads a key from a file, or from a variable with `env:VAR`.
+    ///
+    /// Variables are looked up in the dotenv file first, then in the
+    /// environment. Either way, the key must be 32 random bytes encoded as
+    /// base64.
+    pub fn load(source: &MaybeEnv<PathBuf>, env_file: Option<&DotenvFile>) -> anyhow::Result<Self> {
+        let (encoded, origin) = match source {
+            MaybeEnv::EnvVar(var_name) => (
+                env_file
+                    .and_then(|file| file.parameters.get(var_name).cloned())
+                    .or_else(|| var(var_name).ok())
+                    .with_context(|| format!("'{var_name}' not found in environment"))?,
+                var_name.clone(),
+            ),
+            MaybeEnv::Value(path) => (
+                read_to_string(path)
+                    .with_context(|| format!("Failed to read key file {}", path.display()))?,
+                path.display().to_string(),
+            ),
+        };
+
+        Self::from_base64(&encoded).with_context(|| format!("Invalid encryption key in {origin}"))
+    }
+
+    /// Parses a key encoded as base64.
+    fn from_base64(encoded: &str) -> anyhow::Result<Self> {
+        let bytes = BASE64_STANDARD
+            .decode(encoded.trim())
+            .context("Key isn't valid base64")?;
+        if bytes.len() != KEY_LEN {
+            bail!(
+                "Key must be {KEY_LEN} bytes, but it's {} bytes",
+                bytes.len()
+            );
+        }
+
+        let id = format!("{:x}", Sha256::digest(&bytes))[..16].to_string();
+        let key = UnboundKey::new(&AES_256_GCM, &bytes)
+            .map_err(|_| anyhow::anyhow!("Key can't be used with {ALGORITHM}"))?;
+
+        Ok(Self {
+            key: LessSafeKey::new(key),
+            id,
+        })
+    }
+
+    /// Encrypts the contents of a file for the blob with the given name,
+    /// returning the encrypted contents and the metadata needed to decrypt
+    /// them.
+    ///
+    /// The contents are bound to the blob's name, so they can only be
+    /// decrypted from that blob.
+    pub fn encrypt(
+        &self,
+        blob_name: &str,
+        mut contents: Vec<u8>,
+    ) -> anyhow::Result<(Vec<u8>, HashMap<String, String>)> {
+        let mut nonce = [0; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| anyhow::anyhow!("Failed to generate a nonce"))?;
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(blob_name.as_bytes()),
+                &mut contents,
+            )
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt"))?;
+
+        let metadata = HashMap::from([
+            (ENCRYPTION_META.to_string(), ALGORITHM.to_string()),
+            (NONCE_META.to_string(), BASE64_STANDARD.encode(nonce)),
+            (KEY_ID_META.to_string(), self.id.clone()),
+            (BINDING_META.to_string(), NAME_BINDING.to_string()),
+        ]);
+        Ok((contents, metadata))
+    }
+
+    /// Decrypts the contents of the blob with the given name, encrypted with
+    /// [`Self::encrypt`].
+    pub fn decrypt(
+        &self,
+        blob_name: &str,
+        metadata: &HashMap<String, String>,
+        mut contents: Vec<u8>,
+    ) -> anyhow::Result<Vec<u8>> {
+        let algorithm = metadata.get(ENCRYPTION_META).map(String::as_str);
+        if algorithm != Some(ALGORITHM) {
+            bail!(
+                "Unsupported encryption algorithm {}",
+                algorithm.unwrap_or("(none)")
+            );
+        }
+        let key_id = metadata.get(KEY_ID_META).map(String::as_str);
+        if key_id != Some(self.id.as_str()) {
+            bail!(
+                "Encrypted with a different key ({}, but the key passed is {})",
+                key_id.unwrap_or("unknown"),
+                self.id
+            );
+        }
+        let nonce = metadata
+            .get(NONCE_META)
+            .context("Missing encryption nonce")?;
+        let nonce = BASE64_STANDARD
+            .decode(nonce)
+            .ok()
+            .and_then(|nonce| Nonce::try_assume_unique_for_key(&nonce).ok())
+            .context("Invalid encryption nonce")?;
+
+        // Blobs encrypted before contents were bound to names have no binding
+        let aad = match metadata.get(BINDING_META).map(String::as_str) {
+            Some(NAME_BINDING) => blob_name.as_bytes(),
+            None => &[],
+            Some(binding) => bail!("Unsupported encryption binding {binding}"),
+        };
+        let plaintext_len = self
+            .key
+            .open_in_place(nonce, Aad::from(aad), &mut contents)
+            .map_err(|_| {
+                anyhow::anyhow!("Failed to decrypt, the blob may have been tampered with")
+            })?
+            .len();
+        contents.truncate(plaintext_len);
+        Ok(contents)
+    }
+}
+
+/// Checks whether a blob's metadata says it's encrypted.
+pub fn is_encrypted(metadata: &HashMap<String, String>) -> bool {
+    metadata.contains_key(ENCRYPTION_META)
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    const KEY: &str = "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=";
+    const OTHER_KEY: &str = "HxwdHh8AAQIDBAUGBwgJCgsMDQ4PEBESExQVFhcYGRo=";
+
+    #[test]
+    fn round_trip() {
+        let key = EncryptionKey::from_base64(KEY).unwrap();
+        let (encrypted, metadata) = key.encrypt("app.json", b"secret config".to_vec()).unwrap();
+        assert_ne!(encrypted, b"secret config");
+        assert!(is_encrypted(&metadata));
+
+        let decrypted = key.decrypt("app.json", &metadata, encrypted).unwrap();
+        assert_eq!(decrypted, b"secret config");
+    }
+
+    #[test]
+    fn bound_to_name() {
+        let key = EncryptionKey::from_base64(KEY).unwrap();
+        let (encrypted, metadata) = key.encrypt("app.json", b"secret config".to_vec()).unwrap();
+
+        let error = key.decrypt("other.json", &metadata, encrypted).unwrap_err();
+        assert!(error.to_string().contains("tampered"), "{error}");
+    }
+
+    #[test]
+    fn nonces_are_unique() {
+        let key = EncryptionKey::from_base64(KEY).unwrap();
+        let (first, _) = key.encrypt("app.json", b"same".to_vec()).unwrap();
+        let (second, _) = key.encrypt("app.json", b"same".to_vec()).unwrap();
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn wrong_key() {
+        let key = EncryptionKey::from_base64(KEY).unwrap();
+        let other = EncryptionKey::from_base64(OTHER_KEY).unwrap();
+        let (encrypted, metadata) = key.encrypt("app.json", b"secret config".to_vec()).unwrap();
+
+        let error = other.decrypt("app.json", &metadata, encrypted).unwrap_err();
+        assert!(error.to_string().contains("different key"), "{error}");
+    }
+
+    #[test]
+    fn tampered() {
+        let key = EncryptionKey::from_base64(KEY).unwrap();
+        let (mut encrypted, metadata) = key.encrypt("app.json", b"secret config".to_vec()).unwrap();
+        encrypted[0] ^= 1;
+
+        assert!(key.decrypt("app.json", &metadata, encrypted).is_err());
+    }
+
+    #[test_case(""; "empty")]
+    #[test_case("not base64!"; "not base64")]
+    #[test_case("AAECAwQFBgcICQoLDA0ODw=="; "too short")]
+    fn invalid_key(encoded: &str) {
+        assert!(EncryptionKey::from_base64(encoded).is_err());
+    }
+}
@@ -0,0 +1,373 @@
+use std::{collections::HashMap, fs::File, io::Read, path::Path};
+
+use anyhow::{Context, bail};
+use parquet::{
+    file::reader::{ChunkReader, FileReader, SerializedFileReader},
+    record::Field,
+};
+use time::{
+    OffsetDateTime,
+    format_description::well_known::{Rfc2822, Rfc3339},
+};
+
+use crate::{
+    storage::{CHECKSUM_META, MODIFIED_META, RemoteBlob, TEXT_CHECKSUM_META, compression},
+    versions::{VERSION_KEY, parse_version},
+};
+
+/// The remote state of a container, loaded from an Azure Blob Inventory report.
+///
+/// Reports may be in CSV or Parquet format and must include at least the
+/// `Name` and `Last-Modified` fields. If the `Etag`, `Content-Length`, and
+/// `Metadata` fields are included, they're used as well.
+#[derive(Clone, Debug, Default)]
+pub struct Inventory {
+    /// The blobs listed in the report.
+    blobs: HashMap<String, RemoteBlob>,
+}
+
+/// A blob listed in an inventory report.
+#[derive(Debug, Default)]
+struct Record {
+    name: String,
+    modified: Option<OffsetDateTime>,
+    etag: Option<String>,
+    size: Option<u64>,
+    metadata: HashMap<String, String>,
+    blob_type: Option<String>,
+}
+
+impl Inventory {
+    /// Loads an inventory report from a file, in Parquet format if it has a
+    /// `.parquet` extension and in CSV format otherwise.
+    pub fn from_path(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open inventory report {}", path.display()))?;
+        let inventory = if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("parquet"))
+        {
+            Self::from_parquet(file)
+        } else {
+            Self::from_reader(file)
+        };
+        inventory.with_context(|| format!("Failed to read inventory report {}", path.display()))
+    }
+
+    /// Loads an inventory report in CSV format.
+    pub fn from_reader(reader: impl Read) -> anyhow::Result<Self> {
+        let mut reader = csv::Reader::from_reader(reader);
+
+        // Find the relevant columns
+        let headers = reader.headers()?;
+        let column = |name: &str| headers.iter().position(|header| header == name);
+        let name_column = column("Name").context("Missing 'Name' field")?;
+        let modified_column = column("Last-Modified").context("Missing 'Last-Modified' field")?;
+        let etag_column = column("Etag");
+        let size_column = column("Content-Length");
+        let metadata_column = column("Metadata");
+        let blob_type_column = column("BlobType");
+
+        let mut inventory = Self::default();
+        for record in reader.records() {
+            let record = record?;
+            let field = |column: Option<usize>| {
+                column
+                    .and_then(|column| record.get(column))
+                    .filter(|value| !value.is_empty())
+            };
+
+            let name = field(Some(name_column)).unwrap_or_default();
+            inventory.insert(Record {
+                name: name.to_string(),
+                modified: field(Some(modified_column)).map(parse_time).transpose()?,
+                etag: field(etag_column).map(ToString::to_string),
+                size: field(size_column)
+                    .map(str::parse)
+                    .transpose()
+                    .with_context(|| format!("'{name}' has an invalid size"))?,
+                metadata: field(metadata_column)
+                    .map(parse_metadata)
+                    .unwrap_or_default(),
+                blob_type: field(blob_type_column).map(ToString::to_string),
+            })?;
+        }
+
+        Ok(inventory)
+    }
+
+    /// Loads an inventory report in Parquet format.
+    pub fn from_parquet(reader: impl ChunkReader + 'static) -> anyhow::Result<Self> {
+        let reader = SerializedFileReader::new(reader)?;
+        let schema = reader.metadata().file_metadata().schema_descr_ptr();
+        for required in ["Name", "Last-Modified"] {
+            if !schema
+                .root_schema()
+                .get_fields()
+                .iter()
+                .any(|field| field.name() == required)
+            {
+                bail!("Missing '{required}' field");
+            }
+        }
+
+        let mut inventory = Self::default();
+        for row in reader.get_row_iter(None)? {
+            let mut record = Record::default();
+            for (column, value) in row?.get_column_iter() {
+                match column.as_str() {
+                    "Name" => record.name = parquet_string(value).unwrap_or_default(),
+                    "Last-Modified" => record.modified = parquet_time(value)?,
+                    "Etag" => record.etag = parquet_string(value),
+                    "Content-Length" => {
+                        record.size = parquet_size(value)
+                            .with_context(|| format!("'{}' has an invalid size", record.name))?;
+                    }
+                    "Metadata" => record.metadata = parquet_metadata(value),
+                    "BlobType" => record.blob_type = parquet_string(value),
+                    _ => {}
+                }
+            }
+            inventory.insert(record)?;
+        }
+
+        Ok(inventory)
+    }
+
+    /// Adds a blob listed in a report.
+    fn insert(&mut self, record: Record) -> anyhow::Result<()> {
+        let Record {
+            name,
+            modified,
+            etag,
+            size,
+            metadata,
+            blob_type,
+        } = record;
+        if name.is_empty() {
+            bail!("Blob is missing a name");
+        }
+        let modified = match metadata.get(MODIFIED_META) {
+            Some(time) => OffsetDateTime::parse(time, &Rfc3339)?,
+            None => modified
+                .with_context(|| format!("'{name}' is missing when it was last modified"))?,
+        };
+
+        self.blobs.insert(
+            name,
+            RemoteBlob {
+                modified,
+                etag,
+                checksum: metadata.get(CHECKSUM_META).cloned(),
+                text_checksum: metadata.get(TEXT_CHECKSUM_META).cloned(),
+                compression: compression(&metadata).map(ToString::to_string),
+                blob_type: blob_type.and_then(|value| value.parse().ok()),
+                version: parse_version(metadata.get(VERSION_KEY)),
+                size,
+            },
+        );
+        Ok(())
+    }
+
+    /// Gets the state of a blob in the given container.
+    ///
+    /// Reports may list blobs with or without their container name, so both
+    /// are checked.
+    pub fn get(&self, container_name: &str, blob_name: &str) -> Option<&RemoteBlob> {
+        self.blobs
+            .get(blob_name)
+            .or_else(|| self.blobs.get(&format!("{container_name}/{blob_name}")))
+    }
+}
+
+/// Parses a timestamp from an inventory report.
+fn parse_time(s: &str) -> anyhow::Result<OffsetDateTime> {
+    OffsetDateTime::parse(s, &Rfc3339)
+        .or_else(|_| OffsetDateTime::parse(s, &Rfc2822))
+        .with_context(|| format!("Invalid timestamp: {s:?}"))
+}
+
+/// Reads a text field from a Parquet inventory report.
+fn parquet_string(field: &Field) -> Option<String> {
+    match field {
+        Field::Str(s) => Some(s.clone()),
+        Field::Bytes(bytes) => bytes.as_utf8().ok().map(ToString::to_string),
+        _ => None,
+    }
+    .filter(|s| !s.is_empty())
+}
+
+/// Reads a timestamp from a Parquet inventory report, which may be stored as
+/// text or as a timestamp.
+fn parquet_time(field: &Field) -> anyhow::Result<Option<OffsetDateTime>> {
+    let nanos = match field {
+        Field::Null => return Ok(None),
+        Field::TimestampMillis(millis) => i128::from(*millis) * 1_000_000,
+        Field::TimestampMicros(micros) => i128::from(*micros) * 1_000,
+        _ => return parquet_string(field).as_deref().map(parse_time).transpose(),
+    };
+    OffsetDateTime::from_unix_timestamp_nanos(nanos)
+        .map(Some)
+        .with_context(|| format!("Invalid timestamp: {field}"))
+}
+
+/// Reads a blob's size from a Parquet inventory report.
+fn parquet_size(field: &Field) -> anyhow::Result<Option<u64>> {
+    Ok(match field {
+        Field::Null => None,
+        Field::Int(size) => Some(u64::try_from(*size)?),
+        Field::Long(size) => Some(u64::try_from(*size)?),
+        Field::UInt(size) => Some(u64::from(*size)),
+        Field::ULong(size) => Some(*size),
+        _ => parquet_string(field).map(|s| s.parse()).transpose()?,
+    })
+}
+
+/// Reads a blob's metadata from a Parquet inventory report, which may be
+/// stored as text or as a map.
+fn parquet_metadata(field: &Field) -> HashMap<String, String> {
+    match field {
+        Field::MapInternal(map) => map
+            .entries()
+            .iter()
+            .filter_map(|(key, value)| Some((parquet_string(key)?, parquet_string(value)?)))
+            .collect(),
+        _ => parquet_string(field)
+            .as_deref()
+            .map(parse_metadata)
+            .unwrap_or_default(),
+    }
+}
+
+/// Parses a blob's metadata from an inventory report.
+///
+/// Metadata may be formatted like `{key1=value1,key2=value2}` or as a JSON
+/// object. Values that contain commas aren't supported.
+fn parse_metadata(s: &str) -> HashMap<String, String> {
+    let s = s.trim();
+    let s = s.strip_prefix('{').unwrap_or(s);
+    let s = s.strip_suffix('}').unwrap_or(s);
+    s.split([',', ';'])
+        .filter_map(|pair| {
+            let (key, value) = pair.split_once(['=', ':'])?;
+            let unquote = |s: &str| s.trim().trim_matches('"').to_string();
+            Some((unquote(key), unquote(value)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, sync::Arc};
+
+    use parquet::{
+        data_type::{ByteArray, ByteArrayType, DataType, Int64Type},
+        file::writer::{SerializedFileWriter, SerializedRowGroupWriter},
+        schema::parser::parse_message_type,
+    };
+    use test_case::test_case;
+    use time::macros::datetime;
+    use typespec_client_core::Bytes;
+
+    use super::*;
+
+    const REPORT: &str = concat!(
+        "Name,Creation-Time,Last-Modified,Etag,Content-Length,Metadata\n",
+        "a.txt,2024-01-01T00:00:00.0000000Z,2024-06-01T00:00:00.0000000Z,0x1,3,\n",
+        "container/b.txt,2024-01-01T00:00:00Z,2024-06-01T00:00:00Z,0x2,3,",
+        "\"{modified=2024-02-01T00:00:00Z,sha256=abc}\"\n",
+    );
+
+    #[test]
+    fn report() {
+        let inventory = Inventory::from_reader(REPORT.as_bytes()).unwrap();
+
+        let a = inventory.get("container", "a.txt").unwrap();
+        assert_eq!(a.modified, datetime!(2024-06-01 00:00 UTC));
+        assert_eq!(a.etag.as_deref(), Some("0x1"));
+        assert_eq!(a.checksum, None);
+        assert_eq!(a.size, Some(3));
+
+        let b = inventory.get("container", "b.txt").unwrap();
+        assert_eq!(b.modified, datetime!(2024-02-01 00:00 UTC));
+        assert_eq!(b.checksum.as_deref(), Some("abc"));
+
+        assert!(inventory.get("container", "c.txt").is_none());
+    }
+
+    #[test]
+    fn parquet_report() {
+        let schema = parse_message_type(
+            "message inventory {
+                required binary Name (UTF8);
+                required int64 Last-Modified (TIMESTAMP(MILLIS, true));
+                optional binary Etag (UTF8);
+                optional int64 Content-Length;
+                optional binary Metadata (UTF8);
+            }",
+        )
+        .unwrap();
+        let mut report = Vec::new();
+        let mut writer =
+            SerializedFileWriter::new(&mut report, Arc::new(schema), Arc::default()).unwrap();
+        let mut row_group = writer.next_row_group().unwrap();
+        let text = |values: &[&str]| -> Vec<ByteArray> {
+            values.iter().map(|&value| value.into()).collect()
+        };
+        write_column::<ByteArrayType>(&mut row_group, &text(&["a.txt", "container/b.txt"]), None);
+        write_column::<Int64Type>(&mut row_group, &[1_717_200_000_000; 2], None);
+        write_column::<ByteArrayType>(&mut row_group, &text(&["0x1", "0x2"]), Some(&[1, 1]));
+        write_column::<Int64Type>(&mut row_group, &[3, 3], Some(&[1, 1]));
+        write_column::<ByteArrayType>(
+            &mut row_group,
+            &text(&["{modified=2024-02-01T00:00:00Z,sha256=abc}"]),
+            Some(&[0, 1]),
+        );
+        row_group.close().unwrap();
+        writer.close().unwrap();
+
+        let inventory = Inventory::from_parquet(Bytes::from(report)).unwrap();
+
+        let a = inventory.get("container", "a.txt").unwrap();
+        assert_eq!(a.modified, datetime!(2024-06-01 00:00 UTC));
+        assert_eq!(a.etag.as_deref(), Some("0x1"));
+        assert_eq!(a.checksum, None);
+        assert_eq!(a.size, Some(3));
+
+        let b = inventory.get("container", "b.txt").unwrap();
+        assert_eq!(b.modified, datetime!(2024-02-01 00:00 UTC));
+        assert_eq!(b.checksum.as_deref(), Some("abc"));
+
+        assert!(inventory.get("container", "c.txt").is_none());
+    }
+
+    /// Writes the next column of a Parquet row group.
+    fn write_column<T: DataType>(
+        row_group: &mut SerializedRowGroupWriter<'_, impl Write + Send>,
+        values: &[T::T],
+        definitions: Option<&[i16]>,
+    ) {
+        let mut column = row_group.next_column().unwrap().unwrap();
+        column
+            .typed::<T>()
+            .write_batch(values, definitions, None)
+            .unwrap();
+        column.close().unwrap();
+    }
+
+    #[test]
+    fn missing_fields() {
+        assert!(Inventory::from_reader("Name,Etag\na.txt,0x1\n".as_bytes()).is_err());
+    }
+
+    #[test_case("{modified=2024-01-01T00:00:00Z,sha256=abc}"; "key-value")]
+    #[test_case(r#"{"modified":"2024-01-01T00:00:00Z","sha256":"abc"}"#; "json")]
+    fn metadata(s: &str) {
+        let metadata = parse_metadata(s);
+        assert_eq!(
+            metadata.get(MODIFIED_META).map(String::as_str),
+            Some("2024-01-01T00:00:00Z")
+        );
+        assert_eq!(metadata.get(CHECKSUM_META).map(String::as_str), Some("abc"));
+    }
+}
@@ -0,0 +1,126 @@
+use std::ops::Range;
+
+use anyhow::{Context as _, bail};
+use azure_core::http::{Context, Method, Request};
+use azure_storage_blob::BlobClient;
+use serde::Deserialize;
+use typespec_client_core::xml::read_xml;
+
+use crate::storage::{StorageCredential, blob_request_url, sas::SERVICE_VERSION};
+
+/// The pages of a page blob that were written, from Get Page Ranges.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PageList {
+    #[serde(default)]
+    page_range: Vec<PageRange>,
+    next_marker: Option<String>,
+}
+
+/// A range of written pages, including its last byte.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PageRange {
+    start: u64,
+    end: u64,
+}
+
+/// Gets the byte ranges of a page blob that were written, in order.
+///
+/// Everything else reads as zeros, so it doesn't need to be downloaded. Fails
+/// if the blob no longer has the `ETag` `etag`, if one is given.
+pub async fn get_page_ranges(
+    credential: &StorageCredential,
+    client: &BlobClient,
+    etag: Option<&str>,
+) -> anyhow::Result<Vec<Range<u64>>> {
+    let pipeline = credential.blob_pipeline(
+        client.endpoint(),
+        client.container_name(),
+        client.blob_name(),
+    );
+    let mut ranges = Vec::new();
+    let mut marker: Option<String> = None;
+    loop {
+        let mut url = blob_request_url(client)?;
+        url.query_pairs_mut().append_pair("comp", "pagelist");
+        if let Some(marker) = &marker {
+            url.query_pairs_mut().append_pair("marker", marker);
+        }
+        let mut request = Request::new(url, Method::Get);
+        request.insert_header("accept", "application/xml");
+        request.insert_header("x-ms-version", SERVICE_VERSION);
+        if let Some(etag) = etag {
+            request.insert_header("if-match", etag.to_string());
+        }
+
+        let response = pipeline.send(&Context::new(), &mut request).await?;
+        let status = response.status();
+        let body = response.into_body().collect().await?;
+        if !status.is_success() {
+            bail!(
+                "Failed to get page ranges ({status}): {}",
+                String::from_utf8_lossy(&body)
+            );
+        }
+
+        let page_list: PageList = read_xml(&body).context("Invalid page ranges")?;
+        ranges.extend(
+            page_list
+                .page_range
+                .into_iter()
+                .map(|range| range.start..range.end + 1),
+        );
+        marker = page_list.next_marker.filter(|marker| !marker.is_empty());
+        if marker.is_none() {
+            return Ok(ranges);
+        }
+    }
+}
+
+/// Gets the ranges of a page blob to download, continuing from `offset`.
+///
+/// There's always at least one range, even if nothing after `offset` was
+/// written, so the blob's properties can be read from downloading it.
+pub fn ranges_to_download(written: &[Range<u64>], offset: u64, size: u64) -> Vec<Range<u64>> {
+    let mut ranges: Vec<_> = written
+        .iter()
+        .map(|range| range.start.max(offset)..range.end.min(size))
+        .filter(|range| !range.is_empty())
+        .collect();
+    if ranges.is_empty() && offset < size {
+        ranges.push(offset..offset + 1);
+    }
+
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(&[0..512, 2048..4096], 0, 4096 => vec![0..512, 2048..4096]; "hole between pages")]
+    #[test_case(&[0..512, 2048..4096], 256, 4096 => vec![256..512, 2048..4096]; "resumed in a range")]
+    #[test_case(&[0..512, 2048..4096], 1024, 4096 => vec![2048..4096]; "resumed in a hole")]
+    #[test_case(&[0..512, 1024..1536], 0, 4096 => vec![0..512, 1024..1536]; "hole at the end")]
+    #[test_case(&[], 1024, 4096 => vec![1024..1025]; "nothing written")]
+    #[test_case(&[0..512, 1024..1536], 2048, 4096 => vec![2048..2049]; "nothing left")]
+    #[test_case(&[], 0, 0 => Vec::<Range<u64>>::new(); "empty")]
+    fn ranges(written: &[Range<u64>], offset: u64, size: u64) -> Vec<Range<u64>> {
+        ranges_to_download(written, offset, size)
+    }
+
+    #[test]
+    fn parses_page_list() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?><PageList><PageRange><Start>0</Start><End>511</End></PageRange><PageRange><Start>2048</Start><End>4095</End></PageRange><NextMarker /></PageList>"#;
+        let page_list: PageList = read_xml(xml.as_bytes()).unwrap();
+        let ranges: Vec<_> = page_list
+            .page_range
+            .iter()
+            .map(|range| range.start..range.end + 1)
+            .collect();
+        assert_eq!(ranges, vec![0..512, 2048..4096]);
+    }
+}
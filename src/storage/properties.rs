@@ -0,0 +1,225 @@
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    fs::write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context as _, bail};
+use azure_core::http::{Context, Method, NoFormat, Request, Response, headers::CONTENT_TYPE};
+use azure_storage_blob::{
+    BlobClient,
+    models::{BlobClientDownloadResult, BlobClientDownloadResultHeaders, BlobTags},
+};
+use serde::Serialize;
+use tracing::info;
+use typespec_client_core::xml::read_xml;
+
+use crate::storage::{StorageCredential, blob_request_url, sas::SERVICE_VERSION};
+
+/// Extension added to a pulled file's name for the sidecar file its blob's
+/// properties are written to.
+pub const SIDECAR_EXTENSION: &str = "azmeta";
+
+/// Properties of a blob that are lost when it's saved as a plain file.
+#[derive(Clone, Default, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BlobProperties {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_encoding: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_language: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_disposition: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<String>,
+
+    /// The blob's metadata, including what azsync records when pushing.
+    pub metadata: BTreeMap<String, String>,
+
+    /// The blob's index tags.
+    pub tags: BTreeMap<String, String>,
+}
+
+impl BlobProperties {
+    /// Reads a blob's properties from the response to downloading it.
+    ///
+    /// Tags aren't included in the response, so they're requested separately
+    /// if the blob has any.
+    pub async fn from_download(
+        credential: &StorageCredential,
+        client: &BlobClient,
+        response: &Response<BlobClientDownloadResult, NoFormat>,
+    ) -> anyhow::Result<Self> {
+        let tags = if response.tag_count()?.unwrap_or_default() > 0 {
+            get_blob_tags(credential, client)
+                .await
+                .context("Failed to get blob tags")?
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(Self {
+            content_type: response.headers().get_optional_string(&CONTENT_TYPE),
+            content_encoding: response.content_encoding()?,
+            content_language: response.content_language()?,
+            content_disposition: response.content_disposition()?,
+            cache_control: response.cache_control()?,
+            metadata: response.metadata()?.into_iter().collect(),
+            tags,
+        })
+    }
+
+    /// Writes the properties as JSON to the sidecar file of a pulled file.
+    pub fn write_sidecar(&self, path: &Path) -> anyhow::Result<()> {
+        let sidecar = sidecar_path(path);
+        let json = serde_json::to_string_pretty(self)?;
+        write(&sidecar, json + "\n")
+            .with_context(|| format!("Failed to write {}", sidecar.display()))
+    }
+
+    /// Prints the properties of a blob.
+    pub fn print(&self, blob_name: &str) {
+        info!("Properties of {blob_name}:");
+        let headers = [
+            ("Content-Type", &self.content_type),
+            ("Content-Encoding", &self.content_encoding),
+            ("Content-Language", &self.content_language),
+            ("Content-Disposition", &self.content_disposition),
+            ("Cache-Control", &self.cache_control),
+        ];
+        for (name, value) in headers {
+            if let Some(value) = value {
+                info!("  {name}: {value}");
+            }
+        }
+        for (key, value) in &self.metadata {
+            info!("  Metadata {key}: {value}");
+        }
+        for (key, value) in &self.tags {
+            info!("  Tag {key}: {value}");
+        }
+    }
+}
+
+/// Gets the index tags of a blob.
+///
+/// The SDK's clients can't get tags, so the request is sent through a
+/// pipeline authenticated like the blob's client.
+pub async fn get_blob_tags(
+    credential: &StorageCredential,
+    client: &BlobClient,
+) -> anyhow::Result<BTreeMap<String, String>> {
+    let mut url = blob_request_url(client)?;
+    url.query_pairs_mut().append_pair("comp", "tags");
+    let mut request = Request::new(url, Method::Get);
+    request.insert_header("accept", "application/xml");
+    request.insert_header("x-ms-version", SERVICE_VERSION);
+
+    let pipeline = credential.blob_pipeline(
+        client.endpoint(),
+        client.container_name(),
+        client.blob_name(),
+    );
+    let response = pipeline.send(&Context::new(), &mut request).await?;
+    let status = response.status();
+    let body = response.into_body().collect().await?;
+    if !status.is_success() {
+        bail!(
+            "Failed to get blob tags ({status}): {}",
+            String::from_utf8_lossy(&body)
+        );
+    }
+
+    let tags: BlobTags = read_xml(&body).context("Invalid blob tags")?;
+    Ok(tags
+        .blob_tag_set
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|tag| tag.key.zip(tag.value))
+        .collect())
+}
+
+/// Formats index tags the way they're set when a blob is written, like
+/// `owner=ops&stage=dev`, with each key and value URL-encoded.
+pub fn tags_string(tags: &BTreeMap<String, String>) -> String {
+    let encode = |s: &str| {
+        s.bytes()
+            .map(|b| match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                    char::from(b).to_string()
+                }
+                _ => format!("%{b:02X}"),
+            })
+            .collect::<String>()
+    };
+    tags.iter()
+        .map(|(key, value)| format!("{}={}", encode(key), encode(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Gets the path of the sidecar file for a pulled file.
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(".");
+    name.push(SIDECAR_EXTENSION);
+    path.with_file_name(name)
+}
+
+/// Checks whether a file is a sidecar file.
+pub fn is_sidecar(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == SIDECAR_EXTENSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("file.json", "file.json.azmeta"; "with extension")]
+    #[test_case("dir/Dockerfile", "dir/Dockerfile.azmeta"; "without extension")]
+    fn sidecar(path: &str, expected: &str) {
+        let sidecar = sidecar_path(Path::new(path));
+        assert_eq!(sidecar, Path::new(expected));
+        assert!(is_sidecar(&sidecar));
+        assert!(!is_sidecar(Path::new(path)));
+    }
+
+    #[test]
+    fn tags() {
+        let tags = BTreeMap::from([
+            ("owner".to_string(), "ops team".to_string()),
+            ("stage".to_string(), "a&b=c".to_string()),
+        ]);
+        assert_eq!(tags_string(&tags), "owner=ops%20team&stage=a%26b%3Dc");
+        assert_eq!(tags_string(&BTreeMap::new()), "");
+    }
+
+    #[test]
+    fn serialize() {
+        let properties = BlobProperties {
+            content_type: Some("application/json".to_string()),
+            metadata: BTreeMap::from([("owner".to_string(), "ops".to_string())]),
+            ..Default::default()
+        };
+        let json = serde_json::to_value(&properties).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "content-type": "application/json",
+                "metadata": { "owner": "ops" },
+                "tags": {},
+            })
+        );
+    }
+}
@@ -0,0 +1,205 @@
+use std::sync::Arc;
+
+use anyhow::{Context, bail};
+use azure_core::credentials::TokenCredential;
+use base64::{Engine, prelude::BASE64_STANDARD};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use time::{OffsetDateTime, format_description::FormatItem, macros::format_description};
+use typespec_client_core::{
+    http::{Method, Request, new_http_client},
+    xml::read_xml,
+};
+use url::Url;
+
+/// Storage service version used for requests and signatures.
+pub(super) const SERVICE_VERSION: &str = "2022-11-02";
+
+/// Format of timestamps in requests and signatures.
+const TIME_FORMAT: &[FormatItem<'_>] =
+    format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]Z");
+
+/// A key for signing user delegation SAS tokens.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct UserDelegationKey {
+    signed_oid: String,
+    signed_tid: String,
+    signed_start: String,
+    signed_expiry: String,
+    signed_service: String,
+    signed_version: String,
+    value: String,
+}
+
+/// Requests a user delegation key from the storage account.
+///
+/// The key is valid between `start` and `expiry`, and SAS tokens signed with
+/// it can't outlive it. Keys are valid for at most seven days.
+pub async fn get_user_delegation_key(
+    credential: Arc<dyn TokenCredential>,
+    endpoint: &Url,
+    start: OffsetDateTime,
+    expiry: OffsetDateTime,
+) -> anyhow::Result<UserDelegationKey> {
+    let token = credential
+        .get_token(&["https://storage.azure.com/.default"], None)
+        .await?;
+
+    let mut url = endpoint.clone();
+    url.query_pairs_mut()
+        .append_pair("restype", "service")
+        .append_pair("comp", "userdelegationkey");
+    let mut request = Request::new(url, Method::Post);
+    request.insert_header("authorization", format!("Bearer {}", token.token.secret()));
+    request.insert_header("x-ms-version", SERVICE_VERSION);
+    request.insert_header("content-type", "application/xml");
+    request.set_body(format!(
+        r#"<?xml version="1.0" encoding="utf-8"?><KeyInfo><Start>{}</Start><Expiry>{}</Expiry></KeyInfo>"#,
+        start.format(TIME_FORMAT)?,
+        expiry.format(TIME_FORMAT)?,
+    ));
+
+    let response = new_http_client().execute_request(&request).await?;
+    let status = response.status();
+    let body = response.into_body().collect().await?;
+    if !status.is_success() {
+        bail!(
+            "Failed to get user delegation key ({status}): {}",
+            String::from_utf8_lossy(&body)
+        );
+    }
+
+    read_xml(&body).context("Invalid user delegation key")
+}
+
+/// Creates a URL to a blob with a SAS token granting read access until
+/// `expiry`.
+pub fn blob_read_url(
+    key: &UserDelegationKey,
+    endpoint: &Url,
+    container_name: &str,
+    blob_name: &str,
+    start: OffsetDateTime,
+    expiry: OffsetDateTime,
+) -> anyhow::Result<Url> {
+    let account = endpoint
+        .host_str()
+        .and_then(|host| host.split('.').next())
+        .context("Unable to determine storage account name from endpoint")?;
+    let start = start.format(TIME_FORMAT)?;
+    let expiry = expiry.format(TIME_FORMAT)?;
+
+    // Sign the token
+    let string_to_sign = [
+        "r",
+        &start,
+        &expiry,
+        &format!("/blob/{account}/{container_name}/{blob_name}"),
+        &key.signed_oid,
+        &key.signed_tid,
+        &key.signed_start,
+        &key.signed_expiry,
+        &key.signed_service,
+        &key.signed_version,
+        "",
+        "",
+        "",
+        "",
+        "https",
+        SERVICE_VERSION,
+        "b",
+        "",
+        "",
+        "",
+        "",
+        "",
+        "",
+        "",
+    ]
+    .join("\n");
+    let key_value = BASE64_STANDARD
+        .decode(&key.value)
+        .context("Invalid user delegation key")?;
+    let mut mac = Hmac::<Sha256>::new_from_slice(&key_value)?;
+    mac.update(string_to_sign.as_bytes());
+    let signature = BASE64_STANDARD.encode(mac.finalize().into_bytes());
+
+    // Build the URL
+    let mut url = endpoint.clone();
+    url.path_segments_mut()
+        .map_err(|()| anyhow::anyhow!("Invalid storage account endpoint"))?
+        .pop_if_empty()
+        .push(container_name)
+        .extend(blob_name.split('/'));
+    url.query_pairs_mut()
+        .append_pair("sp", "r")
+        .append_pair("st", &start)
+        .append_pair("se", &expiry)
+        .append_pair("skoid", &key.signed_oid)
+        .append_pair("sktid", &key.signed_tid)
+        .append_pair("skt", &key.signed_start)
+        .append_pair("ske", &key.signed_expiry)
+        .append_pair("sks", &key.signed_service)
+        .append_pair("skv", &key.signed_version)
+        .append_pair("spr", "https")
+        .append_pair("sv", SERVICE_VERSION)
+        .append_pair("sr", "b")
+        .append_pair("sig", &signature);
+
+    Ok(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use time::macros::datetime;
+    use typespec_client_core::xml::read_xml_str;
+
+    use super::*;
+
+    const KEY: &str = concat!(
+        r#"<?xml version="1.0" encoding="utf-8"?>"#,
+        "<UserDelegationKey>",
+        "<SignedOid>oid</SignedOid>",
+        "<SignedTid>tid</SignedTid>",
+        "<SignedStart>2024-01-01T00:00:00Z</SignedStart>",
+        "<SignedExpiry>2024-01-02T00:00:00Z</SignedExpiry>",
+        "<SignedService>b</SignedService>",
+        "<SignedVersion>2022-11-02</SignedVersion>",
+        "<Value>a2V5</Value>",
+        "</UserDelegationKey>",
+    );
+
+    #[test]
+    fn read_url() {
+        let key: UserDelegationKey = read_xml_str(KEY).unwrap();
+        let endpoint = Url::parse("https://account.blob.core.windows.net/").unwrap();
+        let url = blob_read_url(
+            &key,
+            &endpoint,
+            "container",
+            "dir/file name.txt",
+            datetime!(2024-01-01 00:00 UTC),
+            datetime!(2024-01-01 01:00 UTC),
+        )
+        .unwrap();
+
+        assert_eq!(
+            url.path(),
+            "/container/dir/file%20name.txt",
+            "blob path should be encoded"
+        );
+        let query: Vec<_> = url.query_pairs().map(|(key, _)| key.into_owned()).collect();
+        assert_eq!(
+            query,
+            [
+                "sp", "st", "se", "skoid", "sktid", "skt", "ske", "sks", "skv", "spr", "sv", "sr",
+                "sig",
+            ]
+        );
+        let (_, expiry) = url.query_pairs().find(|(key, _)| key == "se").unwrap();
+        assert_eq!(expiry, "2024-01-01T01:00:00Z");
+    }
+}
@@ -0,0 +1,154 @@
+use std::str::FromStr;
+
+use anyhow::Context;
+use url::Url;
+
+use crate::storage::{SharedAuth, credential::same_endpoint};
+
+/// A SAS token, either on its own or in the URL of the storage account,
+/// container, or blob it grants access to.
+#[derive(Clone, Debug)]
+pub struct SasToken {
+    /// What the token is for, if it was given as part of a URL.
+    pub scope: Option<SasScope>,
+
+    /// How requests are authenticated.
+    pub auth: SharedAuth,
+}
+
+/// The storage account, container, or blob a SAS token is for.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SasScope {
+    /// The storage account's endpoint.
+    pub endpoint: Url,
+
+    /// The container, unless the token is for the whole storage account.
+    pub container_name: Option<String>,
+
+    /// The blob, if the token is only for one blob.
+    pub blob_name: Option<String>,
+}
+
+impl SasToken {
+    /// Checks whether this token should be used for a container, or for a blob
+    /// in it if `blob_name` is given.
+    pub fn applies_to(
+        &self,
+        endpoint: &Url,
+        container_name: &str,
+        blob_name: Option<&str>,
+    ) -> bool {
+        let Some(scope) = &self.scope else {
+            return true;
+        };
+
+        same_endpoint(&scope.endpoint, endpoint)
+            && scope
+                .container_name
+                .as_deref()
+                .is_none_or(|name| name == container_name)
+            && scope
+                .blob_name
+                .as_deref()
+                .is_none_or(|name| Some(name) == blob_name)
+    }
+
+    /// Gets how narrow this token's scope is, so the narrowest token that
+    /// applies can be used.
+    pub fn specificity(&self) -> usize {
+        self.scope.as_ref().map_or(0, |scope| {
+            1 + usize::from(scope.container_name.is_some()) + usize::from(scope.blob_name.is_some())
+        })
+    }
+}
+
+impl FromStr for SasToken {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !(s.starts_with("https://") || s.starts_with("http://")) {
+            return Ok(Self {
+                scope: None,
+                auth: SharedAuth::parse_sas(s)?,
+            });
+        }
+
+        // The token is the query of the URL
+        let mut url = Url::parse(s).context("Invalid SAS URL")?;
+        let auth = SharedAuth::parse_sas(url.query().unwrap_or_default())?;
+        url.set_query(None);
+
+        let mut segments = url
+            .path_segments()
+            .context("Invalid SAS URL")?
+            .filter(|segment| !segment.is_empty());
+        let container_name = segments.next().map(ToString::to_string);
+        let blob_name = segments.collect::<Vec<_>>().join("/");
+        let endpoint = Url::parse(&format!("{}/", url.origin().ascii_serialization()))?;
+        Ok(Self {
+            scope: Some(SasScope {
+                endpoint,
+                container_name,
+                blob_name: Some(blob_name).filter(|name| !name.is_empty()),
+            }),
+            auth,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn scope(endpoint: &str, container_name: Option<&str>, blob_name: Option<&str>) -> SasScope {
+        SasScope {
+            endpoint: Url::parse(endpoint).unwrap(),
+            container_name: container_name.map(ToString::to_string),
+            blob_name: blob_name.map(ToString::to_string),
+        }
+    }
+
+    #[test_case("sv=2022-11-02&sig=abc", None; "token")]
+    #[test_case("?sv=2022-11-02&sig=abc", None; "token with question mark")]
+    #[test_case(
+        "https://account.blob.core.windows.net/?sv=2022-11-02&sig=abc",
+        Some(scope("https://account.blob.core.windows.net/", None, None));
+        "account url"
+    )]
+    #[test_case(
+        "https://account.blob.core.windows.net/container?sv=2022-11-02&sig=abc",
+        Some(scope("https://account.blob.core.windows.net/", Some("container"), None));
+        "container url"
+    )]
+    #[test_case(
+        "https://account.blob.core.windows.net/container/dir/file.json?sv=2022-11-02&sig=abc",
+        Some(scope("https://account.blob.core.windows.net/", Some("container"), Some("dir/file.json")));
+        "blob url"
+    )]
+    fn parse(token: &str, expected: Option<SasScope>) {
+        let token: SasToken = token.parse().unwrap();
+        assert_eq!(token.scope, expected);
+    }
+
+    #[test_case("sv=2022-11-02"; "unsigned token")]
+    #[test_case("https://account.blob.core.windows.net/container"; "url without token")]
+    fn invalid(token: &str) {
+        assert!(token.parse::<SasToken>().is_err());
+    }
+
+    #[test_case("sv=1&sig=abc", "container", Some("a.json") => true; "token applies everywhere")]
+    #[test_case("https://account.blob.core.windows.net/container?sig=abc", "container", Some("a.json") => true; "blob in container")]
+    #[test_case("https://account.blob.core.windows.net/container?sig=abc", "container", None => true; "container itself")]
+    #[test_case("https://account.blob.core.windows.net/container?sig=abc", "other", Some("a.json") => false; "other container")]
+    #[test_case("https://account.blob.core.windows.net/container/a.json?sig=abc", "container", Some("a.json") => true; "same blob")]
+    #[test_case("https://account.blob.core.windows.net/container/a.json?sig=abc", "container", Some("b.json") => false; "other blob")]
+    #[test_case("https://account.blob.core.windows.net/container/a.json?sig=abc", "container", None => false; "blob token for container")]
+    #[test_case("https://other.blob.core.windows.net/container?sig=abc", "container", Some("a.json") => false; "other account")]
+    fn applies_to(token: &str, container_name: &str, blob_name: Option<&str>) -> bool {
+        let endpoint = Url::parse("https://account.blob.core.windows.net/").unwrap();
+        let token: SasToken = token.parse().unwrap();
+        token.applies_to(&endpoint, container_name, blob_name)
+    }
+}
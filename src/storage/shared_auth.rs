@@ -0,0 +1,260 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{Debug, Formatter, Write},
+    sync::Arc,
+};
+
+use anyhow::ensure;
+use async_trait::async_trait;
+use azure_core::{
+    credentials::{AccessToken, TokenCredential, TokenRequestOptions},
+    http::{
+        Context, Request,
+        headers::HeaderName,
+        policies::{Policy, PolicyResult},
+    },
+};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use time::{Duration, OffsetDateTime, format_description::FormatItem, macros::format_description};
+
+/// Format of the `x-ms-date` header.
+const DATE_FORMAT: &[FormatItem<'_>] = format_description!(
+    "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT"
+);
+
+/// How requests are authenticated without an Azure credential.
+#[derive(Clone)]
+pub enum SharedAuth {
+    /// Sign each request with the account key.
+    AccountKey { account: String, key: Vec<u8> },
+
+    /// Add a SAS token to each request.
+    Sas(Vec<(String, String)>),
+}
+
+impl SharedAuth {
+    /// Parses a SAS token, like `sv=...&sig=...`.
+    pub fn parse_sas(token: &str) -> anyhow::Result<Self> {
+        let pairs: Vec<_> = url::form_urlencoded::parse(token.trim_start_matches('?').as_bytes())
+            .into_owned()
+            .collect();
+        ensure!(
+            pairs.iter().any(|(key, _)| key == "sig"),
+            "SAS tokens must have a signature (sig)"
+        );
+        Ok(Self::Sas(pairs))
+    }
+
+    /// Gets a policy that authenticates requests this way.
+    ///
+    /// The policy must run after the client's own authentication policy so it
+    /// can replace the bearer token that policy adds.
+    pub fn policy(&self) -> Arc<dyn Policy> {
+        Arc::new(SharedAuthPolicy(self.clone()))
+    }
+}
+
+impl Debug for SharedAuth {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // Never show keys or tokens
+        match self {
+            Self::AccountKey { account, .. } => f
+                .debug_struct("AccountKey")
+                .field("account", account)
+                .finish_non_exhaustive(),
+            Self::Sas(_) => f.debug_struct("Sas").finish_non_exhaustive(),
+        }
+    }
+}
+
+/// A credential for clients authenticated with [`SharedAuth`].
+///
+/// Clients always add a bearer token to requests, so this provides a
+/// placeholder that [`SharedAuthPolicy`] replaces.
+#[derive(Debug)]
+pub struct PlaceholderCredential;
+
+#[async_trait]
+impl TokenCredential for PlaceholderCredential {
+    async fn get_token(
+        &self,
+        _scopes: &[&str],
+        _options: Option<TokenRequestOptions>,
+    ) -> azure_core::Result<AccessToken> {
+        Ok(AccessToken::new(
+            "",
+            OffsetDateTime::now_utc() + Duration::days(1),
+        ))
+    }
+}
+
+/// Replaces the bearer token on each request with an account key signature or
+/// SAS token.
+struct SharedAuthPolicy(SharedAuth);
+
+impl Debug for SharedAuthPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedAuthPolicy").finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl Policy for SharedAuthPolicy {
+    async fn send(
+        &self,
+        ctx: &Context,
+        request: &mut Request,
+        next: &[Arc<dyn Policy>],
+    ) -> PolicyResult {
+        request.headers_mut().remove("authorization");
+        match &self.0 {
+            SharedAuth::AccountKey { account, key } => {
+                let date = OffsetDateTime::now_utc()
+                    .format(DATE_FORMAT)
+                    .map_err(|error| {
+                        azure_core::Error::new(azure_core::error::ErrorKind::Other, error)
+                    })?;
+                request.insert_header("x-ms-date", date);
+                let signature = sign(key, &string_to_sign(account, request));
+                request.insert_header("authorization", format!("SharedKey {account}:{signature}"));
+            }
+            SharedAuth::Sas(pairs) => {
+                // Requests are sent again when retried
+                if !request.url().query_pairs().any(|(key, _)| key == "sig") {
+                    request.url_mut().query_pairs_mut().extend_pairs(pairs);
+                }
+            }
+        }
+
+        next[0].send(ctx, request, &next[1..]).await
+    }
+}
+
+/// Builds the string signed with the account key for a request.
+///
+/// See <https://learn.microsoft.com/rest/api/storageservices/authorize-with-shared-key>.
+fn string_to_sign(account: &str, request: &Request) -> String {
+    let headers = request.headers();
+    let header = |name: &'static str| {
+        headers
+            .get_optional_str(&HeaderName::from_static(name))
+            .unwrap_or_default()
+            .to_string()
+    };
+    let content_length = match request.body().len() {
+        0 => String::new(),
+        length => length.to_string(),
+    };
+
+    // Headers specific to storage, sorted by name
+    let mut storage_headers: Vec<_> = headers
+        .iter()
+        .filter(|(name, _)| name.as_str().starts_with("x-ms-"))
+        .map(|(name, value)| format!("{}:{}\n", name.as_str(), value.as_str().trim()))
+        .collect();
+    storage_headers.sort_unstable();
+
+    // The resource, with query parameters sorted by name
+    let url = request.url();
+    let mut resource = format!("/{account}{}", url.path());
+    let mut parameters: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (key, value) in url.query_pairs() {
+        parameters
+            .entry(key.to_lowercase())
+            .or_default()
+            .push(value.into_owned());
+    }
+    for (key, mut values) in parameters {
+        values.sort_unstable();
+        let _ = write!(resource, "\n{key}:{}", values.join(","));
+    }
+
+    [
+        request.method().as_ref().to_string(),
+        header("content-encoding"),
+        header("content-language"),
+        content_length,
+        header("content-md5"),
+        header("content-type"),
+        String::new(),
+        header("if-modified-since"),
+        header("if-match"),
+        header("if-none-match"),
+        header("if-unmodified-since"),
+        header("range"),
+        storage_headers.concat() + &resource,
+    ]
+    .join("\n")
+}
+
+/// Signs a string with an account key.
+fn sign(key: &[u8], string_to_sign: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(string_to_sign.as_bytes());
+    BASE64_STANDARD.encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use azure_core::http::Method;
+    use pretty_assertions::assert_eq;
+    use url::Url;
+
+    use super::*;
+
+    #[test]
+    fn debug_hides_key() {
+        let auth = SharedAuth::AccountKey {
+            account: "account".to_string(),
+            key: b"secret".to_vec(),
+        };
+        let debug = format!("{auth:?}");
+        assert!(!debug.contains("115, 101"), "{debug}");
+    }
+
+    #[test]
+    fn sas_requires_signature() {
+        assert!(SharedAuth::parse_sas("?sv=2022-11-02&sig=abc").is_ok());
+        assert!(SharedAuth::parse_sas("sv=2022-11-02").is_err());
+    }
+
+    #[test]
+    fn signed_string() {
+        let url = Url::parse(
+            "https://account.blob.core.windows.net/container/a%20b.txt?comp=block&blockid=AAA%3D",
+        )
+        .unwrap();
+        let mut request = Request::new(url, Method::Put);
+        request.insert_header("x-ms-version", "2025-11-05");
+        request.insert_header("x-ms-date", "Mon, 01 Jan 2024 00:00:00 GMT");
+        request.insert_header("content-type", "text/plain");
+        request.insert_header("if-match", "\"etag\"");
+        request.set_body("hello");
+
+        assert_eq!(
+            string_to_sign("account", &request),
+            [
+                "PUT",
+                "",
+                "",
+                "5",
+                "",
+                "text/plain",
+                "",
+                "",
+                "\"etag\"",
+                "",
+                "",
+                "",
+                "x-ms-date:Mon, 01 Jan 2024 00:00:00 GMT",
+                "x-ms-version:2025-11-05",
+                "/account/container/a%20b.txt",
+                "blockid:AAA=",
+                "comp:block",
+            ]
+            .join("\n")
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use std::{
+    fs::File,
+    io::{Seek, SeekFrom, Write},
+    ops::Range,
+};
+
+/// Size of the blocks that are checked for zeros when writing.
+///
+/// This matches the cluster size of most filesystems, which is the smallest
+/// hole a sparse file can have.
+const SPARSE_BLOCK_SIZE: u64 = 4096;
+
+/// Writes a file sequentially, leaving holes instead of writing blocks that
+/// are entirely zeros.
+///
+/// Holes read back as zeros, so the file's contents are the same either way.
+/// On filesystems that support sparse files, they don't take up any space.
+pub struct SparseWriter {
+    file: File,
+    position: u64,
+}
+
+impl SparseWriter {
+    /// Starts writing a file that's already been written up to `position`,
+    /// which is zero for a new, empty file.
+    pub fn resume(file: File, position: u64) -> Self {
+        Self { file, position }
+    }
+
+    /// Makes sure everything written so far is on disk, returning how much
+    /// has been written.
+    pub fn sync(&mut self) -> anyhow::Result<u64> {
+        self.file.set_len(self.position)?;
+        self.file.sync_data()?;
+        Ok(self.position)
+    }
+
+    /// Writes the next chunk of the file.
+    pub fn write(&mut self, chunk: &[u8]) -> anyhow::Result<()> {
+        for segment in data_segments(self.position, chunk) {
+            self.file
+                .seek(SeekFrom::Start(self.position + segment.start as u64))?;
+            self.file.write_all(&chunk[segment])?;
+        }
+        self.position += chunk.len() as u64;
+
+        Ok(())
+    }
+
+    /// Leaves a hole up to `position`, like for the pages of a page blob that
+    /// were never written, returning how many bytes were skipped.
+    pub fn skip_to(&mut self, position: u64) -> u64 {
+        let skipped = position.saturating_sub(self.position);
+        self.position += skipped;
+        skipped
+    }
+
+    /// Finishes writing, extending the file over any hole at its end.
+    pub fn finish(self) -> anyhow::Result<File> {
+        self.file.set_len(self.position)?;
+        Ok(self.file)
+    }
+}
+
+/// Gets the parts of a chunk written at `offset` that need to be written,
+/// skipping blocks that are entirely zeros.
+///
+/// Blocks are aligned to the start of the file, so a chunk can start or end
+/// part way through one.
+fn data_segments(offset: u64, chunk: &[u8]) -> Vec<Range<usize>> {
+    let mut segments: Vec<Range<usize>> = Vec::new();
+    let mut start = 0;
+    while start < chunk.len() {
+        let to_boundary = SPARSE_BLOCK_SIZE - (offset + start as u64) % SPARSE_BLOCK_SIZE;
+        let end = chunk
+            .len()
+            .min(start + usize::try_from(to_boundary).unwrap_or(usize::MAX));
+        if chunk[start..end].iter().any(|&byte| byte != 0) {
+            match segments.last_mut() {
+                Some(last) if last.end == start => last.end = end,
+                _ => segments.push(start..end),
+            }
+        }
+        start = end;
+    }
+
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use test_case::test_case;
+
+    use super::*;
+
+    /// Creates a chunk with the given bytes set to 1.
+    fn chunk(len: usize, set: &[usize]) -> Vec<u8> {
+        let mut chunk = vec![0; len];
+        for &index in set {
+            chunk[index] = 1;
+        }
+        chunk
+    }
+
+    #[test_case(0, chunk(8192, &[]), vec![]; "all zeros")]
+    #[test_case(0, chunk(8192, &[0, 8191]), vec![0..8192]; "adjacent blocks are merged")]
+    #[test_case(0, chunk(12288, &[100, 9000]), vec![0..4096, 8192..12288]; "zero block between")]
+    #[test_case(4000, chunk(200, &[150]), vec![96..200]; "chunk crosses boundary")]
+    #[test_case(4000, chunk(200, &[50]), vec![0..96]; "partial first block")]
+    fn segments(offset: u64, chunk: Vec<u8>, expected: Vec<Range<usize>>) {
+        assert_eq!(data_segments(offset, &chunk), expected);
+    }
+}
@@ -0,0 +1,290 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fs::{File, OpenOptions, copy, read_to_string, remove_file, rename, write},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use anyhow::{Context, bail};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Extension of temporary files.
+const TEMP_EXTENSION: &str = "azsync-tmp";
+
+/// Counter to keep temporary files created by this process unique.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A file that's written to first, then moved into place once complete.
+///
+/// The file is deleted if it's dropped without being persisted, so failed
+/// downloads don't leave partial files behind, unless it can be resumed.
+pub struct TempFile {
+    path: PathBuf,
+    persisted: bool,
+
+    /// Where progress is recorded if the file is kept to resume later.
+    resume: Option<Resume>,
+}
+
+/// Where a resumable download's progress is recorded.
+struct Resume {
+    /// The path to the marker file.
+    marker: PathBuf,
+
+    /// The `ETag` of the blob being downloaded.
+    etag: String,
+}
+
+/// What's recorded about a resumable download.
+#[derive(Deserialize, Serialize)]
+struct ResumeMarker {
+    /// The `ETag` of the blob being downloaded.
+    etag: String,
+
+    /// How many bytes from the start of the blob were completely written.
+    complete: u64,
+}
+
+impl TempFile {
+    /// Creates a temporary file to be moved to `destination` later.
+    ///
+    /// The file is created in `dir` if set, or next to `destination`
+    /// otherwise. Keeping it on the same filesystem as the destination lets
+    /// it be moved into place atomically.
+    pub fn create(dir: Option<&Path>, destination: &Path) -> anyhow::Result<(Self, File)> {
+        let (dir, file_name) = temp_location(dir, destination)?;
+        let path = dir.join(temp_name(
+            file_name,
+            NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        ));
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .with_context(|| format!("Can't create temporary file in {}", dir.display()))?;
+
+        Ok((
+            Self {
+                path,
+                persisted: false,
+                resume: None,
+            },
+            file,
+        ))
+    }
+
+    /// Opens a temporary file for downloading a blob with the given `ETag`,
+    /// which is kept if the download is interrupted so it can be resumed.
+    ///
+    /// The file is kept next to a marker recording the blob's `ETag` and how
+    /// much of it was completely written. Both are named after the destination,
+    /// and the file after the `ETag` too, so downloads to different places or
+    /// of different versions of a blob never share a file. Returns the file and
+    /// how many bytes were already downloaded, which is zero unless there's a
+    /// partial download of a blob with the same `ETag`. The file can be read
+    /// too, to hash what was already downloaded.
+    pub fn resumable(
+        dir: Option<&Path>,
+        destination: &Path,
+        etag: &str,
+    ) -> anyhow::Result<(Self, File, u64)> {
+        let (dir, file_name) = temp_location(dir, destination)?;
+        let path = dir.join(resume_name(file_name, destination, Some(etag), "partial"));
+        let marker = dir.join(resume_name(file_name, destination, None, "resume"));
+
+        // Resume only if the marker is for the same blob, and clean up after
+        // the download it's for otherwise
+        let recorded = read_to_string(&marker)
+            .ok()
+            .and_then(|marker| serde_json::from_str::<ResumeMarker>(&marker).ok());
+        let complete = match recorded {
+            Some(recorded) if recorded.etag == etag && path.exists() => recorded.complete,
+            Some(recorded) if recorded.etag != etag => {
+                let stale = resume_name(file_name, destination, Some(&recorded.etag), "partial");
+                let _ = remove_file(dir.join(stale));
+                0
+            }
+            _ => 0,
+        };
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(complete == 0)
+            .open(&path)
+            .with_context(|| format!("Can't create temporary file in {}", dir.display()))?;
+        file.set_len(complete)?;
+
+        let temp_file = Self {
+            path,
+            persisted: false,
+            resume: Some(Resume {
+                marker,
+                etag: etag.to_string(),
+            }),
+        };
+        temp_file.record(complete)?;
+
+        Ok((temp_file, file, complete))
+    }
+
+    /// Records how many bytes of a resumable download were completely
+    /// written, doing nothing for other temporary files.
+    ///
+    /// The file should be synced first, so that they're actually on disk.
+    pub fn record(&self, complete: u64) -> anyhow::Result<()> {
+        let Some(resume) = &self.resume else {
+            return Ok(());
+        };
+
+        let marker = ResumeMarker {
+            etag: resume.etag.clone(),
+            complete,
+        };
+        write(&resume.marker, serde_json::to_string(&marker)?)
+            .with_context(|| format!("Can't write {}", resume.marker.display()))
+    }
+
+    /// Moves the temporary file to its destination, replacing it.
+    ///
+    /// If the temporary file is on a different filesystem, it's copied instead,
+    /// which isn't atomic.
+    pub fn persist(mut self, destination: &Path) -> anyhow::Result<()> {
+        match rename(&self.path, destination) {
+            Ok(()) => {}
+            Err(error) if error.kind() == ErrorKind::CrossesDevices => {
+                copy(&self.path, destination)?;
+                remove_file(&self.path)?;
+            }
+            Err(error) => {
+                return Err(error)
+                    .with_context(|| format!("Can't move file to {}", destination.display()));
+            }
+        }
+        self.persisted = true;
+        if let Some(resume) = &self.resume {
+            let _ = remove_file(&resume.marker);
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.persisted && self.resume.is_none() {
+            let _ = remove_file(&self.path);
+        }
+    }
+}
+
+/// Gets the directory to create a temporary file in and the name of the file
+/// it will be moved to.
+fn temp_location<'a>(
+    dir: Option<&'a Path>,
+    destination: &'a Path,
+) -> anyhow::Result<(&'a Path, &'a OsStr)> {
+    let dir = match dir {
+        Some(dir) => dir,
+        None => destination
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new(".")),
+    };
+    let Some(file_name) = destination.file_name() else {
+        bail!("{} isn't a file", destination.display());
+    };
+
+    Ok((dir, file_name))
+}
+
+/// Gets the name of a file kept for resuming the download of a blob with the
+/// given `ETag` to `destination`, which has the given name.
+///
+/// Unlike other temporary files, these don't include the process ID so that
+/// later runs can find them. Instead, they include a hash of the destination
+/// and `ETag`, since files with the same name can be downloaded to the same
+/// temporary directory.
+fn resume_name(file_name: &OsStr, destination: &Path, etag: Option<&str>, kind: &str) -> OsString {
+    let destination = std::path::absolute(destination).unwrap_or_else(|_| destination.into());
+    let mut hasher = Sha256::new();
+    hasher.update(destination.as_os_str().as_encoded_bytes());
+    if let Some(etag) = etag {
+        hasher.update([0]);
+        hasher.update(etag);
+    }
+    let hash = format!("{:x}", hasher.finalize());
+
+    let mut name = OsString::from(".");
+    name.push(file_name);
+    name.push(format!(".{}.{kind}.{TEMP_EXTENSION}", &hash[..16]));
+    name
+}
+
+/// Gets the name of a temporary file for a file with the given name.
+///
+/// Temporary files are hidden and include the process ID so that concurrent
+/// processes don't collide.
+fn temp_name(file_name: &OsStr, id: usize) -> OsString {
+    let mut name = OsString::from(".");
+    name.push(file_name);
+    name.push(format!(".{}-{id}.{TEMP_EXTENSION}", process::id()));
+    name
+}
+
+/// Checks whether a path is a temporary file, like one left behind by an
+/// interrupted download.
+pub fn is_temp_file(path: &Path) -> bool {
+    path.extension() == Some(OsStr::new(TEMP_EXTENSION))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temp_names_are_hidden_and_unique() {
+        let first = temp_name(OsStr::new("app.db"), 0);
+        let second = temp_name(OsStr::new("app.db"), 1);
+        assert_ne!(first, second);
+
+        let first = first.to_string_lossy();
+        assert!(first.starts_with(".app.db."), "{first}");
+        assert!(first.ends_with("-0.azsync-tmp"), "{first}");
+        assert!(is_temp_file(Path::new(&*first)));
+        assert!(!is_temp_file(Path::new("app.db")));
+    }
+
+    #[test]
+    fn resume_names_are_temp_files() {
+        let partial = resume_name(
+            OsStr::new("app.db"),
+            Path::new("a/app.db"),
+            Some("1"),
+            "partial",
+        );
+        let partial = partial.to_string_lossy();
+        assert!(partial.starts_with(".app.db."), "{partial}");
+        assert!(partial.ends_with(".partial.azsync-tmp"), "{partial}");
+        assert!(is_temp_file(Path::new(&*partial)));
+    }
+
+    #[test]
+    fn resume_names_are_unique() {
+        let name = |destination, etag| {
+            resume_name(
+                OsStr::new("app.db"),
+                Path::new(destination),
+                etag,
+                "partial",
+            )
+        };
+        assert_eq!(name("a/app.db", Some("1")), name("a/app.db", Some("1")));
+        assert_ne!(name("a/app.db", Some("1")), name("b/app.db", Some("1")));
+        assert_ne!(name("a/app.db", Some("1")), name("a/app.db", Some("2")));
+        assert_ne!(name("a/app.db", Some("1")), name("a/app.db", None));
+    }
+}
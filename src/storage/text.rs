@@ -0,0 +1,124 @@
+use sha2::{Digest, Sha256};
+
+/// Computes a checksum of text that ignores differences in line endings and
+/// trailing newlines.
+///
+/// CRLF line endings are hashed as LF, and newlines at the end are left out,
+/// so the same text saved on Windows and on Linux has the same checksum.
+/// Content containing NUL bytes isn't considered text, and has no such
+/// checksum.
+#[derive(Debug, Default)]
+pub struct TextHasher {
+    hasher: Sha256,
+
+    /// The run of newlines at the end of what's been hashed so far, which is
+    /// only hashed once more text follows it.
+    newlines: Vec<u8>,
+
+    /// Whether the content turned out not to be text.
+    binary: bool,
+}
+
+impl TextHasher {
+    /// Hashes the next chunk of content.
+    pub fn update(&mut self, chunk: &[u8]) {
+        if self.binary {
+            return;
+        }
+        if chunk.contains(&0) {
+            self.binary = true;
+            return;
+        }
+
+        let mut rest = chunk;
+        while !rest.is_empty() {
+            let newlines = rest
+                .iter()
+                .position(|&byte| !is_newline(byte))
+                .unwrap_or(rest.len());
+            self.newlines.extend_from_slice(&rest[..newlines]);
+            rest = &rest[newlines..];
+            if rest.is_empty() {
+                break;
+            }
+
+            // Text follows, so the newlines before it aren't trailing
+            self.flush_newlines();
+            let text = rest
+                .iter()
+                .position(|&byte| is_newline(byte))
+                .unwrap_or(rest.len());
+            self.hasher.update(&rest[..text]);
+            rest = &rest[text..];
+        }
+    }
+
+    /// Gets the checksum, or `None` if the content isn't text.
+    pub fn finalize(self) -> Option<String> {
+        (!self.binary).then(|| format!("{:x}", self.hasher.finalize()))
+    }
+
+    /// Hashes the pending newlines with CRLF line endings replaced by LF.
+    fn flush_newlines(&mut self) {
+        let mut normalized = Vec::with_capacity(self.newlines.len());
+        let mut newlines = self.newlines.iter().peekable();
+        while let Some(&byte) = newlines.next() {
+            if byte == b'\r' && newlines.peek() == Some(&&b'\n') {
+                continue;
+            }
+            normalized.push(byte);
+        }
+        self.hasher.update(&normalized);
+        self.newlines.clear();
+    }
+}
+
+/// Computes the checksum of some text that ignores differences in line
+/// endings and trailing newlines, or `None` if it isn't text.
+pub fn hash_text(contents: &[u8]) -> Option<String> {
+    let mut hasher = TextHasher::default();
+    hasher.update(contents);
+    hasher.finalize()
+}
+
+/// Checks whether a byte is part of a line ending.
+fn is_newline(byte: u8) -> bool {
+    byte == b'\r' || byte == b'\n'
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(b"a\nb\n", b"a\r\nb\r\n"; "line endings")]
+    #[test_case(b"a\nb", b"a\nb\n\n"; "trailing newlines")]
+    #[test_case(b"a\r\nb", b"a\nb\r\n"; "both")]
+    #[test_case(b"", b"\r\n"; "only newlines")]
+    fn same(first: &[u8], second: &[u8]) {
+        assert_eq!(hash_text(first), hash_text(second));
+    }
+
+    #[test_case(b"a\nb", b"a\n\nb"; "blank line")]
+    #[test_case(b"a\nb", b"a b"; "joined lines")]
+    #[test_case(b"a\rb", b"a\nb"; "lone carriage return")]
+    #[test_case(b"a\n", b"\na"; "leading newline")]
+    fn different(first: &[u8], second: &[u8]) {
+        assert_ne!(hash_text(first), hash_text(second));
+    }
+
+    #[test]
+    fn split_across_chunks() {
+        let mut hasher = TextHasher::default();
+        hasher.update(b"a\r");
+        hasher.update(b"\nb\r\n");
+        hasher.update(b"\r\n");
+        assert_eq!(hasher.finalize(), hash_text(b"a\nb"));
+    }
+
+    #[test]
+    fn binary() {
+        assert_eq!(hash_text(b"a\0b"), None);
+    }
+}
@@ -1,147 +1,1035 @@
-use std::io::{Write, stdin, stdout};
+use std::{
+    cell::Cell,
+    cmp::Ordering,
+    collections::BTreeMap,
+    convert::Infallible,
+    fmt::{self, Display, Formatter},
+    fs::{OpenOptions, metadata},
+    io::{ErrorKind, Write, stdin, stdout},
+    num::NonZeroUsize,
+    path::Path,
+    pin::pin,
+    sync::Mutex,
+};
 
-use anyhow::bail;
-use time::{Duration, OffsetDateTime};
+use anyhow::{Context, bail};
+use clap::ValueEnum;
+use futures::{StreamExt, TryStreamExt, stream};
+use serde::{Deserialize, Serialize};
+use time::{Duration, OffsetDateTime, format_description::well_known::Rfc3339};
+use tokio::time::{Instant, timeout_at};
+use tracing::{Instrument, error, info, info_span, warn};
 
-use crate::cli::SyncMode;
+use crate::{
+    audit::AuditLog,
+    cli::{SyncMode, SyncOptions},
+    format::{Elapsed, newer_side},
+    log_format::ACTION_TARGET,
+};
+
+/// How far apart modified times can be while still being considered the same,
+/// unless --time-tolerance is passed.
+pub const MODIFIED_TOLERANCE: Duration = Duration::minutes(1);
 
 /// An action that can be taken on a synchronized resource.
 pub trait SyncAction {
-    /// Execute this action.
-    async fn execute(self) -> anyhow::Result<()>;
+    /// Execute this action, recording any changes in the audit log.
+    async fn execute(self, audit: &AuditLog) -> anyhow::Result<()>;
+
+    /// Checks whether this action would succeed, without making any changes.
+    async fn dry_run(&self) -> anyhow::Result<()>;
+
+    /// Fails if anything this action depends on changed since it was planned,
+    /// with a [`Changed`] error somewhere in the chain.
+    async fn check_fresh(&self) -> anyhow::Result<()>;
+
+    /// When the copy this action synchronizes from was changed, which is how
+    /// long the resource has been out of sync, if known.
+    fn changed_at(&self) -> Option<OffsetDateTime> {
+        None
+    }
 }
 
 /// A kind of synchronization operation.
+///
+/// Resources that can't be deleted leave `DeleteLocal` and `DeleteRemote` as
+/// [`Infallible`].
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
-pub enum SyncType<Push, Pull, Skip> {
+pub enum SyncType<Push, Pull, Skip, DeleteLocal = Infallible, DeleteRemote = Infallible> {
     /// Push local data to remote storage.
     Push(Push),
 
     /// Pull remote data to local storage.
     Pull(Pull),
 
+    /// Delete local data that was deleted from remote storage.
+    DeleteLocal(DeleteLocal),
+
+    /// Delete remote data that was deleted locally.
+    DeleteRemote(DeleteRemote),
+
     /// Do nothing.
     Skip {
         /// The reason for skipping.
-        reason: &'static str,
+        reason: SkipReason,
 
         /// Data associated with skipping.
         data: Skip,
     },
 }
 
-impl<Push, Pull, Skip> SyncType<Push, Pull, Skip> {
+/// Why a resource is skipped.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SkipReason {
+    /// The local and remote copies are the same.
+    Unchanged,
+
+    /// The sync mode only pushes, but the resource would be pulled or
+    /// deleted.
+    PushDisabled,
+
+    /// The sync mode only pulls, but the resource would be pushed.
+    PullDisabled,
+
+    /// The sync mode always pushes, but there's no local copy.
+    NothingToPush,
+
+    /// The sync mode always pulls, but there's no remote copy.
+    NothingToPull,
+
+    /// There's neither a local nor a remote copy.
+    NotFound,
+
+    /// The resource would be pushed, but remote state must not be modified.
+    ReadOnly,
+
+    /// Both copies changed, and the conflict was skipped or reported.
+    Conflict,
+
+    /// The newer copy can't be synchronized because the sync mode only
+    /// synchronizes the other way, so the copies have diverged.
+    ///
+    /// Unlike other skips, synchronizing in the allowed direction would lose
+    /// the newer copy. `--check-only` exits with [`EXIT_DIVERGED`] if anything
+    /// diverged.
+    Diverged,
+
+    /// A directive says to never synchronize the resource.
+    SkipDirective,
+
+    /// A directive says the variable is only listed in the template for
+    /// developers to fill in, so it's never synchronized.
+    TemplateOnly,
+
+    /// A directive says to only push the resource, but it would be pulled.
+    PushOnlyDirective,
+
+    /// A directive says to only pull the resource, but it would be pushed.
+    PullOnlyDirective,
+
+    /// The remote copy was deleted.
+    DeletedRemotely,
+
+    /// The remote copy is disabled.
+    Disabled,
+
+    /// The value is empty, and the remote store can't hold empty values.
+    EmptyValue,
+
+    /// The resource changed too recently to be cleaned up.
+    TooRecent,
+
+    /// The resource will be cleaned up by Azure on its own.
+    WillExpire,
+}
+
+impl SkipReason {
+    /// Describes the reason in a few words.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Unchanged => "unchanged",
+            Self::PushDisabled => "push disabled",
+            Self::PullDisabled => "pull disabled",
+            Self::NothingToPush => "nothing to push",
+            Self::NothingToPull => "nothing to pull",
+            Self::NotFound => "not found",
+            Self::ReadOnly => "read-only",
+            Self::Conflict => "conflict",
+            Self::Diverged => "diverged",
+            Self::SkipDirective => "skip directive",
+            Self::TemplateOnly => "template only",
+            Self::PushOnlyDirective => "push-only directive",
+            Self::PullOnlyDirective => "pull-only directive",
+            Self::DeletedRemotely => "deleted remotely",
+            Self::Disabled => "disabled remotely",
+            Self::EmptyValue => "empty value",
+            Self::TooRecent => "too recent",
+            Self::WillExpire => "will expire",
+        }
+    }
+}
+
+impl Display for SkipReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Exit status when anything is out of sync, like with `--check-only`.
+pub const EXIT_DRIFTED: u8 = 1;
+
+/// Exit status when a command fails.
+pub const EXIT_FAILED: u8 = 2;
+
+/// Exit status of `--check-only` when any resources diverged, to tell them
+/// apart from resources that are only out of date.
+pub const EXIT_DIVERGED: u8 = 3;
+
+/// How many resources a plan does each thing to.
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PlanSummary {
+    /// How many resources will be pushed.
+    pub push: usize,
+
+    /// How many resources will be pulled.
+    pub pull: usize,
+
+    /// How many local resources will be deleted.
+    pub delete_local: usize,
+
+    /// How many remote resources will be deleted.
+    ///
+    /// Left out of JSON output when nothing is, since only files can be
+    /// deleted remotely.
+    #[serde(skip_serializing_if = "is_zero")]
+    pub delete_remote: usize,
+
+    /// How many resources will be skipped, by reason.
+    pub skip: BTreeMap<SkipReason, usize>,
+}
+
+/// Checks whether a count is zero, to leave it out of JSON output.
+#[expect(clippy::trivially_copy_pass_by_ref, reason = "Required by serde")]
+fn is_zero(count: &usize) -> bool {
+    *count == 0
+}
+
+impl PlanSummary {
+    /// Counts the planned actions.
+    pub fn new<'a, Push, Pull, Skip, DeleteLocal, DeleteRemote>(
+        actions: impl IntoIterator<Item = &'a SyncType<Push, Pull, Skip, DeleteLocal, DeleteRemote>>,
+    ) -> Self
+    where
+        Push: 'a,
+        Pull: 'a,
+        Skip: 'a,
+        DeleteLocal: 'a,
+        DeleteRemote: 'a,
+    {
+        let mut summary = Self::default();
+        for action in actions {
+            match action {
+                SyncType::Push(_) => summary.push += 1,
+                SyncType::Pull(_) => summary.pull += 1,
+                SyncType::DeleteLocal(_) => summary.delete_local += 1,
+                SyncType::DeleteRemote(_) => summary.delete_remote += 1,
+                SyncType::Skip { reason, .. } => *summary.skip.entry(*reason).or_default() += 1,
+            }
+        }
+
+        summary
+    }
+
+    /// Counts the resources skipped for reasons that are treated as failures.
+    pub fn failed_skips(&self, fail_on_skip: &[SkipReason]) -> BTreeMap<SkipReason, usize> {
+        self.skip
+            .iter()
+            .filter(|(reason, _)| fail_on_skip.contains(reason))
+            .map(|(&reason, &count)| (reason, count))
+            .collect()
+    }
+
+    /// Checks whether anything was skipped for a reason that's treated as a
+    /// failure, reporting what was.
+    pub fn has_failed_skips(&self, fail_on_skip: &[SkipReason]) -> bool {
+        let failed = self.failed_skips(fail_on_skip);
+        for (reason, count) in &failed {
+            error!("{count} resource(s) skipped ({reason})");
+        }
+        !failed.is_empty()
+    }
+
+    /// Checks whether any resources diverged, reporting how many did.
+    pub fn has_diverged(&self) -> bool {
+        let count = self
+            .skip
+            .get(&SkipReason::Diverged)
+            .copied()
+            .unwrap_or_default();
+        if count > 0 {
+            error!("{count} resource(s) diverged, and the newer copy can't be synchronized");
+        }
+        count > 0
+    }
+
+    /// Adds the counts from another summary to this one.
+    pub fn add(&mut self, other: &Self) {
+        self.push += other.push;
+        self.pull += other.pull;
+        self.delete_local += other.delete_local;
+        self.delete_remote += other.delete_remote;
+        for (&reason, &count) in &other.skip {
+            *self.skip.entry(reason).or_default() += count;
+        }
+    }
+
+    /// Logs the summary, and prints it to stdout as JSON if requested.
+    pub fn report(&self, json: bool) -> anyhow::Result<()> {
+        info!("Summary: {self}");
+        if json {
+            println!("{}", serde_json::to_string(self)?);
+        }
+
+        Ok(())
+    }
+}
+
+/// What a job in `azsync batch` planned to do, shared between planning the
+/// job and running it.
+#[derive(Debug, Default)]
+pub struct ConfirmedPlan {
+    summary: Mutex<Option<PlanSummary>>,
+}
+
+impl ConfirmedPlan {
+    /// Records what the job plans to do.
+    pub fn record(&self, summary: &PlanSummary) {
+        *self
+            .summary
+            .lock()
+            .expect("plan lock should not be poisoned") = Some(summary.clone());
+    }
+
+    /// Gets what the job planned to do, if it was planned.
+    pub fn get(&self) -> Option<PlanSummary> {
+        self.summary
+            .lock()
+            .expect("plan lock should not be poisoned")
+            .clone()
+    }
+}
+
+impl Display for PlanSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} to push, {} to pull", self.push, self.pull)?;
+        if self.delete_local > 0 {
+            write!(f, ", {} to delete", self.delete_local)?;
+        }
+        if self.delete_remote > 0 {
+            write!(f, ", {} to delete remotely", self.delete_remote)?;
+        }
+
+        let skipped: usize = self.skip.values().sum();
+        write!(f, ", {skipped} skipped")?;
+        if skipped > 0 {
+            let reasons = self
+                .skip
+                .iter()
+                .map(|(reason, count)| format!("{count} {reason}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            write!(f, " ({reasons})")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<Push, Pull, Skip, DeleteLocal, DeleteRemote>
+    SyncType<Push, Pull, Skip, DeleteLocal, DeleteRemote>
+{
     /// Sync based on the last modified times of the local and remote value.
+    ///
+    /// Times less than `tolerance` apart are considered the same.
     #[must_use]
+    #[expect(clippy::too_many_arguments, reason = "Each is needed to decide")]
     pub fn from_modified<T>(
         sync_mode: SyncMode,
         local_modified: Option<OffsetDateTime>,
         remote_modified: Option<OffsetDateTime>,
+        tolerance: Duration,
         seed: T,
         push: impl FnOnce(OffsetDateTime, T) -> Push,
         pull: impl FnOnce(OffsetDateTime, T) -> Pull,
         skip: impl FnOnce(T) -> Skip,
     ) -> Self {
-        match (local_modified, remote_modified) {
-            // Both present but modified very close to each other
-            (Some(local), Some(remote)) if (local - remote).abs() < Duration::minutes(1) => {
-                match sync_mode {
-                    SyncMode::Sync | SyncMode::Push | SyncMode::Pull => SyncType::Skip {
-                        reason: "unchanged",
-                        data: skip(seed),
-                    },
-                    SyncMode::PushAlways => Self::Push(push(local, seed)),
-                    SyncMode::PullAlways => Self::Pull(pull(local, seed)),
-                }
-            }
+        decide_modified(sync_mode, local_modified, remote_modified, tolerance)
+            .direction
+            .into_sync_type(seed, push, pull, skip)
+    }
 
-            // Local newer
-            (Some(local), Some(remote)) if local > remote => match sync_mode {
-                SyncMode::Sync | SyncMode::Push | SyncMode::PushAlways => {
-                    Self::Push(push(local, seed))
-                }
-                SyncMode::Pull => Self::Skip {
-                    reason: "pull disabled",
-                    data: skip(seed),
-                },
-                SyncMode::PullAlways => Self::Pull(pull(remote, seed)),
-            },
-            (Some(local), None) => match sync_mode {
-                SyncMode::Sync | SyncMode::Push | SyncMode::PushAlways => {
-                    Self::Push(push(local, seed))
-                }
-                SyncMode::Pull => Self::Skip {
-                    reason: "pull disabled",
-                    data: skip(seed),
-                },
-                SyncMode::PullAlways => Self::Skip {
-                    reason: "nothing to pull",
-                    data: skip(seed),
-                },
-            },
+    /// Sync based on whether the local value is newer than the remote value,
+    /// when both are present.
+    ///
+    /// `local_modified` and `remote_modified` are only passed through to
+    /// `push` and `pull`.
+    #[must_use]
+    #[expect(clippy::too_many_arguments, reason = "Mirrors from_modified")]
+    pub fn from_ordering<T>(
+        sync_mode: SyncMode,
+        ordering: Ordering,
+        local_modified: OffsetDateTime,
+        remote_modified: OffsetDateTime,
+        seed: T,
+        push: impl FnOnce(OffsetDateTime, T) -> Push,
+        pull: impl FnOnce(OffsetDateTime, T) -> Pull,
+        skip: impl FnOnce(T) -> Skip,
+    ) -> Self {
+        decide_ordering(
+            sync_mode,
+            ordering,
+            local_modified,
+            remote_modified,
+            String::new(),
+        )
+        .direction
+        .into_sync_type(seed, push, pull, skip)
+    }
 
-            // Remote newer
-            (Some(local), Some(remote)) => match sync_mode {
-                SyncMode::Sync | SyncMode::Pull | SyncMode::PullAlways => {
-                    Self::Pull(pull(remote, seed))
-                }
-                SyncMode::Push => Self::Skip {
-                    reason: "push disabled",
-                    data: skip(seed),
-                },
-                SyncMode::PushAlways => Self::Push(push(local, seed)),
-            },
-            (None, Some(remote)) => match sync_mode {
-                SyncMode::Sync | SyncMode::Pull | SyncMode::PullAlways => {
-                    Self::Pull(pull(remote, seed))
-                }
-                SyncMode::Push => Self::Skip {
-                    reason: "push disabled",
-                    data: skip(seed),
-                },
-                SyncMode::PushAlways => Self::Skip {
-                    reason: "nothing to push",
-                    data: skip(seed),
-                },
+    /// Skip this action if it would push, for when remote state must not be
+    /// modified.
+    #[must_use]
+    pub fn read_only(self, skip: impl FnOnce(Push) -> Skip) -> Self {
+        match self {
+            Self::Push(inner) => Self::Skip {
+                reason: SkipReason::ReadOnly,
+                data: skip(inner),
             },
+            other => other,
+        }
+    }
+}
+
+/// Explains which rule [`SyncType::from_modified`] follows for a resource.
+pub fn explain_modified(
+    sync_mode: SyncMode,
+    local_modified: Option<OffsetDateTime>,
+    remote_modified: Option<OffsetDateTime>,
+    tolerance: Duration,
+) -> String {
+    decide_modified(sync_mode, local_modified, remote_modified, tolerance).rule
+}
+
+/// Explains which rule [`SyncType::from_ordering`] follows for a resource,
+/// given why its copies are ordered the way they are.
+pub fn explain_ordering(sync_mode: SyncMode, ordering: Ordering, why: String) -> String {
+    let time = OffsetDateTime::UNIX_EPOCH;
+    decide_ordering(sync_mode, ordering, time, time, why).rule
+}
+
+/// What to do with a resource, and the rule that decided it.
+struct Decision {
+    direction: Direction,
+    rule: String,
+}
+
+/// Which way to synchronize a resource.
+enum Direction {
+    /// Push, passing along the given modified time.
+    Push(OffsetDateTime),
 
-            // Neither present
-            (None, None) => Self::Skip {
-                reason: "not found",
+    /// Pull, passing along the given modified time.
+    Pull(OffsetDateTime),
+
+    /// Skip for the given reason.
+    Skip(SkipReason),
+}
+
+impl Direction {
+    /// Builds the action for this direction.
+    fn into_sync_type<T, Push, Pull, Skip, DeleteLocal, DeleteRemote>(
+        self,
+        seed: T,
+        push: impl FnOnce(OffsetDateTime, T) -> Push,
+        pull: impl FnOnce(OffsetDateTime, T) -> Pull,
+        skip: impl FnOnce(T) -> Skip,
+    ) -> SyncType<Push, Pull, Skip, DeleteLocal, DeleteRemote> {
+        match self {
+            Self::Push(time) => SyncType::Push(push(time, seed)),
+            Self::Pull(time) => SyncType::Pull(pull(time, seed)),
+            Self::Skip(reason) => SyncType::Skip {
+                reason,
                 data: skip(seed),
             },
         }
     }
 }
 
-impl<Push, Pull, Skip> SyncAction for SyncType<Push, Pull, Skip>
+/// Decides what to do based on the last modified times of the local and
+/// remote value.
+fn decide_modified(
+    sync_mode: SyncMode,
+    local_modified: Option<OffsetDateTime>,
+    remote_modified: Option<OffsetDateTime>,
+    tolerance: Duration,
+) -> Decision {
+    let mode = sync_mode_name(sync_mode);
+    let (direction, rule) = match (local_modified, remote_modified) {
+        // Both present, so compare them (unless modified very close to each other)
+        (Some(local), Some(remote)) => {
+            let difference = local - remote;
+            let (ordering, why) = if difference.abs() < tolerance {
+                (
+                    Ordering::Equal,
+                    format!(
+                        "local ({}) and remote ({}) were modified within {} of each other",
+                        format_time(local),
+                        format_time(remote),
+                        Elapsed(tolerance),
+                    ),
+                )
+            } else {
+                (
+                    local.cmp(&remote),
+                    format!(
+                        "local modified {}, remote modified {}: {}",
+                        format_time(local),
+                        format_time(remote),
+                        newer_side(local, remote),
+                    ),
+                )
+            };
+            return decide_ordering(sync_mode, ordering, local, remote, why);
+        }
+
+        // Only local present
+        (Some(local), None) => match sync_mode {
+            SyncMode::Sync | SyncMode::Push | SyncMode::PushAlways => {
+                (Direction::Push(local), "so it's pushed".to_string())
+            }
+            SyncMode::Pull => (
+                Direction::Skip(SkipReason::PullDisabled),
+                format!("but sync mode {mode} never pushes"),
+            ),
+            SyncMode::PullAlways => (
+                Direction::Skip(SkipReason::NothingToPull),
+                format!("but sync mode {mode} only pulls"),
+            ),
+        },
+
+        // Only remote present
+        (None, Some(remote)) => match sync_mode {
+            SyncMode::Sync | SyncMode::Pull | SyncMode::PullAlways => {
+                (Direction::Pull(remote), "so it's pulled".to_string())
+            }
+            SyncMode::Push => (
+                Direction::Skip(SkipReason::PushDisabled),
+                format!("but sync mode {mode} never pulls"),
+            ),
+            SyncMode::PushAlways => (
+                Direction::Skip(SkipReason::NothingToPush),
+                format!("but sync mode {mode} only pushes"),
+            ),
+        },
+
+        // Neither present
+        (None, None) => (
+            Direction::Skip(SkipReason::NotFound),
+            "so there's nothing to synchronize".to_string(),
+        ),
+    };
+
+    let which = match (local_modified, remote_modified) {
+        (Some(_), None) => "only the local copy exists",
+        (None, Some(_)) => "only the remote copy exists",
+        _ => "neither copy exists",
+    };
+    Decision {
+        direction,
+        rule: format!("{which}, {rule}"),
+    }
+}
+
+/// Decides what to do based on whether the local value is newer than the
+/// remote value, when both are present.
+///
+/// `why` explains how the values were compared.
+fn decide_ordering(
+    sync_mode: SyncMode,
+    ordering: Ordering,
+    local_modified: OffsetDateTime,
+    remote_modified: OffsetDateTime,
+    why: String,
+) -> Decision {
+    let mode = sync_mode_name(sync_mode);
+    let (direction, rule) = match ordering {
+        Ordering::Equal => match sync_mode {
+            SyncMode::Sync | SyncMode::Push | SyncMode::Pull => (
+                Direction::Skip(SkipReason::Unchanged),
+                "so it's considered unchanged".to_string(),
+            ),
+            SyncMode::PushAlways => (
+                Direction::Push(local_modified),
+                format!("but sync mode {mode} pushes anyway"),
+            ),
+            SyncMode::PullAlways => (
+                Direction::Pull(local_modified),
+                format!("but sync mode {mode} pulls anyway"),
+            ),
+        },
+
+        // Local newer
+        Ordering::Greater => match sync_mode {
+            SyncMode::Sync | SyncMode::Push | SyncMode::PushAlways => (
+                Direction::Push(local_modified),
+                "so it's pushed".to_string(),
+            ),
+            SyncMode::Pull => (
+                Direction::Skip(SkipReason::Diverged),
+                format!("but sync mode {mode} never pushes"),
+            ),
+            SyncMode::PullAlways => (
+                Direction::Pull(remote_modified),
+                format!("but sync mode {mode} pulls anyway"),
+            ),
+        },
+
+        // Remote newer
+        Ordering::Less => match sync_mode {
+            SyncMode::Sync | SyncMode::Pull | SyncMode::PullAlways => (
+                Direction::Pull(remote_modified),
+                "so it's pulled".to_string(),
+            ),
+            SyncMode::Push => (
+                Direction::Skip(SkipReason::Diverged),
+                format!("but sync mode {mode} never pulls"),
+            ),
+            SyncMode::PushAlways => (
+                Direction::Push(local_modified),
+                format!("but sync mode {mode} pushes anyway"),
+            ),
+        },
+    };
+
+    Decision {
+        direction,
+        rule: format!("{why}, {rule}"),
+    }
+}
+
+/// Gets the name of a sync mode as it's passed on the command line.
+fn sync_mode_name(sync_mode: SyncMode) -> String {
+    sync_mode
+        .to_possible_value()
+        .map_or_else(String::new, |value| format!("`{}`", value.get_name()))
+}
+
+/// Formats a time for explanations.
+fn format_time(time: OffsetDateTime) -> String {
+    time.format(&Rfc3339).unwrap_or_else(|_| time.to_string())
+}
+
+impl<Push, Pull, Skip, DeleteLocal, DeleteRemote> SyncAction
+    for SyncType<Push, Pull, Skip, DeleteLocal, DeleteRemote>
 where
     Push: SyncAction,
     Pull: SyncAction,
+    DeleteLocal: SyncAction,
+    DeleteRemote: SyncAction,
 {
-    async fn execute(self) -> anyhow::Result<()> {
+    async fn execute(self, audit: &AuditLog) -> anyhow::Result<()> {
         match self {
-            SyncType::Push(inner) => inner.execute().await,
-            SyncType::Pull(inner) => inner.execute().await,
+            SyncType::Push(inner) => inner.execute(audit).await,
+            SyncType::Pull(inner) => inner.execute(audit).await,
+            SyncType::DeleteLocal(inner) => inner.execute(audit).await,
+            SyncType::DeleteRemote(inner) => inner.execute(audit).await,
             SyncType::Skip { .. } => Ok(()),
         }
     }
+
+    async fn dry_run(&self) -> anyhow::Result<()> {
+        match self {
+            SyncType::Push(inner) => inner.dry_run().await,
+            SyncType::Pull(inner) => inner.dry_run().await,
+            SyncType::DeleteLocal(inner) => inner.dry_run().await,
+            SyncType::DeleteRemote(inner) => inner.dry_run().await,
+            SyncType::Skip { .. } => Ok(()),
+        }
+    }
+
+    async fn check_fresh(&self) -> anyhow::Result<()> {
+        match self {
+            SyncType::Push(inner) => inner.check_fresh().await,
+            SyncType::Pull(inner) => inner.check_fresh().await,
+            SyncType::DeleteLocal(inner) => inner.check_fresh().await,
+            SyncType::DeleteRemote(inner) => inner.check_fresh().await,
+            SyncType::Skip { .. } => Ok(()),
+        }
+    }
+
+    fn changed_at(&self) -> Option<OffsetDateTime> {
+        match self {
+            SyncType::Push(inner) => inner.changed_at(),
+            SyncType::Pull(inner) => inner.changed_at(),
+            SyncType::DeleteLocal(inner) => inner.changed_at(),
+            SyncType::DeleteRemote(inner) => inner.changed_at(),
+            SyncType::Skip { .. } => None,
+        }
+    }
+}
+
+impl SyncAction for Infallible {
+    async fn execute(self, _audit: &AuditLog) -> anyhow::Result<()> {
+        match self {}
+    }
+
+    async fn dry_run(&self) -> anyhow::Result<()> {
+        match *self {}
+    }
+
+    async fn check_fresh(&self) -> anyhow::Result<()> {
+        match *self {}
+    }
+}
+
+/// Checks whether a plan leaves anything out of sync.
+///
+/// With `max_drift`, only resources that have been out of sync for longer than
+/// that count, and they're reported. Resources that are out of sync for an
+/// unknown amount of time are treated as just having changed.
+pub fn is_drifted<Push, Pull, Skip, DeleteLocal, DeleteRemote>(
+    actions: &[SyncType<Push, Pull, Skip, DeleteLocal, DeleteRemote>],
+    max_drift: Option<Duration>,
+) -> bool
+where
+    Push: SyncAction,
+    Pull: SyncAction,
+    DeleteLocal: SyncAction,
+    DeleteRemote: SyncAction,
+{
+    let now = OffsetDateTime::now_utc();
+    let ages = actions
+        .iter()
+        .filter(|action| !matches!(action, SyncType::Skip { .. }))
+        .map(|action| {
+            action
+                .changed_at()
+                .map_or(Duration::ZERO, |since| now - since)
+        });
+    let Some(max_drift) = max_drift else {
+        return ages.count() > 0;
+    };
+
+    let (count, oldest) = count_drifted(ages, max_drift);
+    if count > 0 {
+        error!(
+            "{count} resource(s) out of sync for longer than {} (oldest for {})",
+            Elapsed(max_drift),
+            Elapsed(oldest)
+        );
+    }
+    count > 0
+}
+
+/// Counts how many resources have been out of sync for longer than
+/// `max_drift`, and how long the oldest has been.
+fn count_drifted(
+    ages: impl IntoIterator<Item = Duration>,
+    max_drift: Duration,
+) -> (usize, Duration) {
+    ages.into_iter()
+        .filter(|&age| age > max_drift)
+        .fold((0, Duration::ZERO), |(count, oldest), age| {
+            (count + 1, oldest.max(age))
+        })
+}
+
+/// Maximum number of times to plan again when values change while waiting for
+/// confirmation.
+pub const MAX_REPLANS: usize = 3;
+
+/// Maximum number of actions to check at once, unless --max-concurrency is
+/// passed.
+const MAX_CONCURRENT_CHECKS: usize = 32;
+
+/// The error for something an action depends on that changed since it was
+/// planned, which makes the action stale instead of failed.
+#[derive(Debug)]
+pub struct Changed(pub String);
+
+impl Display for Changed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Changed {}
+
+/// Splits actions into ones that can still be executed and ones whose inputs
+/// changed since they were planned.
+///
+/// Fails if checking an action fails for any other reason, like a request
+/// that couldn't be sent.
+pub async fn split_stale<A: SyncAction>(
+    actions: Vec<A>,
+    options: &SyncOptions,
+) -> anyhow::Result<(Vec<A>, Vec<A>)> {
+    let results: Vec<_> = stream::iter(actions.iter().map(SyncAction::check_fresh))
+        .buffered(options.plan_concurrency(MAX_CONCURRENT_CHECKS))
+        .collect()
+        .await;
+    let mut fresh = Vec::with_capacity(actions.len());
+    let mut stale = Vec::new();
+    for (action, result) in actions.into_iter().zip(results) {
+        match result {
+            Ok(()) => fresh.push(action),
+            Err(error) if error.is::<Changed>() => {
+                warn!("{error:#}");
+                stale.push(action);
+            }
+            Err(error) => return Err(error),
+        }
+    }
+
+    Ok((fresh, stale))
+}
+
+/// Executes actions, making at most `--max-concurrency` changes at once.
+///
+/// Stops at the first action that fails or makes no progress for
+/// `--action-timeout`, unless it's zero.
+pub async fn execute_all<A: SyncAction>(
+    actions: Vec<A>,
+    audit: &AuditLog,
+    options: &SyncOptions,
+) -> anyhow::Result<()> {
+    let limit = options
+        .max_concurrency
+        .map_or(actions.len().max(1), NonZeroUsize::get);
+    let action_timeout = Some(options.action_timeout)
+        .filter(|duration| !duration.is_zero())
+        .map(|duration| std::time::Duration::try_from(duration).map(|std| (duration, std)))
+        .transpose()
+        .context("Invalid action timeout")?;
+    stream::iter(actions)
+        .map(|action| {
+            // Each action is logged with how long it took when it's recorded
+            let span = info_span!(target: ACTION_TARGET, "action");
+            async move {
+                let Some(idle) = action_timeout else {
+                    return action.execute(audit).await;
+                };
+                LAST_PROGRESS
+                    .scope(
+                        Cell::new(Instant::now()),
+                        execute_watched(action, audit, idle),
+                    )
+                    .await
+            }
+            .instrument(span)
+        })
+        .buffer_unordered(limit)
+        .try_collect()
+        .await
+}
+
+tokio::task_local! {
+    /// When the action being executed last made progress, if it's watched for
+    /// stalls.
+    static LAST_PROGRESS: Cell<Instant>;
+}
+
+/// Records that the action being executed made progress, like transferring a
+/// chunk or block, so `--action-timeout` only stops it once it stalls.
+pub fn report_progress() {
+    // Nothing is watching unless there's a timeout
+    let _ = LAST_PROGRESS.try_with(|last| last.set(Instant::now()));
+}
+
+/// Executes an action, giving up if it doesn't finish or report progress for
+/// `idle`.
+async fn execute_watched<A: SyncAction>(
+    action: A,
+    audit: &AuditLog,
+    (idle, std): (Duration, std::time::Duration),
+) -> anyhow::Result<()> {
+    let mut execution = pin!(action.execute(audit));
+    loop {
+        let deadline = LAST_PROGRESS.with(Cell::get) + std;
+        if let Ok(result) = timeout_at(deadline, &mut execution).await {
+            return result;
+        }
+        if LAST_PROGRESS.with(Cell::get) + std <= Instant::now() {
+            bail!(
+                "A change made no progress for {}, so its connection may have stalled",
+                Elapsed(idle)
+            );
+        }
+    }
+}
+
+/// Keeps only the first `limit` actions that change anything, so a plan can be
+/// tried out a few changes at a time with `--canary`.
+///
+/// Returns the actions to execute and how many changes were held back.
+pub fn take_canary<Push, Pull, Skip, DeleteLocal, DeleteRemote>(
+    actions: Vec<SyncType<Push, Pull, Skip, DeleteLocal, DeleteRemote>>,
+    limit: Option<NonZeroUsize>,
+) -> (
+    Vec<SyncType<Push, Pull, Skip, DeleteLocal, DeleteRemote>>,
+    usize,
+) {
+    let Some(limit) = limit else {
+        return (actions, 0);
+    };
+
+    let mut changes = 0;
+    let mut held_back = 0;
+    let actions = actions
+        .into_iter()
+        .filter(|action| {
+            if matches!(action, SyncType::Skip { .. }) {
+                return true;
+            }
+            changes += 1;
+            if changes > limit.get() {
+                held_back += 1;
+                return false;
+            }
+            true
+        })
+        .collect();
+    if held_back > 0 {
+        info!("Canary: making {limit} of {changes} change(s)");
+    }
+
+    (actions, held_back)
+}
+
+/// Reports the changes held back by `--canary` once the others were made.
+pub fn report_canary(held_back: usize) {
+    if held_back > 0 {
+        info!("{held_back} change(s) held back by --canary, run again to continue");
+    }
+}
+
+/// Gets when a local file was last modified, if it exists.
+pub fn modified_time(path: &Path) -> anyhow::Result<Option<OffsetDateTime>> {
+    match metadata(path) {
+        Ok(metadata) => Ok(Some(metadata.modified()?.into())),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error).with_context(|| format!("Can't read {}", path.display())),
+    }
+}
+
+/// Checks whether each action would succeed, reporting any that would fail.
+pub async fn dry_run<'a, A>(
+    actions: impl IntoIterator<Item = &'a A>,
+    options: &SyncOptions,
+) -> anyhow::Result<()>
+where
+    A: SyncAction + 'a,
+{
+    let results: Vec<_> = stream::iter(actions.into_iter().map(SyncAction::dry_run))
+        .buffer_unordered(options.plan_concurrency(MAX_CONCURRENT_CHECKS))
+        .collect()
+        .await;
+    let failed = results
+        .iter()
+        .filter_map(|result| result.as_ref().err())
+        .inspect(|error| error!("{error:#}"))
+        .count();
+    if failed > 0 {
+        bail!("{failed} of {} action(s) would fail", results.len());
+    }
+
+    info!("All {} action(s) would succeed", results.len());
+    Ok(())
+}
+
+/// Fails if a local file couldn't be written to.
+///
+/// The file is not modified or created.
+pub fn check_writable(path: &Path) -> anyhow::Result<()> {
+    match OpenOptions::new().append(true).open(path) {
+        Ok(_) => Ok(()),
+        Err(error) if error.kind() == ErrorKind::NotFound => {
+            let parent = path
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            if !metadata(parent).is_ok_and(|metadata| metadata.is_dir()) {
+                bail!("Directory {} doesn't exist", parent.display());
+            }
+            Ok(())
+        }
+        Err(error) => Err(error).with_context(|| format!("Can't write to {}", path.display())),
+    }
+}
+
+/// Ask the user to pick one of several choices, returning its index.
+///
+/// Each choice is a key the user can type and a description of the choice.
+pub fn choose(choices: &[(&str, &str)]) -> anyhow::Result<usize> {
+    let prompt = choices
+        .iter()
+        .map(|(key, description)| format!("{description} ({key})"))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let mut input = String::new();
+    loop {
+        print!("{prompt}? ");
+        stdout().flush()?;
+        input.clear();
+        if stdin().read_line(&mut input)? == 0 {
+            bail!("Aborted");
+        }
+
+        let input = input.trim_end();
+        if let Some(index) = choices.iter().position(|&(key, _)| key == input) {
+            return Ok(index);
+        }
+    }
 }
 
 /// Ask the user for confirmation on a set of actions.
 pub fn confirm() -> anyhow::Result<()> {
+    ask("Confirm")
+}
+
+/// Asks the user to confirm deleting resources, separately from confirming the
+/// rest of the plan.
+pub fn confirm_deletions(count: usize) -> anyhow::Result<()> {
+    warn!("{count} file(s) or blob(s) will be deleted");
+    ask("Delete them")
+}
+
+/// Asks the user a yes/no question, failing if they answer no.
+fn ask(question: &str) -> anyhow::Result<()> {
+    if !ask_yes_no(question)? {
+        bail!("Aborted");
+    }
+
+    Ok(())
+}
+
+/// Asks the user a yes/no question.
+pub fn ask_yes_no(question: &str) -> anyhow::Result<bool> {
     let mut input = String::new();
     loop {
-        print!("Confirm (yes/no)? ");
+        print!("{question} (yes/no)? ");
         stdout().flush()?;
         input.clear();
-        stdin().read_line(&mut input)?;
+        if stdin().read_line(&mut input)? == 0 {
+            bail!("Aborted");
+        }
 
         match input.as_str().trim_end() {
-            "y" | "yes" => return Ok(()),
-            "n" | "no" => {
-                bail!("Aborted");
-            }
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
             _ => {
                 // Ask again
             }
@@ -149,6 +1037,18 @@ pub fn confirm() -> anyhow::Result<()> {
     }
 }
 
+/// Asks the user to type an answer, which may be empty.
+pub fn ask_text(question: &str) -> anyhow::Result<String> {
+    print!("{question}: ");
+    stdout().flush()?;
+    let mut input = String::new();
+    if stdin().read_line(&mut input)? == 0 {
+        bail!("Aborted");
+    }
+
+    Ok(input.trim().to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use test_case::test_case;
@@ -159,6 +1059,37 @@ mod tests {
     const DT_2024: OffsetDateTime = datetime!(2024-01-01 00:00 +00:00);
     const DT_2025: OffsetDateTime = datetime!(2025-01-01 00:00 +00:00);
 
+    #[test]
+    fn failed_skips() {
+        let summary = PlanSummary {
+            skip: BTreeMap::from([(SkipReason::NotFound, 2), (SkipReason::PushDisabled, 1)]),
+            ..Default::default()
+        };
+        assert_eq!(
+            summary.failed_skips(&[SkipReason::NotFound, SkipReason::Conflict]),
+            BTreeMap::from([(SkipReason::NotFound, 2)])
+        );
+        assert!(summary.failed_skips(&[]).is_empty());
+    }
+
+    #[test_case(None => (5, 0); "no limit")]
+    #[test_case(NonZeroUsize::new(2) => (3, 2); "limited")]
+    #[test_case(NonZeroUsize::new(10) => (5, 0); "limit above changes")]
+    fn canary(limit: Option<NonZeroUsize>) -> (usize, usize) {
+        let actions: Vec<SyncType<u8, u8, u8>> = vec![
+            SyncType::Push(1),
+            SyncType::Skip {
+                reason: SkipReason::Unchanged,
+                data: 2,
+            },
+            SyncType::Pull(3),
+            SyncType::Push(4),
+            SyncType::Pull(5),
+        ];
+        let (actions, held_back) = take_canary(actions, limit);
+        (actions.len(), held_back)
+    }
+
     // SyncMode::Sync
     #[test_case(SyncMode::Sync, None, None => matches SyncType::Skip { .. }; "sync not-found")]
     #[test_case(SyncMode::Sync, Some(DT_2025), None => SyncType::Push(DT_2025); "sync local-only")]
@@ -203,10 +1134,143 @@ mod tests {
             sync_mode,
             local,
             remote,
+            MODIFIED_TOLERANCE,
+            (),
+            |time, ()| time,
+            |time, ()| time,
+            |()| (),
+        )
+    }
+
+    #[test_case(SyncMode::Sync, Some(DT_2025), Some(DT_2025 + Duration::seconds(30)) => "local (2025-01-01T00:00:00Z) and remote (2025-01-01T00:00:30Z) were modified within 1m 0s of each other, so it's considered unchanged"; "within tolerance")]
+    #[test_case(SyncMode::Push, Some(DT_2024), Some(DT_2025) => "local modified 2024-01-01T00:00:00Z, remote modified 2025-01-01T00:00:00Z: remote newer by 366d 0h, but sync mode `push` never pulls"; "mode constraint")]
+    #[test_case(SyncMode::PullAlways, Some(DT_2025), None => "only the local copy exists, but sync mode `pull-always` only pulls"; "local only")]
+    #[test_case(SyncMode::Sync, None, None => "neither copy exists, so there's nothing to synchronize"; "not found")]
+    fn explain(
+        sync_mode: SyncMode,
+        local: Option<OffsetDateTime>,
+        remote: Option<OffsetDateTime>,
+    ) -> String {
+        explain_modified(sync_mode, local, remote, MODIFIED_TOLERANCE)
+    }
+
+    #[test_case(Duration::ZERO, DT_2025 => matches SyncType::Skip { .. }; "exact match")]
+    #[test_case(Duration::ZERO, DT_2025 + Duration::seconds(1) => SyncType::Pull(DT_2025 + Duration::seconds(1)); "exact mismatch")]
+    #[test_case(Duration::seconds(5), DT_2025 + Duration::seconds(4) => matches SyncType::Skip { .. }; "within tolerance")]
+    #[test_case(Duration::seconds(5), DT_2025 + Duration::seconds(5) => SyncType::Pull(DT_2025 + Duration::seconds(5)); "at tolerance")]
+    #[test_case(Duration::minutes(10), DT_2025 + Duration::minutes(5) => matches SyncType::Skip { .. }; "lax")]
+    fn tolerance(
+        tolerance: Duration,
+        remote: OffsetDateTime,
+    ) -> SyncType<OffsetDateTime, OffsetDateTime, ()> {
+        SyncType::from_modified(
+            SyncMode::Sync,
+            Some(DT_2025),
+            Some(remote),
+            tolerance,
             (),
             |time, ()| time,
             |time, ()| time,
             |()| (),
         )
     }
+
+    #[test_case(SyncMode::Push, Some(DT_2024), Some(DT_2025) => SkipReason::Diverged; "push remote newer")]
+    #[test_case(SyncMode::Pull, Some(DT_2025), Some(DT_2024) => SkipReason::Diverged; "pull local newer")]
+    #[test_case(SyncMode::Push, None, Some(DT_2025) => SkipReason::PushDisabled; "push remote only")]
+    #[test_case(SyncMode::Pull, Some(DT_2025), None => SkipReason::PullDisabled; "pull local only")]
+    fn skip_reason(
+        sync_mode: SyncMode,
+        local: Option<OffsetDateTime>,
+        remote: Option<OffsetDateTime>,
+    ) -> SkipReason {
+        match SyncType::<_, _, _>::from_modified(
+            sync_mode,
+            local,
+            remote,
+            MODIFIED_TOLERANCE,
+            (),
+            |_, ()| (),
+            |_, ()| (),
+            |()| (),
+        ) {
+            SyncType::Skip { reason, .. } => reason,
+            _ => panic!("expected a skip"),
+        }
+    }
+
+    #[test_case(SyncType::Push(DT_2025) => SyncType::Skip { reason: SkipReason::ReadOnly, data: DT_2025 }; "push")]
+    #[test_case(SyncType::Pull(DT_2025) => SyncType::Pull(DT_2025); "pull")]
+    #[test_case(SyncType::Skip { reason: SkipReason::Unchanged, data: DT_2025 } => SyncType::Skip { reason: SkipReason::Unchanged, data: DT_2025 }; "skip")]
+    fn read_only_skips_pushes(
+        action: SyncType<OffsetDateTime, OffsetDateTime, OffsetDateTime>,
+    ) -> SyncType<OffsetDateTime, OffsetDateTime, OffsetDateTime> {
+        action.read_only(|time| time)
+    }
+
+    #[test_case("Cargo.toml" => true; "existing file")]
+    #[test_case("not-created.env" => true; "new file")]
+    #[test_case("missing/not-created.env" => false; "missing directory")]
+    fn writable(path: &str) -> bool {
+        check_writable(Path::new(path)).is_ok()
+    }
+
+    #[test]
+    fn summary_groups_skips() {
+        let actions: [SyncType<(), (), ()>; 4] = [
+            SyncType::Push(()),
+            SyncType::Skip {
+                reason: SkipReason::Unchanged,
+                data: (),
+            },
+            SyncType::Skip {
+                reason: SkipReason::PushDisabled,
+                data: (),
+            },
+            SyncType::Skip {
+                reason: SkipReason::Unchanged,
+                data: (),
+            },
+        ];
+        let summary = PlanSummary::new(&actions);
+
+        assert_eq!(
+            summary.to_string(),
+            "1 to push, 0 to pull, 3 skipped (2 unchanged, 1 push disabled)"
+        );
+        assert_eq!(
+            serde_json::to_string(&summary).unwrap(),
+            r#"{"push":1,"pull":0,"delete-local":0,"skip":{"unchanged":2,"push-disabled":1}}"#
+        );
+    }
+
+    #[test]
+    fn summary_counts_deletions() {
+        let actions: [SyncType<(), (), (), (), ()>; 3] = [
+            SyncType::DeleteLocal(()),
+            SyncType::DeleteRemote(()),
+            SyncType::DeleteRemote(()),
+        ];
+        let summary = PlanSummary::new(&actions);
+
+        assert_eq!(
+            summary.to_string(),
+            "0 to push, 0 to pull, 1 to delete, 2 to delete remotely, 0 skipped"
+        );
+        assert_eq!(
+            serde_json::to_string(&summary).unwrap(),
+            r#"{"push":0,"pull":0,"delete-local":1,"delete-remote":2,"skip":{}}"#
+        );
+    }
+
+    #[test_case(&[], 60 => (0, 0); "nothing out of sync")]
+    #[test_case(&[30, 60], 60 => (0, 0); "within threshold")]
+    #[test_case(&[30, 90, 120], 60 => (2, 120); "beyond threshold")]
+    fn drifted(ages: &[i64], max_drift: i64) -> (usize, i64) {
+        let (count, oldest) = count_drifted(
+            ages.iter().copied().map(Duration::seconds),
+            Duration::seconds(max_drift),
+        );
+        (count, oldest.whole_seconds())
+    }
 }
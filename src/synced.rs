@@ -0,0 +1,244 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+use crate::manifest::{MANIFEST_FILE, Section, load_entries, save_entries};
+
+/// What a resource looked like when it was last synchronized.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct SyncedState {
+    /// When it was synchronized.
+    pub synced: String,
+
+    /// The `ETag` of the remote copy, if it has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+
+    /// A hash of the contents both copies had.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+
+    /// When the local copy was modified, if it's a file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+}
+
+impl SyncedState {
+    /// Describes a resource that was just synchronized.
+    pub fn now(
+        etag: Option<String>,
+        hash: Option<String>,
+        modified: Option<OffsetDateTime>,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            synced: OffsetDateTime::now_utc().format(&Rfc3339)?,
+            etag,
+            hash,
+            modified: modified.map(|time| time.format(&Rfc3339)).transpose()?,
+        })
+    }
+
+    /// Gets when the resource was synchronized.
+    pub fn synced_at(&self) -> anyhow::Result<OffsetDateTime> {
+        OffsetDateTime::parse(&self.synced, &Rfc3339)
+            .with_context(|| format!("Invalid time in {MANIFEST_FILE}: {}", self.synced))
+    }
+
+    /// Checks whether the local copy of a file was modified at a different
+    /// time than when it was synchronized.
+    pub fn modified_since(&self, local_modified: OffsetDateTime) -> anyhow::Result<bool> {
+        Ok(self.modified.as_deref() != Some(&*local_modified.format(&Rfc3339)?))
+    }
+
+    /// Compares hashes of both copies of a value with the hash it had when it
+    /// was synchronized.
+    pub fn value_changes(&self, local_hash: &str, remote_hash: &str) -> Changes {
+        Changes {
+            local: self.hash.as_deref() != Some(local_hash),
+            remote: self.hash.as_deref() != Some(remote_hash),
+        }
+    }
+}
+
+/// Which copies of a resource changed since it was last synchronized.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Changes {
+    /// Whether the local copy changed.
+    pub local: bool,
+
+    /// Whether the remote copy changed.
+    pub remote: bool,
+}
+
+impl Changes {
+    /// Whether both copies changed, so keeping either one would lose changes.
+    pub fn both(self) -> bool {
+        self.local && self.remote
+    }
+
+    /// Gets whether the local copy is newer ([`Ordering::Greater`]), older
+    /// ([`Ordering::Less`]), or the same as the remote copy, like
+    /// [`compare_versions`](crate::versions::compare_versions) does.
+    ///
+    /// Returns `None` if both copies changed.
+    pub fn ordering(self) -> Option<Ordering> {
+        match (self.local, self.remote) {
+            (false, false) => Some(Ordering::Equal),
+            (true, false) => Some(Ordering::Greater),
+            (false, true) => Some(Ordering::Less),
+            (true, true) => None,
+        }
+    }
+}
+
+/// Explains how the local and remote copies of a resource compare according
+/// to their recorded state.
+pub fn explain_changes(ordering: Ordering) -> String {
+    let changed = match ordering {
+        Ordering::Equal => "neither copy",
+        Ordering::Greater => "only the local copy",
+        Ordering::Less => "only the remote copy",
+    };
+    format!("{MANIFEST_FILE} shows {changed} changed since it was last synchronized")
+}
+
+/// The state of resources as of when they were last synchronized, by the URL
+/// of each resource.
+///
+/// This is used to tell whether a resource changed locally, remotely, or on
+/// both sides since it was last synchronized, without comparing clocks.
+#[derive(Debug)]
+pub struct SyncHistory {
+    path: PathBuf,
+    states: Mutex<BTreeMap<String, SyncedState>>,
+
+    /// The resources recorded since loading, which are the only ones saved.
+    recorded: Mutex<BTreeSet<String>>,
+}
+
+impl SyncHistory {
+    /// Loads the recorded states from a manifest, if it exists.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        Ok(Self {
+            path: path.to_owned(),
+            states: Mutex::new(load_entries(path, Section::Synced)?),
+            recorded: Mutex::default(),
+        })
+    }
+
+    /// Gets what a resource looked like when it was last synchronized, if
+    /// it's known.
+    pub fn get(&self, resource: &str) -> Option<SyncedState> {
+        self.states
+            .lock()
+            .expect("history lock should not be poisoned")
+            .get(resource)
+            .cloned()
+    }
+
+    /// Records what a resource looks like now that it was synchronized.
+    pub fn record(&self, resource: String, state: SyncedState) {
+        self.recorded
+            .lock()
+            .expect("history lock should not be poisoned")
+            .insert(resource.clone());
+        self.states
+            .lock()
+            .expect("history lock should not be poisoned")
+            .insert(resource, state);
+    }
+
+    /// Writes the states recorded since loading back to the manifest.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let recorded = self
+            .recorded
+            .lock()
+            .expect("history lock should not be poisoned");
+        let states = self
+            .states
+            .lock()
+            .expect("history lock should not be poisoned");
+        let entries = recorded
+            .iter()
+            .filter_map(|resource| Some((resource.clone(), states.get(resource)?.clone())));
+        save_entries(&self.path, Section::Synced, entries)
+    }
+}
+
+/// The state to record once a resource is synchronized.
+#[derive(Clone, Debug)]
+pub struct SyncedUpdate {
+    /// Where to record the state.
+    pub history: Arc<SyncHistory>,
+
+    /// The URL of the resource.
+    pub resource: String,
+}
+
+impl SyncedUpdate {
+    /// Records what the resource looks like now that it was synchronized.
+    pub fn record(
+        self,
+        etag: Option<String>,
+        hash: Option<String>,
+        modified: Option<OffsetDateTime>,
+    ) -> anyhow::Result<()> {
+        let state = SyncedState::now(etag, hash, modified)?;
+        self.history.record(self.resource, state);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test_case("a", "a", false, false; "unchanged")]
+    #[test_case("b", "a", true, false; "local changed")]
+    #[test_case("a", "b", false, true; "remote changed")]
+    #[test_case("b", "c", true, true; "both changed")]
+    fn value_changes(local: &str, remote: &str, expected_local: bool, expected_remote: bool) {
+        let state = SyncedState::now(None, Some("a".to_string()), None).unwrap();
+        let changes = state.value_changes(local, remote);
+        assert_eq!(changes.local, expected_local);
+        assert_eq!(changes.remote, expected_remote);
+    }
+
+    #[test_case(false, false, Some(Ordering::Equal); "neither")]
+    #[test_case(true, false, Some(Ordering::Greater); "local")]
+    #[test_case(false, true, Some(Ordering::Less); "remote")]
+    #[test_case(true, true, None; "both")]
+    fn ordering(local: bool, remote: bool, expected: Option<Ordering>) {
+        assert_eq!(Changes { local, remote }.ordering(), expected);
+    }
+
+    #[test]
+    fn modified_since() {
+        let modified = datetime!(2025-01-02 03:04:05 UTC);
+        let state = SyncedState::now(None, None, Some(modified)).unwrap();
+        assert!(!state.modified_since(modified).unwrap());
+        assert!(
+            state
+                .modified_since(datetime!(2025-01-02 03:04:06 UTC))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let state = SyncedState::now(Some("\"0x1\"".to_string()), None, None).unwrap();
+        let json = serde_json::to_string(&state).unwrap();
+        assert!(!json.contains("hash"));
+        assert_eq!(serde_json::from_str::<SyncedState>(&json).unwrap(), state);
+    }
+}
@@ -0,0 +1,162 @@
+use serde::{Serialize, Serializer};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+/// The kind of a tracked resource.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TrackedKind {
+    /// A local file synchronized with a blob.
+    File,
+
+    /// A dotenv variable synchronized with a secret.
+    Variable,
+}
+
+impl TrackedKind {
+    /// Gets the name of this kind, like `file`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::File => "file",
+            Self::Variable => "variable",
+        }
+    }
+}
+
+/// A local resource and the remote resource it's synchronized with.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize)]
+pub struct TrackedResource {
+    /// What kind of resource this is.
+    pub kind: TrackedKind,
+
+    /// The path to the local file, or the name of the variable.
+    pub local: String,
+
+    /// The URL of the blob or secret it's synchronized with.
+    pub remote: String,
+
+    /// When it was last synchronized from this directory, according to its
+    /// recorded state, if it ever was.
+    #[serde(serialize_with = "serialize_optional_time")]
+    pub last_synced: Option<OffsetDateTime>,
+
+    /// A hash of the local contents or value, if it exists.
+    ///
+    /// Values are hashed with [`hash_secret`](crate::audit::hash_secret).
+    pub hash: Option<String>,
+
+    /// The size of the local contents or value in bytes, if it exists.
+    pub size: Option<u64>,
+}
+
+/// Every resource tracked by azsync, as of when the report was generated.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize)]
+pub struct TrackedReport {
+    /// When the report was generated.
+    #[serde(serialize_with = "serialize_time")]
+    pub generated: OffsetDateTime,
+
+    /// The tracked resources, files first.
+    pub resources: Vec<TrackedResource>,
+}
+
+impl TrackedReport {
+    /// Starts an empty report generated now.
+    pub fn new() -> Self {
+        Self {
+            generated: OffsetDateTime::now_utc(),
+            resources: Vec::new(),
+        }
+    }
+
+    /// Adds a resource to the report.
+    pub fn add(&mut self, resource: TrackedResource) {
+        self.resources.push(resource);
+    }
+}
+
+/// Formats a time as an RFC 3339 timestamp, to the second.
+pub fn timestamp(time: OffsetDateTime) -> Result<String, time::error::Format> {
+    time.replace_nanosecond(0).unwrap_or(time).format(&Rfc3339)
+}
+
+/// Serializes a time as a timestamp.
+fn serialize_time<S>(time: &OffsetDateTime, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    timestamp(*time)
+        .map_err(serde::ser::Error::custom)?
+        .serialize(serializer)
+}
+
+/// Serializes a time as a timestamp, if there is one.
+#[expect(clippy::ref_option, reason = "serde passes fields by reference")]
+fn serialize_optional_time<S>(
+    time: &Option<OffsetDateTime>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    time.map(timestamp)
+        .transpose()
+        .map_err(serde::ser::Error::custom)?
+        .serialize(serializer)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn serializes() {
+        let report = TrackedReport {
+            generated: datetime!(2025-01-02 03:04:05.678 UTC),
+            resources: vec![
+                TrackedResource {
+                    kind: TrackedKind::File,
+                    local: "config/app.json".to_string(),
+                    remote: "https://account.blob.core.windows.net/files/app.json".to_string(),
+                    last_synced: Some(datetime!(2025-01-01 00:00:00 UTC)),
+                    hash: Some("abc".to_string()),
+                    size: Some(12),
+                },
+                TrackedResource {
+                    kind: TrackedKind::Variable,
+                    local: "API_KEY".to_string(),
+                    remote: "https://vault.vault.azure.net/secrets/API-KEY".to_string(),
+                    last_synced: None,
+                    hash: None,
+                    size: None,
+                },
+            ],
+        };
+
+        let expected = json!({
+            "generated": "2025-01-02T03:04:05Z",
+            "resources": [
+                {
+                    "kind": "file",
+                    "local": "config/app.json",
+                    "remote": "https://account.blob.core.windows.net/files/app.json",
+                    "last_synced": "2025-01-01T00:00:00Z",
+                    "hash": "abc",
+                    "size": 12,
+                },
+                {
+                    "kind": "variable",
+                    "local": "API_KEY",
+                    "remote": "https://vault.vault.azure.net/secrets/API-KEY",
+                    "last_synced": null,
+                    "hash": null,
+                    "size": null,
+                },
+            ],
+        });
+        assert_eq!(serde_json::to_value(&report).unwrap(), expected);
+    }
+}
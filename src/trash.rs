@@ -0,0 +1,202 @@
+use std::{
+    env::current_dir,
+    fs::{copy, create_dir_all, read_dir, remove_dir_all, remove_file, rename, symlink_metadata},
+    io::ErrorKind,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::Context;
+use time::{
+    OffsetDateTime, PrimitiveDateTime, format_description::BorrowedFormatItem,
+    macros::format_description,
+};
+
+/// Where local files deleted when mirroring deletions are moved to.
+pub const TRASH_DIR: &str = ".azsync/trash";
+
+/// How each run's directory in the trash is named.
+const RUN_FORMAT: &[BorrowedFormatItem<'_>] =
+    format_description!("[year][month][day]T[hour][minute][second]Z");
+
+/// The directory in the trash that local files deleted in one run are moved
+/// to, like `.azsync/trash/20250101T120000Z/`.
+///
+/// Files keep their paths relative to the current directory, so they can be
+/// restored by moving them back.
+#[derive(Clone, Debug)]
+pub struct Trash {
+    dir: PathBuf,
+
+    /// The directory paths in the trash are relative to.
+    current_dir: PathBuf,
+}
+
+impl Trash {
+    /// Picks a directory in the trash for this run.
+    ///
+    /// The directory is only created once something is moved to it.
+    pub fn new() -> anyhow::Result<Self> {
+        let current_dir = current_dir().context("Failed to get the current directory")?;
+        let name = OffsetDateTime::now_utc().format(RUN_FORMAT)?;
+        let mut dir = Path::new(TRASH_DIR).join(&name);
+        let mut suffix = 1;
+        while dir.exists() {
+            dir = Path::new(TRASH_DIR).join(format!("{name}-{suffix}"));
+            suffix += 1;
+        }
+
+        Ok(Self { dir, current_dir })
+    }
+
+    /// Gets the directory files are moved to.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Moves a local file to the trash instead of deleting it.
+    ///
+    /// If the trash is on a different filesystem, the file is copied and then
+    /// deleted instead.
+    pub fn discard(&self, path: &Path) -> anyhow::Result<()> {
+        let destination = trashed_path(&self.dir, &self.current_dir, path);
+        if let Some(parent) = destination.parent() {
+            create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+
+        match rename(path, &destination) {
+            Ok(()) => Ok(()),
+            Err(error) if error.kind() == ErrorKind::CrossesDevices => {
+                copy(path, &destination)?;
+                remove_file(path)?;
+                Ok(())
+            }
+            Err(error) => Err(error)
+                .with_context(|| format!("Failed to move file to {}", destination.display())),
+        }
+    }
+}
+
+/// Gets where a file is moved to in a run's directory in the trash.
+///
+/// Files in the current directory keep their relative paths. Files elsewhere
+/// keep their full paths, without the root or any drive prefix.
+fn trashed_path(dir: &Path, current_dir: &Path, path: &Path) -> PathBuf {
+    let relative = path.strip_prefix(current_dir).unwrap_or(path);
+    let mut trashed = dir.to_path_buf();
+    trashed.extend(
+        relative
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name),
+                _ => None,
+            }),
+    );
+    trashed
+}
+
+/// A run's directory in the trash.
+#[derive(Clone, Debug)]
+pub struct TrashedRun {
+    /// The path to the directory.
+    pub path: PathBuf,
+
+    /// When the run happened, if it can be told from the directory's name.
+    pub time: Option<OffsetDateTime>,
+
+    /// How many files are in the directory.
+    pub files: u64,
+
+    /// The total size of the files in bytes.
+    pub bytes: u64,
+}
+
+impl TrashedRun {
+    /// Deletes the directory and everything in it.
+    pub fn remove(&self) -> anyhow::Result<()> {
+        remove_dir_all(&self.path)
+            .with_context(|| format!("Failed to delete {}", self.path.display()))
+    }
+}
+
+/// Lists the runs whose deleted files are in the trash.
+pub fn list_trash() -> anyhow::Result<Vec<TrashedRun>> {
+    let entries = match read_dir(TRASH_DIR) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error).with_context(|| format!("Can't read {TRASH_DIR}")),
+    };
+
+    let mut runs = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+
+        let path = entry.path();
+        let (files, bytes) = measure(&path)?;
+        runs.push(TrashedRun {
+            time: parse_run_time(&entry.file_name().to_string_lossy()),
+            path,
+            files,
+            bytes,
+        });
+    }
+    runs.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(runs)
+}
+
+/// Counts the files in a directory and adds up their sizes, including those in
+/// subdirectories.
+fn measure(dir: &Path) -> anyhow::Result<(u64, u64)> {
+    let (mut files, mut bytes) = (0, 0);
+    for entry in read_dir(dir).with_context(|| format!("Can't read {}", dir.display()))? {
+        let entry = entry?;
+        let metadata = symlink_metadata(entry.path())?;
+        if metadata.is_dir() {
+            let (dir_files, dir_bytes) = measure(&entry.path())?;
+            files += dir_files;
+            bytes += dir_bytes;
+        } else {
+            files += 1;
+            bytes += metadata.len();
+        }
+    }
+
+    Ok((files, bytes))
+}
+
+/// Parses when a run happened from the name of its directory in the trash.
+fn parse_run_time(name: &str) -> Option<OffsetDateTime> {
+    let timestamp = name.split('-').next().unwrap_or(name);
+    PrimitiveDateTime::parse(timestamp, RUN_FORMAT)
+        .ok()
+        .map(PrimitiveDateTime::assume_utc)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use test_case::test_case;
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test_case("/work/config/app.json", "trash/config/app.json"; "in current directory")]
+    #[test_case("config/app.json", "trash/config/app.json"; "relative")]
+    #[test_case("/etc/app.json", "trash/etc/app.json"; "outside current directory")]
+    #[test_case("../app.json", "trash/app.json"; "parent directory")]
+    fn trashed(path: &str, expected: &str) {
+        let trashed = trashed_path(Path::new("trash"), Path::new("/work"), Path::new(path));
+        assert_eq!(trashed, Path::new(expected));
+    }
+
+    #[test_case("20250102T030405Z", Some(datetime!(2025-01-02 03:04:05 UTC)); "timestamp")]
+    #[test_case("20250102T030405Z-1", Some(datetime!(2025-01-02 03:04:05 UTC)); "suffixed")]
+    #[test_case("restored", None; "other")]
+    fn run_time(name: &str, expected: Option<OffsetDateTime>) {
+        assert_eq!(parse_run_time(name), expected);
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::cli::GroupBy;
+
+/// Group for blobs without an extension.
+const NO_EXTENSION: &str = "(none)";
+
+/// Group for blobs that aren't in a folder.
+const NO_FOLDER: &str = "(root)";
+
+/// How much space a set of blobs takes up.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize)]
+pub struct Totals {
+    /// How many blobs there are.
+    pub count: u64,
+
+    /// Their total size in bytes.
+    pub bytes: u64,
+}
+
+impl Totals {
+    /// Counts another blob.
+    fn add(&mut self, size: u64) {
+        self.count += 1;
+        self.bytes += size;
+    }
+}
+
+/// How much space blobs take up, grouped by extension or folder.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize)]
+pub struct UsageReport {
+    /// The totals of each group, by name.
+    pub groups: BTreeMap<String, Totals>,
+
+    /// The totals of all blobs.
+    pub total: Totals,
+
+    /// How blobs are grouped.
+    #[serde(skip)]
+    group_by: GroupBy,
+}
+
+impl UsageReport {
+    /// Starts an empty report.
+    pub fn new(group_by: GroupBy) -> Self {
+        Self {
+            groups: BTreeMap::new(),
+            total: Totals::default(),
+            group_by,
+        }
+    }
+
+    /// Counts a blob, named relative to the prefix being reported on.
+    pub fn add(&mut self, name: &str, size: u64) {
+        let group = match self.group_by {
+            GroupBy::Extension => extension_group(name),
+            GroupBy::Folder => folder_group(name),
+        };
+        self.groups.entry(group).or_default().add(size);
+        self.total.add(size);
+    }
+
+    /// Gets the groups from largest to smallest.
+    pub fn largest(&self) -> Vec<(&str, Totals)> {
+        let mut groups: Vec<_> = self
+            .groups
+            .iter()
+            .map(|(name, totals)| (name.as_str(), *totals))
+            .collect();
+        groups.sort_by(|(a_name, a), (b_name, b)| b.bytes.cmp(&a.bytes).then(a_name.cmp(b_name)));
+        groups
+    }
+}
+
+/// Gets the group of a blob by its extension, like `.json`.
+///
+/// Extensions are compared case-insensitively. Names that start with a dot,
+/// like `.env`, don't have an extension.
+fn extension_group(name: &str) -> String {
+    let file_name = name.rsplit('/').next().unwrap_or(name);
+    match file_name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() && !extension.is_empty() => {
+            format!(".{}", extension.to_lowercase())
+        }
+        _ => NO_EXTENSION.to_string(),
+    }
+}
+
+/// Gets the group of a blob by its top-level folder, like `config/`.
+fn folder_group(name: &str) -> String {
+    match name.split_once('/') {
+        Some((folder, _)) => format!("{folder}/"),
+        None => NO_FOLDER.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("config/app.json", ".json"; "nested")]
+    #[test_case("photo.JPG", ".jpg"; "uppercase")]
+    #[test_case("archive.tar.gz", ".gz"; "multiple dots")]
+    #[test_case(".env", "(none)"; "dotfile")]
+    #[test_case("dir.d/README", "(none)"; "dot in folder")]
+    #[test_case("trailing.", "(none)"; "trailing dot")]
+    fn extension(name: &str, expected: &str) {
+        assert_eq!(extension_group(name), expected);
+    }
+
+    #[test_case("config/app.json", "config/"; "nested")]
+    #[test_case("config/prod/app.json", "config/"; "deeply nested")]
+    #[test_case("app.json", "(root)"; "root")]
+    fn folder(name: &str, expected: &str) {
+        assert_eq!(folder_group(name), expected);
+    }
+
+    #[test]
+    fn report() {
+        let mut report = UsageReport::new(GroupBy::Folder);
+        report.add("config/a.json", 10);
+        report.add("config/b.json", 20);
+        report.add("data/c.db", 100);
+        report.add("README.md", 5);
+
+        assert_eq!(
+            report.total,
+            Totals {
+                count: 4,
+                bytes: 135
+            }
+        );
+        assert_eq!(
+            report.largest(),
+            vec![
+                (
+                    "data/",
+                    Totals {
+                        count: 1,
+                        bytes: 100
+                    }
+                ),
+                (
+                    "config/",
+                    Totals {
+                        count: 2,
+                        bytes: 30
+                    }
+                ),
+                ("(root)", Totals { count: 1, bytes: 5 }),
+            ]
+        );
+    }
+}
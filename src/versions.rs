@@ -0,0 +1,190 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+use crate::manifest::{Section, load_entries, save_entries};
+
+/// Blob metadata key and secret tag for the version counter.
+pub const VERSION_KEY: &str = "azsync_version";
+
+/// What was synchronized the last time a resource was pushed or pulled.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct SyncedVersion {
+    /// The remote version counter.
+    pub version: u64,
+
+    /// A fingerprint of the local copy, like its modified time or a hash of
+    /// its value.
+    pub local: String,
+}
+
+/// The versions of resources as of when they were last synchronized, by the
+/// URL of each resource.
+#[derive(Debug)]
+pub struct VersionStore {
+    path: PathBuf,
+    versions: Mutex<BTreeMap<String, SyncedVersion>>,
+
+    /// The resources recorded since loading, which are the only ones saved.
+    recorded: Mutex<BTreeSet<String>>,
+}
+
+impl VersionStore {
+    /// Loads the recorded versions from a manifest, if it exists.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        Ok(Self {
+            path: path.to_owned(),
+            versions: Mutex::new(load_entries(path, Section::Versions)?),
+            recorded: Mutex::default(),
+        })
+    }
+
+    /// Gets what was last synchronized for a resource.
+    pub fn get(&self, resource: &str) -> Option<SyncedVersion> {
+        self.versions
+            .lock()
+            .expect("versions lock should not be poisoned")
+            .get(resource)
+            .cloned()
+    }
+
+    /// Records what was just synchronized for a resource.
+    pub fn record(&self, resource: String, version: SyncedVersion) {
+        self.recorded
+            .lock()
+            .expect("versions lock should not be poisoned")
+            .insert(resource.clone());
+        self.versions
+            .lock()
+            .expect("versions lock should not be poisoned")
+            .insert(resource, version);
+    }
+
+    /// Writes the versions recorded since loading back to the manifest.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let recorded = self
+            .recorded
+            .lock()
+            .expect("versions lock should not be poisoned");
+        let versions = self
+            .versions
+            .lock()
+            .expect("versions lock should not be poisoned");
+        let entries = recorded
+            .iter()
+            .filter_map(|resource| Some((resource.clone(), versions.get(resource)?.clone())));
+        save_entries(&self.path, Section::Versions, entries)
+    }
+}
+
+/// A version to record once a resource is synchronized.
+#[derive(Clone, Debug)]
+pub struct VersionUpdate {
+    /// Where to record the version.
+    pub store: Arc<VersionStore>,
+
+    /// The URL of the resource.
+    pub resource: String,
+
+    /// The remote version counter after synchronizing.
+    pub version: u64,
+}
+
+impl VersionUpdate {
+    /// Records that the resource was synchronized at this version, with the
+    /// given fingerprint of its local copy.
+    pub fn record(self, local: String) {
+        self.store.record(
+            self.resource,
+            SyncedVersion {
+                version: self.version,
+                local,
+            },
+        );
+    }
+}
+
+/// Fingerprints a local file by when it was last modified.
+pub fn time_fingerprint(time: OffsetDateTime) -> anyhow::Result<String> {
+    Ok(time.format(&Rfc3339)?)
+}
+
+/// Parses a version counter from blob metadata or secret tags.
+pub fn parse_version(value: Option<&String>) -> Option<u64> {
+    value.and_then(|value| value.parse().ok())
+}
+
+/// Compares the local and remote copies of a resource using version counters.
+///
+/// Returns whether the local copy is newer ([`Ordering::Greater`]), older
+/// ([`Ordering::Less`]), or the same as the remote copy. Returns `None` if this
+/// can't be determined from versions alone, like when the resource was never
+/// synchronized with version counters or both copies changed since then.
+///
+/// Only the local fingerprint and remote version are compared with what was
+/// last synchronized, so clocks never need to agree.
+pub fn compare_versions(
+    last_synced: Option<&SyncedVersion>,
+    local: &str,
+    remote_version: Option<u64>,
+) -> Option<Ordering> {
+    let last_synced = last_synced?;
+    let remote_version = remote_version?;
+    let local_changed = local != last_synced.local;
+    let remote_changed = remote_version != last_synced.version;
+    match (local_changed, remote_changed) {
+        (false, false) => Some(Ordering::Equal),
+        (true, false) => Some(Ordering::Greater),
+        (false, true) => Some(Ordering::Less),
+        (true, true) => None,
+    }
+}
+
+/// Explains how the local and remote copies of a resource compare according
+/// to their version counters.
+pub fn explain_versions(ordering: Ordering) -> String {
+    let changed = match ordering {
+        Ordering::Equal => "neither copy",
+        Ordering::Greater => "only the local copy",
+        Ordering::Less => "only the remote copy",
+    };
+    format!("version counters show {changed} changed since it was last synchronized")
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("a", Some(1) => Some(Ordering::Equal); "unchanged")]
+    #[test_case("b", Some(1) => Some(Ordering::Greater); "local changed")]
+    #[test_case("a", Some(2) => Some(Ordering::Less); "remote changed")]
+    #[test_case("b", Some(2) => None; "both changed")]
+    #[test_case("a", None => None; "remote unversioned")]
+    fn compare(local: &str, remote_version: Option<u64>) -> Option<Ordering> {
+        let last_synced = SyncedVersion {
+            version: 1,
+            local: "a".to_string(),
+        };
+        compare_versions(Some(&last_synced), local, remote_version)
+    }
+
+    #[test]
+    fn never_synchronized() {
+        assert_eq!(compare_versions(None, "a", Some(1)), None);
+    }
+
+    #[test_case(Some("3") => Some(3); "valid")]
+    #[test_case(Some("three") => None; "invalid")]
+    #[test_case(None => None; "missing")]
+    fn parse(value: Option<&str>) -> Option<u64> {
+        parse_version(value.map(str::to_string).as_ref())
+    }
+}
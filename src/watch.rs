@@ -0,0 +1,130 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher, recommended_watcher};
+use tokio::{
+    sync::mpsc::{UnboundedReceiver, unbounded_channel},
+    time::timeout,
+};
+
+/// What to watch for changes.
+pub enum Watched<'a> {
+    /// Individual files, which don't need to exist yet.
+    Files(&'a [PathBuf]),
+
+    /// Everything in a directory and its subdirectories, except paths the
+    /// filter rejects.
+    Directory {
+        root: &'a Path,
+        filter: &'a dyn Fn(&Path) -> bool,
+    },
+}
+
+/// Waits until anything watched changes, and then until it stops changing
+/// for `debounce`.
+///
+/// Files are watched through the directories they're in, so they're still
+/// noticed when they're created, deleted, or replaced by renaming another file
+/// over them, like editors do when saving. Changes on network drives may not be
+/// noticed, depending on the platform.
+pub async fn wait_for_changes(
+    watched: &Watched<'_>,
+    debounce: time::Duration,
+) -> anyhow::Result<()> {
+    let (tx, mut rx) = unbounded_channel();
+    let mut notifier = recommended_watcher(move |event| {
+        // Nobody is waiting anymore once the receiver is dropped
+        let _ = tx.send(event);
+    })
+    .context("Failed to start watching for changes")?;
+
+    let files: HashSet<PathBuf>;
+    let is_relevant: Box<dyn Fn(&Path) -> bool + '_> = match watched {
+        Watched::Files(paths) => {
+            files = watch_files(&mut notifier, paths)?;
+            Box::new(|path| files.contains(path))
+        }
+        Watched::Directory { root, filter } => {
+            notifier
+                .watch(root, RecursiveMode::Recursive)
+                .with_context(|| format!("Failed to watch {}", root.display()))?;
+            Box::new(filter)
+        }
+    };
+
+    next_change(&mut rx, &is_relevant).await?;
+
+    // Let changes settle, like when an editor saves in several steps
+    loop {
+        match timeout(debounce.unsigned_abs(), next_change(&mut rx, &is_relevant)).await {
+            Ok(result) => result?,
+            Err(_) => return Ok(()),
+        }
+    }
+}
+
+/// Watches the directories files are in, returning the paths changes to the
+/// files are reported with.
+///
+/// Directories that don't exist yet are watched through the closest one that
+/// does, including its subdirectories.
+fn watch_files(
+    notifier: &mut RecommendedWatcher,
+    paths: &[PathBuf],
+) -> anyhow::Result<HashSet<PathBuf>> {
+    let mut files = HashSet::with_capacity(paths.len());
+    let mut directories = HashSet::new();
+    for path in paths {
+        let Some(name) = path.file_name() else {
+            continue;
+        };
+        let parent = path.parent().unwrap_or(Path::new(""));
+
+        // Events name files by the directory that was watched, so paths are
+        // compared with it canonicalized
+        let existing = parent
+            .ancestors()
+            .find(|ancestor| ancestor.as_os_str().is_empty() || ancestor.exists())
+            .unwrap_or(parent);
+        let directory = Path::new(".")
+            .join(existing)
+            .canonicalize()
+            .with_context(|| format!("Can't watch {}", path.display()))?;
+        let missing = parent.strip_prefix(existing).unwrap_or(Path::new(""));
+        files.insert(directory.join(missing).join(name));
+        let mode = if existing == parent {
+            RecursiveMode::NonRecursive
+        } else {
+            RecursiveMode::Recursive
+        };
+        if directories.insert((directory.clone(), mode == RecursiveMode::Recursive)) {
+            notifier
+                .watch(&directory, mode)
+                .with_context(|| format!("Failed to watch {}", directory.display()))?;
+        }
+    }
+
+    Ok(files)
+}
+
+/// Waits for an event that changes a relevant path.
+async fn next_change(
+    rx: &mut UnboundedReceiver<notify::Result<notify::Event>>,
+    is_relevant: &dyn Fn(&Path) -> bool,
+) -> anyhow::Result<()> {
+    loop {
+        let event = rx
+            .recv()
+            .await
+            .context("Stopped watching for changes")?
+            .context("Failed to watch for changes")?;
+        if !matches!(event.kind, EventKind::Access(_))
+            && event.paths.iter().any(|path| is_relevant(path))
+        {
+            return Ok(());
+        }
+    }
+}